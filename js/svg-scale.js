@@ -0,0 +1,38 @@
+// Thin wrapper around the raw ABI exported by the `wasm` feature (src/wasm.rs).
+//
+//   import { init, scaleSvg } from "./svg-scale.js";
+//   await init(fetch("svg_scale.wasm"));
+//   const out = scaleSvg(svgText, { to: 16, fixStroke: true });
+
+let wasm = null;
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+export async function init(source) {
+  const { instance } = await WebAssembly.instantiateStreaming(await source, {});
+  wasm = instance.exports;
+}
+
+function pass(str) {
+  const bytes = encoder.encode(str);
+  const ptr = wasm.svg_scale_alloc(bytes.length);
+  new Uint8Array(wasm.memory.buffer, ptr, bytes.length).set(bytes);
+  return [ptr, bytes.length];
+}
+
+export function scaleSvg(svgText, options = {}) {
+  if (!wasm) throw new Error("svg-scale: call init() first");
+  const [svgPtr, svgLen] = pass(svgText);
+  const [optsPtr, optsLen] = pass(JSON.stringify(options));
+  try {
+    const status = wasm.svg_scale_run(svgPtr, svgLen, optsPtr, optsLen);
+    const out = decoder.decode(
+      new Uint8Array(wasm.memory.buffer, wasm.svg_scale_result_ptr(), wasm.svg_scale_result_len())
+    );
+    if (status !== 0) throw new Error(out);
+    return out;
+  } finally {
+    wasm.svg_scale_free(svgPtr, svgLen);
+    wasm.svg_scale_free(optsPtr, optsLen);
+  }
+}
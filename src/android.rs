@@ -0,0 +1,75 @@
+//! Android launcher icons.
+//!
+//! A legacy launcher icon is 48 dp, written once per density bucket into
+//! `mipmap-<density>/`. An adaptive icon's foreground layer is 108 dp, of
+//! which launchers may mask everything outside the central 66 dp safe zone,
+//! so the artwork is fitted into that zone and padded out to the full layer.
+
+use anyhow::Result;
+
+use crate::scale::ScaleCtx;
+use crate::viewbox::{self, ViewBox};
+
+/// Density bucket and launcher icon size in px (48 dp at 1, 1.5, 2, 3 and 4x)
+pub const MIPMAP_DENSITIES: &[(&str, u32)] = &[
+    ("mdpi", 48),
+    ("hdpi", 72),
+    ("xhdpi", 96),
+    ("xxhdpi", 144),
+    ("xxxhdpi", 192),
+];
+
+/// Side of an adaptive icon layer, in dp
+pub const ADAPTIVE_LAYER: f64 = 108.0;
+
+/// Side of the area no launcher mask cuts into, in dp
+pub const ADAPTIVE_SAFE_ZONE: f64 = 66.0;
+
+/// The document as an adaptive icon foreground: scaled to fit and centered
+/// in the safe zone, on a `ADAPTIVE_LAYER` canvas in dp. Non-scaling strokes
+/// are baked in, since vector drawables have no such effect.
+pub fn adaptive_foreground(
+    doc: &roxmltree::Document,
+    precision: usize,
+    dpi: f64,
+) -> Result<String> {
+    let zone = ViewBox {
+        x: 0.0,
+        y: 0.0,
+        width: ADAPTIVE_SAFE_ZONE,
+        height: ADAPTIVE_SAFE_ZONE,
+    };
+    let (fitted, scale) = viewbox::fit_to_view_box(doc, zone, dpi)?;
+    let ctx = ScaleCtx {
+        scale,
+        precision,
+        fix_stroke: true,
+        ..ScaleCtx::default()
+    };
+    let scaled = crate::write_svg(&roxmltree::Document::parse(&fitted)?, &ctx)?;
+    let padding = (ADAPTIVE_LAYER - ADAPTIVE_SAFE_ZONE) / 2.0;
+    viewbox::pad_view_box(&roxmltree::Document::parse(&scaled)?, padding, dpi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn foreground_fills_the_safe_zone_of_the_layer() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="12"><rect width="24" height="12"/></svg>"#,
+        )?;
+        let out = adaptive_foreground(&doc, 4, 96.0)?;
+        assert!(
+            out.contains(r#"width="108" height="108" viewBox="-21 -21 108 108""#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<rect width="66" height="33"/>"#),
+            "got: {out}"
+        );
+        assert!(out.contains("translate(0,16.5)"), "got: {out}");
+        Ok(())
+    }
+}
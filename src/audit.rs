@@ -0,0 +1,153 @@
+//! Read-only checks of icons against a pixel grid for `svg-scale audit`.
+//!
+//! An icon drawn on an N-pixel grid declares `width`/`height` of N, has a
+//! `0 0 N N` viewBox, keeps its drawn content inside that box with edges on
+//! whole pixels, and has no `non-scaling-stroke` that would keep its stroke
+//! width when scaled without `--fix-stroke`.
+
+use anyhow::Result;
+use std::fmt;
+
+use crate::svg::{self, VectorEffects};
+use crate::viewbox::ViewBox;
+
+/// How far, in px at the expected size, a content edge may be off the grid
+const GRID_TOLERANCE: f64 = 0.01;
+
+/// One way an icon deviates from the grid
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    /// `width`/`height` differ from the grid size; `None` when missing
+    Size(Option<(f64, f64)>),
+    /// The viewBox is not `0 0 N N`; `None` when missing
+    ViewBox(Option<ViewBox>),
+    /// The drawn content (in grid px) reaches outside the canvas
+    Overflow(ViewBox),
+    /// The drawn content (in grid px) has edges between pixels
+    OffGrid(ViewBox),
+    /// Elements with `vector-effect: non-scaling-stroke`
+    NonScalingStroke(usize),
+}
+
+fn fmt_box(b: &ViewBox) -> String {
+    let n = |v: f64| format!("{}", (v * 1000.0).round() / 1000.0);
+    format!("{} {} {} {}", n(b.x), n(b.y), n(b.width), n(b.height))
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Size(Some((w, h))) => write!(f, "declared size {}x{}", w, h),
+            Finding::Size(None) => f.write_str("no declared width/height"),
+            Finding::ViewBox(Some(vb)) => write!(f, "viewBox {}", fmt_box(vb)),
+            Finding::ViewBox(None) => f.write_str("no viewBox"),
+            Finding::Overflow(b) => write!(f, "content {} reaches outside the grid", fmt_box(b)),
+            Finding::OffGrid(b) => write!(f, "content {} is not on whole pixels", fmt_box(b)),
+            Finding::NonScalingStroke(n) => write!(
+                f,
+                "{} element(s) with non-scaling-stroke need --fix-stroke",
+                n
+            ),
+        }
+    }
+}
+
+/// Everything in `doc` that deviates from an `expect`-pixel grid, in the
+/// order of [`Finding`]'s variants
+pub fn audit_document(doc: &roxmltree::Document, expect: f64, dpi: f64) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    let root = doc.root_element();
+    let near = |a: f64, b: f64| (a - b).abs() <= GRID_TOLERANCE;
+
+    let length = |name| root.attribute(name).and_then(|v| svg::length_to_px(v, dpi));
+    match (length("width"), length("height")) {
+        (Some(w), Some(h)) if near(w, expect) && near(h, expect) => {}
+        (Some(w), Some(h)) => findings.push(Finding::Size(Some((w, h)))),
+        _ => findings.push(Finding::Size(None)),
+    }
+    let view_box = ViewBox::of_root(doc);
+    match view_box {
+        Some(vb)
+            if near(vb.x, 0.0)
+                && near(vb.y, 0.0)
+                && near(vb.width, expect)
+                && near(vb.height, expect) => {}
+        vb => findings.push(Finding::ViewBox(vb)),
+    }
+
+    // Content is judged in grid pixels, whatever the viewBox says
+    if let Some(vb) = view_box.filter(|vb| vb.width > 0.0 && vb.height > 0.0) {
+        if let Ok(content) = crate::ops::content_view_box(doc, dpi) {
+            let (fx, fy) = (expect / vb.width, expect / vb.height);
+            let grid = ViewBox {
+                x: (content.x - vb.x) * fx,
+                y: (content.y - vb.y) * fy,
+                width: content.width * fx,
+                height: content.height * fy,
+            };
+            let (right, bottom) = (grid.x + grid.width, grid.y + grid.height);
+            let limit = -GRID_TOLERANCE..=expect + GRID_TOLERANCE;
+            if ![grid.x, grid.y, right, bottom]
+                .iter()
+                .all(|v| limit.contains(v))
+            {
+                findings.push(Finding::Overflow(grid));
+            } else if ![grid.x, grid.y, right, bottom]
+                .iter()
+                .all(|v| near(*v, v.round()))
+            {
+                findings.push(Finding::OffGrid(grid));
+            }
+        }
+    }
+
+    let non_scaling = root
+        .descendants()
+        .filter(|n| n.is_element())
+        .filter(|n| {
+            let css = svg::css_property(*n, "vector-effect");
+            VectorEffects::from_values(
+                n.attribute("vector-effect")
+                    .into_iter()
+                    .chain(css.as_deref()),
+            )
+            .non_scaling_stroke
+        })
+        .count();
+    if non_scaling > 0 {
+        findings.push(Finding::NonScalingStroke(non_scaling));
+    }
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_grid_icon_has_no_findings() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24"><rect x="2" y="2" width="20" height="20"/></svg>"#,
+        )?;
+        assert_eq!(audit_document(&doc, 24.0, 96.0)?, []);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_size_view_box_content_and_strokes() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="32" height="32" viewBox="0 0 48 48"><rect x="3" y="3" width="40" height="40" style="vector-effect:non-scaling-stroke"/></svg>"#,
+        )?;
+        let findings = audit_document(&doc, 24.0, 96.0)?;
+        let shown: Vec<String> = findings.iter().map(ToString::to_string).collect();
+        let mut expected = vec![
+            "declared size 32x32".to_string(),
+            "viewBox 0 0 48 48".to_string(),
+        ];
+        #[cfg(feature = "render")]
+        expected.push("content 1.5 1.5 20 20 is not on whole pixels".to_string());
+        expected.push("1 element(s) with non-scaling-stroke need --fix-stroke".to_string());
+        assert_eq!(shown, expected);
+        Ok(())
+    }
+}
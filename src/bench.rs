@@ -0,0 +1,161 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::fs;
+use std::time::Duration;
+use xmlwriter::XmlWriter;
+
+use svg_scale::render::render_svg_to_pixmap;
+use svg_scale::scale::{self, ScaleCtx};
+use svg_scale::svg::{self, StyleSheet, WalkOptions};
+use svg_scale::{finish_document, get_svg_dimensions, DEFAULT_DPI};
+
+use crate::timings::Timings;
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// 输入 SVG 文件
+    pub input: String,
+
+    /// 每个阶段重复次数
+    #[arg(long, default_value = "100")]
+    pub iterations: usize,
+
+    /// 目标尺寸（同时作为渲染尺寸）
+    #[arg(long, default_value = "128")]
+    pub to: f64,
+
+    #[arg(long, default_value = "4", value_parser = scale::parse_precision)]
+    pub precision: usize,
+
+    /// 移除 non-scaling-stroke
+    #[arg(long)]
+    pub fix_stroke: bool,
+
+    /// 跳过 PNG 渲染阶段
+    #[arg(long)]
+    pub no_render: bool,
+}
+
+/// Wall-clock samples of one pipeline stage, one per iteration
+struct Stage {
+    name: &'static str,
+    samples: Vec<Duration>,
+}
+
+impl Stage {
+    fn new(name: &'static str, iterations: usize) -> Self {
+        Stage {
+            name,
+            samples: Vec::with_capacity(iterations),
+        }
+    }
+
+    fn report(&self) -> String {
+        let total: Duration = self.samples.iter().sum();
+        let avg = total / self.samples.len().max(1) as u32;
+        let min = self.samples.iter().min().copied().unwrap_or_default();
+        let max = self.samples.iter().max().copied().unwrap_or_default();
+        format!(
+            "{:<10} 平均 {:>12.3?}  最小 {:>12.3?}  最大 {:>12.3?}",
+            self.name, avg, min, max
+        )
+    }
+}
+
+pub fn run(args: &BenchArgs) -> Result<()> {
+    if args.iterations == 0 {
+        bail!("--iterations 必须大于 0");
+    }
+    let input_svg =
+        fs::read_to_string(&args.input).with_context(|| format!("read {}", args.input))?;
+
+    let mut stages: Vec<Stage> = Vec::new();
+    let opts = WalkOptions::default();
+    let mut output_len = 0;
+
+    for _ in 0..args.iterations {
+        let mut timings = Timings::default();
+        let doc = timings.time("parse", || Ok(roxmltree::Document::parse(&input_svg)?))?;
+        let (from_w, from_h) = match get_svg_dimensions(&doc, DEFAULT_DPI) {
+            Some(dims) => dims,
+            None => bail!("未能从SVG检测到尺寸"),
+        };
+        let ctx = ScaleCtx {
+            scale: args.to / from_w,
+            precision: args.precision,
+            fix_stroke: args.fix_stroke,
+            ..ScaleCtx::default()
+        };
+        let sheet = timings.time("styles", || Ok(StyleSheet::collect(doc.root_element())))?;
+        let writer = timings.time("walk", || {
+            let mut w = XmlWriter::new(xmlwriter::Options::default());
+            svg::walk_with_styles(doc.root_element(), &mut w, &ctx, &opts, &sheet)?;
+            Ok(w)
+        })?;
+        let out = timings.time("serialize", || Ok(finish_document(&doc, writer)))?;
+        output_len = out.len();
+        if !args.no_render {
+            let height = (from_h * ctx.scale).round().max(1.0) as u32;
+            let width = args.to.round().max(1.0) as u32;
+            timings.time("render", || render_svg_to_pixmap(&out, width, height))?;
+        }
+        add_samples(&mut stages, &timings, args.iterations);
+    }
+
+    println!(
+        "{}: {} 次, 输入 {} 字节, 输出 {} 字节",
+        args.input,
+        args.iterations,
+        input_svg.len(),
+        output_len
+    );
+    for stage in &stages {
+        println!("{}", stage.report());
+    }
+    match peak_memory_kb() {
+        Some(kb) => println!("峰值内存   {:.1} MiB", kb as f64 / 1024.0),
+        None => println!("峰值内存   不可用"),
+    }
+    Ok(())
+}
+
+/// Add one iteration's stage durations to their samples
+fn add_samples(stages: &mut Vec<Stage>, timings: &Timings, iterations: usize) {
+    for (name, d) in timings.stages() {
+        match stages.iter_mut().find(|s| s.name == name) {
+            Some(stage) => stage.samples.push(d),
+            None => {
+                let mut stage = Stage::new(name, iterations);
+                stage.samples.push(d);
+                stages.push(stage);
+            }
+        }
+    }
+}
+
+/// Peak resident set size, where the platform reports it
+fn peak_memory_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_report_summarizes_samples() {
+        let mut stage = Stage::new("walk", 3);
+        for ms in [1, 2, 6] {
+            stage.samples.push(Duration::from_millis(ms));
+        }
+        let report = stage.report();
+        assert!(report.starts_with("walk"), "got: {report}");
+        assert!(
+            report.contains("3.000ms"),
+            "expected average, got: {report}"
+        );
+        assert!(report.contains("6.000ms"), "expected max, got: {report}");
+    }
+}
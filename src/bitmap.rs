@@ -0,0 +1,258 @@
+//! Uncompressed pixel dumps for targets without an image decoder.
+//!
+//! [`encode_raw`] writes the straight RGBA pixels row by row, top to bottom,
+//! in the requested channel order, optionally after an 8-byte header holding
+//! the width and height as little-endian `u32`s. [`encode_bmp`] wraps the
+//! same pixels in a 32-bit BMP with an alpha channel mask. [`encode_c_array`]
+//! and [`encode_lvgl`] emit them as C source for firmware builds.
+
+use anyhow::{bail, Result};
+
+/// Channel order of each pixel in a raw dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PixelOrder {
+    #[default]
+    Rgba,
+    Bgra,
+    Argb,
+    Abgr,
+}
+
+impl PixelOrder {
+    /// Positions of the R, G, B and A source channels in output order
+    fn channels(self) -> [usize; 4] {
+        match self {
+            PixelOrder::Rgba => [0, 1, 2, 3],
+            PixelOrder::Bgra => [2, 1, 0, 3],
+            PixelOrder::Argb => [3, 0, 1, 2],
+            PixelOrder::Abgr => [3, 2, 1, 0],
+        }
+    }
+}
+
+impl std::str::FromStr for PixelOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "rgba" => Ok(PixelOrder::Rgba),
+            "bgra" => Ok(PixelOrder::Bgra),
+            "argb" => Ok(PixelOrder::Argb),
+            "abgr" => Ok(PixelOrder::Abgr),
+            _ => bail!(
+                "unknown pixel order: {} (expected rgba, bgra, argb or abgr)",
+                s
+            ),
+        }
+    }
+}
+
+/// Reorder straight RGBA pixels into `order`, with an optional
+/// width/height header
+pub fn encode_raw(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    order: PixelOrder,
+    header: bool,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rgba.len() + 8);
+    if header {
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+    }
+    let channels = order.channels();
+    for p in rgba.chunks_exact(4) {
+        out.extend(channels.iter().map(|&c| p[c]));
+    }
+    out
+}
+
+/// Size of the BITMAPFILEHEADER plus the BITMAPV4HEADER
+const BMP_HEADER_LEN: u32 = 14 + 108;
+
+/// Encode straight RGBA pixels as a bottom-up 32-bit BMP
+pub fn encode_bmp(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let image_len = width * height * 4;
+    let mut out = Vec::with_capacity((BMP_HEADER_LEN + image_len) as usize);
+    // BITMAPFILEHEADER
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(BMP_HEADER_LEN + image_len).to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&BMP_HEADER_LEN.to_le_bytes());
+    // BITMAPV4HEADER with BI_BITFIELDS masks, so readers keep the alpha
+    out.extend_from_slice(&108u32.to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&32u16.to_le_bytes());
+    out.extend_from_slice(&3u32.to_le_bytes());
+    out.extend_from_slice(&image_len.to_le_bytes());
+    // 96 DPI in pixels per metre, then no palette
+    out.extend_from_slice(&3780u32.to_le_bytes());
+    out.extend_from_slice(&3780u32.to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+    for mask in [0x00ff_0000u32, 0x0000_ff00, 0x0000_00ff, 0xff00_0000] {
+        out.extend_from_slice(&mask.to_le_bytes());
+    }
+    // LCS_sRGB, then unused endpoints and gamma
+    out.extend_from_slice(b"BGRs");
+    out.extend_from_slice(&[0; 48]);
+
+    let row_len = width as usize * 4;
+    for row in rgba.chunks_exact(row_len.max(1)).rev() {
+        out.extend(encode_raw(row, 0, 0, PixelOrder::Bgra, false));
+    }
+    out
+}
+
+/// A `.h`/`.c` pair declaring and defining one image
+#[derive(Debug, Clone, PartialEq)]
+pub struct CSource {
+    pub header: String,
+    pub source: String,
+}
+
+/// Turn a file stem into a C identifier: other characters become `_`, and a
+/// leading digit gets a `_` prefix
+pub fn c_identifier(stem: &str) -> String {
+    let mut name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Byte array initializer, 16 bytes per line
+fn c_bytes(data: &[u8]) -> String {
+    let mut out = String::new();
+    for line in data.chunks(16) {
+        out.push_str("    ");
+        let bytes: Vec<String> = line.iter().map(|b| format!("0x{:02x}", b)).collect();
+        out.push_str(&bytes.join(", "));
+        out.push_str(",\n");
+    }
+    out
+}
+
+fn include_guard(name: &str) -> String {
+    format!("{}_H", name.to_ascii_uppercase())
+}
+
+/// Emit straight RGBA pixels as a `uint8_t` array named `name` (in `order`,
+/// row by row from the top) with `name_width` and `name_height` constants.
+/// `header_file` is the name the source uses to include the header.
+pub fn encode_c_array(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    order: PixelOrder,
+    name: &str,
+    header_file: &str,
+) -> CSource {
+    let guard = include_guard(name);
+    let len = (width * height * 4) as usize;
+    let order_name = format!("{:?}", order).to_ascii_lowercase();
+    let header = format!(
+        "#ifndef {guard}\n#define {guard}\n\n#include <stdint.h>\n\n\
+         /* {width}x{height}, 4 bytes per pixel ({order_name}) */\n\
+         extern const uint8_t {name}[{len}];\n\
+         extern const uint32_t {name}_width;\n\
+         extern const uint32_t {name}_height;\n\n#endif\n"
+    );
+    let source = format!(
+        "#include \"{header_file}\"\n\n\
+         const uint32_t {name}_width = {width};\n\
+         const uint32_t {name}_height = {height};\n\n\
+         const uint8_t {name}[{len}] = {{\n{}}};\n",
+        c_bytes(&encode_raw(rgba, width, height, order, false))
+    );
+    CSource { header, source }
+}
+
+/// Emit straight RGBA pixels as an LVGL 8 `lv_img_dsc_t` named `name`, in
+/// the `LV_IMG_CF_TRUE_COLOR_ALPHA` layout of `LV_COLOR_DEPTH` 32 (BGRA)
+pub fn encode_lvgl(rgba: &[u8], width: u32, height: u32, name: &str, header_file: &str) -> CSource {
+    let guard = include_guard(name);
+    let header = format!(
+        "#ifndef {guard}\n#define {guard}\n\n#include \"lvgl.h\"\n\n\
+         LV_IMG_DECLARE({name});\n\n#endif\n"
+    );
+    let data = encode_raw(rgba, width, height, PixelOrder::Bgra, false);
+    let source = format!(
+        "#include \"{header_file}\"\n\n\
+         #if LV_COLOR_DEPTH != 32\n\
+         #error \"{name} was generated for LV_COLOR_DEPTH 32\"\n\
+         #endif\n\n\
+         static const uint8_t {name}_map[] = {{\n{}}};\n\n\
+         const lv_img_dsc_t {name} = {{\n\
+         \x20   .header.cf = LV_IMG_CF_TRUE_COLOR_ALPHA,\n\
+         \x20   .header.always_zero = 0,\n\
+         \x20   .header.reserved = 0,\n\
+         \x20   .header.w = {width},\n\
+         \x20   .header.h = {height},\n\
+         \x20   .data_size = {},\n\
+         \x20   .data = {name}_map,\n\
+         }};\n",
+        c_bytes(&data),
+        data.len()
+    );
+    CSource { header, source }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_dump_reorders_channels() {
+        let rgba = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(
+            encode_raw(&rgba, 2, 1, PixelOrder::Argb, false),
+            vec![4, 1, 2, 3, 8, 5, 6, 7]
+        );
+        let with_header = encode_raw(&rgba, 2, 1, PixelOrder::Bgra, true);
+        assert_eq!(with_header[..8], [2, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(with_header[8..], [3, 2, 1, 4, 7, 6, 5, 8]);
+        assert_eq!("ABGR".parse::<PixelOrder>().unwrap(), PixelOrder::Abgr);
+        assert!("rgb".parse::<PixelOrder>().is_err());
+    }
+
+    #[test]
+    fn bmp_rows_are_bottom_up_bgra() {
+        // 1x2: red on top, blue below
+        let rgba = [255, 0, 0, 255, 0, 0, 255, 128];
+        let bmp = encode_bmp(&rgba, 1, 2);
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(bmp.len(), BMP_HEADER_LEN as usize + 8);
+        assert_eq!(
+            u32::from_le_bytes(bmp[2..6].try_into().unwrap()),
+            bmp.len() as u32
+        );
+        let pixels = &bmp[BMP_HEADER_LEN as usize..];
+        assert_eq!(pixels, [255, 0, 0, 128, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn c_sources_declare_the_image() {
+        let rgba = [1, 2, 3, 4];
+        let plain = encode_c_array(&rgba, 1, 1, PixelOrder::Argb, "icon_16", "icon-16.h");
+        assert!(plain.header.contains("#ifndef ICON_16_H"));
+        assert!(plain.header.contains("extern const uint8_t icon_16[4];"));
+        assert!(plain.source.starts_with("#include \"icon-16.h\""));
+        assert!(plain.source.contains("const uint32_t icon_16_width = 1;"));
+        assert!(plain.source.contains("    0x04, 0x01, 0x02, 0x03,\n};"));
+
+        let lvgl = encode_lvgl(&rgba, 1, 1, "icon_16", "icon-16.h");
+        assert!(lvgl.header.contains("LV_IMG_DECLARE(icon_16);"));
+        assert!(lvgl.source.contains("    0x03, 0x02, 0x01, 0x04,\n};"));
+        assert!(lvgl.source.contains("    .header.w = 1,\n"));
+        assert!(lvgl.source.contains("    .data_size = 4,\n"));
+
+        assert_eq!(c_identifier("icon-16"), "icon_16");
+        assert_eq!(c_identifier("16px"), "_16px");
+    }
+}
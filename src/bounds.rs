@@ -0,0 +1,184 @@
+//! Rough bounds of what an element draws, found without rendering.
+//!
+//! Shapes, the boxes of `<image>`, `<use>` and nested `<svg>`, text anchor
+//! points and the end and control points of paths are collected through
+//! the `transform` attributes in between. Strokes, glyph extents and arcs
+//! bulging past their ends are not measured, and lengths in percentages are
+//! skipped, so the result suits anchoring rather than layout.
+
+use roxmltree::Node;
+
+use crate::path::path_points;
+use crate::svg::length_to_px;
+use crate::transform::{mat_mul, parse_transform_list, transform_to_matrix};
+use crate::viewbox::ViewBox;
+
+/// Elements that draw nothing where they stand
+const NON_RENDERING_TAGS: &[&str] = &[
+    "defs",
+    "clipPath",
+    "mask",
+    "marker",
+    "pattern",
+    "symbol",
+    "linearGradient",
+    "radialGradient",
+    "filter",
+    "style",
+    "script",
+    "title",
+    "desc",
+    "metadata",
+];
+
+const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Bounds of `node` and its descendants in the user space its `transform`
+/// maps into, i.e. its parent's; `None` when nothing measurable is drawn
+pub fn element_bounds(node: Node, dpi: f64) -> Option<ViewBox> {
+    let mut acc = Bounds::default();
+    collect(node, own_transform(node)?, dpi, &mut acc);
+    acc.view_box()
+}
+
+/// `node`'s transform attribute as a matrix; `None` when it does not parse
+fn own_transform(node: Node) -> Option<[f64; 6]> {
+    match node.attribute("transform") {
+        Some(t) => transform_to_matrix(&parse_transform_list(t).ok()?).ok(),
+        None => Some(IDENTITY),
+    }
+}
+
+#[derive(Default)]
+struct Bounds(Option<[f64; 4]>);
+
+impl Bounds {
+    fn add(&mut self, m: [f64; 6], (x, y): (f64, f64)) {
+        let p = (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5]);
+        let b = self.0.get_or_insert([p.0, p.1, p.0, p.1]);
+        *b = [b[0].min(p.0), b[1].min(p.1), b[2].max(p.0), b[3].max(p.1)];
+    }
+
+    fn add_box(&mut self, m: [f64; 6], x: f64, y: f64, width: f64, height: f64) {
+        for corner in [
+            (x, y),
+            (x + width, y),
+            (x, y + height),
+            (x + width, y + height),
+        ] {
+            self.add(m, corner);
+        }
+    }
+
+    fn view_box(&self) -> Option<ViewBox> {
+        let [x0, y0, x1, y1] = self.0?;
+        Some(ViewBox {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        })
+    }
+}
+
+/// Add what `node` draws, with `m` mapping its own user space (inside its
+/// transform) to the measured one
+fn collect(node: Node, m: [f64; 6], dpi: f64, acc: &mut Bounds) {
+    let tag_name = node.tag_name().name();
+    if NON_RENDERING_TAGS.contains(&tag_name) {
+        return;
+    }
+    let length = |name: &str| node.attribute(name).map(|v| length_to_px(v, dpi));
+    // Missing lengths are 0; ones that cannot be measured leave the shape out
+    let get = |name: &str| length(name).unwrap_or(Some(0.0));
+    match tag_name {
+        "rect" | "image" | "use" | "foreignObject" | "svg" if node.parent_element().is_some() => {
+            if let (Some(x), Some(y), Some(width), Some(height)) =
+                (get("x"), get("y"), get("width"), get("height"))
+            {
+                acc.add_box(m, x, y, width, height);
+            }
+            // A nested viewport's content is clipped to its box
+            if tag_name != "use" {
+                return;
+            }
+        }
+        "circle" => {
+            if let (Some(cx), Some(cy), Some(r)) = (get("cx"), get("cy"), get("r")) {
+                acc.add_box(m, cx - r, cy - r, 2.0 * r, 2.0 * r);
+            }
+        }
+        "ellipse" => {
+            if let (Some(cx), Some(cy), Some(rx), Some(ry)) =
+                (get("cx"), get("cy"), get("rx"), get("ry"))
+            {
+                acc.add_box(m, cx - rx, cy - ry, 2.0 * rx, 2.0 * ry);
+            }
+        }
+        "line" => {
+            if let (Some(x1), Some(y1), Some(x2), Some(y2)) =
+                (get("x1"), get("y1"), get("x2"), get("y2"))
+            {
+                acc.add(m, (x1, y1));
+                acc.add(m, (x2, y2));
+            }
+        }
+        "polyline" | "polygon" => {
+            let numbers: Vec<f64> = node
+                .attribute("points")
+                .unwrap_or("")
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter_map(|s| s.parse().ok())
+                .collect();
+            for pair in numbers.chunks_exact(2) {
+                acc.add(m, (pair[0], pair[1]));
+            }
+        }
+        "path" => {
+            for point in path_points(node.attribute("d").unwrap_or("")).unwrap_or_default() {
+                acc.add(m, point);
+            }
+        }
+        "text" | "tspan" => {
+            let first = |name: &str| {
+                let v = node.attribute(name)?.split([' ', ',']).next()?;
+                length_to_px(v, dpi)
+            };
+            if let (Some(x), Some(y)) = (first("x"), first("y")) {
+                acc.add(m, (x, y));
+            }
+        }
+        _ => {}
+    }
+    for child in node.children().filter(Node::is_element) {
+        if let Some(t) = own_transform(child) {
+            collect(child, mat_mul(m, t), dpi, acc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, Result};
+
+    #[test]
+    fn bounds_follow_shapes_through_transforms() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><g id="badge" transform="translate(10 0)"><defs><rect width="500" height="500"/></defs><circle cx="85" cy="85" r="10"/><g transform="scale(2)"><path d="M0 0 h5 v5 z"/></g></g><rect id="pct" width="50%" height="10"/></svg>"#,
+        )?;
+        let by_id = |id| doc.descendants().find(|n| n.attribute("id") == Some(id));
+        let badge = element_bounds(by_id("badge").context("badge")?, 96.0);
+        assert_eq!(
+            badge,
+            Some(ViewBox {
+                x: 10.0,
+                y: 0.0,
+                width: 95.0,
+                height: 95.0
+            })
+        );
+        assert_eq!(element_bounds(by_id("pct").context("pct")?, 96.0), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use svg_scale::hash::sha256_hex;
+
+/// Rendered PNGs stored by `--render-cache`, one file per key
+pub struct RenderCache {
+    dir: PathBuf,
+}
+
+impl RenderCache {
+    /// Open the cache in `dir`, or in the user cache directory
+    /// (`$XDG_CACHE_HOME`, `~/.cache` or `%LOCALAPPDATA%`) when `None`
+    pub fn open(dir: Option<&Path>) -> Result<Self> {
+        let dir = match dir {
+            Some(dir) => dir.to_path_buf(),
+            None => default_dir().context("找不到用户缓存目录，请用 --render-cache <DIR> 指定")?,
+        };
+        fs::create_dir_all(&dir).with_context(|| format!("创建缓存目录 {}", dir.display()))?;
+        Ok(RenderCache { dir })
+    }
+
+    /// Key of one rendering: the scaled document, the pixel size and the
+    /// options that change the encoded bytes. The crate version is part of
+    /// the key so a renderer upgrade never serves stale images.
+    pub fn key(svg: &str, width: u32, height: u32, options: &str) -> String {
+        let mut data = format!(
+            "{}\n{}x{}\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            width,
+            height,
+            options
+        )
+        .into_bytes();
+        data.extend_from_slice(svg.as_bytes());
+        sha256_hex(&data)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(key)).ok()
+    }
+
+    /// Store `data` under `key`; written to a temporary file first so
+    /// concurrent runs never read a partial entry
+    pub fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, data).context("write render cache")?;
+        fs::rename(&tmp, &path).context("write render cache")?;
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.png", key))
+    }
+}
+
+fn default_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+    let base = var("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| Path::new(&home).join(".cache")))
+        .or_else(|| var("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(base.join("svg-scale"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_entries_by_key() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("svg-scale-cache-{}", std::process::id()));
+        let cache = RenderCache::open(Some(&dir))?;
+        let key = RenderCache::key("<svg/>", 16, 16, "");
+        assert_ne!(key, RenderCache::key("<svg/>", 32, 32, ""));
+        assert_ne!(key, RenderCache::key("<svg/>", 16, 16, "colors=4"));
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, b"png")?;
+        assert_eq!(cache.get(&key).as_deref(), Some(&b"png"[..]));
+        let _ = fs::remove_dir_all(&dir);
+        Ok(())
+    }
+}
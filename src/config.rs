@@ -0,0 +1,323 @@
+//! Named pipelines from `svg-scale.toml`.
+//!
+//! Each `[preset.NAME]` table sets defaults for the options of the same
+//! name, e.g.
+//!
+//! ```toml
+//! [preset.web]
+//! to = [16, 32, 48]
+//! formats = ["svg", "png"]
+//! name-template = "{stem}-{size}.{format}"
+//! out-dir = "dist/icons"
+//! fix-stroke = true
+//! ```
+//!
+//! Keys may use `-` or `_`. Only the TOML this needs is read: tables,
+//! strings, numbers, booleans and arrays of them, and comments.
+
+use anyhow::{bail, Context, Result};
+
+use crate::json::Value;
+
+/// Config file looked up in the current directory when none is given
+pub const CONFIG_FILE: &str = "svg-scale.toml";
+
+/// Presets every config starts with; a file may redefine them
+pub const BUILTIN_PRESETS: &str = r#"
+# VSCode Marketplace icon: a 512 px source drawn at 128 px
+[preset.vscode]
+from = 512
+to = 128
+formats = ["svg", "png"]
+name-template = "icon.{format}"
+out-dir = "images/dist"
+fix-stroke = true
+"#;
+
+/// Defaults one preset gives to the command line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PipelinePreset {
+    /// `--to` list, e.g. `16,32:fix-stroke`
+    pub to: Option<String>,
+    pub from: Option<f64>,
+    pub scale: Option<f64>,
+    pub formats: Vec<String>,
+    pub name_template: Option<String>,
+    pub out_dir: Option<String>,
+    pub fix_stroke: Option<bool>,
+    pub precision: Option<usize>,
+}
+
+/// The preset called `name` from `config` (the text of a config file) or,
+/// failing that, the built-in ones
+pub fn find_preset(config: Option<&str>, name: &str) -> Result<Option<PipelinePreset>> {
+    if let Some(text) = config {
+        let presets = parse_presets(text).context("invalid config")?;
+        if let Some((_, preset)) = presets.into_iter().find(|(n, _)| n == name) {
+            return Ok(Some(preset));
+        }
+    }
+    let builtin = parse_presets(BUILTIN_PRESETS).expect("built-in presets parse");
+    Ok(builtin.into_iter().find(|(n, _)| n == name).map(|(_, p)| p))
+}
+
+/// Names of the presets `config` and the built-ins define, each once, in
+/// the order they first appear
+pub fn preset_names(config: Option<&str>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in config
+        .and_then(|text| parse_presets(text).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .chain(parse_presets(BUILTIN_PRESETS).unwrap_or_default())
+        .map(|(name, _)| name)
+    {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Every `[preset.NAME]` table of a config file, in file order
+pub fn parse_presets(text: &str) -> Result<Vec<(String, PipelinePreset)>> {
+    let mut presets: Vec<(String, PipelinePreset)> = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let line_no = i + 1;
+        let mut line = strip_comment(line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .with_context(|| format!("line {}: unclosed table header", line_no))?
+                .trim();
+            let Some(name) = header.strip_prefix("preset.") else {
+                bail!("line {}: unknown table [{}]", line_no, header);
+            };
+            let name = name.trim().trim_matches(['"', '\'']).to_string();
+            if presets.iter().any(|(n, _)| *n == name) {
+                bail!("line {}: preset {} is defined twice", line_no, name);
+            }
+            presets.push((name, PipelinePreset::default()));
+            continue;
+        }
+        // Arrays may span lines
+        while bracket_depth(&line) > 0 {
+            let (_, next) = lines
+                .next()
+                .with_context(|| format!("line {}: unclosed array", line_no))?;
+            line.push(' ');
+            line.push_str(strip_comment(next).trim());
+        }
+        let (key, value) = line
+            .split_once('=')
+            .with_context(|| format!("line {}: expected key = value", line_no))?;
+        let key = key.trim().replace('_', "-");
+        let value = parse_value(value.trim()).with_context(|| format!("line {}", line_no))?;
+        let Some((name, preset)) = presets.last_mut() else {
+            bail!("line {}: {} is outside a [preset.NAME] table", line_no, key);
+        };
+        set_key(preset, &key, value)
+            .with_context(|| format!("line {}: preset {}", line_no, name))?;
+    }
+    Ok(presets)
+}
+
+fn set_key(preset: &mut PipelinePreset, key: &str, value: Value) -> Result<()> {
+    let string = |v: Value| match v {
+        Value::String(s) => Ok(s),
+        _ => bail!("{} must be a string", key),
+    };
+    let number = |v: &Value| {
+        v.as_f64()
+            .with_context(|| format!("{} must be a number", key))
+    };
+    match key {
+        "to" => {
+            preset.to = Some(match value {
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        Value::Number(n) => Ok(n.to_string()),
+                        Value::String(s) => Ok(s.clone()),
+                        _ => bail!("to entries must be numbers or strings"),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .join(";"),
+                Value::Number(n) => n.to_string(),
+                other => string(other)?,
+            })
+        }
+        "from" => preset.from = Some(number(&value)?),
+        "scale" => preset.scale = Some(number(&value)?),
+        "precision" => preset.precision = Some(number(&value)? as usize),
+        "fix-stroke" => {
+            preset.fix_stroke = Some(
+                value
+                    .as_bool()
+                    .context("fix-stroke must be true or false")?,
+            )
+        }
+        "formats" => {
+            preset.formats = match value {
+                Value::Array(items) => items.into_iter().map(string).collect::<Result<_>>()?,
+                other => string(other)?
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .collect(),
+            }
+        }
+        "name-template" => preset.name_template = Some(string(value)?),
+        "out-dir" => preset.out_dir = Some(string(value)?),
+        _ => bail!("unknown key {}", key),
+    }
+    Ok(())
+}
+
+/// `line` without a trailing `#` comment outside of strings
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+fn bracket_depth(line: &str) -> i32 {
+    let value = line.split_once('=').map_or("", |(_, v)| v);
+    let mut quote = None;
+    let mut depth = 0;
+    for c in value.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+fn parse_value(s: &str) -> Result<Value> {
+    let (value, rest) = value_prefix(s)?;
+    if !rest.trim().is_empty() {
+        bail!("unexpected {} after value", rest.trim());
+    }
+    Ok(value)
+}
+
+/// Parse one value off the front of `s`, returning it and the rest
+fn value_prefix(s: &str) -> Result<(Value, &str)> {
+    let s = s.trim_start();
+    if let Some(rest) = s.strip_prefix('[') {
+        let mut items = Vec::new();
+        let mut rest = rest.trim_start();
+        loop {
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = value_prefix(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after.trim_start();
+            } else if !rest.starts_with(']') {
+                bail!("expected , or ] in array");
+            }
+        }
+    }
+    if let Some(rest) = s.strip_prefix('\'') {
+        let end = rest.find('\'').context("unclosed string")?;
+        return Ok((rest[..end].into(), &rest[end + 1..]));
+    }
+    if let Some(rest) = s.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((out.into(), &rest[i + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(c @ ('"' | '\\')) => out.push(c),
+                    _ => bail!("unsupported escape in string"),
+                },
+                c => out.push(c),
+            }
+        }
+        bail!("unclosed string");
+    }
+    let end = s.find([',', ']', ' ']).unwrap_or(s.len());
+    let (word, rest) = s.split_at(end);
+    let value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match word.replace('_', "").parse::<f64>() {
+            Ok(n) => Value::Number(n),
+            Err(_) => bail!("invalid value {}", word),
+        },
+    };
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_preset_tables() -> Result<()> {
+        let presets = parse_presets(
+            r#"
+# Icons for the docs site
+[preset.web]
+to = [16, 32,
+      "48:precision=2"]  # the large one keeps fewer digits
+formats = ["svg", 'png']
+name_template = "{stem}-{size}.{format}"
+fix-stroke = true
+
+[preset."print"]
+to = "25mm"
+precision = 3
+"#,
+        )?;
+        assert_eq!(presets.len(), 2);
+        let (name, web) = &presets[0];
+        assert_eq!(name, "web");
+        assert_eq!(web.to.as_deref(), Some("16;32;48:precision=2"));
+        assert_eq!(web.formats, ["svg", "png"]);
+        assert_eq!(web.name_template.as_deref(), Some("{stem}-{size}.{format}"));
+        assert_eq!(web.fix_stroke, Some(true));
+        assert_eq!(presets[1].0, "print");
+        assert_eq!(presets[1].1.precision, Some(3));
+
+        assert!(parse_presets("[preset.a]\nsize = 3").is_err());
+        assert!(parse_presets("to = 3").is_err());
+        assert!(parse_presets("[other]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn files_override_built_in_presets() -> Result<()> {
+        let vscode = find_preset(None, "vscode")?.context("built in")?;
+        assert_eq!(vscode.to.as_deref(), Some("128"));
+        assert_eq!(vscode.from, Some(512.0));
+        let custom = find_preset(Some("[preset.vscode]\nto = 256"), "vscode")?;
+        assert_eq!(custom.and_then(|p| p.to).as_deref(), Some("256"));
+        assert_eq!(find_preset(Some(""), "missing")?, None);
+        assert_eq!(
+            preset_names(Some("[preset.vscode]\nto = 256\n[preset.web]\nto = 32")),
+            ["vscode", "web"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{BufRead, Write};
+
+use svg_scale::json::{self, Value};
+use svg_scale::{scale_svg, ScaleOptions};
+
+const PARSE_ERROR: f64 = -32700.0;
+const INVALID_REQUEST: f64 = -32600.0;
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const INVALID_PARAMS: f64 = -32602.0;
+const SCALE_FAILED: f64 = -32000.0;
+
+/// Serve newline-delimited JSON-RPC 2.0 requests until stdin is closed.
+///
+/// Supported method: `scale` with params `svg` (document text) or `input`
+/// (file path), plus `scale` / `to` / `from` / `precision` / `fix_stroke` / `dpi` / `exact`.
+pub fn run(input: impl BufRead, mut output: impl Write) -> Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(resp) = handle_line(&line) {
+            writeln!(output, "{}", resp)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn response(id: Value, body: (&str, Value)) -> Value {
+    Value::Object(vec![
+        ("jsonrpc".to_string(), "2.0".into()),
+        ("id".to_string(), id),
+        (body.0.to_string(), body.1),
+    ])
+}
+
+fn error(id: Value, code: f64, message: String) -> Value {
+    response(
+        id,
+        (
+            "error",
+            Value::Object(vec![
+                ("code".to_string(), code.into()),
+                ("message".to_string(), message.into()),
+            ]),
+        ),
+    )
+}
+
+/// The response to one request line; `None` for notifications (objects
+/// without an `id`), which get no response, not even an error. Scaling has
+/// no side effects, so they are not run either.
+fn handle_line(line: &str) -> Option<Value> {
+    let req = match json::parse(line) {
+        Ok(v) => v,
+        Err(e) => return Some(error(Value::Null, PARSE_ERROR, e.to_string())),
+    };
+    let id = match req.get("id") {
+        Some(id) => id.clone(),
+        None if matches!(req, Value::Object(_)) => return None,
+        None => Value::Null,
+    };
+    let Some(method) = req.get("method").and_then(Value::as_str) else {
+        return Some(error(id, INVALID_REQUEST, "missing method".to_string()));
+    };
+    if method != "scale" {
+        return Some(error(
+            id,
+            METHOD_NOT_FOUND,
+            format!("unknown method: {}", method),
+        ));
+    }
+    let params = req.get("params").cloned().unwrap_or(Value::Object(vec![]));
+    let opts = match ScaleOptions::from_json(&params) {
+        Ok(opts) => opts,
+        Err(e) => return Some(error(id, INVALID_PARAMS, format!("{:#}", e))),
+    };
+    Some(match scale_request(&params, &opts) {
+        Ok(svg) => response(
+            id,
+            (
+                "result",
+                Value::Object(vec![("svg".to_string(), svg.into())]),
+            ),
+        ),
+        Err(e) => error(id, SCALE_FAILED, format!("{:#}", e)),
+    })
+}
+
+fn scale_request(params: &Value, opts: &ScaleOptions) -> Result<String> {
+    let input_svg = match (
+        params.get("svg").and_then(Value::as_str),
+        params.get("input").and_then(Value::as_str),
+    ) {
+        (Some(svg), _) => svg.to_string(),
+        (None, Some(path)) => fs::read_to_string(path).with_context(|| format!("read {}", path))?,
+        (None, None) => bail!("params must contain svg or input"),
+    };
+    scale_svg(&input_svg, opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daemon_scales_each_request_line() -> Result<()> {
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"scale","params":{"svg":"<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\"><rect width=\"50\"/></svg>","to":50}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"scale","params":{"svg":"<svg/>","to":50}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"nope"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"nope"}"#,
+            "\n",
+        );
+        let mut out = Vec::new();
+        run(input.as_bytes(), &mut out)?;
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let first = json::parse(lines[0])?;
+        let svg = first
+            .get("result")
+            .and_then(|r| r.get("svg"))
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        assert!(svg.contains(r#"<rect width="25"/>"#), "got: {svg}");
+
+        let second = json::parse(lines[1])?;
+        assert_eq!(second.get("id").and_then(Value::as_f64), Some(2.0));
+        assert_eq!(
+            second
+                .get("error")
+                .and_then(|e| e.get("code"))
+                .and_then(Value::as_f64),
+            Some(METHOD_NOT_FOUND)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn daemon_reports_parse_errors() {
+        let resp = handle_line("{not json");
+        assert_eq!(
+            resp.as_ref()
+                .and_then(|r| r.get("error"))
+                .and_then(|e| e.get("code"))
+                .and_then(Value::as_f64),
+            Some(PARSE_ERROR)
+        );
+    }
+
+    #[test]
+    fn daemon_rejects_out_of_range_precision() -> Result<()> {
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"scale","params":{"svg":"<svg width=\"10\"/>","scale":2,"precision":20000000}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"scale","params":{"svg":"<svg width=\"10\"/>","scale":2}}"#,
+            "\n",
+        );
+        let mut out = Vec::new();
+        run(input.as_bytes(), &mut out)?;
+        let out = String::from_utf8(out)?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2, "got: {out}");
+        let first = json::parse(lines[0])?;
+        let code = first
+            .get("error")
+            .and_then(|e| e.get("code"))
+            .and_then(Value::as_f64);
+        assert_eq!(code, Some(INVALID_PARAMS), "got: {first}");
+        let second = json::parse(lines[1])?;
+        assert!(second.get("result").is_some(), "got: {second}");
+        Ok(())
+    }
+}
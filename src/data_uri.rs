@@ -0,0 +1,188 @@
+//! `data:` URI decoding and encoding for SVG documents embedded in CSS,
+//! HTML or other SVG.
+
+use anyhow::{bail, Context, Result};
+
+/// Decode `data:image/svg+xml[;charset=...][;base64],...` to the document text.
+///
+/// Both the base64 and the percent-encoded (URL-encoded) forms are accepted;
+/// an empty media type is taken as SVG. Returns `None` when `input` is not a
+/// `data:` URI at all.
+pub fn decode(input: &str) -> Result<Option<String>> {
+    let Some(rest) = strip_scheme(input.trim()) else {
+        return Ok(None);
+    };
+    let (header, payload) = rest.split_once(',').context("data URI is missing ','")?;
+    let mut params = header.split(';').map(str::trim);
+    let media_type = params.next().unwrap_or("").to_ascii_lowercase();
+    if !matches!(
+        media_type.as_str(),
+        "" | "image/svg+xml" | "image/svg" | "text/xml" | "application/xml"
+    ) {
+        bail!(
+            "data URI has media type {}, expected image/svg+xml",
+            media_type
+        );
+    }
+    let base64 = params.any(|p| p.eq_ignore_ascii_case("base64"));
+
+    let bytes = percent_decode(payload)?;
+    let bytes = if base64 {
+        base64_decode(&bytes)?
+    } else {
+        bytes
+    };
+    let text = String::from_utf8(bytes).context("data URI content is not UTF-8")?;
+    Ok(Some(text))
+}
+
+/// The lowercased media type of a `data:` URI; `None` when `input` is not one
+pub fn media_type(input: &str) -> Option<String> {
+    let rest = strip_scheme(input.trim())?;
+    let header = rest.split(',').next()?;
+    Some(header.split(';').next()?.trim().to_ascii_lowercase())
+}
+
+/// Encode `text` as a `data:` URI with the same header as `original`: base64
+/// when it was, percent-encoded otherwise
+pub fn encode_like(original: &str, text: &str) -> String {
+    let rest = strip_scheme(original.trim()).unwrap_or("image/svg+xml,");
+    let header = rest.split(',').next().unwrap_or("image/svg+xml");
+    let base64 = header
+        .split(';')
+        .any(|p| p.trim().eq_ignore_ascii_case("base64"));
+    let payload = if base64 {
+        base64_encode(text.as_bytes())
+    } else {
+        percent_encode(text)
+    };
+    format!("data:{},{}", header, payload)
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for &b in s.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => out.push(b as char),
+            b'-' | b'_' | b'.' | b'~' | b'/' | b':' | b'=' | b';' | b',' | b'(' | b')' | b'!'
+            | b'*' | b'+' | b'@' | b'?' | b'&' | b'$' | b'[' | b']' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn strip_scheme(s: &str) -> Option<&str> {
+    let prefix = s.get(..5)?;
+    prefix.eq_ignore_ascii_case("data:").then(|| &s[5..])
+}
+
+fn percent_decode(s: &str) -> Result<Vec<u8>> {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' {
+            let hex = s
+                .get(i + 1..i + 3)
+                .context("truncated %-escape in data URI")?;
+            let byte = u8::from_str_radix(hex, 16)
+                .with_context(|| format!("invalid %-escape in data URI: %{}", hex))?;
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(b[i]);
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+fn base64_decode(input: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &c in input {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            // URL-safe alphabet too
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            c => bail!("invalid base64 character {:?} in data URI", c as char),
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_base64_and_percent_encoded_forms() -> Result<()> {
+        // <svg/>
+        assert_eq!(
+            decode("data:image/svg+xml;base64,PHN2Zy8+")?.as_deref(),
+            Some("<svg/>")
+        );
+        assert_eq!(
+            decode("data:image/svg+xml;charset=utf-8,%3Csvg%20width='2'/%3E")?.as_deref(),
+            Some("<svg width='2'/>")
+        );
+        assert_eq!(decode("icon.svg")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_other_media_types() {
+        assert!(decode("data:image/png;base64,AAAA").is_err());
+    }
+
+    #[test]
+    fn encodes_in_the_original_form() -> Result<()> {
+        let base64 = "data:image/svg+xml;base64,PHN2Zy8+";
+        assert_eq!(encode_like(base64, "<svg/>"), base64);
+        assert_eq!(encode_like(base64, "<g>"), "data:image/svg+xml;base64,PGc+");
+        let percent = encode_like("data:image/svg+xml;utf8,", "<svg width='2'/>");
+        assert_eq!(
+            percent,
+            "data:image/svg+xml;utf8,%3Csvg%20width=%272%27/%3E"
+        );
+        assert_eq!(decode(&percent)?.as_deref(), Some("<svg width='2'/>"));
+        assert_eq!(
+            media_type(" data:Image/SVG+xml;base64,x").as_deref(),
+            Some("image/svg+xml")
+        );
+        assert_eq!(media_type("icon.svg"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+//! Exit codes for the failure classes wrapper scripts branch on, so they
+//! need not match the (localized) error text.
+
+use std::fmt;
+
+/// Exit code table appended to `--help`
+pub const HELP: &str = "退出码:
+  0  成功
+  1  其他错误
+  2  命令行参数错误
+  3  输入不是合法的 SVG/XML
+  4  --strict 下出现警告（字体缺失、宽高比不符、标记尺寸变化）
+  5  --verify 或 --self-check 校验失败
+  6  批量处理中有输入或尺寸失败（其余已完成，见 --keep-going）";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Failure {
+    Parse,
+    Strict,
+    Verify,
+    Partial,
+}
+
+impl Failure {
+    pub fn code(self) -> u8 {
+        match self {
+            Failure::Parse => 3,
+            Failure::Strict => 4,
+            Failure::Verify => 5,
+            Failure::Partial => 6,
+        }
+    }
+
+    /// An error with `message` that exits with this failure's code
+    pub fn error(self, message: impl Into<String>) -> anyhow::Error {
+        anyhow::Error::new(Classified {
+            failure: self,
+            message: message.into(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Classified {
+    failure: Failure,
+    message: String,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Classified {}
+
+/// Exit code for `e`: the failure class it or any cause was raised with,
+/// [`Failure::Parse`] for malformed XML, 1 otherwise
+pub fn code_of(e: &anyhow::Error) -> u8 {
+    for cause in e.chain() {
+        if let Some(classified) = cause.downcast_ref::<Classified>() {
+            return classified.failure.code();
+        }
+        if cause.is::<roxmltree::Error>() {
+            return Failure::Parse.code();
+        }
+    }
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn codes_survive_added_context() {
+        let strict = Err::<(), _>(Failure::Strict.error("missing font")).context("处理 a.svg 失败");
+        let strict = strict.unwrap_err();
+        assert_eq!(code_of(&strict), 4);
+        assert_eq!(format!("{:#}", strict), "处理 a.svg 失败: missing font");
+
+        let parse = roxmltree::Document::parse("<svg").map_err(anyhow::Error::from);
+        let parse = parse.context("处理 b.svg 失败").unwrap_err();
+        assert_eq!(code_of(&parse), 3);
+        assert_eq!(code_of(&anyhow::anyhow!("other")), 1);
+    }
+}
@@ -0,0 +1,313 @@
+//! Per-attribute account of what the scaling walk did, for `--explain`.
+//!
+//! The walk records one [`Explanation`] for every attribute (and style
+//! property) of the elements matching the requested selectors, so users can
+//! see why a value was left alone without reading the walker.
+//! [`ScaleEvents`] reports every element, for tools that consume the
+//! decisions rather than the scaled document. It runs the whole walk up
+//! front, so it holds one [`Explanation`] per scaled attribute in memory.
+
+use anyhow::Result;
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::scale::ScaleCtx;
+use crate::svg::{self, SelectorList, StyleSheet, WalkOptions};
+
+/// What happened to one attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Scaled,
+    /// A [`WalkOptions::hooks`] entry decided
+    Hook,
+    /// Not a geometric attribute, or nothing in the value depends on the scale
+    NotGeometry,
+    /// Percentages and units the scaler does not touch (`em`, `ex`, ...)
+    UnsupportedUnit,
+    /// Scaling is carried by an ancestor's rotation/skew/scale transform
+    AncestorTransform,
+    /// Scaling is carried by the element's own non-translate transform
+    OwnTransform,
+    /// Coordinates are fractions of a bounding box (`objectBoundingBox` units)
+    ObjectBoundingBox,
+    /// A pattern's `viewBox` maps its unscaled content onto the scaled tile
+    PatternViewBox,
+    /// `vector-effect="non-scaling-stroke"` keeps the stroke width
+    NonScalingStroke,
+    /// `vector-effect="non-scaling-size"` keeps the element's geometry
+    NonScalingSize,
+    /// Matched by [`WalkOptions::exclude`]
+    Excluded,
+    /// Outside every [`WalkOptions::only`] match
+    OutsideOnly,
+    /// Root `width`/`height` rewritten by [`WalkOptions::root_size`]
+    RootSize,
+    /// `vector-effect` keywords dropped by `--fix-stroke`
+    FixStroke,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Decision::Scaled => "scaled",
+            Decision::Hook => "handled by a hook",
+            Decision::NotGeometry => "not scaled: no length in the value",
+            Decision::UnsupportedUnit => "not scaled: percentage or unsupported unit",
+            Decision::AncestorTransform => {
+                "skipped: an ancestor's non-translate transform carries the scale"
+            }
+            Decision::OwnTransform => "skipped: the element's own transform carries the scale",
+            Decision::ObjectBoundingBox => "skipped: objectBoundingBox units",
+            Decision::PatternViewBox => "kept: the pattern content stays in viewBox coordinates",
+            Decision::NonScalingStroke => "kept: vector-effect non-scaling-stroke",
+            Decision::NonScalingSize => "kept: vector-effect non-scaling-size",
+            Decision::Excluded => "kept: matched --exclude",
+            Decision::OutsideOnly => "kept: outside every --only match",
+            Decision::RootSize => "rewritten by the root size mode",
+            Decision::FixStroke => "rewritten by --fix-stroke",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// 1-based line of the element in the source
+    pub line: u32,
+    /// Tag name, with `#id` when the element has one
+    pub element: String,
+    /// Position of the element from the root, e.g. `/svg[1]/g[2]/rect[1]`,
+    /// counting only siblings with the same tag name
+    pub path: String,
+    /// Attribute name; style properties are written as `style:name`
+    pub attribute: String,
+    pub original: String,
+    /// Emitted value; `None` when the attribute was dropped
+    pub result: Option<String>,
+    pub decision: Decision,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {} <{}> {}=\"{}\": {}",
+            self.line, self.element, self.attribute, self.original, self.decision
+        )?;
+        match &self.result {
+            Some(r) if *r != self.original => write!(f, " -> \"{}\"", r),
+            Some(_) => Ok(()),
+            None => f.write_str(" (removed)"),
+        }
+    }
+}
+
+/// Collects explanations for the elements matching `selectors` (all of them
+/// when `None`) during a walk
+pub(crate) struct Recorder<'a> {
+    pub selectors: Option<&'a SelectorList>,
+    pub records: RefCell<Vec<Explanation>>,
+}
+
+impl Recorder<'_> {
+    pub fn wants(&self, node: roxmltree::Node) -> bool {
+        self.selectors.is_none_or(|s| s.matches(node))
+    }
+
+    pub fn record(
+        &self,
+        node: roxmltree::Node,
+        attribute: impl Into<String>,
+        original: &str,
+        result: Option<&str>,
+        decision: Decision,
+    ) {
+        let id = node.attribute("id").unwrap_or("");
+        let tag = node.tag_name().name();
+        self.records.borrow_mut().push(Explanation {
+            line: node.document().text_pos_at(node.range().start).row,
+            element: if id.is_empty() {
+                tag.to_string()
+            } else {
+                format!("{}#{}", tag, id)
+            },
+            path: element_path(node),
+            attribute: attribute.into(),
+            original: original.to_string(),
+            result: result.map(str::to_string),
+            decision,
+        });
+    }
+}
+
+/// Run the scaling walk over `doc` and report every attribute decision on
+/// the elements matching `selectors`, in document order
+pub fn explain(
+    doc: &roxmltree::Document,
+    ctx: &ScaleCtx,
+    opts: &WalkOptions,
+    selectors: &SelectorList,
+) -> Result<Vec<Explanation>> {
+    record_walk(doc, ctx, opts, Some(selectors))
+}
+
+/// Every attribute decision of a scaling walk over all elements, in
+/// document order. The decisions are collected when it is created and then
+/// handed out one by one; it is not a lazy walk
+pub struct ScaleEvents {
+    events: std::vec::IntoIter<Explanation>,
+}
+
+impl ScaleEvents {
+    /// Run the walk over `doc` and collect its decisions, without building
+    /// the scaled document
+    pub fn new(doc: &roxmltree::Document, ctx: &ScaleCtx, opts: &WalkOptions) -> Result<Self> {
+        Ok(ScaleEvents {
+            events: record_walk(doc, ctx, opts, None)?.into_iter(),
+        })
+    }
+}
+
+impl Iterator for ScaleEvents {
+    type Item = Explanation;
+
+    fn next(&mut self) -> Option<Explanation> {
+        self.events.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.events.size_hint()
+    }
+}
+
+impl ExactSizeIterator for ScaleEvents {}
+
+fn record_walk(
+    doc: &roxmltree::Document,
+    ctx: &ScaleCtx,
+    opts: &WalkOptions,
+    selectors: Option<&SelectorList>,
+) -> Result<Vec<Explanation>> {
+    let recorder = Recorder {
+        selectors,
+        records: RefCell::new(Vec::new()),
+    };
+    let styles = StyleSheet::collect(doc.root_element());
+    svg::walk_recorded(
+        doc.root_element(),
+        &mut svg::Discard,
+        ctx,
+        opts,
+        &styles,
+        &recorder,
+    )?;
+    Ok(recorder.records.into_inner())
+}
+
+fn element_path(node: roxmltree::Node) -> String {
+    let mut steps: Vec<String> = node
+        .ancestors()
+        .filter(|n| n.is_element())
+        .map(|n| {
+            let tag = n.tag_name();
+            // `prev_siblings` starts at the node itself
+            let index = n
+                .prev_siblings()
+                .filter(|s| s.is_element() && s.tag_name() == tag)
+                .count();
+            format!("{}[{}]", tag.name(), index)
+        })
+        .collect();
+    steps.reverse();
+    format!("/{}", steps.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str, selectors: &str) -> Result<Vec<Explanation>> {
+        let doc = roxmltree::Document::parse(input)?;
+        let ctx = ScaleCtx {
+            scale: 2.0,
+            ..ScaleCtx::default()
+        };
+        explain(&doc, &ctx, &WalkOptions::default(), &selectors.parse()?)
+    }
+
+    fn decision_of(found: &[Explanation], element: &str, attribute: &str) -> Option<Decision> {
+        found
+            .iter()
+            .find(|e| e.element == element && e.attribute == attribute)
+            .map(|e| e.decision)
+    }
+
+    #[test]
+    fn reports_why_values_were_left_alone() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24">
+<g transform="rotate(30)"><rect id="a" x="1" width="50%" fill="red"/></g>
+<rect id="b" x="1" width="2em" style="stroke-width:2" vector-effect="non-scaling-stroke" stroke-width="3"/>
+<clipPath clipPathUnits="objectBoundingBox"><rect id="c" x="0.5"/></clipPath>
+</svg>"#;
+        let found = run(input, "rect")?;
+        assert_eq!(
+            decision_of(&found, "rect#a", "x"),
+            Some(Decision::AncestorTransform)
+        );
+        assert_eq!(
+            decision_of(&found, "rect#a", "fill"),
+            Some(Decision::NotGeometry)
+        );
+        assert_eq!(decision_of(&found, "rect#b", "x"), Some(Decision::Scaled));
+        assert_eq!(
+            decision_of(&found, "rect#b", "width"),
+            Some(Decision::UnsupportedUnit)
+        );
+        assert_eq!(
+            decision_of(&found, "rect#b", "stroke-width"),
+            Some(Decision::NonScalingStroke)
+        );
+        assert_eq!(
+            decision_of(&found, "rect#b", "style:stroke-width"),
+            Some(Decision::NonScalingStroke)
+        );
+        assert_eq!(
+            decision_of(&found, "rect#c", "x"),
+            Some(Decision::ObjectBoundingBox)
+        );
+        assert!(found.iter().all(|e| e.element.starts_with("rect")));
+        let b_x = found
+            .iter()
+            .find(|e| e.element == "rect#b" && e.attribute == "x")
+            .unwrap();
+        assert_eq!(b_x.line, 3);
+        assert_eq!(b_x.to_string(), "line 3 <rect#b> x=\"1\": scaled -> \"2\"");
+        Ok(())
+    }
+
+    #[test]
+    fn events_cover_every_element() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><g/><g><rect x="1"/><circle r="2"/><rect x="3"/></g></svg>"#,
+        )?;
+        let ctx = ScaleCtx {
+            scale: 2.0,
+            ..ScaleCtx::default()
+        };
+        let events: Vec<_> = ScaleEvents::new(&doc, &ctx, &WalkOptions::default())?
+            .map(|e| (e.path, e.attribute, e.result))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                ("/svg[1]/g[2]/rect[1]".into(), "x".into(), Some("2".into())),
+                (
+                    "/svg[1]/g[2]/circle[1]".into(),
+                    "r".into(),
+                    Some("4".into())
+                ),
+                ("/svg[1]/g[2]/rect[2]".into(), "x".into(), Some("6".into())),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! Browser extension icons.
+//!
+//! Chrome and Firefox both take the toolbar, management page and store
+//! icons from the manifest's `icons` map, keyed by size. Chrome only reads
+//! PNGs, named `icon<size>.png` in its samples; Firefox also reads SVG and
+//! its samples use `icon-<size>`.
+
+use crate::json::Value;
+
+pub const EXTENSION_SIZES: &[u32] = &[16, 32, 48, 128];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Store {
+    Chrome,
+    Firefox,
+}
+
+impl Store {
+    /// File name of the icon at `size` with extension `ext`
+    pub fn file_name(self, size: u32, ext: &str) -> String {
+        match self {
+            Store::Chrome => format!("icon{}.{}", size, ext),
+            Store::Firefox => format!("icon-{}.{}", size, ext),
+        }
+    }
+
+    /// The manifest's `"icons"` entry for icons under `dir`: PNGs for
+    /// Chrome, the SVGs for Firefox
+    pub fn manifest_icons(self, dir: &str) -> String {
+        let ext = match self {
+            Store::Chrome => "png",
+            Store::Firefox => "svg",
+        };
+        let dir = dir.trim_end_matches('/');
+        let icons = EXTENSION_SIZES
+            .iter()
+            .map(|&size| {
+                let name = self.file_name(size, ext);
+                let path = match dir {
+                    "" | "." => name,
+                    dir => format!("{}/{}", dir, name),
+                };
+                (size.to_string(), path.into())
+            })
+            .collect();
+        format!("\"icons\":{}", Value::Object(icons))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_name_their_icons_differently() {
+        assert_eq!(Store::Chrome.file_name(16, "png"), "icon16.png");
+        assert_eq!(Store::Firefox.file_name(16, "svg"), "icon-16.svg");
+        assert_eq!(
+            Store::Chrome.manifest_icons("icons/"),
+            r#""icons":{"16":"icons/icon16.png","32":"icons/icon32.png","48":"icons/icon48.png","128":"icons/icon128.png"}"#
+        );
+        assert!(Store::Firefox
+            .manifest_icons(".")
+            .starts_with(r#""icons":{"16":"icon-16.svg""#));
+    }
+}
@@ -0,0 +1,109 @@
+//! Website favicon bundles.
+//!
+//! The set covers browsers (`favicon.ico` and PNGs at 16, 32 and 48 px), iOS
+//! home screens (`apple-touch-icon.png`) and installed web apps (maskable
+//! PNGs listed in `site.webmanifest`). A maskable icon may be cropped to any
+//! shape that keeps the central 80% circle, so its artwork is padded into it.
+
+use anyhow::Result;
+
+use crate::json::Value;
+use crate::viewbox;
+
+/// Sizes of the standalone PNGs, which are also the `favicon.ico` frames
+pub const FAVICON_SIZES: &[u32] = &[16, 32, 48];
+
+/// Size iOS asks for on every device since the iPhone 6 Plus
+pub const APPLE_TOUCH_SIZE: u32 = 180;
+
+/// Maskable icon sizes the web app manifest lists
+pub const MASKABLE_SIZES: &[u32] = &[192, 512];
+
+/// Fraction of a maskable icon's side inside its safe zone
+pub const MASKABLE_SAFE_ZONE: f64 = 0.8;
+
+pub fn png_name(size: u32) -> String {
+    format!("favicon-{size}x{size}.png")
+}
+
+pub fn maskable_name(size: u32) -> String {
+    format!("icon-{size}-maskable.png")
+}
+
+/// The document with its viewBox grown evenly so the drawing's longer side
+/// spans [`MASKABLE_SAFE_ZONE`] of the square it is rendered into
+pub fn maskable_document(doc: &roxmltree::Document, dpi: f64) -> Result<String> {
+    let current = viewbox::current_view_box(doc, dpi)?;
+    let side = current.width.max(current.height);
+    let padding = side * (1.0 / MASKABLE_SAFE_ZONE - 1.0) / 2.0;
+    viewbox::pad_view_box(doc, padding, dpi)
+}
+
+/// `site.webmanifest` listing the maskable icons
+pub fn webmanifest() -> String {
+    let icons = MASKABLE_SIZES
+        .iter()
+        .map(|&size| {
+            Value::Object(vec![
+                (
+                    "src".to_string(),
+                    format!("/{}", maskable_name(size)).into(),
+                ),
+                ("sizes".to_string(), format!("{size}x{size}").into()),
+                ("type".to_string(), "image/png".into()),
+                ("purpose".to_string(), "maskable".into()),
+            ])
+        })
+        .collect();
+    let manifest = Value::Object(vec![("icons".to_string(), Value::Array(icons))]);
+    format!("{}\n", manifest)
+}
+
+/// The `<link>` tags that reference the bundle from a page's `<head>`
+pub fn link_tags() -> String {
+    let mut tags = vec![r#"<link rel="icon" href="/favicon.ico" sizes="48x48">"#.to_string()];
+    for &size in FAVICON_SIZES.iter().rev() {
+        tags.push(format!(
+            r#"<link rel="icon" type="image/png" sizes="{size}x{size}" href="/{}">"#,
+            png_name(size)
+        ));
+    }
+    tags.push(r#"<link rel="apple-touch-icon" href="/apple-touch-icon.png">"#.to_string());
+    tags.push(r#"<link rel="manifest" href="/site.webmanifest">"#.to_string());
+    tags.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn maskable_artwork_fits_the_safe_zone() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 40 20"/>"#,
+        )?;
+        let padded = maskable_document(&doc, 96.0)?;
+        assert!(padded.contains(r#"viewBox="-5 -5 50 30""#), "got: {padded}");
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_lists_the_maskable_icons() -> Result<()> {
+        let manifest = json::parse(&webmanifest())?;
+        let Some(Value::Array(icons)) = manifest.get("icons") else {
+            panic!("no icons in {manifest}");
+        };
+        assert_eq!(icons.len(), MASKABLE_SIZES.len());
+        assert_eq!(
+            icons[1].get("src").and_then(Value::as_str),
+            Some("/icon-512-maskable.png")
+        );
+        assert_eq!(
+            icons[1].get("purpose").and_then(Value::as_str),
+            Some("maskable")
+        );
+        assert!(link_tags().contains(r#"href="/favicon-32x32.png""#));
+        Ok(())
+    }
+}
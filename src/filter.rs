@@ -0,0 +1,158 @@
+//! Filter primitive geometry that is not a plain length.
+//!
+//! `feTurbulence` samples noise at `baseFrequency` cycles per user unit, so
+//! the frequency scales by `1 / scale` for the pattern to keep its look.
+//! With `stitchTiles="stitch"` the renderer first rounds the frequency to a
+//! whole number of cycles across the tile (the primitive subregion, else the
+//! filter region); that rounding is done before scaling when the tile is
+//! known in user units, so the scaled tile holds the same number of cycles.
+//! `seed`, `numOctaves`, `type` and `stitchTiles` pick the noise, not its
+//! size, and are never changed.
+
+use roxmltree::Node;
+
+use crate::scale::ScaleCtx;
+
+/// `feTurbulence` attributes that hold no geometry
+pub fn is_turbulence_constant(name: &str) -> bool {
+    matches!(name, "seed" | "numOctaves" | "type" | "stitchTiles")
+}
+
+/// The `fx fy` of a `baseFrequency`; a single number is used for both
+pub fn parse_base_frequency(value: &str) -> Option<(f64, f64)> {
+    let mut numbers = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0));
+    let fx = numbers.next()??;
+    let fy = match numbers.next() {
+        Some(fy) => fy?,
+        None => fx,
+    };
+    if numbers.next().is_some() {
+        return None;
+    }
+    Some((fx, fy))
+}
+
+/// `baseFrequency` scaled by `1 / scale`; values that do not parse are kept
+pub fn scale_base_frequency(value: &str, ctx: &ScaleCtx) -> String {
+    match parse_base_frequency(value) {
+        Some((fx, fy)) if ctx.scale != 0.0 => {
+            format_base_frequency(value, fx / ctx.scale, fy / ctx.scale, ctx)
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// The frequency a stitching `feTurbulence` renders with, or `None` when it
+/// does not stitch or its tile size is not known in user units
+pub fn stitched_base_frequency(node: Node, value: &str) -> Option<(f64, f64)> {
+    if node.attribute("stitchTiles").map(str::trim) != Some("stitch") {
+        return None;
+    }
+    let (fx, fy) = parse_base_frequency(value)?;
+    let (width, height) = tile_size(node)?;
+    Some((stitch(fx, width), stitch(fy, height)))
+}
+
+/// `fx fy` written in `value`'s form: one number when it had one and
+/// both axes still agree
+pub fn format_base_frequency(value: &str, fx: f64, fy: f64, ctx: &ScaleCtx) -> String {
+    let single = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .count()
+        == 1;
+    let (x, y) = (ctx.fmt(fx), ctx.fmt(fy));
+    if single && x == y {
+        x
+    } else {
+        format!("{} {}", x, y)
+    }
+}
+
+/// Round `freq` to a whole number of cycles over `tile`, the way the Filter
+/// Effects reference implementation does
+fn stitch(freq: f64, tile: f64) -> f64 {
+    if freq == 0.0 {
+        return freq;
+    }
+    let lo = (tile * freq).floor() / tile;
+    let hi = (tile * freq).ceil() / tile;
+    if freq / lo < hi / freq {
+        lo
+    } else {
+        hi
+    }
+}
+
+/// Width and height of the tile a `feTurbulence` stitches: its own subregion
+/// in user units, else the region of a `userSpaceOnUse` filter
+fn tile_size(node: Node) -> Option<(f64, f64)> {
+    let filter = node.parent_element().filter(|p| p.has_tag_name("filter"))?;
+    let units = |name, default| filter.attribute(name).unwrap_or(default).trim();
+    let own = |name| user_units(node.attribute(name)?);
+    let region = |name| {
+        if units("filterUnits", "objectBoundingBox") == "userSpaceOnUse" {
+            user_units(filter.attribute(name)?)
+        } else {
+            None
+        }
+    };
+    let user_space_primitive = units("primitiveUnits", "userSpaceOnUse") == "userSpaceOnUse";
+    let side = |name| {
+        user_space_primitive
+            .then(|| own(name))
+            .flatten()
+            .or_else(|| region(name))
+            .filter(|&n| n > 0.0)
+    };
+    Some((side("width")?, side("height")?))
+}
+
+fn user_units(value: &str) -> Option<f64> {
+    let value = value.trim();
+    value
+        .strip_suffix("px")
+        .unwrap_or(value)
+        .trim()
+        .parse::<f64>()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn stitching_rounds_to_whole_cycles_over_the_tile() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><filter filterUnits="userSpaceOnUse" width="100" height="50"><feTurbulence id="region" baseFrequency="0.023" stitchTiles="stitch"/><feTurbulence id="own" width="20" height="20" baseFrequency="0.1 0.12" stitchTiles="stitch"/><feTurbulence id="plain" baseFrequency="0.023"/></filter></svg>"#,
+        )?;
+        let node = |id| doc.descendants().find(|n| n.attribute("id") == Some(id));
+        let freq = |id| {
+            let n = node(id)?;
+            stitched_base_frequency(n, n.attribute("baseFrequency")?)
+        };
+        // 2.3 cycles over 100 -> 2; 1.15 over 50 -> 1
+        assert_eq!(freq("region"), Some((0.02, 0.02)));
+        // 2 over 20 stays; 2.4 -> 2
+        assert_eq!(freq("own"), Some((0.1, 0.1)));
+        assert_eq!(freq("plain"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn base_frequency_keeps_its_form() {
+        let ctx = ScaleCtx {
+            scale: 0.5,
+            ..ScaleCtx::default()
+        };
+        assert_eq!(scale_base_frequency("0.05", &ctx), "0.1");
+        assert_eq!(scale_base_frequency("0.05, 0.1", &ctx), "0.1 0.2");
+        assert_eq!(scale_base_frequency("0.05 0.1 0.2", &ctx), "0.05 0.1 0.2");
+        assert_eq!(scale_base_frequency("-1", &ctx), "-1");
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Whether a path component uses any wildcard
+fn has_wildcard(component: &str) -> bool {
+    component.contains(['*', '?', '['])
+}
+
+/// Expand `*`, `?`, `[abc]` / `[a-z]` and `**` (any number of directories)
+/// in `pattern`, for shells such as cmd.exe that pass them through. Inputs
+/// without wildcards and `data:` URIs are returned unchanged.
+pub fn expand(pattern: &str) -> Result<Vec<String>> {
+    let is_data_uri = pattern
+        .get(..5)
+        .is_some_and(|p| p.eq_ignore_ascii_case("data:"));
+    if is_data_uri || !has_wildcard(pattern) {
+        return Ok(vec![pattern.to_string()]);
+    }
+    let (base, components) = split_root(pattern);
+    let mut found = Vec::new();
+    walk(&base, &components, &mut found);
+    found.sort();
+    found.dedup();
+    if found.is_empty() {
+        bail!("没有文件匹配 {}", pattern);
+    }
+    Ok(found
+        .into_iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect())
+}
+
+/// The directory a wildcard pattern is matched under: its components
+/// before the first one with a wildcard. `None` for plain paths.
+pub fn base(pattern: &str) -> Option<PathBuf> {
+    if !has_wildcard(pattern) {
+        return None;
+    }
+    let (mut base, components) = split_root(pattern);
+    for component in components {
+        if has_wildcard(component) {
+            break;
+        }
+        base.push(component);
+    }
+    Some(base)
+}
+
+/// `pattern`'s drive or share prefix and root (`/`, `C:\`), where matching
+/// starts, and the components after them. Keeping the prefix and root
+/// together matters on Windows, where `C:` joined with `icons` is relative
+/// to the drive's current directory.
+fn split_root(pattern: &str) -> (PathBuf, Vec<&str>) {
+    let mut root = PathBuf::new();
+    let mut consumed = 0;
+    for component in Path::new(pattern).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => {
+                root.push(component.as_os_str());
+                consumed += component.as_os_str().len();
+            }
+            _ => break,
+        }
+    }
+    let rest = pattern.get(consumed..).unwrap_or("");
+    let components = rest.split(['/', '\\']).filter(|c| !c.is_empty()).collect();
+    (root, components)
+}
+
+fn walk(dir: &Path, components: &[&str], found: &mut Vec<PathBuf>) {
+    let Some((&first, rest)) = components.split_first() else {
+        if dir.is_file() {
+            found.push(dir.to_path_buf());
+        }
+        return;
+    };
+    if first == "**" {
+        // Zero directories, then one more level with `**` still in front.
+        // Symlinked directories are not followed, so a link back up the
+        // tree cannot repeat it, and hidden ones are skipped like hidden files
+        walk(dir, rest, found);
+        for entry in read_dir_sorted(dir) {
+            let hidden = entry
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            let is_dir = fs::symlink_metadata(&entry).is_ok_and(|m| m.is_dir());
+            if is_dir && !hidden {
+                walk(&entry, components, found);
+            }
+        }
+    } else if has_wildcard(first) {
+        for entry in read_dir_sorted(dir) {
+            let name = entry.file_name().map(|n| n.to_string_lossy().into_owned());
+            // Like shells, wildcards do not match hidden files
+            if name.is_some_and(|n| !n.starts_with('.') && matches(first, &n)) {
+                walk(&entry, rest, found);
+            }
+        }
+    } else {
+        walk(&dir.join(first), rest, found);
+    }
+}
+
+fn read_dir_sorted(dir: &Path) -> Vec<PathBuf> {
+    // Entries of the working directory are listed without a `./` prefix
+    let cwd = dir.as_os_str().is_empty();
+    let mut entries: Vec<PathBuf> = fs::read_dir(if cwd { Path::new(".") } else { dir })
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| if cwd { e.file_name().into() } else { e.path() })
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// Match one file name against one pattern component
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches_from(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && matches_from(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let Some(close) = rest.iter().skip(1).position(|&c| c == ']').map(|i| i + 1) else {
+                // An unclosed `[` is an ordinary character
+                return name.first() == Some(&'[') && matches_from(rest, &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let (negated, set) = match rest[..close].split_first() {
+                Some(('!' | '^', set)) => (true, set),
+                _ => (false, &rest[..close]),
+            };
+            let mut in_set = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    in_set |= (set[i]..=set[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    in_set |= set[i] == c;
+                    i += 1;
+                }
+            }
+            in_set != negated && matches_from(&rest[close + 1..], &name[1..])
+        }
+        Some((&p, rest)) => name.first() == Some(&p) && matches_from(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards_in_a_file_name() {
+        assert!(matches("*.svg", "home.svg"));
+        assert!(!matches("*.svg", "home.png"));
+        assert!(matches("icon-??.svg", "icon-16.svg"));
+        assert!(!matches("icon-??.svg", "icon-128.svg"));
+        assert!(matches("[a-c]*", "bell.svg"));
+        assert!(!matches("[!a-c]*", "bell.svg"));
+        assert!(matches("[x", "[x"));
+    }
+
+    #[test]
+    fn expands_patterns_against_the_file_system() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("svg-scale-glob-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nav/deep"))?;
+        for file in [
+            "a.svg",
+            "b.svg",
+            "c.png",
+            ".hidden.svg",
+            "nav/menu.svg",
+            "nav/deep/x.svg",
+            ".cache/y.svg",
+        ] {
+            fs::create_dir_all(dir.join(file).parent().unwrap_or(&dir))?;
+            fs::write(dir.join(file), "")?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("..", dir.join("nav/up"))?;
+        let root = dir.to_string_lossy();
+        let names = |pattern: &str| -> Result<Vec<String>> {
+            Ok(expand(&format!("{}/{}", root, pattern))?
+                .into_iter()
+                .map(|p| p[root.len() + 1..].replace('\\', "/"))
+                .collect())
+        };
+        assert_eq!(names("*.svg")?, ["a.svg", "b.svg"]);
+        assert_eq!(names("*/*.svg")?, ["nav/menu.svg"]);
+        assert_eq!(
+            names("**/*.svg")?,
+            ["a.svg", "b.svg", "nav/deep/x.svg", "nav/menu.svg"]
+        );
+        assert!(expand(&format!("{}/*.gif", root)).is_err());
+        assert_eq!(expand("data:image/svg+xml,*")?, ["data:image/svg+xml,*"]);
+        assert_eq!(base("icons/**/*.svg"), Some(PathBuf::from("icons")));
+        assert_eq!(base("/srv/a/*/x.svg"), Some(PathBuf::from("/srv/a")));
+        assert_eq!(base("*.svg"), Some(PathBuf::new()));
+        assert_eq!(base("icons/home.svg"), None);
+        #[cfg(windows)]
+        assert_eq!(base(r"C:\icons\*.svg"), Some(PathBuf::from(r"C:\icons")));
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
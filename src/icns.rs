@@ -0,0 +1,78 @@
+//! macOS `.icns` files.
+//!
+//! Every image is stored as a PNG under the OSType macOS 10.7 and later
+//! read it from. [`ICNS_ICONS`] is the set `iconutil` builds from an
+//! `.iconset`: 16 to 512 pt, each at 1x and @2x, so seven distinct pixel
+//! sizes from 16 to 1024.
+
+use anyhow::{bail, Result};
+
+/// OSType, pixel size and `.iconset` name of each standard image
+pub const ICNS_ICONS: &[(&[u8; 4], u32, &str)] = &[
+    (b"icp4", 16, "16x16"),
+    (b"ic11", 32, "16x16@2x"),
+    (b"icp5", 32, "32x32"),
+    (b"ic12", 64, "32x32@2x"),
+    (b"ic07", 128, "128x128"),
+    (b"ic13", 256, "128x128@2x"),
+    (b"ic08", 256, "256x256"),
+    (b"ic14", 512, "256x256@2x"),
+    (b"ic09", 512, "512x512"),
+    (b"ic10", 1024, "512x512@2x"),
+];
+
+/// Length of the file header and of each entry header (OSType and length)
+const HEADER_LEN: usize = 8;
+
+/// One image: its OSType and PNG data
+pub struct Entry<'a> {
+    pub kind: [u8; 4],
+    pub png: &'a [u8],
+}
+
+/// Pack PNG images into an `.icns` file, in the given order. Lengths are
+/// big-endian and include their own 8-byte header.
+pub fn encode_icns(entries: &[Entry]) -> Result<Vec<u8>> {
+    if entries.is_empty() {
+        bail!("an icon needs at least one image");
+    }
+    let total = HEADER_LEN
+        + entries
+            .iter()
+            .map(|e| HEADER_LEN + e.png.len())
+            .sum::<usize>();
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(b"icns");
+    out.extend_from_slice(&(total as u32).to_be_bytes());
+    for entry in entries {
+        out.extend_from_slice(&entry.kind);
+        out.extend_from_slice(&((HEADER_LEN + entry.png.len()) as u32).to_be_bytes());
+        out.extend_from_slice(entry.png);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_carry_their_own_lengths() -> Result<()> {
+        let entries = [
+            Entry {
+                kind: *b"icp4",
+                png: &[1, 2, 3],
+            },
+            Entry {
+                kind: *b"ic10",
+                png: &[4],
+            },
+        ];
+        let icns = encode_icns(&entries)?;
+        assert_eq!(&icns[..8], b"icns\0\0\0\x1c");
+        assert_eq!(&icns[8..19], b"icp4\0\0\0\x0b\x01\x02\x03");
+        assert_eq!(&icns[19..], b"ic10\0\0\0\x09\x04");
+        assert!(encode_icns(&[]).is_err());
+        Ok(())
+    }
+}
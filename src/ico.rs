@@ -0,0 +1,86 @@
+//! Windows `.ico` files.
+//!
+//! Every frame is stored as a PNG, which Windows Vista and later read at any
+//! size; [`ICO_SIZES`] is the set Windows picks from for the taskbar,
+//! Explorer views and high-DPI displays.
+
+use anyhow::{bail, Result};
+
+/// Frame sizes written when none are given
+pub const ICO_SIZES: &[u32] = &[16, 24, 32, 48, 64, 128, 256];
+
+/// Size of the ICONDIR header and of each ICONDIRENTRY
+const DIR_LEN: usize = 6;
+const ENTRY_LEN: usize = 16;
+
+/// One square frame: its size in px and its PNG data
+pub struct Frame {
+    pub size: u32,
+    pub png: Vec<u8>,
+}
+
+/// Pack PNG frames into an `.ico` file, in the given order
+pub fn encode_ico(frames: &[Frame]) -> Result<Vec<u8>> {
+    if frames.is_empty() {
+        bail!("an icon needs at least one frame");
+    }
+    if let Some(frame) = frames.iter().find(|f| !(1..=256).contains(&f.size)) {
+        bail!("icon frames must be 1 to 256 px, got {}", frame.size);
+    }
+    let mut out = Vec::new();
+    // ICONDIR: reserved, type 1 (icon), frame count
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&(frames.len() as u16).to_le_bytes());
+    let mut offset = DIR_LEN + ENTRY_LEN * frames.len();
+    for frame in frames {
+        // 256 is stored as 0 in the one-byte width and height
+        let side = (frame.size % 256) as u8;
+        out.extend_from_slice(&[side, side, 0, 0]);
+        // Colour planes and bits per pixel
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&32u16.to_le_bytes());
+        out.extend_from_slice(&(frame.png.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += frame.png.len();
+    }
+    for frame in frames {
+        out.extend_from_slice(&frame.png);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_follow_the_directory() -> Result<()> {
+        let frames = [
+            Frame {
+                size: 16,
+                png: vec![1, 2, 3],
+            },
+            Frame {
+                size: 256,
+                png: vec![4, 5],
+            },
+        ];
+        let ico = encode_ico(&frames)?;
+        assert_eq!(ico[..6], [0, 0, 1, 0, 2, 0]);
+        // First entry: 16x16, 3 bytes at offset 6 + 2 * 16
+        assert_eq!(ico[6..10], [16, 16, 0, 0]);
+        assert_eq!(ico[14..22], [3, 0, 0, 0, 38, 0, 0, 0]);
+        // Second entry: 256 written as 0, data right after the first frame
+        assert_eq!(ico[22..24], [0, 0]);
+        assert_eq!(ico[30..38], [2, 0, 0, 0, 41, 0, 0, 0]);
+        assert_eq!(ico[38..], [1, 2, 3, 4, 5]);
+        assert!(encode_ico(&[]).is_err());
+        assert!(encode_ico(&[Frame {
+            size: 512,
+            png: Vec::new()
+        }])
+        .is_err());
+        Ok(())
+    }
+}
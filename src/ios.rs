@@ -0,0 +1,121 @@
+//! iOS `AppIcon.appiconset` asset catalogs.
+//!
+//! [`IOS_APP_ICONS`] is the full iPhone, iPad and App Store set Xcode lists
+//! for an app icon; `Contents.json` maps each slot to its PNG. Slots with
+//! the same pixel size share one file.
+
+use crate::json::Value;
+
+/// One slot of the app icon set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AppIcon {
+    pub idiom: &'static str,
+    /// Size in points
+    pub points: f64,
+    pub scale: u32,
+}
+
+impl AppIcon {
+    pub fn pixels(&self) -> u32 {
+        (self.points * self.scale as f64).round() as u32
+    }
+
+    pub fn filename(&self) -> String {
+        format!("icon-{}.png", self.pixels())
+    }
+
+    /// The App Store icon, which must not have an alpha channel
+    pub fn is_marketing(&self) -> bool {
+        self.idiom == "ios-marketing"
+    }
+}
+
+const fn icon(idiom: &'static str, points: f64, scale: u32) -> AppIcon {
+    AppIcon {
+        idiom,
+        points,
+        scale,
+    }
+}
+
+pub const IOS_APP_ICONS: &[AppIcon] = &[
+    icon("iphone", 20.0, 2),
+    icon("iphone", 20.0, 3),
+    icon("iphone", 29.0, 2),
+    icon("iphone", 29.0, 3),
+    icon("iphone", 40.0, 2),
+    icon("iphone", 40.0, 3),
+    icon("iphone", 60.0, 2),
+    icon("iphone", 60.0, 3),
+    icon("ipad", 20.0, 1),
+    icon("ipad", 20.0, 2),
+    icon("ipad", 29.0, 1),
+    icon("ipad", 29.0, 2),
+    icon("ipad", 40.0, 1),
+    icon("ipad", 40.0, 2),
+    icon("ipad", 76.0, 1),
+    icon("ipad", 76.0, 2),
+    icon("ipad", 83.5, 2),
+    icon("ios-marketing", 1024.0, 1),
+];
+
+/// `Contents.json` for an icon set holding `icons`
+pub fn contents_json(icons: &[AppIcon]) -> String {
+    let images = icons
+        .iter()
+        .map(|icon| {
+            Value::Object(vec![
+                ("filename".to_string(), icon.filename().into()),
+                ("idiom".to_string(), icon.idiom.into()),
+                ("scale".to_string(), format!("{}x", icon.scale).into()),
+                (
+                    "size".to_string(),
+                    format!("{}x{}", icon.points, icon.points).into(),
+                ),
+            ])
+        })
+        .collect();
+    let contents = Value::Object(vec![
+        ("images".to_string(), Value::Array(images)),
+        (
+            "info".to_string(),
+            Value::Object(vec![
+                ("author".to_string(), "xcode".into()),
+                ("version".to_string(), 1.0.into()),
+            ]),
+        ),
+    ]);
+    format!("{}\n", contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contents_list_every_slot_by_pixel_file() -> anyhow::Result<()> {
+        let contents = crate::json::parse(&contents_json(IOS_APP_ICONS))?;
+        let Some(Value::Array(images)) = contents.get("images") else {
+            panic!("no images in {contents}");
+        };
+        assert_eq!(images.len(), IOS_APP_ICONS.len());
+        let ipad_pro = &images[16];
+        assert_eq!(
+            ipad_pro.get("size").and_then(Value::as_str),
+            Some("83.5x83.5")
+        );
+        assert_eq!(ipad_pro.get("scale").and_then(Value::as_str), Some("2x"));
+        assert_eq!(
+            ipad_pro.get("filename").and_then(Value::as_str),
+            Some("icon-167.png")
+        );
+        assert_eq!(
+            contents
+                .get("info")
+                .and_then(|i| i.get("version"))
+                .and_then(Value::as_f64),
+            Some(1.0)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// Minimal JSON value used for the daemon protocol and generated metadata files
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) if !n.is_finite() => f.write_str("null"),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write_str(f, s),
+            Value::Array(items) => {
+                f.write_str("[")?;
+                for (i, v) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                f.write_str("]")
+            }
+            Value::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_str(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Value> {
+    let mut p = Parser {
+        chars: input.char_indices().peekable(),
+        input,
+    };
+    let v = p.value()?;
+    p.skip_ws();
+    if let Some((pos, _)) = p.chars.peek() {
+        bail!("invalid json: trailing characters at byte {}", pos);
+    }
+    Ok(v)
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    input: &'a str,
+}
+
+impl Parser<'_> {
+    fn skip_ws(&mut self) {
+        while matches!(self.chars.peek(), Some((_, c)) if c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, want: char) -> Result<()> {
+        match self.chars.next() {
+            Some((_, c)) if c == want => Ok(()),
+            Some((pos, c)) => bail!(
+                "invalid json: expected '{}' at byte {}, got '{}'",
+                want,
+                pos,
+                c
+            ),
+            None => bail!("invalid json: expected '{}', got end of input", want),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, v: Value) -> Result<Value> {
+        for want in word.chars() {
+            self.expect(want)?;
+        }
+        Ok(v)
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_ws();
+        match self.chars.peek().copied() {
+            Some((_, '{')) => self.object(),
+            Some((_, '[')) => self.array(),
+            Some((_, '"')) => Ok(Value::String(self.string()?)),
+            Some((_, 't')) => self.keyword("true", Value::Bool(true)),
+            Some((_, 'f')) => self.keyword("false", Value::Bool(false)),
+            Some((_, 'n')) => self.keyword("null", Value::Null),
+            Some((_, c)) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some((pos, c)) => bail!("invalid json: unexpected '{}' at byte {}", c, pos),
+            None => bail!("invalid json: unexpected end of input"),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_ws();
+        if matches!(self.chars.peek(), Some((_, '}'))) {
+            self.chars.next();
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.skip_ws();
+            let key = self.string()?;
+            self.skip_ws();
+            self.expect(':')?;
+            let v = self.value()?;
+            fields.push((key, v));
+            self.skip_ws();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, '}')) => return Ok(Value::Object(fields)),
+                Some((pos, c)) => bail!("invalid json: unexpected '{}' at byte {}", c, pos),
+                None => bail!("invalid json: unterminated object"),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if matches!(self.chars.peek(), Some((_, ']'))) {
+            self.chars.next();
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, ']')) => return Ok(Value::Array(items)),
+                Some((pos, c)) => bail!("invalid json: unexpected '{}' at byte {}", c, pos),
+                None => bail!("invalid json: unterminated array"),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(out),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, '"')) => out.push('"'),
+                    Some((_, '\\')) => out.push('\\'),
+                    Some((_, '/')) => out.push('/'),
+                    Some((_, 'b')) => out.push('\u{8}'),
+                    Some((_, 'f')) => out.push('\u{c}'),
+                    Some((_, 'n')) => out.push('\n'),
+                    Some((_, 'r')) => out.push('\r'),
+                    Some((_, 't')) => out.push('\t'),
+                    Some((pos, 'u')) => {
+                        let hi = self.hex4()?;
+                        let code = if (0xD800..0xDC00).contains(&hi) {
+                            let lo = match (self.chars.next(), self.chars.next()) {
+                                (Some((_, '\\')), Some((_, 'u'))) => self.hex4()?,
+                                _ => bail!("invalid json: unpaired surrogate at byte {}", pos),
+                            };
+                            if !(0xDC00..0xE000).contains(&lo) {
+                                bail!("invalid json: unpaired surrogate at byte {}", pos);
+                            }
+                            0x10000 + ((hi - 0xD800) << 10) + (lo - 0xDC00)
+                        } else {
+                            hi
+                        };
+                        match char::from_u32(code) {
+                            Some(c) => out.push(c),
+                            None => bail!("invalid json: unpaired surrogate at byte {}", pos),
+                        }
+                    }
+                    Some((pos, c)) => bail!("invalid json: bad escape '\\{}' at byte {}", c, pos),
+                    None => bail!("invalid json: unterminated string"),
+                },
+                Some((_, c)) => out.push(c),
+                None => bail!("invalid json: unterminated string"),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let Some((pos, c)) = self.chars.next() else {
+                bail!("invalid json: unterminated unicode escape");
+            };
+            let Some(d) = c.to_digit(16) else {
+                bail!("invalid json: bad unicode escape at byte {}", pos);
+            };
+            code = code * 16 + d;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.chars.peek().map(|(i, _)| *i).unwrap_or(0);
+        let mut end = start;
+        while let Some((i, c)) = self.chars.peek().copied() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                end = i + c.len_utf8();
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        let raw = &self.input[start..end];
+        match raw.parse::<f64>() {
+            Ok(n) => Ok(Value::Number(n)),
+            Err(_) => bail!("invalid json: bad number '{}' at byte {}", raw, start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_serialize_round_trip() -> Result<()> {
+        let input = r#" {"id": 7, "ok": true, "list": [1.5, null, "a\"b\n"], "u": "é"} "#;
+        let v = parse(input)?;
+        assert_eq!(v.get("id").and_then(Value::as_f64), Some(7.0));
+        assert_eq!(v.get("ok").and_then(Value::as_bool), Some(true));
+        assert_eq!(v.get("u").and_then(Value::as_str), Some("é"));
+        assert_eq!(
+            v.to_string(),
+            r#"{"id":7,"ok":true,"list":[1.5,null,"a\"b\n"],"u":"é"}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rejects_trailing_garbage() {
+        let err = parse(r#"{"a":1} x"#).unwrap_err();
+        assert!(err.to_string().contains("trailing characters"));
+    }
+
+    #[test]
+    fn parse_checks_surrogate_pairs() -> Result<()> {
+        assert_eq!(parse(r#""\ud83d\ude00""#)?.as_str(), Some("\u{1F600}"));
+        for bad in [
+            r#""\ud83d""#,
+            r#""\ud83dx""#,
+            r#""\ud83d\u0041""#,
+            r#""\ude00""#,
+        ] {
+            let err = parse(bad).unwrap_err();
+            assert!(
+                err.to_string().contains("unpaired surrogate"),
+                "{bad}: {err}"
+            );
+        }
+        Ok(())
+    }
+}
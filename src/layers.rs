@@ -0,0 +1,147 @@
+//! Inkscape layers (`<g inkscape:groupmode="layer">`) for `--layer` and
+//! `--each-layer`.
+//!
+//! Only outermost layers count; sublayers travel with their parent. Keeping
+//! a layer drops the other layers and everything else stays, so shared
+//! `<defs>` and unlayered artwork remain. A kept layer that was hidden in
+//! the editor (`display:none`) is made visible, since stacked variants are
+//! usually saved with all but one layer hidden.
+
+use anyhow::{bail, Result};
+use roxmltree::Node;
+use xmlwriter::XmlWriter;
+
+use crate::svg;
+
+const INKSCAPE_NS: &str = "http://www.inkscape.org/namespaces/inkscape";
+
+fn is_layer(node: Node) -> bool {
+    node.has_tag_name("g") && node.attribute((INKSCAPE_NS, "groupmode")) == Some("layer")
+}
+
+/// Name of a layer: its `inkscape:label`, else its `id`
+fn layer_name<'a>(node: Node<'a, '_>) -> Option<&'a str> {
+    node.attribute((INKSCAPE_NS, "label"))
+        .or_else(|| node.attribute("id"))
+}
+
+/// Outermost layers in document order
+fn outermost_layers<'a, 'i>(doc: &'a roxmltree::Document<'i>) -> Vec<Node<'a, 'i>> {
+    doc.root_element()
+        .descendants()
+        .filter(|n| is_layer(*n) && !n.ancestors().skip(1).any(is_layer))
+        .collect()
+}
+
+/// Names of the outermost layers, in document order; unnamed layers are
+/// left out
+pub fn layer_names(doc: &roxmltree::Document) -> Vec<String> {
+    outermost_layers(doc)
+        .into_iter()
+        .filter_map(layer_name)
+        .map(str::to_string)
+        .collect()
+}
+
+/// The document, unindented, with only the layer named `name` left among
+/// the layers
+pub fn keep_layer(doc: &roxmltree::Document, name: &str) -> Result<String> {
+    let layers = outermost_layers(doc);
+    let Some(&kept) = layers.iter().find(|l| layer_name(**l) == Some(name)) else {
+        let names = layer_names(doc);
+        if names.is_empty() {
+            bail!("the document has no named Inkscape layers");
+        }
+        bail!("no layer named {} (layers: {})", name, names.join(", "));
+    };
+    let mut w = XmlWriter::new(xmlwriter::Options::default());
+    write_without(doc.root_element(), &layers, kept, &mut w);
+    crate::strip_indentation(&crate::finish_document(doc, w))
+}
+
+fn write_without(node: Node, layers: &[Node], kept: Node, w: &mut XmlWriter) {
+    match node.node_type() {
+        roxmltree::NodeType::Element => {
+            if node != kept && layers.contains(&node) {
+                return;
+            }
+            w.start_element(node.tag_name().name());
+            for attr in node.attributes() {
+                let name = svg::qualified_attr_name(node, &attr);
+                if node == kept && name == "display" && attr.value().trim() == "none" {
+                    continue;
+                }
+                if node == kept && name == "style" {
+                    let props: Vec<_> = svg::parse_style(attr.value())
+                        .into_iter()
+                        .filter(|(k, v)| !(k == "display" && v.trim() == "none"))
+                        .collect();
+                    if !props.is_empty() {
+                        w.write_attribute("style", &svg::serialize_style(&props));
+                    }
+                    continue;
+                }
+                w.write_attribute(&name, attr.value());
+            }
+            for c in node.children() {
+                write_without(c, layers, kept, w);
+            }
+            w.end_element();
+        }
+        roxmltree::NodeType::Text => {
+            w.write_text(node.text().unwrap_or(""));
+        }
+        _ => {}
+    }
+}
+
+/// A layer name usable in a file name: characters other than ASCII
+/// letters, digits, `-` and `_` become `-`
+pub fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYERED: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" viewBox="0 0 24 24"><defs><linearGradient id="g"/></defs><rect id="bg" width="24" height="24"/><g inkscape:groupmode="layer" inkscape:label="Filled"><circle r="8"/></g><g inkscape:groupmode="layer" inkscape:label="Outline 2" style="display:none;opacity:0.5"><g inkscape:groupmode="layer" inkscape:label="Detail"/><circle r="8" fill="none"/></g></svg>"##;
+
+    #[test]
+    fn lists_outermost_layers() -> Result<()> {
+        let doc = roxmltree::Document::parse(LAYERED)?;
+        assert_eq!(layer_names(&doc), ["Filled", "Outline 2"]);
+        assert_eq!(file_stem("Outline 2"), "Outline-2");
+        Ok(())
+    }
+
+    #[test]
+    fn keeping_a_layer_drops_the_others_and_shows_it() -> Result<()> {
+        let doc = roxmltree::Document::parse(LAYERED)?;
+        let out = keep_layer(&doc, "Outline 2")?;
+        assert!(
+            out.contains(r#"<linearGradient id="g"/>"#) && out.contains(r#"id="bg""#),
+            "got: {out}"
+        );
+        assert!(!out.contains("Filled"), "got: {out}");
+        assert!(
+            out.contains(r#"inkscape:label="Outline 2" style="opacity:0.5""#),
+            "got: {out}"
+        );
+        assert!(out.contains(r#"inkscape:label="Detail""#), "got: {out}");
+        let err = keep_layer(&doc, "Missing").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no layer named Missing (layers: Filled, Outline 2)"
+        );
+        Ok(())
+    }
+}
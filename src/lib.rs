@@ -0,0 +1,751 @@
+//! Geometry-true SVG scaling engine behind the `svg-scale` CLI.
+
+use anyhow::{bail, Result};
+use std::io::{self, Write};
+
+pub mod android;
+pub mod audit;
+pub mod bitmap;
+pub mod bounds;
+pub mod config;
+pub mod data_uri;
+pub mod explain;
+pub mod extension;
+pub mod favicon;
+pub mod filter;
+pub mod hash;
+pub mod icns;
+pub mod ico;
+pub mod ios;
+pub mod json;
+pub mod layers;
+pub mod markers;
+pub mod ops;
+pub mod optimize;
+pub mod path;
+#[cfg(feature = "render")]
+pub mod pdf;
+pub mod quantize;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod roundtrip;
+pub mod scale;
+pub mod split;
+pub mod stream;
+pub mod stretch;
+pub mod svg;
+pub mod symbols;
+pub mod transform;
+#[cfg(feature = "render")]
+pub mod verify;
+pub mod viewbox;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod webp;
+
+use json::Value;
+use scale::ScaleCtx;
+
+/// Scale a parsed document and serialize it, keeping the root namespace declarations
+pub fn write_svg(doc: &roxmltree::Document, ctx: &ScaleCtx) -> Result<String> {
+    write_svg_with_options(doc, ctx, &svg::WalkOptions::default())
+}
+
+/// Like [`write_svg`], with hooks and selector filters (see [`svg::WalkOptions`])
+pub fn write_svg_with_options(
+    doc: &roxmltree::Document,
+    ctx: &ScaleCtx,
+    opts: &svg::WalkOptions,
+) -> Result<String> {
+    let mut writer = xmlwriter::XmlWriter::new(xmlwriter::Options::default());
+    svg::walk_with_options(doc.root_element(), &mut writer, ctx, opts)?;
+    Ok(finish_document(doc, writer))
+}
+
+/// Like [`write_svg_with_options`], writing the markup into `out` as the walk
+/// produces it instead of building the document in memory
+pub fn write_svg_to(
+    doc: &roxmltree::Document,
+    ctx: &ScaleCtx,
+    opts: &svg::WalkOptions,
+    out: &mut impl Write,
+) -> Result<()> {
+    let styles = svg::StyleSheet::collect(doc.root_element());
+    write_svg_with_styles_to(doc, &styles, ctx, opts, out)
+}
+
+/// Like [`write_svg_to`], reusing `<style>` rules collected once for several scales
+pub fn write_svg_with_styles_to(
+    doc: &roxmltree::Document,
+    styles: &svg::StyleSheet,
+    ctx: &ScaleCtx,
+    opts: &svg::WalkOptions,
+    out: &mut impl Write,
+) -> Result<()> {
+    out.write_all(XML_DECLARATION)?;
+    let mut sink = svg::StreamWriter::new(
+        io::BufWriter::new(out),
+        root_namespace_attrs(doc, &|_| true),
+    );
+    svg::walk_into(doc.root_element(), &mut sink, ctx, opts, styles)?;
+    sink.finish()?;
+    Ok(())
+}
+
+/// Like [`finish_document`], writing into `out`
+pub fn finish_document_to(
+    doc: &roxmltree::Document,
+    writer: xmlwriter::XmlWriter,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    write_document(doc, &writer.end_document(), out, &|_| true)
+}
+
+/// Close the writer, adding the XML declaration and the source's root namespace declarations
+pub fn finish_document(doc: &roxmltree::Document, writer: xmlwriter::XmlWriter) -> String {
+    finish_document_with_namespaces(doc, writer, &|_| true)
+}
+
+/// Like [`finish_document`], declaring only the root namespaces `keep` accepts
+pub(crate) fn finish_document_with_namespaces(
+    doc: &roxmltree::Document,
+    writer: xmlwriter::XmlWriter,
+    keep: &dyn Fn(&roxmltree::Namespace) -> bool,
+) -> String {
+    let body = writer.end_document();
+    let mut out = Vec::with_capacity(body.len() + 128);
+    write_document(doc, &body, &mut out, keep).expect("writing to a Vec cannot fail");
+    String::from_utf8(out).expect("serialized SVG is UTF-8")
+}
+
+fn write_document(
+    doc: &roxmltree::Document,
+    body: &str,
+    out: &mut impl Write,
+    keep: &dyn Fn(&roxmltree::Namespace) -> bool,
+) -> io::Result<()> {
+    out.write_all(XML_DECLARATION)?;
+
+    // Namespace declarations from the source root go at the end of the opening <svg tag
+    let insert_pos = body.find("<svg").and_then(|pos| {
+        let end = pos + body[pos..].find('>')?;
+        Some(if body[..end].ends_with('/') {
+            end - 1
+        } else {
+            end
+        })
+    });
+    let Some(insert_pos) = insert_pos else {
+        return out.write_all(body.as_bytes());
+    };
+    out.write_all(&body.as_bytes()[..insert_pos])?;
+    out.write_all(root_namespace_attrs(doc, keep).as_bytes())?;
+    out.write_all(&body.as_bytes()[insert_pos..])
+}
+
+const XML_DECLARATION: &[u8] = b"<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n";
+
+/// The source root's namespace declarations that `keep` accepts, as
+/// attributes to append to the output's root start tag
+fn root_namespace_attrs(
+    doc: &roxmltree::Document,
+    keep: &dyn Fn(&roxmltree::Namespace) -> bool,
+) -> String {
+    let mut attrs = String::new();
+    for ns in doc.root_element().namespaces().filter(|ns| keep(ns)) {
+        match ns.name() {
+            Some(name) => attrs.push_str(&format!(" xmlns:{}=\"{}\"", name, ns.uri())),
+            None => attrs.push_str(&format!(" xmlns=\"{}\"", ns.uri())),
+        }
+    }
+    attrs
+}
+
+/// Rewrite serialized SVG with attributes in a stable order (`id`, `class`,
+/// geometry, presentation, `style`) and double quotes throughout, so files
+/// exported by different tools diff cleanly
+pub fn canonicalize(svg_text: &str) -> Result<String> {
+    let doc = roxmltree::Document::parse(svg_text)?;
+    let mut writer = xmlwriter::XmlWriter::new(xmlwriter::Options::default());
+    svg::write_canonical(doc.root_element(), &mut writer);
+    Ok(finish_document(&doc, writer))
+}
+
+/// Rewrite serialized SVG without any indentation, so passing a document
+/// through the scaler repeatedly does not stack blank lines
+pub(crate) fn strip_indentation(svg_text: &str) -> Result<String> {
+    let doc = roxmltree::Document::parse(svg_text)?;
+    let mut writer = xmlwriter::XmlWriter::new(xmlwriter::Options {
+        indent: xmlwriter::Indent::None,
+        ..xmlwriter::Options::default()
+    });
+    svg::write_reindented(doc.root_element(), &mut writer, false);
+    Ok(finish_document(&doc, writer))
+}
+
+/// Resolution used to convert physical units (`mm`, `pt`, ...) to px
+pub const DEFAULT_DPI: f64 = 96.0;
+
+/// CSS `width`/`height` of the root (inline style or a `<style>` rule), in px
+fn css_root_length(doc: &roxmltree::Document, name: &str, dpi: f64) -> Option<f64> {
+    svg::length_to_px(&svg::css_property(doc.root_element(), name)?, dpi)
+}
+
+/// Width of the root element in px, from CSS, `width` or the viewBox
+pub fn get_svg_size(doc: &roxmltree::Document, dpi: f64) -> Option<f64> {
+    let root = doc.root_element();
+    // CSS overrides the attribute, as in browsers
+    if let Some(w) = css_root_length(doc, "width", dpi) {
+        return Some(w);
+    }
+    // Try width attribute first
+    if let Some(w) = root.attribute("width") {
+        if let Some(val) = svg::length_to_px(w, dpi) {
+            return Some(val);
+        }
+    }
+    // Try viewBox
+    viewbox::ViewBox::of_root(doc).map(|vb| vb.width)
+}
+
+/// Width and height of the root element in px, from CSS, `width`/`height` or the viewBox
+pub fn get_svg_dimensions(doc: &roxmltree::Document, dpi: f64) -> Option<(f64, f64)> {
+    let root = doc.root_element();
+    // Prefer CSS, then width/height attributes, each taken from CSS when set there
+    let length = |name| {
+        css_root_length(doc, name, dpi)
+            .or_else(|| root.attribute(name).and_then(|v| svg::length_to_px(v, dpi)))
+    };
+    if let (Some(w), Some(h)) = (length("width"), length("height")) {
+        return Some((w, h));
+    }
+
+    // Fall back to viewBox if present, also for relative sizes such as `100%`
+    if let Some(vb) = viewbox::ViewBox::of_root(doc) {
+        return Some((vb.width, vb.height));
+    }
+
+    // Last resort: if width exists but height doesn't, assume square
+    get_svg_size(doc, dpi).map(|w| (w, w))
+}
+
+/// A size given as `W` or `WxH`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeSpec {
+    pub width: f64,
+    pub height: Option<f64>,
+}
+
+impl std::str::FromStr for SizeSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |v: &str| -> Result<f64> {
+            let v = v.trim().trim_end_matches("px");
+            match v.parse::<f64>() {
+                Ok(n) if n > 0.0 && n.is_finite() => Ok(n),
+                _ => bail!("invalid size: {}", s),
+            }
+        };
+        match s.split_once(['x', 'X']) {
+            Some((w, h)) => Ok(SizeSpec {
+                width: parse(w)?,
+                height: Some(parse(h)?),
+            }),
+            None => Ok(SizeSpec {
+                width: parse(s)?,
+                height: None,
+            }),
+        }
+    }
+}
+
+/// Physical unit of a `--to` entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    Mm,
+    Cm,
+    In,
+    Pt,
+    Pc,
+}
+
+impl LengthUnit {
+    /// Parse a unit suffix; `None` for anything other than the five
+    /// physical units
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "mm" => Self::Mm,
+            "cm" => Self::Cm,
+            "in" => Self::In,
+            "pt" => Self::Pt,
+            "pc" => Self::Pc,
+            _ => return None,
+        })
+    }
+
+    /// The suffix as written in SVG, e.g. `mm`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Mm => "mm",
+            Self::Cm => "cm",
+            Self::In => "in",
+            Self::Pt => "pt",
+            Self::Pc => "pc",
+        }
+    }
+
+    /// Pixels per unit at `dpi`
+    pub fn px_per_unit(self, dpi: f64) -> f64 {
+        match self {
+            Self::Mm => dpi / 25.4,
+            Self::Cm => dpi / 2.54,
+            Self::In => dpi,
+            Self::Pt => dpi / 72.0,
+            Self::Pc => dpi / 6.0,
+        }
+    }
+}
+
+/// One `--to` entry: a target width and the options it overrides
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetSpec {
+    /// Width in `unit`
+    pub size: f64,
+    /// Height in `unit`, for a `WxH` entry
+    pub height: Option<f64>,
+    /// Given as a height (`--to-height`), so labelled by it
+    pub by_height: bool,
+    /// Physical unit the width was given in; `None` for px
+    pub unit: Option<LengthUnit>,
+    pub fix_stroke: Option<bool>,
+    pub precision: Option<usize>,
+}
+
+impl TargetSpec {
+    /// The target width in px at `dpi`
+    pub fn px(&self, dpi: f64) -> f64 {
+        match self.unit {
+            Some(unit) => self.size * unit.px_per_unit(dpi),
+            None => self.size,
+        }
+    }
+
+    /// The target height in px at `dpi`, for a `WxH` entry
+    pub fn px_height(&self, dpi: f64) -> Option<f64> {
+        let height = self.height?;
+        Some(self.px(dpi) * height / self.size)
+    }
+
+    /// The size as written, e.g. `48`, `25mm` or `64x32`
+    pub fn label(&self) -> String {
+        let unit = self.unit.map_or("", LengthUnit::as_str);
+        match self.height {
+            Some(height) if self.by_height => format!("{}{}", height, unit),
+            Some(height) => format!("{}x{}{}", self.size, height, unit),
+            None => format!("{}{}", self.size, unit),
+        }
+    }
+}
+
+/// Parse a `--to` list: plain `16,32,48`, or `;`-separated entries with
+/// per-target overrides after a colon, e.g. `16:fix-stroke,precision=2;48;128:precision=5`.
+/// An entry can give a width and height, e.g. `64x32` or `50x20mm`, with
+/// the unit after the height
+pub fn parse_targets(s: &str) -> Result<Vec<TargetSpec>> {
+    let has_overrides = s.contains([':', ';']);
+    let entries: Vec<&str> = if has_overrides {
+        s.split(';').collect()
+    } else {
+        s.split(',').collect()
+    };
+    let mut targets = Vec::with_capacity(entries.len());
+    for entry in entries.iter().map(|e| e.trim()).filter(|e| !e.is_empty()) {
+        let (size, opts) = entry.split_once(':').unwrap_or((entry, ""));
+        let size = size.trim();
+        // `px` contains an `x` too, so the width must be a plain number
+        let (width, size) = match size.split_once(['x', 'X']) {
+            Some((w, h)) if w.trim().parse::<f64>().is_ok() => (Some(w.trim()), h.trim()),
+            _ => (None, size),
+        };
+        let number = size.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let unit = match &size[number.len()..] {
+            "" | "px" => None,
+            suffix => match LengthUnit::parse(suffix) {
+                Some(unit) => Some(unit),
+                None => bail!("invalid target size: {}", size),
+            },
+        };
+        let parse = |number: &str| match number.parse::<f64>() {
+            Ok(n) if n > 0.0 && n.is_finite() => Ok(n),
+            _ => bail!("invalid target size: {}", entry),
+        };
+        let (size, height) = match width {
+            Some(width) => (parse(width)?, Some(parse(number)?)),
+            None => (parse(number)?, None),
+        };
+        let mut target = TargetSpec {
+            size,
+            height,
+            by_height: false,
+            unit,
+            fix_stroke: None,
+            precision: None,
+        };
+        for opt in opts.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            match opt.split_once('=') {
+                None if opt == "fix-stroke" => target.fix_stroke = Some(true),
+                None if opt == "no-fix-stroke" => target.fix_stroke = Some(false),
+                Some(("precision", n)) => {
+                    let n = scale::parse_precision(n).map_err(|e| {
+                        anyhow::anyhow!("invalid precision for target {}: {}", size, e)
+                    })?;
+                    target.precision = Some(n);
+                }
+                _ => bail!("unknown option for target {}: {}", size, opt),
+            }
+        }
+        targets.push(target);
+    }
+    if targets.is_empty() {
+        bail!("no target size in: {}", s);
+    }
+    Ok(targets)
+}
+
+/// Source width and height: `from` when given (a bare width keeps the
+/// document's aspect ratio), otherwise detected from the root element
+pub fn source_dimensions(
+    from: Option<SizeSpec>,
+    doc: &roxmltree::Document,
+    dpi: f64,
+) -> Option<(f64, f64)> {
+    let aspect = get_svg_dimensions(doc, dpi)
+        .filter(|(w, h)| *w > 0.0 && *h > 0.0)
+        .map(|(w, h)| h / w)
+        .unwrap_or(1.0);
+    match from {
+        Some(SizeSpec {
+            width,
+            height: Some(height),
+        }) => Some((width, height)),
+        Some(SizeSpec {
+            width,
+            height: None,
+        }) => Some((width, width * aspect)),
+        None => get_svg_size(doc, dpi).map(|w| (w, w * aspect)),
+    }
+}
+
+/// How far the width/height ratio of `target` strays from that of
+/// `source`, as a fraction of the source ratio (0 when they agree)
+pub fn aspect_distortion(source: (f64, f64), target: (f64, f64)) -> f64 {
+    let ratio = |(w, h): (f64, f64)| w / h;
+    (ratio(target) / ratio(source) - 1.0).abs()
+}
+
+/// Options for [`scale_svg`], mirroring the CLI flags of the same name
+#[derive(Debug, Clone)]
+pub struct ScaleOptions {
+    pub scale: Option<f64>,
+    pub to: Option<f64>,
+    pub from: Option<f64>,
+    pub precision: usize,
+    pub fix_stroke: bool,
+    pub dpi: f64,
+    pub exact: bool,
+}
+
+impl Default for ScaleOptions {
+    fn default() -> Self {
+        Self {
+            scale: None,
+            to: None,
+            from: None,
+            precision: 4,
+            fix_stroke: false,
+            dpi: DEFAULT_DPI,
+            exact: false,
+        }
+    }
+}
+
+impl ScaleOptions {
+    /// Read options from a JSON object; `fixStroke` is accepted as an alias of `fix_stroke`.
+    /// Fails on a `precision` that is not a whole number up to [`scale::MAX_PRECISION`]
+    pub fn from_json(v: &Value) -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            scale: v.get("scale").and_then(Value::as_f64),
+            to: v.get("to").and_then(Value::as_f64),
+            from: v.get("from").and_then(Value::as_f64),
+            precision: match v.get("precision") {
+                None => defaults.precision,
+                Some(p) => match p.as_f64() {
+                    Some(n) if n >= 0.0 && n.fract() == 0.0 && n <= scale::MAX_PRECISION as f64 => {
+                        n as usize
+                    }
+                    _ => bail!(
+                        "precision must be a whole number from 0 to {}, got {}",
+                        scale::MAX_PRECISION,
+                        p
+                    ),
+                },
+            },
+            fix_stroke: v
+                .get("fix_stroke")
+                .or_else(|| v.get("fixStroke"))
+                .and_then(Value::as_bool)
+                .unwrap_or(defaults.fix_stroke),
+            dpi: v.get("dpi").and_then(Value::as_f64).unwrap_or(defaults.dpi),
+            exact: v
+                .get("exact")
+                .and_then(Value::as_bool)
+                .unwrap_or(defaults.exact),
+        })
+    }
+}
+
+/// Scale an SVG document given as text
+pub fn scale_svg(svg_text: &str, opts: &ScaleOptions) -> Result<String> {
+    let doc = roxmltree::Document::parse(svg_text)?;
+
+    let scale = if let Some(s) = opts.scale {
+        s
+    } else if let Some(to) = opts.to {
+        let from = match opts.from.or_else(|| get_svg_size(&doc, opts.dpi)) {
+            Some(f) => f,
+            None => bail!("未能从SVG检测到尺寸，请指定 from"),
+        };
+        to / from
+    } else {
+        bail!("必须指定 scale 或 to");
+    };
+    scale::check_scale(scale, false)?;
+
+    let ctx = ScaleCtx {
+        scale,
+        precision: opts.precision,
+        fix_stroke: opts.fix_stroke,
+        exact: opts.exact,
+        ..ScaleCtx::default()
+    };
+    write_svg(&doc, &ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_parse_plain_lists_and_overrides() -> Result<()> {
+        let plain = parse_targets("16, 32,48")?;
+        assert_eq!(
+            plain.iter().map(|t| t.size).collect::<Vec<_>>(),
+            [16.0, 32.0, 48.0]
+        );
+        assert!(plain
+            .iter()
+            .all(|t| t.fix_stroke.is_none() && t.precision.is_none()));
+
+        let tuned = parse_targets("16:fix-stroke,precision=2;48;128:precision=5,no-fix-stroke")?;
+        assert_eq!(
+            tuned,
+            vec![
+                TargetSpec {
+                    size: 16.0,
+                    height: None,
+                    by_height: false,
+                    unit: None,
+                    fix_stroke: Some(true),
+                    precision: Some(2)
+                },
+                TargetSpec {
+                    size: 48.0,
+                    height: None,
+                    by_height: false,
+                    unit: None,
+                    fix_stroke: None,
+                    precision: None
+                },
+                TargetSpec {
+                    size: 128.0,
+                    height: None,
+                    by_height: false,
+                    unit: None,
+                    fix_stroke: Some(false),
+                    precision: Some(5)
+                },
+            ]
+        );
+        assert!(parse_targets("16:bold").is_err());
+        assert!(parse_targets("0").is_err());
+
+        let physical = parse_targets("25mm,1in,24px")?;
+        assert_eq!(physical[0].unit, Some(LengthUnit::Mm));
+        assert_eq!(physical[0].label(), "25mm");
+        assert!((physical[0].px(96.0) - 94.488).abs() < 1e-3);
+        assert_eq!(physical[1].px(300.0), 300.0);
+        assert_eq!((physical[2].unit, physical[2].px(300.0)), (None, 24.0));
+        assert!(parse_targets("25em").is_err());
+        for t in parse_targets("2.54cm,72pt,6pc")? {
+            assert!((t.px(96.0) - 96.0).abs() < 1e-9, "{}", t.label());
+        }
+
+        let sized = parse_targets("64x32,50X20mm,24px")?;
+        assert_eq!((sized[0].size, sized[0].height), (64.0, Some(32.0)));
+        assert_eq!(sized[0].label(), "64x32");
+        assert_eq!(sized[1].label(), "50x20mm");
+        assert!((sized[1].px_height(96.0).unwrap() - 75.591).abs() < 1e-3);
+        assert_eq!((sized[2].size, sized[2].height), (24.0, None));
+        assert!(parse_targets("64x0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn size_spec_parses_width_and_height() -> Result<()> {
+        assert_eq!(
+            "200x100".parse::<SizeSpec>()?,
+            SizeSpec {
+                width: 200.0,
+                height: Some(100.0)
+            }
+        );
+        assert_eq!(
+            "64".parse::<SizeSpec>()?,
+            SizeSpec {
+                width: 64.0,
+                height: None
+            }
+        );
+        assert!("0x10".parse::<SizeSpec>().is_err());
+        assert!("axb".parse::<SizeSpec>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn aspect_distortion_compares_ratios() {
+        assert_eq!(aspect_distortion((24.0, 12.0), (48.0, 24.0)), 0.0);
+        assert!((aspect_distortion((24.0, 24.0), (48.0, 24.0)) - 1.0).abs() < 1e-12);
+        assert!((aspect_distortion((100.0, 50.0), (100.0, 51.0)) - 1.0 / 51.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn source_dimensions_keep_aspect_for_bare_width() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" viewBox="0 0 100 50"/>"#,
+        )?;
+        assert_eq!(
+            source_dimensions(None, &doc, DEFAULT_DPI),
+            Some((200.0, 100.0))
+        );
+        assert_eq!(
+            source_dimensions(Some("400".parse()?), &doc, DEFAULT_DPI),
+            Some((400.0, 200.0))
+        );
+        assert_eq!(
+            source_dimensions(Some("300x300".parse()?), &doc, DEFAULT_DPI),
+            Some((300.0, 300.0))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn relative_root_sizes_fall_back_to_the_view_box() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100%" height="100%" viewBox="0 0 24 12"/>"#,
+        )?;
+        assert_eq!(get_svg_dimensions(&doc, DEFAULT_DPI), Some((24.0, 12.0)));
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100%" height="100%"/>"#,
+        )?;
+        assert_eq!(source_dimensions(None, &doc, DEFAULT_DPI), None);
+        Ok(())
+    }
+
+    #[test]
+    fn root_size_detection_converts_physical_units() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="1in" height="72pt"/>"#,
+        )?;
+        assert_eq!(get_svg_size(&doc, DEFAULT_DPI), Some(96.0));
+        assert_eq!(get_svg_dimensions(&doc, 300.0), Some((300.0, 300.0)));
+
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="25.4mm" height="100%" viewBox="0 0 24 12"/>"#,
+        )?;
+        assert_eq!(get_svg_size(&doc, DEFAULT_DPI), Some(96.0));
+        assert_eq!(get_svg_dimensions(&doc, DEFAULT_DPI), Some((24.0, 12.0)));
+
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100%" viewBox="0 0 24 24"/>"#,
+        )?;
+        assert_eq!(get_svg_size(&doc, DEFAULT_DPI), Some(24.0));
+        Ok(())
+    }
+
+    #[test]
+    fn root_size_detection_prefers_css() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100" style="width:24px; height:12px"/>"#,
+        )?;
+        assert_eq!(get_svg_size(&doc, DEFAULT_DPI), Some(24.0));
+        assert_eq!(get_svg_dimensions(&doc, DEFAULT_DPI), Some((24.0, 12.0)));
+
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" height="10"><style>svg { width: 0.5in }</style></svg>"#,
+        )?;
+        assert_eq!(get_svg_dimensions(&doc, DEFAULT_DPI), Some((48.0, 10.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn namespaces_are_declared_on_self_closing_roots() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="10"/>"#,
+        )?;
+        let ctx = ScaleCtx {
+            scale: 2.0,
+            ..ScaleCtx::default()
+        };
+        let mut out = Vec::new();
+        write_svg_to(&doc, &ctx, &svg::WalkOptions::default(), &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(
+            out.contains(r#"<svg width="20" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"/>"#),
+            "got: {out}"
+        );
+        assert_eq!(out, write_svg(&doc, &ctx)?);
+        Ok(())
+    }
+
+    #[test]
+    fn canonical_order_is_independent_of_the_source() -> Result<()> {
+        let a = canonicalize(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><rect style='opacity:1' fill="red" height="2" x="1" class='c' id="r" stroke="blue" width="2"/></svg>"#,
+        )?;
+        let b = canonicalize(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><rect id="r" width="2" stroke="blue" x="1" style="opacity:1" height="2" fill="red" class="c"/></svg>"#,
+        )?;
+        assert_eq!(a, b);
+        assert!(a.contains(
+            r#"<rect id="r" class="c" x="1" width="2" height="2" fill="red" stroke="blue" style="opacity:1"/>"#
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn streamed_output_matches_the_buffered_writer() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10"><g><title>a &lt; b</title><rect width="1" font-family='"Noto Sans"'/><g/></g><text x="1">hi<tspan>there</tspan></text></svg>"#,
+        )?;
+        let ctx = ScaleCtx {
+            scale: 2.0,
+            ..ScaleCtx::default()
+        };
+        let mut out = Vec::new();
+        write_svg_to(&doc, &ctx, &svg::WalkOptions::default(), &mut out)?;
+        assert_eq!(String::from_utf8(out)?, write_svg(&doc, &ctx)?);
+        Ok(())
+    }
+}
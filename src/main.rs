@@ -1,352 +1,2552 @@
 use anyhow::*;
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::result::Result::Ok;
-use std::{fs, path::Path};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+use xmlwriter::XmlWriter;
 
-use resvg::{tiny_skia, usvg};
+use cache::RenderCache;
+use exit::Failure;
+use svg_scale::android::{self, MIPMAP_DENSITIES};
+use svg_scale::bitmap::PixelOrder;
+use svg_scale::config;
+use svg_scale::data_uri;
+use svg_scale::explain::explain;
+use svg_scale::extension::{Store, EXTENSION_SIZES};
+use svg_scale::favicon::{
+    self, APPLE_TOUCH_SIZE, FAVICON_SIZES, MASKABLE_SAFE_ZONE, MASKABLE_SIZES,
+};
+use svg_scale::icns::{self, ICNS_ICONS};
+use svg_scale::ico::{self, ICO_SIZES};
+use svg_scale::ios::{self, IOS_APP_ICONS};
+use svg_scale::layers;
+use svg_scale::markers::{marker_effects, MarkerChange};
+use svg_scale::pdf;
+use svg_scale::render::{
+    encode_opaque_png, render_svg_to_pdf_page, render_svg_to_pixmap, render_svg_to_png_tiled,
+    render_svg_to_square_pixmap, unavailable_font_families, write_bmp, write_c_source,
+    write_indexed_png, write_raw, write_webp, MAX_PIXMAP_PIXELS,
+};
+use svg_scale::roundtrip::check_round_trip;
+use svg_scale::scale::{self, ScaleCtx};
+use svg_scale::stream::{scale_stream_with, StreamRoot};
+use svg_scale::stretch;
+use svg_scale::svg::{self, RootSize, SelectorList, StyleSheet, ValueKind, WalkOptions};
+use svg_scale::symbols;
+use svg_scale::verify::compare_geometry;
+use svg_scale::viewbox::{self, ViewBox};
+use svg_scale::{
+    aspect_distortion, canonicalize, finish_document, finish_document_to, get_svg_dimensions,
+    get_svg_size, parse_targets, source_dimensions, write_svg_with_options, SizeSpec, TargetSpec,
+    DEFAULT_DPI,
+};
+use timings::Timings;
 
-mod path;
-mod scale;
-mod svg;
-mod transform;
-
-use scale::ScaleCtx;
+mod bench;
+mod cache;
+mod daemon;
+mod exit;
+mod glob;
+mod manifest;
+mod normalize;
+mod serve;
+mod timings;
+mod tools;
+mod tui;
 
 #[derive(Parser)]
+#[command(
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true,
+    after_help = exit::HELP
+)]
 struct Cli {
-    /// 输入 SVG 文件
-    #[arg(short, long)]
-    input: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// 输入 SVG 文件，或 data:image/svg+xml URI（base64 或 URL 编码）；可重复，与 -o 按顺序配对
+    #[arg(short, long, required_unless_present_any = ["daemon", "inputs", "input_dir"])]
+    input: Vec<String>,
+
+    /// 更多输入文件，接在 -i 之后；支持通配符，如 icons/*.svg、icons/**/*.svg
+    #[arg(value_name = "FILE")]
+    inputs: Vec<String>,
+
+    /// 常驻模式：从 stdin 逐行读取 JSON-RPC 请求，结果写到 stdout
+    #[arg(long)]
+    daemon: bool,
 
     #[arg(long)]
     vscode: bool,
 
-    #[arg(long, default_value = "4")]
+    /// 按平台规范生成整套图标：ios-appicon 在输出目录（默认当前目录）写出 AppIcon.appiconset/，含 iPhone、iPad 和 App Store 所需的全部 PNG 及 Contents.json；android 写出 mipmap-mdpi 到 mipmap-xxxhdpi 的 ic_launcher.png；favicon 写出 favicon.ico、16/32/48 PNG、apple-touch-icon.png、192/512 maskable PNG 和 site.webmanifest，并打印要粘贴到 HTML 的 <link> 标签；chrome-extension / firefox-extension 在输出目录（默认 icons）写出 16/32/48/128 的 SVG 和 PNG，按各商店的命名习惯，并打印 manifest.json 的 icons 项；其他名称取自 svg-scale.toml 中的 [preset.NAME]（尺寸、格式、命名模板、输出目录、fix-stroke 等，命令行给出的选项优先），vscode 是内置的同类预设
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["vscode", "stream"])]
+    preset: Option<String>,
+
+    /// --preset 读取的配置文件，默认为当前目录下的 svg-scale.toml（若存在）
+    #[arg(long, value_name = "FILE", requires = "preset")]
+    config: Option<PathBuf>,
+
+    /// 配合 --preset android，另写出自适应图标前景 ic_launcher_foreground.svg：108dp 画布，图形居中缩放到 66dp 安全区内
+    #[arg(long, requires = "preset")]
+    adaptive_icon: bool,
+
+    #[arg(long, default_value = "4", value_parser = scale::parse_precision)]
     precision: usize,
 
-    /// 原始尺寸（可选）
+    /// 用十进制精确计算缩放结果，避免二进制浮点误差（如 0.1 * 3）
     #[arg(long)]
-    from: Option<f64>,
+    exact: bool,
 
-    /// 目标尺寸，如 128 或 16,32,48
+    /// 物理单位（mm、pt 等）换算为像素时使用的 DPI
+    #[arg(long, default_value_t = DEFAULT_DPI)]
+    dpi: f64,
+
+    /// 原始尺寸（可选），如 512 或 200x100
     #[arg(long)]
-    to: Option<String>,
+    from: Option<SizeSpec>,
 
-    /// 直接指定比例（优先级最高）
+    /// 原始高度（可选），宽度按文档宽高比计算
+    #[arg(long, value_name = "N", conflicts_with = "from")]
+    from_height: Option<f64>,
+
+    /// 目标尺寸，如 128 或 16,32,48；可为单个尺寸覆盖选项，如 "16:fix-stroke,precision=2;48;128:precision=5"
     #[arg(long)]
+    to: Option<String>,
+
+    /// 按高度指定目标尺寸，如 32 或 16,32；宽度按原始宽高比计算，适合非正方形图标
+    #[arg(long, value_name = "SIZE|LIST", conflicts_with_all = ["to", "stream"])]
+    to_height: Option<String>,
+
+    /// 直接指定比例（优先级最高），支持 0.5、2/3、150% 等表达式
+    #[arg(long, value_parser = scale::parse_scale_expr, allow_negative_numbers = true)]
     scale: Option<f64>,
 
-    /// 输出文件（单尺寸）
+    /// 水平方向比例，与 --scale-y 一起做非等比缩放；未给出的方向使用 --scale（默认 1）
+    #[arg(long, value_parser = scale::parse_scale_expr, allow_negative_numbers = true, conflicts_with_all = ["to", "to_height", "to_area", "target_viewbox", "out_dir", "stream"])]
+    scale_x: Option<f64>,
+
+    /// 垂直方向比例，见 --scale-x
+    #[arg(long, value_parser = scale::parse_scale_expr, allow_negative_numbers = true, conflicts_with_all = ["to", "to_height", "to_area", "target_viewbox", "out_dir", "stream"])]
+    scale_y: Option<f64>,
+
+    /// 允许负的比例：按绝对值缩放并镜像图形（负的 --scale 同时翻转两个方向，--scale-x/--scale-y 只翻转对应方向）
+    #[arg(long, conflicts_with = "stream")]
+    allow_negative_scale: bool,
+
+    /// Axes to mirror, taken from negative factors under --allow-negative-scale
+    #[arg(skip)]
+    mirror: (bool, bool),
+
+    /// Directory each input was found under by a wildcard or --input-dir,
+    /// parallel to `input`
+    #[arg(skip)]
+    input_bases: Vec<Option<PathBuf>>,
+
+    /// 按内容包围盒面积（px²）选择比例，使长宽比不同的图标视觉分量一致，如 256
+    #[arg(long, value_name = "N", conflicts_with_all = ["scale", "to", "to_height", "target_viewbox", "out_dir", "stream"])]
+    to_area: Option<f64>,
+
+    /// 目标坐标系，如 "0 0 24 24"：自动计算比例和平移，使内容恰好映射到该 viewBox
+    #[arg(long, value_name = "VIEWBOX", value_parser = ViewBox::parse_arg, conflicts_with_all = ["scale", "to", "to_height", "out_dir"])]
+    target_viewbox: Option<ViewBox>,
+
+    /// 按顺序在内存中执行的操作，如 "trim,pad=2,scale=0.5,snap"；可用 trim、pad=N、scale=F、to=N、snap[=STEP]、normalize、optimize、canonical
+    #[arg(long, value_name = "LIST", conflicts_with = "stream")]
+    ops: Option<svg_scale::ops::OpList>,
+
+    /// 输出文件（单尺寸）；多个 -i 时每个输入对应一个 -o
     #[arg(short, long)]
-    output: Option<String>,
+    output: Vec<String>,
 
     /// 批量输出目录
     #[arg(long)]
     out_dir: Option<String>,
 
+    /// 直接指定单个位图输出（-o x.png 等）的像素尺寸，如 64 或 64x32，覆盖按比例算出的尺寸
+    #[arg(long, value_name = "SIZE", conflicts_with_all = ["out_dir", "vscode", "stream"])]
+    png_size: Option<SizeSpec>,
+
+    /// 递归处理目录下的所有 .svg，并在 --out-dir 中保留相同的子目录结构
+    #[arg(
+        long,
+        value_name = "DIR",
+        requires = "out_dir",
+        conflicts_with = "vscode"
+    )]
+    input_dir: Option<String>,
+
+    /// 批量输出的格式，如 svg,png,webp,pdf,rgba,bmp,c-array,lvgl（每个尺寸各生成一份）
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "svg",
+        requires = "out_dir"
+    )]
+    formats: Vec<ExportFormat>,
+
+    /// 缓存渲染出的 PNG（按缩放后内容的哈希、尺寸和渲染选项），重复运行时跳过未变的图标；默认目录为用户缓存目录下的 svg-scale
+    #[arg(long, value_name = "DIR", num_args = 0..=1)]
+    render_cache: Option<Option<PathBuf>>,
+
+    /// PNG 输出使用最多 N 色的调色板（索引色），适合 16x16 等小图标，体积明显更小
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(2..=256))]
+    png_colors: Option<u16>,
+
+    /// rgba 原始像素和 c-array 输出的通道顺序: rgba、bgra、argb 或 abgr
+    #[arg(long, value_name = "ORDER", default_value = "rgba")]
+    pixel_order: PixelOrder,
+
+    /// rgba 原始像素前写入 8 字节头（宽、高，各为小端 u32）
+    #[arg(long)]
+    raw_header: bool,
+
+    /// 把所有输入的所有尺寸合并成一个多页 PDF（每页一个图标，页面即目标尺寸），便于评审签字
+    #[arg(long, value_name = "FILE", requires = "out_dir")]
+    combine_pdf: Option<PathBuf>,
+
+    /// 并行处理各尺寸的线程数，默认与 CPU 核数相同
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), requires = "out_dir")]
+    threads: Option<u16>,
+
+    /// 批量输出的文件名模板，可用 {stem}（输入文件名）、{size} 和 {format}，默认 {stem}-{size}.{format}
+    #[arg(long, value_name = "TEMPLATE", requires = "out_dir")]
+    name_template: Option<String>,
+
     /// 移除 non-scaling-stroke
     #[arg(long)]
     fix_stroke: bool,
-}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+    /// 描边宽度按 scale^EXPONENT 缩放（如 0.75），而不是线性缩放，使线条图标在 16–128px 间保持视觉均衡
+    #[arg(long, value_name = "EXPONENT")]
+    stroke_compensation: Option<f64>,
 
-    if cli.vscode {
-        vscode_pipeline(&cli)?;
-    } else {
-        normal_pipeline(&cli)?;
+    /// 按固定顺序（id、class、几何属性、表现属性、style）排列属性并统一引号，便于不同工具导出的文件互相 diff
+    #[arg(long)]
+    canonical: bool,
+
+    /// 将缩放后的根 viewBox 向外取整（宽高随之取整），并微调比例使内容宽度恰好为整数，适配不接受小数画布尺寸的平台
+    #[arg(long)]
+    round_viewbox: bool,
+
+    /// 额外缩放的属性，如 data-x 或 data-pts:list（类型: length|list|inverse，默认 length）
+    #[arg(long = "scale-attr", value_name = "NAME[:KIND]", value_parser = svg::parse_scale_attr)]
+    scale_attrs: Vec<(String, ValueKind)>,
+
+    /// 匹配的元素（及其子树）保持原样不缩放，如 "#watermark"
+    #[arg(long, value_name = "SELECTORS")]
+    exclude: Option<SelectorList>,
+
+    /// 只缩放匹配的元素（及其子树），如 ".glyph"；未匹配祖先的 transform 保持不变，
+    /// 匹配元素会补一个平移，落在整体缩放时的位置
+    #[arg(long, value_name = "SELECTORS")]
+    only: Option<SelectorList>,
+
+    /// 把图标表中匹配的最外层元素（如 "g[id^=icon-]"）各自裁剪到内容边界后单独输出；以元素 id（无 id 时为 文件名-序号）作为 {stem}
+    #[arg(
+        long,
+        value_name = "SELECTORS",
+        requires = "out_dir",
+        conflicts_with = "stream"
+    )]
+    split_by: Option<SelectorList>,
+
+    /// 只保留名为 NAME 的 Inkscape 图层（inkscape:label 或 id），删除其它图层；隐藏的图层会被显示
+    #[arg(long, value_name = "NAME", conflicts_with = "stream")]
+    layer: Option<String>,
+
+    /// 列出以不同尺寸引用同一 <symbol> 的 <use>，以及缩放后各实例的尺寸和 symbol 单位到用户坐标的倍数
+    #[arg(long)]
+    check_symbols: bool,
+
+    /// 把以不同尺寸引用同一 <symbol> 的 <use> 各自的缩放写进其 transform，使所有实例按 symbol 的 viewBox 尺寸引用，便于之后内联或重写 id
+    #[arg(long, conflicts_with = "stream")]
+    bake_symbol_scales: bool,
+
+    /// 每个 Inkscape 图层单独输出一份（删除其它图层），以 文件名-图层名 作为 {stem}
+    #[arg(long, requires = "out_dir", conflicts_with_all = ["stream", "layer", "split_by"])]
+    each_layer: bool,
+
+    /// 匹配的子树在整体比例之外再乘以该比例，并以其图形中心为基准缩放、保持原位，如 "#badge=1.5"（可重复）
+    #[arg(long = "scale-for", value_name = "SELECTORS=FACTOR", value_parser = svg::parse_scale_override)]
+    scale_for: Vec<(SelectorList, f64)>,
+
+    /// 移除根元素的 width/height（必要时补充 viewBox），内联到 HTML 时可随容器缩放
+    #[arg(long, conflicts_with = "explicit_size")]
+    responsive: bool,
+
+    /// 根据（缩放后的）viewBox 写出根元素的 width/height，供要求明确尺寸的工具使用
+    #[arg(long, conflicts_with = "keep_size")]
+    explicit_size: bool,
+
+    /// 保持根元素的显示尺寸不变，只按比例提高（或降低）内部坐标精度，如 --scale 10 --keep-size
+    #[arg(long, conflicts_with = "responsive")]
+    keep_size: bool,
+
+    /// 把 marker 的 refX/refY 关键字（left、center、bottom 等）换算为数值，供不支持 SVG 2 的工具使用
+    #[arg(long)]
+    bake_marker_refs: bool,
+
+    /// 把 stroke-dasharray 中的百分比按视口对角线换算为用户单位后再缩放
+    #[arg(long)]
+    resolve_dash_percentages: bool,
+
+    /// 删除 SVG 2 已废弃的元素（cursor、SVG 字体、tref、animateColor 等），altGlyph 替换为其文本
+    #[arg(long)]
+    drop_deprecated: bool,
+
+    /// 同时缩放 <image> 中以 data:image/svg+xml URI 内嵌的 SVG，并按原编码方式（base64 或百分号编码）写回
+    #[arg(long)]
+    recurse_embedded: bool,
+
+    /// 按 requiredFeatures/requiredExtensions/systemLanguage 求值 <switch>，只保留生效的分支；可指定语言，默认 en
+    #[arg(long, value_name = "LANG", num_args = 0..=1, default_missing_value = "en")]
+    resolve_switch: Option<String>,
+
+    /// 流式处理超大文件（内存占用有限）；不支持 <style> 规则和选择器相关选项
+    #[arg(long, conflicts_with_all = [
+        "vscode", "out_dir", "target_viewbox", "responsive", "explicit_size", "keep_size",
+        "exclude", "only", "scale_for", "scale_attrs", "bake_marker_refs",
+        "resolve_dash_percentages", "resolve_switch", "drop_deprecated", "recurse_embedded",
+        "manifest", "metadata",
+        "canonical",
+    ])]
+    stream: bool,
+
+    /// 用 usvg 解析输入和输出，逐个元素校验缩放后的几何（路径、变换、描边），报告不一致的元素
+    #[arg(long, conflicts_with = "stream")]
+    verify: bool,
+
+    /// 渲染位图前发现文字所需字体不可用、或宽高比失真超过 --aspect-tolerance 时报错退出（默认只警告）
+    #[arg(long)]
+    strict: bool,
+
+    /// 指定的宽高比与文档自身宽高比允许的相对偏差，超过时警告（例如 0.01 表示 1%）
+    #[arg(long, value_name = "FRACTION", default_value = "0.01")]
+    aspect_tolerance: f64,
+
+    /// WxH 目标或非等比的 --scale-x/--scale-y 与原图宽高比不符时的处理方式：stretch 拉伸填满，meet 等比缩放放入并居中留白，slice 等比缩放铺满并居中裁切；未指定时拉伸，失真超过 --aspect-tolerance 时警告
+    #[arg(long, value_enum, value_name = "MODE", conflicts_with = "stream")]
+    fit_mode: Option<FitMode>,
+
+    /// --verify 允许的最大偏差（输出像素）
+    #[arg(long, default_value = "0.01", requires = "verify")]
+    verify_tolerance: f64,
+
+    /// 先按比例缩放再按倒数缩放回来，检查结果是否与原文件在精度范围内一致，列出无法还原的属性
+    #[arg(long, conflicts_with_all = ["stream", "out_dir", "scale_for"])]
+    self_check: bool,
+
+    /// 在输出目录写出 manifest.json，列出每个生成文件的格式、尺寸、字节数和 SHA-256（用于 --out-dir 或 --vscode）
+    #[arg(long)]
+    manifest: bool,
+
+    /// 在输出目录写出资源元数据模块（assets.ts 或 assets.json），列出每个文件的名称、路径、尺寸和 viewBox，供设计系统直接导入
+    #[arg(long, value_enum, value_name = "FORMAT", requires = "out_dir")]
+    metadata: Option<manifest::MetadataFormat>,
+
+    /// 批量输出或多个输入时某个文件失败只记录错误并继续，结束时汇总失败项并以非零状态退出
+    #[arg(long)]
+    keep_going: bool,
+
+    /// 输入的声明尺寸已等于目标尺寸时不再按 1.0 重新缩放，而是原样复制（其它改写选项对其无效）
+    #[arg(long, conflicts_with = "stream")]
+    skip_up_to_date_size: bool,
+
+    /// 在 stderr 列出匹配元素的每个属性及其处理方式（已缩放、因祖先变换跳过、objectBoundingBox、不支持的单位等）
+    #[arg(long, value_name = "SELECTORS", conflicts_with_all = ["stream", "out_dir"])]
+    explain: Option<SelectorList>,
+
+    /// 在 stderr 输出每个文件的解析、缩放、序列化和渲染耗时以及总耗时
+    #[arg(long, conflicts_with = "stream")]
+    timings: bool,
+}
+
+/// Validate the given factors; negative ones allowed by
+/// --allow-negative-scale become mirrored axes and their magnitude
+fn check_scale_factors(cli: &mut Cli) -> Result<()> {
+    for (flag, factor) in [
+        ("--scale", cli.scale),
+        ("--scale-x", cli.scale_x),
+        ("--scale-y", cli.scale_y),
+    ] {
+        let Some(factor) = factor else { continue };
+        if factor < 0.0 && !cli.allow_negative_scale {
+            bail!(
+                "{} {} 为负，会镜像图形；如需镜像请加 --allow-negative-scale",
+                flag,
+                factor
+            );
+        }
+        scale::check_scale(factor, true).with_context(|| format!("{} 的比例不可用", flag))?;
     }
+    let negative = |factor: Option<f64>| factor.is_some_and(|f| f < 0.0);
+    cli.mirror = (
+        negative(cli.scale_x.or(cli.scale)),
+        negative(cli.scale_y.or(cli.scale)),
+    );
+    for factor in [&mut cli.scale, &mut cli.scale_x, &mut cli.scale_y] {
+        *factor = factor.map(f64::abs);
+    }
+    Ok(())
+}
 
+/// Fill in the options the pipeline preset `name` sets, from --config or
+/// `svg-scale.toml` and then the built-ins; options given on the command
+/// line win
+fn apply_pipeline_preset(cli: &mut Cli, matches: &ArgMatches, name: &str) -> Result<()> {
+    let path = match &cli.config {
+        Some(path) => Some(path.clone()),
+        None => Some(PathBuf::from(config::CONFIG_FILE)).filter(|p| p.is_file()),
+    };
+    let text = match &path {
+        Some(path) => {
+            Some(fs::read_to_string(path).with_context(|| format!("读取 {}", path.display()))?)
+        }
+        None => None,
+    };
+    let source = path.map_or("内置预设".into(), |p| p.display().to_string());
+    let Some(preset) = config::find_preset(text.as_deref(), name)
+        .with_context(|| format!("无法读取预设: {}", source))?
+    else {
+        let mut names: Vec<String> = Preset::value_variants()
+            .iter()
+            .filter_map(|p| Some(p.to_possible_value()?.get_name().to_string()))
+            .collect();
+        names.extend(config::preset_names(text.as_deref()));
+        bail!("未知预设 {}，可用: {}", name, names.join(", "));
+    };
+    if cli.adaptive_icon {
+        bail!("--adaptive-icon 只能配合 --preset android 使用");
+    }
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let sized = [
+        "scale",
+        "to",
+        "to_height",
+        "to_area",
+        "target_viewbox",
+        "scale_x",
+        "scale_y",
+    ]
+    .iter()
+    .any(|id| given(id));
+    if !sized {
+        cli.to = preset.to.or(cli.to.take());
+        cli.scale = preset.scale.or(cli.scale);
+    }
+    if !given("from") && !given("from_height") {
+        if let Some(width) = preset.from {
+            cli.from = Some(SizeSpec {
+                width,
+                height: None,
+            });
+        }
+    }
+    if !given("formats") && !preset.formats.is_empty() {
+        cli.formats = preset
+            .formats
+            .iter()
+            .map(|f| {
+                ExportFormat::from_str(f, true)
+                    .map_err(|_| anyhow!("预设 {} 的格式 {} 无效", name, f))
+            })
+            .collect::<Result<_>>()?;
+    }
+    if !given("name_template") {
+        cli.name_template = preset.name_template.or(cli.name_template.take());
+    }
+    if !given("out_dir") {
+        cli.out_dir = preset.out_dir.or(cli.out_dir.take());
+    }
+    if !given("fix_stroke") {
+        cli.fix_stroke = preset.fix_stroke.unwrap_or(cli.fix_stroke);
+    }
+    if !given("precision") {
+        cli.precision = preset.precision.unwrap_or(cli.precision);
+    }
     Ok(())
 }
 
-fn write_svg(doc: &roxmltree::Document, ctx: &ScaleCtx) -> Result<String> {
-    let mut writer = xmlwriter::XmlWriter::new(xmlwriter::Options::default());
-    svg::walk(doc.root_element(), &mut writer, ctx)?;
-    let mut svg = writer.end_document();
+/// Platform icon sets written by --preset
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Preset {
+    /// Xcode `AppIcon.appiconset` with `Contents.json`
+    IosAppicon,
+    /// Launcher icons in `mipmap-<density>/`
+    Android,
+    /// Website favicons, touch and maskable icons with `site.webmanifest`
+    Favicon,
+    /// Chrome extension icons, `icon<size>.png`
+    ChromeExtension,
+    /// Firefox extension icons, `icon-<size>.svg`
+    FirefoxExtension,
+}
 
-    // Prepend XML declaration
-    svg.insert_str(
-        0,
-        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n",
-    );
+/// How a box with another aspect ratio than the source's is filled
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum FitMode {
+    /// Stretch the drawing to the box
+    Stretch,
+    /// Scale uniformly to fit inside the box, centered between margins
+    Meet,
+    /// Scale uniformly to cover the box, centered and cropped
+    Slice,
+}
 
-    // Preserve namespace declarations from root element
-    let mut ns_decls: Vec<String> = Vec::new();
-    for ns in doc.root_element().namespaces() {
-        if let Some(name) = ns.name() {
-            ns_decls.push(format!(" xmlns:{}=\"{}\"", name, ns.uri()));
-        } else {
-            ns_decls.push(format!(" xmlns=\"{}\"", ns.uri()));
+/// What reaching a box of another aspect ratio does to the source before
+/// the uniform pass: a vertical stretch, or under --fit-mode meet/slice a
+/// root viewBox grown or shrunk about its center by `(kx, ky)` around the
+/// unchanged drawing
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reshape {
+    Stretch(f64),
+    Frame(f64, f64),
+}
+
+impl Reshape {
+    /// For a box whose width/height ratio is `q` times the source's
+    fn for_ratio(q: f64, mode: Option<FitMode>) -> Reshape {
+        match (mode, q > 1.0) {
+            (Some(FitMode::Meet), true) | (Some(FitMode::Slice), false) => Reshape::Frame(q, 1.0),
+            (Some(FitMode::Meet), false) | (Some(FitMode::Slice), true) => {
+                Reshape::Frame(1.0, 1.0 / q)
+            }
+            _ => Reshape::Stretch(1.0 / q),
         }
     }
 
-    // Insert namespace declarations after the opening <svg tag
-    if let Some(pos) = svg.find("<svg") {
-        if let Some(end_pos) = svg[pos..].find('>') {
-            let insert_pos = pos + end_pos;
-            let ns_str = ns_decls.join("");
-            svg.insert_str(insert_pos, &ns_str);
+    /// Factors the source's width and height change by
+    fn factors(self) -> (f64, f64) {
+        match self {
+            Reshape::Stretch(ratio) => (1.0, ratio),
+            Reshape::Frame(kx, ky) => (kx, ky),
         }
     }
 
-    Ok(svg)
+    fn apply(self, doc: &roxmltree::Document, dpi: f64) -> Result<String> {
+        match self {
+            Reshape::Stretch(ratio) => stretch::stretch_document(doc, 1.0, ratio),
+            Reshape::Frame(kx, ky) => viewbox::resize_view_box(doc, kx, ky, dpi),
+        }
+    }
 }
 
-fn get_svg_size(doc: &roxmltree::Document) -> Option<f64> {
-    let root = doc.root_element();
-    // Try width attribute first
-    if let Some(w) = root.attribute("width") {
-        // Remove "px" if present and parse
-        let w_str = w.trim_end_matches("px");
-        if let Ok(val) = w_str.parse::<f64>() {
-            return Some(val);
-        }
-    }
-    // Try viewBox
-    if let Some(view_box) = root.attribute("viewBox") {
-        let parts: Vec<&str> = view_box.split_whitespace().collect();
-        if parts.len() == 4 {
-            if let Ok(w) = parts[2].parse::<f64>() {
-                return Some(w);
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ExportFormat {
+    Svg,
+    Png,
+    Webp,
+    Pdf,
+    /// Raw pixel dump in --pixel-order
+    Rgba,
+    Bmp,
+    /// `.c`/`.h` pair with a byte array in --pixel-order
+    CArray,
+    /// `.c`/`.h` pair with an LVGL image descriptor
+    Lvgl,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Svg => "svg",
+            ExportFormat::Png => "png",
+            ExportFormat::Webp => "webp",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Rgba => "rgba",
+            ExportFormat::Bmp => "bmp",
+            ExportFormat::CArray | ExportFormat::Lvgl => "c",
+        }
+    }
+
+    /// Raster format implied by an output file's extension
+    fn from_raster_path(path: &str) -> Option<Self> {
+        [
+            ExportFormat::Png,
+            ExportFormat::Webp,
+            ExportFormat::Rgba,
+            ExportFormat::Bmp,
+            ExportFormat::CArray,
+        ]
+        .into_iter()
+        .find(|f| path.ends_with(&format!(".{}", f.extension())))
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 启动本地预览服务器，并排显示原图和各尺寸缩放结果
+    Serve(serve::ServeArgs),
+    /// 将原点不为 0 的 viewBox 归一化为 "0 0 W H"（内容随之平移），可选再缩放
+    Normalize(normalize::NormalizeArgs),
+    /// 清理 SVG（移除未引用的 defs、隐藏元素、编辑器数据，合并分组，压缩数字和路径），可选先缩放
+    Optimize(tools::OptimizeArgs),
+    /// 只缩放路径数据（d 属性的值），结果输出到 stdout
+    Path(tools::PathArgs),
+    /// 输出根元素某个属性的值（可选先缩放），便于编辑器片段和 Makefile 查询尺寸
+    Get(tools::GetArgs),
+    /// 合成变换列表并输出等价矩阵（可选分解或求逆）
+    Transform(tools::TransformArgs),
+    /// 只读检查图标目录：报告尺寸、viewBox 或内容边界偏离网格以及需要 --fix-stroke 的文件
+    Audit(tools::AuditArgs),
+    /// 测量解析、遍历、序列化和渲染耗时以及峰值内存
+    Bench(bench::BenchArgs),
+    /// 在终端中交互调整比例、精度、fix-stroke 和坐标对齐，实时预览后写出结果
+    Tui(tui::TuiArgs),
+}
+
+/// One `-i` and the `-o` paired with it
+#[derive(Clone, Copy)]
+struct Job<'a> {
+    input: &'a str,
+    output: Option<&'a str>,
+    /// Directory of the wildcard or --input-dir that found the input
+    base: Option<&'a Path>,
+}
+
+impl<'a> Job<'a> {
+    /// The input file, or `None` when --input is a `data:` URI
+    fn input_path(&self) -> Option<&'a Path> {
+        let is_data_uri = self
+            .input
+            .get(..5)
+            .is_some_and(|p| p.eq_ignore_ascii_case("data:"));
+        (!is_data_uri).then(|| Path::new(self.input))
+    }
+
+    /// Name of the input in messages
+    fn input_label(&self) -> String {
+        match self.input_path() {
+            Some(path) => path.display().to_string(),
+            None => "data: URI".to_string(),
+        }
+    }
+
+    /// Document text from --input: a file, or a base64 / URL-encoded `data:` URI
+    fn read_input(&self) -> Result<String> {
+        match self.input_path() {
+            Some(path) => Ok(fs::read_to_string(path)?),
+            None => Ok(data_uri::decode(self.input)?.context("无法解析 data: URI")?),
+        }
+    }
+}
+
+impl Cli {
+    /// The --to targets, or the --to-height heights as targets with the
+    /// width that keeps the source's aspect ratio
+    fn targets(&self, from_w: f64, from_h: f64) -> Result<Option<Vec<TargetSpec>>> {
+        if let Some(to) = &self.to {
+            return parse_targets(to).map(Some);
+        }
+        let Some(heights) = &self.to_height else {
+            return Ok(None);
+        };
+        let mut targets = parse_targets(heights)?;
+        for target in &mut targets {
+            if target.height.is_some() {
+                bail!("--to-height 只接受高度，如 32；同时指定宽高请用 --to WxH");
             }
+            target.height = Some(target.size);
+            target.by_height = true;
+            target.size *= from_w / from_h;
+        }
+        Ok(Some(targets))
+    }
+
+    /// The --preset that writes a platform icon set, if it names one
+    fn icon_preset(&self) -> Option<Preset> {
+        self.preset
+            .as_deref()
+            .and_then(|name| Preset::from_str(name, false).ok())
+    }
+
+    /// Pair every `-i` with its `-o`, rejecting counts that do not line up
+    /// with a usage error
+    fn jobs(&self) -> Result<Vec<Job<'_>>> {
+        let (inputs, outputs) = (self.input.len(), self.output.len());
+        let paired = outputs == inputs || (outputs == 0 && (inputs == 1 || self.out_dir.is_some()));
+        if self.vscode && inputs > 1 {
+            return Err(Cli::command()
+                .error(ErrorKind::ArgumentConflict, "--vscode 只支持一个 --input")
+                .into());
+        }
+        if !paired {
+            return Err(Cli::command()
+                .error(
+                    ErrorKind::WrongNumberOfValues,
+                    format!(
+                        "{} 个 --input 需要对应 {} 个 --output（或使用 --out-dir），实际为 {} 个",
+                        inputs, inputs, outputs
+                    ),
+                )
+                .into());
+        }
+        Ok(self
+            .input
+            .iter()
+            .enumerate()
+            .map(|(i, input)| Job {
+                input,
+                output: self.output.get(i).map(String::as_str),
+                base: self.input_bases.get(i).and_then(Option::as_deref),
+            })
+            .collect())
+    }
+
+    /// Output directory of one input: --out-dir, or for inputs found by a
+    /// wildcard or --input-dir their folder below its base mirrored inside
+    /// --out-dir
+    fn out_dir_for(&self, job: Job) -> Option<PathBuf> {
+        let out_dir = Path::new(self.out_dir.as_ref()?);
+        let folder = job
+            .base
+            .zip(job.input_path())
+            .and_then(|(base, input)| input.parent()?.strip_prefix(base).ok());
+        Some(match folder {
+            Some(folder) => out_dir.join(folder),
+            None => out_dir.to_path_buf(),
+        })
+    }
+
+    fn threads(&self) -> usize {
+        match self.threads {
+            Some(n) => n.into(),
+            None => thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    fn walk_options(&self) -> WalkOptions {
+        let mut hooks = Vec::new();
+        if !self.scale_attrs.is_empty() {
+            hooks.push(svg::scale_attr_hook(self.scale_attrs.clone()));
+        }
+        WalkOptions {
+            hooks,
+            exclude: self.exclude.clone(),
+            only: self.only.clone(),
+            scale_overrides: self.scale_for.clone(),
+            root_size: if self.responsive {
+                RootSize::Responsive
+            } else if self.explicit_size {
+                RootSize::Explicit
+            } else if self.keep_size {
+                RootSize::Preserve
+            } else {
+                RootSize::Keep
+            },
+            bake_marker_refs: self.bake_marker_refs,
+            resolve_dash_percentages: self.resolve_dash_percentages,
+            switch_language: self.resolve_switch.clone(),
+            drop_deprecated: self.drop_deprecated,
+            recurse_embedded: self.recurse_embedded,
         }
     }
-    None
 }
 
-fn get_svg_dimensions(doc: &roxmltree::Document) -> Option<(f64, f64)> {
-    let root = doc.root_element();
-    // Prefer width/height attributes if both are available
-    if let (Some(w), Some(h)) = (root.attribute("width"), root.attribute("height")) {
-        let w_str = w.trim_end_matches("px");
-        let h_str = h.trim_end_matches("px");
-        if let (Ok(w_val), Ok(h_val)) = (w_str.parse::<f64>(), h_str.parse::<f64>()) {
-            return Some((w_val, h_val));
+fn main() -> ExitCode {
+    match run(std::env::args_os()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Usage errors found after parsing print like clap's own
+            if let Some(e) = e.downcast_ref::<clap::Error>() {
+                e.exit();
+            }
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(exit::code_of(&e))
+        }
+    }
+}
+
+fn run(args: impl IntoIterator<Item = std::ffi::OsString>) -> Result<()> {
+    let matches = Cli::command().get_matches_from(args);
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match &cli.command {
+        Some(Command::Serve(args)) => return serve::run(args),
+        Some(Command::Normalize(args)) => return normalize::run(args),
+        Some(Command::Optimize(args)) => return tools::run_optimize(args),
+        Some(Command::Path(args)) => return tools::run_path(args),
+        Some(Command::Get(args)) => return tools::run_get(args),
+        Some(Command::Transform(args)) => return tools::run_transform(args),
+        Some(Command::Audit(args)) => return tools::run_audit(args),
+        Some(Command::Bench(args)) => return bench::run(args),
+        Some(Command::Tui(args)) => return tui::run(args),
+        None => {}
+    }
+
+    // --vscode is the built-in pipeline preset of that name
+    if cli.vscode {
+        cli.preset = Some("vscode".into());
+    }
+    if let Some(name) = cli.preset.clone().filter(|_| cli.icon_preset().is_none()) {
+        apply_pipeline_preset(&mut cli, &matches, &name)?;
+    }
+
+    check_scale_factors(&mut cli)?;
+
+    if cli.daemon {
+        return daemon::run(std::io::stdin().lock(), std::io::stdout().lock());
+    }
+
+    // Shells such as cmd.exe leave wildcards to the program
+    let mut inputs = Vec::new();
+    let mut bases = Vec::new();
+    for pattern in cli.input.iter().chain(&cli.inputs) {
+        let found = glob::expand(pattern)?;
+        bases.extend(std::iter::repeat_n(glob::base(pattern), found.len()));
+        inputs.extend(found);
+    }
+    if let Some(dir) = &cli.input_dir {
+        if !Path::new(dir).is_dir() {
+            bail!("--input-dir 不是目录: {}", dir);
+        }
+        let pattern = Path::new(dir).join("**").join("*.svg");
+        let found = glob::expand(&pattern.to_string_lossy())?;
+        bases.extend(std::iter::repeat_n(Some(PathBuf::from(dir)), found.len()));
+        inputs.extend(found);
+    }
+    cli.input = inputs;
+    cli.input_bases = bases;
+
+    let jobs = cli.jobs()?;
+    check_output_collisions(&cli, &jobs)?;
+    let mut failed = Vec::new();
+    // Pages for --combine-pdf, in input order and then size order
+    let mut pdf_pages = Vec::new();
+    for &job in &jobs {
+        let result = if cli.stream {
+            stream_pipeline(&cli, job)
+        } else if cli.split_by.is_some() || cli.each_layer {
+            split_pipeline(&cli, job, &mut pdf_pages)
+        } else {
+            normal_pipeline(&cli, job, None, &mut pdf_pages)
+        };
+        match result {
+            Err(e) if jobs.len() > 1 => {
+                let e = e.context(format!("处理 {} 失败", job.input_label()));
+                if !cli.keep_going {
+                    return Err(e);
+                }
+                eprintln!("错误: {:#}", e);
+                failed.push(job.input_label());
+            }
+            r => r?,
+        }
+    }
+    if let Some(path) = &cli.combine_pdf {
+        fs::write(path, pdf::write_pdf(&pdf_pages)).context("write pdf output")?;
+        println!("输出: {} ({} 页)", path.display(), pdf_pages.len());
+    }
+    if !failed.is_empty() {
+        eprintln!("失败汇总:");
+        for label in &failed {
+            eprintln!("  {}", label);
+        }
+        return Err(Failure::Partial.error(format!(
+            "{} / {} 个输入失败",
+            failed.len(),
+            jobs.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Fail when two inputs would write the same files inside --out-dir: same
+/// file name and the same folder there
+fn check_output_collisions(cli: &Cli, jobs: &[Job]) -> Result<()> {
+    let mut seen: Vec<(PathBuf, &Path, Job)> = Vec::new();
+    for &job in jobs.iter().filter(|j| j.output.is_none()) {
+        let (Some(dir), Some(name)) = (
+            cli.out_dir_for(job),
+            job.input_path().and_then(Path::file_stem),
+        ) else {
+            continue;
+        };
+        let name = Path::new(name);
+        if let Some((_, _, first)) = seen.iter().find(|(d, n, _)| *d == dir && *n == name) {
+            bail!(
+                "{} 和 {} 会在 {} 中生成同名文件",
+                first.input_label(),
+                job.input_label(),
+                dir.display()
+            );
         }
+        seen.push((dir, name, job));
     }
+    Ok(())
+}
+
+/// One icon of a --split-by sheet or one --each-layer layer, processed in
+/// place of the whole input
+struct SplitPiece {
+    stem: String,
+    svg: String,
+}
 
-    // Fall back to viewBox if present
-    if let Some(view_box) = root.attribute("viewBox") {
-        let parts: Vec<&str> = view_box.split_whitespace().collect();
-        if parts.len() == 4 {
-            if let (Ok(w), Ok(h)) = (parts[2].parse::<f64>(), parts[3].parse::<f64>()) {
-                return Some((w, h));
+fn split_pipeline(cli: &Cli, job: Job, pdf_pages: &mut Vec<pdf::Page>) -> Result<()> {
+    let mut sheet = job.read_input()?;
+    if let Some(name) = &cli.layer {
+        sheet = layers::keep_layer(&roxmltree::Document::parse(&sheet)?, name)?;
+    }
+    let doc = roxmltree::Document::parse(&sheet)?;
+    let stem = job
+        .input_path()
+        .and_then(Path::file_stem)
+        .map_or("icon".into(), |s| s.to_string_lossy());
+    let pieces: Vec<SplitPiece> = match &cli.split_by {
+        Some(selectors) => {
+            let pieces = svg_scale::split::split_document(&doc, selectors, cli.dpi)?;
+            println!("拆分出 {} 个图标", pieces.len());
+            pieces
+                .into_iter()
+                .enumerate()
+                .map(|(i, piece)| SplitPiece {
+                    stem: piece.id.unwrap_or_else(|| format!("{}-{}", stem, i + 1)),
+                    svg: piece.svg,
+                })
+                .collect()
+        }
+        None => {
+            let names = layers::layer_names(&doc);
+            if names.is_empty() {
+                bail!("文档中没有命名的 Inkscape 图层");
             }
+            println!("拆分出 {} 个图层", names.len());
+            names
+                .iter()
+                .map(|name| {
+                    Ok(SplitPiece {
+                        stem: format!("{}-{}", stem, layers::file_stem(name)),
+                        svg: layers::keep_layer(&doc, name)?,
+                    })
+                })
+                .collect::<Result<_>>()?
         }
+    };
+    for piece in &pieces {
+        normal_pipeline(cli, job, Some(piece), pdf_pages)?;
     }
+    Ok(())
+}
 
-    // Last resort: if width exists but height doesn't, assume square
-    get_svg_size(doc).map(|w| (w, w))
+/// `f` over `items` on at most `threads` scoped workers, in input order;
+/// an item whose call panicked gets `Err`
+fn map_parallel<T: Sync, R: Send>(
+    items: &[T],
+    threads: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<thread::Result<R>> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<thread::Result<R>>> = items.iter().map(|_| None).collect();
+    thread::scope(|s| {
+        let workers: Vec<_> = (0..threads.clamp(1, items.len().max(1)))
+            .map(|_| {
+                s.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            return done;
+                        };
+                        done.push((i, panic::catch_unwind(AssertUnwindSafe(|| f(item)))));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            // Panics are caught per item, so a worker always returns
+            for (i, result) in worker.join().unwrap_or_default() {
+                results[i] = Some(result);
+            }
+        }
+    });
+    results
+        .into_iter()
+        .map(|r| r.expect("every item is taken by a worker"))
+        .collect()
 }
 
-fn normal_pipeline(cli: &Cli) -> Result<()> {
+fn normal_pipeline(
+    cli: &Cli,
+    job: Job,
+    piece: Option<&SplitPiece>,
+    pdf_pages: &mut Vec<pdf::Page>,
+) -> Result<()> {
+    let run_start = Instant::now();
+    let mut timings = Timings::default();
+
     // 1. Parse SVG first
-    let input_svg = fs::read_to_string(&cli.input)?;
-    let doc = roxmltree::Document::parse(&input_svg)?;
+    let (input, mut input_svg) = match piece {
+        Some(piece) => (
+            format!("{}#{}", job.input_label(), piece.stem),
+            piece.svg.clone(),
+        ),
+        None => (
+            job.input_label(),
+            timings.time("parse", || job.read_input())?,
+        ),
+    };
+    if let Some(name) = &cli.layer {
+        input_svg = layers::keep_layer(&roxmltree::Document::parse(&input_svg)?, name)?;
+    }
+    if cli.bake_symbol_scales {
+        input_svg = symbols::bake_instance_scales(&roxmltree::Document::parse(&input_svg)?)?;
+    }
+    if let Some(ops) = &cli.ops {
+        input_svg = svg_scale::ops::run_ops(&input_svg, &ops.0, cli.dpi)
+            .with_context(|| format!("{}: --ops", input))?;
+    }
+    let (mirror_x, mirror_y) = cli.mirror;
+    if mirror_x || mirror_y {
+        let doc = roxmltree::Document::parse(&input_svg)?;
+        input_svg = viewbox::mirror_view_box(&doc, mirror_x, mirror_y, cli.dpi)?;
+    }
+    // --scale-x/--scale-y reshape the document to the right aspect ratio
+    // first (stretched, or reframed under --fit-mode); the uniform pass
+    // then applies the horizontal factor
+    let mut reshaped = (1.0, 1.0);
+    let stretch_scale = match (cli.scale_x, cli.scale_y) {
+        (None, None) => None,
+        (sx, sy) => {
+            let sx = sx.or(cli.scale).unwrap_or(1.0);
+            let sy = sy.or(cli.scale).unwrap_or(1.0);
+            if sx != sy {
+                let doc = roxmltree::Document::parse(&input_svg)?;
+                if let Ok(source) = viewbox::current_view_box(&doc, cli.dpi) {
+                    let (w, h) = (source.width, source.height);
+                    let what = format!("--scale-x {} --scale-y {}", sx, sy);
+                    check_fit(cli, &what, (w, h), (w * sx, h * sy))?;
+                }
+                let reshape = Reshape::for_ratio(sx / sy, cli.fit_mode);
+                input_svg = reshape
+                    .apply(&doc, cli.dpi)
+                    .with_context(|| format!("{}: --scale-x/--scale-y", input))?;
+                reshaped = reshape.factors();
+            }
+            Some(sx / reshaped.0)
+        }
+    };
+    let doc = timings.time("parse", || Ok(roxmltree::Document::parse(&input_svg)?))?;
+    let walk_opts = cli.walk_options();
 
-    // 2. Determine 'from' size
-    let from_size = if let Some(f) = cli.from {
-        f
-    } else {
-        match get_svg_size(&doc) {
-            Some(s) => {
-                println!("自动检测到原始尺寸: {}", s);
-                s
+    // --target-viewbox rewrites the document first and fixes the scale
+    let fitted_svg;
+    let (doc, fitted_scale) = match cli.target_viewbox {
+        Some(target) => {
+            let (text, scale) = viewbox::fit_to_view_box(&doc, target, cli.dpi)?;
+            fitted_svg = text;
+            (roxmltree::Document::parse(&fitted_svg)?, Some(scale))
+        }
+        None => (doc, stretch_scale),
+    };
+
+    // 2. Determine 'from' size (per axis)
+    // --from-height gives the width the document's aspect ratio implies
+    let from = match cli.from_height {
+        Some(height) => {
+            let (w, h) = get_svg_dimensions(&doc, cli.dpi)
+                .filter(|(w, h)| *w > 0.0 && *h > 0.0)
+                .unwrap_or((1.0, 1.0));
+            Some(SizeSpec {
+                width: height * w / h,
+                height: Some(height),
+            })
+        }
+        None => cli.from,
+    };
+    let (from_w, from_h) = match source_dimensions(from, &doc, cli.dpi) {
+        Some(dims) => dims,
+        None => return Err(undetected_size(&doc, cli.dpi)),
+    };
+    // --out-dir without --to writes each input under its own file name,
+    // as -o would
+    let out_dir = cli.out_dir_for(job);
+    let kept_name;
+    let targets = cli.targets(from_w, from_h)?;
+    let job = match (&out_dir, &targets) {
+        (Some(out_dir), None) if cli.formats == [ExportFormat::Svg] => {
+            let name = match piece {
+                Some(piece) => format!("{}.svg", piece.stem),
+                None => job
+                    .input_path()
+                    .and_then(Path::file_name)
+                    .map_or("icon.svg".into(), |n| n.to_string_lossy().into_owned()),
+            };
+            fs::create_dir_all(out_dir)?;
+            let out_path = out_dir.join(name);
+            let input_path = job.input_path().map(fs::canonicalize).transpose()?;
+            if input_path.is_some() && input_path == fs::canonicalize(&out_path).ok() {
+                bail!("输出文件 {} 会覆盖输入文件", out_path.display());
+            }
+            kept_name = out_path.to_string_lossy().into_owned();
+            Job {
+                output: Some(kept_name.as_str()),
+                ..job
             }
-            None => bail!("未能从SVG检测到尺寸，请使用 --from 指定原始尺寸"),
         }
+        _ => job,
+    };
+    if from.is_none() {
+        // Report the size before --scale-y stretched it
+        let (input_w, input_h) = (from_w / reshaped.0, from_h / reshaped.1);
+        if input_w == input_h {
+            println!("自动检测到原始尺寸: {}", input_w);
+        } else {
+            println!("自动检测到原始尺寸: {}x{}", input_w, input_h);
+        }
+    }
+    check_aspect(cli, &doc, from_w, from_h)?;
+    let fitted_scale = match cli.to_area {
+        Some(area) => Some(area_scale(cli, &doc, area, from_w)?),
+        None => fitted_scale,
     };
 
+    if let Some(preset) = cli.icon_preset() {
+        if cli.scale.is_some()
+            || !cli.output.is_empty()
+            || cli.to.is_some()
+            || cli.to_height.is_some()
+        {
+            bail!("图标预设使用固定的尺寸和文件名，不能与 --scale、--to、--to-height 或 -o 同用");
+        }
+        if cli.adaptive_icon && preset != Preset::Android {
+            bail!("--adaptive-icon 只能配合 --preset android 使用");
+        }
+        let is_extension = matches!(preset, Preset::ChromeExtension | Preset::FirefoxExtension);
+        if cli.manifest && !is_extension {
+            bail!("--manifest 不支持此预设，只能用于 chrome-extension 和 firefox-extension");
+        }
+        return match preset {
+            Preset::IosAppicon => write_ios_appicon(cli, &doc, from_w, from_h),
+            Preset::Android => write_android_icons(cli, &doc, from_w, from_h),
+            Preset::Favicon => write_favicons(cli, &doc, from_w, from_h),
+            Preset::ChromeExtension => {
+                write_extension_icons(cli, &doc, from_w, from_h, Store::Chrome)
+            }
+            Preset::FirefoxExtension => {
+                write_extension_icons(cli, &doc, from_w, from_h, Store::Firefox)
+            }
+        };
+    }
+
     // 3. Calculate scale or output modes
     // Check if we are in single output mode or multi-output directory mode
-    if let Some(out_dir) = &cli.out_dir {
+    if let Some(out_dir) = out_dir.filter(|_| job.output.is_none()) {
         // Multi-file output mode (requires --to)
-        let to_str = cli
-            .to
-            .as_ref()
+        let targets = targets
+            .clone()
             .context("批量输出模式需要指定 --to (例如: --to 16,32,48)")?;
-        let to_values: Vec<f64> = to_str
-            .split(',')
-            .map(|s| s.trim().parse())
-            .collect::<Result<_, _>>()?;
-
-        fs::create_dir_all(out_dir)?;
-        for &to_size in to_values.iter() {
-            let scale_i = to_size / from_size;
-            let ctx_i = ScaleCtx {
-                scale: scale_i,
-                precision: cli.precision,
-                fix_stroke: cli.fix_stroke,
-            };
-
-            let svg_i = write_svg(&doc, &ctx_i)?;
+        let to_values: Vec<f64> = targets.iter().map(|t| t.px(cli.dpi)).collect();
+        let template = match &cli.name_template {
+            Some(t) => t.as_str(),
+            None => "{stem}-{size}.{format}",
+        };
+        let stem = match piece {
+            Some(piece) => piece.stem.as_str().into(),
+            None => job
+                .input_path()
+                .and_then(Path::file_stem)
+                .map_or("icon".into(), |s| s.to_string_lossy()),
+        };
+        if to_values.len() > 1 && !template.contains("{size}") {
+            bail!("多个尺寸时 --name-template 必须包含 {{size}}");
+        }
+        if cli.formats.len() > 1 && !template.contains("{format}") {
+            bail!("多个格式时 --name-template 必须包含 {{format}}");
+        }
+        if cli.formats.contains(&ExportFormat::CArray) && cli.formats.contains(&ExportFormat::Lvgl)
+        {
+            bail!("c-array 和 lvgl 都输出 .c 文件，不能同时使用");
+        }
+        // Every size gets one file per format
+        let jobs: Vec<Vec<(ExportFormat, PathBuf)>> = targets
+            .iter()
+            .map(|target| {
+                cli.formats
+                    .iter()
+                    .map(|&format| {
+                        let name = template
+                            .replace("{stem}", &stem)
+                            .replace("{size}", &target.label())
+                            .replace("{format}", format.extension());
+                        (format, out_dir.join(name))
+                    })
+                    .collect()
+            })
+            .collect();
 
-            let name = if to_values.len() == 1 {
-                "icon.svg".to_string()
-            } else {
-                format!("icon-{}.svg", to_size as u32)
+        fs::create_dir_all(&out_dir)?;
+        let input_path = job.input_path().map(fs::canonicalize).transpose()?;
+        for (_, out_path) in jobs.iter().flatten() {
+            let resolved = match (out_path.parent(), out_path.file_name()) {
+                (Some(dir), Some(name)) => fs::canonicalize(dir)?.join(name),
+                _ => continue,
             };
-            let out_path = Path::new(out_dir).join(&name);
-            fs::write(&out_path, &svg_i)?;
-            println!("输出: {}", out_path.display());
+            if Some(&resolved) == input_path.as_ref() {
+                bail!("输出文件 {} 会覆盖输入文件", out_path.display());
+            }
+        }
+        // One walk per size over the shared document and stylesheet, spread over
+        // `--threads` workers; all formats of a size share the scaled tree (and
+        // the rendered pixmap)
+        let styles = timings.time("styles", || Ok(StyleSheet::collect(doc.root_element())))?;
+        // A physical target rewrites the root size, so it is never a plain copy
+        check_target_fit(cli, &targets, from_w, from_h)?;
+        // Source width and height once a WxH target has reshaped it
+        let reshaped_size = |target: &TargetSpec| {
+            let (kx, ky) =
+                target_reshape(cli, target, from_w, from_h).map_or((1.0, 1.0), Reshape::factors);
+            (from_w * kx, from_h * ky)
+        };
+        let up_to_date = |target: &TargetSpec| {
+            cli.skip_up_to_date_size
+                && target.unit.is_none()
+                && target_reshape(cli, target, from_w, from_h).is_none()
+                && is_same_size(target.size, from_w)
+        };
+        let root_sizes: Vec<Option<RootSize>> = targets
+            .iter()
+            .map(|t| physical_root_size(cli, t, from_w, from_h))
+            .collect::<Result<_>>()?;
+        let marker_ctxs: Vec<ScaleCtx> = targets
+            .iter()
+            .map(|t| ScaleCtx {
+                scale: t.px(cli.dpi) / reshaped_size(t).0,
+                precision: t.precision.unwrap_or(cli.precision),
+                fix_stroke: t.fix_stroke.unwrap_or(cli.fix_stroke),
+                exact: cli.exact,
+                stroke_exponent: cli.stroke_compensation,
+            })
+            .collect();
+        for (ctx, target) in marker_ctxs.iter().zip(&targets) {
+            scale::check_scale(ctx.scale, false)
+                .with_context(|| format!("尺寸 {} 算出的缩放比例不可用", target.label()))?;
+        }
+        check_markers(cli, &doc, &marker_ctxs)?;
+        if cli.check_symbols {
+            report_symbol_reuse(&doc, &marker_ctxs);
+        }
+        let wants_pdf = cli.combine_pdf.is_some() || cli.formats.contains(&ExportFormat::Pdf);
+        if wants_pdf || cli.formats.iter().any(|&f| f != ExportFormat::Svg) {
+            check_fonts(cli, &doc)?;
+        }
+        let work: Vec<_> = targets.iter().zip(&jobs).zip(&root_sizes).collect();
+        let results: Vec<Result<(Timings, String, Option<pdf::Page>)>> = map_parallel(
+            &work,
+            cli.threads(),
+            |&((target, outputs), root_size)| -> Result<_> {
+                let (doc, styles, walk_opts) = (&doc, &styles, &walk_opts);
+                let physical_opts;
+                let walk_opts = match *root_size {
+                    Some(root_size) => {
+                        physical_opts = WalkOptions {
+                            root_size,
+                            ..cli.walk_options()
+                        };
+                        &physical_opts
+                    }
+                    None => walk_opts,
+                };
+                // A WxH target with another aspect ratio walks its
+                // own reshaped copy of the document
+                let reshaped_svg;
+                let reshaped_doc;
+                let reshaped_styles;
+                let (doc, styles) = match target_reshape(cli, target, from_w, from_h) {
+                    Some(reshape) => {
+                        reshaped_svg = reshape.apply(doc, cli.dpi)?;
+                        reshaped_doc = roxmltree::Document::parse(&reshaped_svg)?;
+                        reshaped_styles = StyleSheet::collect(reshaped_doc.root_element());
+                        (&reshaped_doc, &reshaped_styles)
+                    }
+                    None => (doc, styles),
+                };
+                let (from_w, from_h) = reshaped_size(target);
+                let ctx_i = ScaleCtx {
+                    scale: target.px(cli.dpi) / from_w,
+                    precision: target.precision.unwrap_or(cli.precision),
+                    fix_stroke: target.fix_stroke.unwrap_or(cli.fix_stroke),
+                    exact: cli.exact,
+                    stroke_exponent: cli.stroke_compensation,
+                };
+                let mut timings = Timings::default();
+                let scaled_svg = if up_to_date(target) {
+                    doc.input_text().to_string()
+                } else {
+                    let writer = scale_document(doc, styles, &ctx_i, walk_opts, &mut timings)?;
+                    timings.time("serialize", || finish_output(cli, doc, writer))?
+                };
+                let width = (from_w * ctx_i.scale).round().max(1.0) as u32;
+                let height = (from_h * ctx_i.scale).round().max(1.0) as u32;
+                let mut raster = Raster::new(&scaled_svg, width, height);
+                let page = if wants_pdf {
+                    Some(timings.time("render", || {
+                        render_svg_to_pdf_page(&scaled_svg, width, height)
+                    })?)
+                } else {
+                    None
+                };
+                for (format, out_path) in outputs {
+                    match (format, &page) {
+                        (ExportFormat::Svg, _) => {
+                            timings.time("serialize", || Ok(fs::write(out_path, &scaled_svg)?))?;
+                            continue;
+                        }
+                        (ExportFormat::Pdf, Some(page)) => {
+                            let data = pdf::write_pdf(std::slice::from_ref(page));
+                            fs::write(out_path, data).context("write pdf output")?;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    timings.time("render", || {
+                        save_raster(cli, *format, &mut raster, out_path)
+                    })?;
+                }
+                Ok((timings, scaled_svg, page))
+            },
+        )
+        .into_iter()
+        .map(|r| r.unwrap_or_else(|_| bail!("生成线程异常退出")))
+        .collect();
+        if cli.timings {
+            eprintln!("耗时 {}: {}", input, timings);
+        }
+        let mut failures = Vec::new();
+        let mut generated = Vec::new();
+        for ((result, target), outputs) in results.into_iter().zip(&targets).zip(&jobs) {
+            let (to_size, label) = (target.px(cli.dpi), target.label());
+            if up_to_date(target) {
+                println!("已是目标尺寸 {}，原样复制", label);
+            }
+            let outcome = result.and_then(|(out_timings, scaled_svg, page)| {
+                if cli.timings {
+                    eprintln!("耗时 {}: {}", label, out_timings);
+                }
+                if cli.verify {
+                    let source = match target_reshape(cli, target, from_w, from_h) {
+                        Some(reshape) => reshape.apply(&doc, cli.dpi)?,
+                        None => doc.input_text().to_string(),
+                    };
+                    verify_output(cli, &source, &scaled_svg, to_size / reshaped_size(target).0)?;
+                }
+                Ok((output_view_box(&scaled_svg), page))
+            });
+            match outcome {
+                Ok((view_box, page)) => {
+                    if cli.combine_pdf.is_some() {
+                        pdf_pages.extend(page);
+                    }
+                    let (source_w, source_h) = reshaped_size(target);
+                    let (width, height) = (to_size, source_h * to_size / source_w);
+                    for (format, out_path) in outputs {
+                        println!("输出: {}", out_path.display());
+                        let (width, height) = match format {
+                            ExportFormat::Svg => (width, height),
+                            _ => (width.round().max(1.0), height.round().max(1.0)),
+                        };
+                        generated.push(manifest::Entry {
+                            path: out_path.clone(),
+                            format: format.extension(),
+                            width,
+                            height,
+                            view_box: view_box.clone().filter(|_| *format == ExportFormat::Svg),
+                        });
+                    }
+                }
+                Err(e) if cli.keep_going => {
+                    eprintln!("错误: {}: {:#}", label, e);
+                    failures.push((label, e));
+                }
+                Err(e) => return Err(e.context(format!("生成尺寸 {} 失败", label))),
+            }
+        }
+        if cli.timings {
+            eprintln!("总耗时 {:.3?}", run_start.elapsed());
+        }
+        if cli.manifest {
+            let path = manifest::write(&out_dir, &generated)?;
+            println!("输出: {}", path.display());
+        }
+        if let Some(format) = cli.metadata {
+            let path = manifest::write_metadata(&out_dir, &generated, format)?;
+            println!("输出: {}", path.display());
+        }
+        if !failures.is_empty() {
+            eprintln!("失败汇总:");
+            for (label, e) in &failures {
+                eprintln!("  {}: {:#}", label, e);
+            }
+            return Err(Failure::Partial.error(format!(
+                "{} / {} 个尺寸失败",
+                failures.len(),
+                to_values.len()
+            )));
         }
         return Ok(());
     }
 
     // Single file output or stdout mode
-    let scale = if let Some(s) = cli.scale {
+    if cli.manifest {
+        bail!("--manifest 需要配合 --out-dir 和 --to，或 --vscode 使用");
+    }
+    if let Some(output) = job.output.filter(|o| o.ends_with(".ico")) {
+        return write_ico(
+            cli,
+            &doc,
+            targets.as_deref(),
+            from_w,
+            from_h,
+            Path::new(output),
+        );
+    }
+    if let Some(output) = job.output.filter(|o| o.ends_with(".icns")) {
+        return write_icns(cli, &doc, from_w, from_h, Path::new(output));
+    }
+    // Use the first target (and its overrides) for single file output
+    let target = targets.as_ref().map(|t| &t[0]);
+    // A --to WxH with another aspect ratio reshapes the document to it
+    // first; the width then sets the scale
+    let reshaped_svg;
+    let uneven = target
+        .filter(|_| fitted_scale.or(cli.scale).is_none())
+        .and_then(|t| Some((t, target_reshape(cli, t, from_w, from_h)?)));
+    let (doc, from_w, from_h) = match uneven {
+        Some((target, reshape)) => {
+            check_target_fit(cli, std::slice::from_ref(target), from_w, from_h)?;
+            reshaped_svg = reshape.apply(&doc, cli.dpi)?;
+            match reshape {
+                Reshape::Stretch(_) => println!("按 {} 非等比缩放", target.label()),
+                Reshape::Frame(..) => {
+                    println!("按 {} 等比缩放，画布按 --fit-mode 调整", target.label())
+                }
+            }
+            let (kx, ky) = reshape.factors();
+            (
+                roxmltree::Document::parse(&reshaped_svg)?,
+                from_w * kx,
+                from_h * ky,
+            )
+        }
+        None => (doc, from_w, from_h),
+    };
+    let scale = if let Some(s) = fitted_scale.or(cli.scale) {
         s
-    } else if let Some(to_str) = &cli.to {
-        // Only verify first value if multiple provided, though single output usually implies single 'to'
-        let to_values: Vec<f64> = to_str
-            .split(',')
-            .map(|s| s.trim().parse())
-            .collect::<Result<_, _>>()?;
-        // Use the first target size for single file output
-        to_values[0] / from_size
+    } else if let Some(target) = target {
+        target.px(cli.dpi) / from_w
+    } else if cli.ops.is_some() {
+        // The operations already sized the document; this pass only
+        // applies --precision
+        1.0
     } else {
-        bail!("必须指定 --scale 或 --to");
+        bail!("必须指定 --scale、--to 或 --to-height");
+    };
+    // --round-viewbox: nudge the scale so the viewBox width comes out whole
+    // and only the other axis needs rounding
+    let scale = match cli.round_viewbox {
+        true => {
+            let width = viewbox::current_view_box(&doc, cli.dpi)?.width;
+            (width * scale).round().max(1.0) / width
+        }
+        false => scale,
+    };
+    scale::check_scale(scale, false).context("算出的缩放比例不可用，请检查 --to 和 --from")?;
+    let walk_opts = match target {
+        Some(target) if fitted_scale.or(cli.scale).is_none() => {
+            match physical_root_size(cli, target, from_w, from_h)? {
+                Some(root_size) => WalkOptions {
+                    root_size,
+                    ..walk_opts
+                },
+                None => walk_opts,
+            }
+        }
+        _ => walk_opts,
     };
 
     let ctx = ScaleCtx {
         scale,
-        precision: cli.precision,
-        fix_stroke: cli.fix_stroke,
+        precision: target.and_then(|t| t.precision).unwrap_or(cli.precision),
+        fix_stroke: target.and_then(|t| t.fix_stroke).unwrap_or(cli.fix_stroke),
+        exact: cli.exact,
+        stroke_exponent: cli.stroke_compensation,
     };
 
-    let scaled_svg = write_svg(&doc, &ctx)?;
+    check_markers(cli, &doc, std::slice::from_ref(&ctx))?;
+    if cli.check_symbols {
+        report_symbol_reuse(&doc, std::slice::from_ref(&ctx));
+    }
+
+    // --keep-size only changes coordinates, not the displayed size
+    let display_scale = if cli.keep_size { 1.0 } else { scale };
+    if cli.verify {
+        let scaled_svg = write_svg_with_options(&doc, &ctx, &walk_opts)?;
+        verify_output(cli, doc.input_text(), &scaled_svg, display_scale)?;
+    }
+    if cli.self_check {
+        self_check(cli, &doc, &ctx)?;
+    }
+    if let Some(selectors) = &cli.explain {
+        let found = explain(&doc, &ctx, &walk_opts, selectors)?;
+        if found.is_empty() {
+            eprintln!("--explain 没有匹配的元素");
+        }
+        for e in &found {
+            eprintln!("说明: {}", e);
+        }
+    }
 
     // Output file
-    if let Some(output) = &cli.output {
-        if output.ends_with(".png") {
-            let (w, h) = if let Some(dims) = get_svg_dimensions(&doc) {
-                dims
-            } else if let Some(f) = cli.from {
-                (f, f)
-            } else {
-                bail!("未能从SVG检测到尺寸，请使用 --from 指定原始尺寸");
+    let is_svg_output = job
+        .output
+        .is_some_and(|o| ExportFormat::from_raster_path(o).is_none());
+    let is_physical = target.is_some_and(|t| t.unit.is_some());
+    if cli.skip_up_to_date_size
+        && is_svg_output
+        && !is_physical
+        && is_same_size(from_w * scale, from_w)
+    {
+        let output = job.output.expect("checked above");
+        fs::write(output, doc.input_text())?;
+        println!("已是目标尺寸，原样复制: {}", output);
+        return Ok(());
+    }
+
+    let styles = timings.time("scale", || Ok(StyleSheet::collect(doc.root_element())))?;
+    let writer = scale_document(&doc, &styles, &ctx, &walk_opts, &mut timings)?;
+    if let Some(output) = job.output {
+        if let Some(format) = ExportFormat::from_raster_path(output) {
+            check_fonts(cli, &doc)?;
+            let scaled_svg = timings.time("serialize", || finish_output(cli, &doc, writer))?;
+            let (target_w, target_h) = match cli.png_size {
+                Some(size) => (
+                    size.width,
+                    size.height.unwrap_or(size.width * from_h / from_w),
+                ),
+                None => (from_w * display_scale, from_h * display_scale),
             };
-            let target_w = (w * scale).round().max(1.0) as u32;
-            let target_h = (h * scale).round().max(1.0) as u32;
-            render_svg_to_png(&scaled_svg, target_w, target_h, Path::new(output))?;
+            let target_w = target_w.round().max(1.0) as u32;
+            let target_h = target_h.round().max(1.0) as u32;
+            timings.time("render", || {
+                let mut raster = Raster::new(&scaled_svg, target_w, target_h);
+                save_raster(cli, format, &mut raster, Path::new(output))
+            })?;
         } else {
-            fs::write(output, &scaled_svg)?;
+            timings.time("serialize", || {
+                let mut file = BufWriter::new(File::create(output)?);
+                write_output(cli, &doc, writer, &mut file)?;
+                file.flush()?;
+                Ok(())
+            })?;
         }
         println!("输出: {}", output);
     } else {
         // Default to stdout
-        println!("{}", scaled_svg);
+        timings.time("serialize", || {
+            let mut stdout = BufWriter::new(std::io::stdout().lock());
+            write_output(cli, &doc, writer, &mut stdout)?;
+            writeln!(stdout)?;
+            stdout.flush()?;
+            Ok(())
+        })?;
+    }
+    if cli.timings {
+        eprintln!("耗时 {}: {}", input, timings);
+        eprintln!("总耗时 {:.3?}", run_start.elapsed());
     }
 
     Ok(())
 }
 
-fn vscode_pipeline(cli: &Cli) -> Result<()> {
-    let scale = 128.0 / 512.0;
+/// How a `--to WxH` target reshapes the source ahead of its uniform scale,
+/// when its aspect ratio differs from the source's by more than
+/// --aspect-tolerance
+fn target_reshape(cli: &Cli, target: &TargetSpec, from_w: f64, from_h: f64) -> Option<Reshape> {
+    let width = target.px(cli.dpi);
+    let height = target.px_height(cli.dpi)?;
+    (aspect_distortion((from_w, from_h), (width, height)) > cli.aspect_tolerance)
+        .then(|| Reshape::for_ratio(width * from_h / (height * from_w), cli.fit_mode))
+}
 
-    let ctx = ScaleCtx {
-        scale,
-        precision: cli.precision,
-        fix_stroke: true,
+/// [`check_fit`] for every `--to WxH` target
+fn check_target_fit(cli: &Cli, targets: &[TargetSpec], from_w: f64, from_h: f64) -> Result<()> {
+    for target in targets {
+        if let Some(height) = target.px_height(cli.dpi) {
+            let what = format!("--to {}", target.label());
+            check_fit(cli, &what, (from_w, from_h), (target.px(cli.dpi), height))?;
+        }
+    }
+    Ok(())
+}
+
+/// Root size for a `--to` given in a physical unit such as `25mm`
+fn physical_root_size(
+    cli: &Cli,
+    target: &TargetSpec,
+    from_w: f64,
+    from_h: f64,
+) -> Result<Option<RootSize>> {
+    let Some(unit) = target.unit else {
+        return Ok(None);
     };
+    if cli.responsive || cli.explicit_size || cli.keep_size {
+        bail!("带单位的 --to 不能与 --responsive、--explicit-size 或 --keep-size 同时使用");
+    }
+    Ok(Some(RootSize::Physical {
+        width: target.size,
+        height: target.height.unwrap_or(target.size * from_h / from_w),
+        unit: unit.as_str(),
+    }))
+}
 
-    let input_svg = fs::read_to_string(&cli.input)?;
-    let doc = roxmltree::Document::parse(&input_svg)?;
+/// Root viewBox of a generated SVG, for the asset metadata
+fn output_view_box(svg: &str) -> Option<String> {
+    let doc = roxmltree::Document::parse(svg).ok()?;
+    doc.root_element().attribute("viewBox").map(str::to_string)
+}
 
-    let scaled_svg = write_svg(&doc, &ctx)?;
+/// Why the source size could not be found: a relative root size (`100%`)
+/// with no viewBox to fall back to, or no size at all
+fn undetected_size(doc: &roxmltree::Document, dpi: f64) -> Error {
+    let root = doc.root_element();
+    let relative = ["width", "height"]
+        .into_iter()
+        .filter_map(|name| Some((name, root.attribute(name)?)))
+        .find(|(_, v)| svg::length_to_px(v, dpi).is_none());
+    match relative {
+        Some((name, value)) => anyhow!(
+            "根元素的 {}=\"{}\" 是相对尺寸且没有 viewBox，无法确定像素大小；请使用 --from 指定原始尺寸",
+            name,
+            value
+        ),
+        None => anyhow!("未能从SVG检测到尺寸，请使用 --from 指定原始尺寸"),
+    }
+}
 
-    // Use --out-dir if provided, otherwise default to images/dist
-    let out_dir: &Path = if let Some(dir) = &cli.out_dir {
-        Path::new(dir)
-    } else {
-        Path::new("images/dist")
+/// Scale at which the drawn content's bounding box covers `area` px²
+fn area_scale(cli: &Cli, doc: &roxmltree::Document, area: f64, from_w: f64) -> Result<f64> {
+    if !(area > 0.0 && area.is_finite()) {
+        bail!("--to-area 必须是正数: {}", area);
+    }
+    let (w, h) = svg_scale::ops::content_size(doc, cli.dpi)?;
+    // Measured at the document's own size, which --from may restate
+    let at_from = from_w / get_svg_size(doc, cli.dpi).unwrap_or(from_w);
+    let scale = (area / (w * h * at_from * at_from)).sqrt();
+    println!("按内容面积选择比例: {}", scale);
+    Ok(scale)
+}
+
+/// Warn (or fail under --strict) when an explicit `--from WxH` gives the
+/// output a different shape than the document's own, which squashes or
+/// letterboxes the artwork
+fn check_aspect(cli: &Cli, doc: &roxmltree::Document, from_w: f64, from_h: f64) -> Result<()> {
+    if cli.from.is_none_or(|f| f.height.is_none()) {
+        return Ok(());
+    }
+    let Some((doc_w, doc_h)) =
+        get_svg_dimensions(doc, cli.dpi).filter(|(w, h)| *w > 0.0 && *h > 0.0)
+    else {
+        return Ok(());
+    };
+    let distortion = aspect_distortion((doc_w, doc_h), (from_w, from_h));
+    if distortion <= cli.aspect_tolerance {
+        return Ok(());
+    }
+    let message = format!(
+        "--from {}x{} 的宽高比 {:.3} 与文档的 {:.3}（{}x{}）相差 {:.1}%，输出会被拉伸或留白；只给宽度（--from {}）可保持原比例",
+        from_w,
+        from_h,
+        from_w / from_h,
+        doc_w / doc_h,
+        doc_w,
+        doc_h,
+        distortion * 100.0,
+        from_w
+    );
+    if cli.strict {
+        return Err(Failure::Strict.error(message));
+    }
+    eprintln!("警告: {}", message);
+    Ok(())
+}
+
+/// Warn (or fail under --strict) when `what` stretches a `source`-sized
+/// drawing to the shape of `target` and no --fit-mode says how to fill it
+fn check_fit(cli: &Cli, what: &str, source: (f64, f64), target: (f64, f64)) -> Result<()> {
+    let distortion = aspect_distortion(source, target);
+    if cli.fit_mode.is_some() || distortion <= cli.aspect_tolerance {
+        return Ok(());
+    }
+    let message = format!(
+        "{} 的宽高比 {:.3} 与原图的 {:.3}（{}x{}）相差 {:.1}%，图形会被拉伸；--fit-mode meet 可等比放入并留白，--fit-mode slice 可等比铺满并裁切，--fit-mode stretch 保持拉伸且不再提示",
+        what,
+        target.0 / target.1,
+        source.0 / source.1,
+        source.0,
+        source.1,
+        distortion * 100.0
+    );
+    if cli.strict {
+        return Err(Failure::Strict.error(message));
+    }
+    eprintln!("警告: {}", message);
+    Ok(())
+}
+
+/// Warn (or fail under --strict) when markers sized in stroke widths stop
+/// scaling with their paths: kept or removed `non-scaling-stroke`, and
+/// `--stroke-compensation`. Batches report each message once.
+fn check_markers(cli: &Cli, doc: &roxmltree::Document, ctxs: &[ScaleCtx]) -> Result<()> {
+    let mut messages: Vec<String> = Vec::new();
+    for ctx in ctxs {
+        for effect in marker_effects(doc, ctx) {
+            let ids = effect.markers.join(", ");
+            let message = match effect.change {
+                MarkerChange::KeepsSize => format!(
+                    "{} 的描边为 non-scaling-stroke，按描边宽度定尺寸的标记 {} 不会随路径缩放；--fix-stroke 可让标记随图形一起缩放",
+                    effect.element, ids
+                ),
+                MarkerChange::NowScales => format!(
+                    "--fix-stroke 移除了 {} 的 non-scaling-stroke，标记 {} 原本保持屏幕尺寸，现在会随路径一起缩放",
+                    effect.element, ids
+                ),
+                MarkerChange::Compensated(factor) => format!(
+                    "--stroke-compensation 使 {} 的描边相对路径缩放 {:.3} 倍，按描边宽度定尺寸的标记 {} 也会随之变{}",
+                    effect.element,
+                    factor,
+                    ids,
+                    if factor > 1.0 { "大" } else { "小" }
+                ),
+            };
+            if !messages.contains(&message) {
+                messages.push(message);
+            }
+        }
+    }
+    if let Some(first) = messages.first().filter(|_| cli.strict) {
+        return Err(Failure::Strict.error(first.clone()));
+    }
+    for message in &messages {
+        eprintln!("警告: {}", message);
+    }
+    Ok(())
+}
+
+/// Print each symbol used at several sizes, with every instance's size
+/// before and after each of `ctxs`
+fn report_symbol_reuse(doc: &roxmltree::Document, ctxs: &[ScaleCtx]) {
+    let mixed = symbols::mixed_size_symbols(doc);
+    if mixed.is_empty() {
+        eprintln!("没有以不同尺寸引用的 symbol");
+        return;
+    }
+    let n = |v: f64| format!("{}", (v * 1000.0).round() / 1000.0);
+    for symbol in &mixed {
+        eprintln!(
+            "symbol #{}（viewBox {}x{}）被 {} 个 <use> 以不同尺寸引用:",
+            symbol.id,
+            n(symbol.view_box.width),
+            n(symbol.view_box.height),
+            symbol.instances.len()
+        );
+        for instance in &symbol.instances {
+            let sizes: Vec<String> = ctxs
+                .iter()
+                .map(|ctx| {
+                    format!(
+                        "{}x{}",
+                        n(instance.width * ctx.scale),
+                        n(instance.height * ctx.scale)
+                    )
+                })
+                .collect();
+            let (kx, ky) = instance.factor;
+            eprintln!(
+                "  第 {} 行 <{}>: {}x{} -> {}，symbol 单位 ×{}",
+                instance.line,
+                instance.element,
+                n(instance.width),
+                n(instance.height),
+                sizes.join(", "),
+                if kx == ky {
+                    n(kx)
+                } else {
+                    format!("{}/{}", n(kx), n(ky))
+                }
+            );
+        }
+    }
+}
+
+/// Warn (or fail under --strict) when the document's text needs fonts the
+/// renderer does not have
+fn check_fonts(cli: &Cli, doc: &roxmltree::Document) -> Result<()> {
+    let missing = unavailable_font_families(doc);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let message = format!(
+        "文档包含文字，但渲染器没有以下字体: {}，位图中的文字会缺失或使用替代字体",
+        missing.join(", ")
+    );
+    if cli.strict {
+        return Err(Failure::Strict.error(message));
+    }
+    eprintln!("警告: {}", message);
+    Ok(())
+}
+
+/// A scaled document rasterized on first use, so the raster formats of one
+/// size share a single rendering and render cache hits need none
+struct Raster<'a> {
+    svg: &'a str,
+    width: u32,
+    height: u32,
+    pixmap: Option<resvg::tiny_skia::Pixmap>,
+}
+
+impl<'a> Raster<'a> {
+    fn new(svg: &'a str, width: u32, height: u32) -> Self {
+        Raster {
+            svg,
+            width,
+            height,
+            pixmap: None,
+        }
+    }
+
+    fn pixmap(&mut self) -> Result<&resvg::tiny_skia::Pixmap> {
+        if self.pixmap.is_none() {
+            self.pixmap = Some(render_svg_to_pixmap(self.svg, self.width, self.height)?);
+        }
+        Ok(self.pixmap.as_ref().expect("rendered above"))
+    }
+}
+
+/// Render the document at each icon size (the --to sizes, else
+/// [`ICO_SIZES`]) and pack the frames into one `.ico`
+fn write_ico(
+    cli: &Cli,
+    doc: &roxmltree::Document,
+    targets: Option<&[TargetSpec]>,
+    from_w: f64,
+    from_h: f64,
+    output: &Path,
+) -> Result<()> {
+    if cli.scale.is_some() {
+        bail!(".ico 输出按 --to 列出的尺寸（默认 16 到 256）渲染，不能使用 --scale");
+    }
+    // Each size with its --to overrides
+    let sizes: Vec<(u32, Option<usize>, Option<bool>)> = match targets {
+        Some(targets) => targets
+            .iter()
+            .map(|t| (t.px(cli.dpi).round() as u32, t.precision, t.fix_stroke))
+            .collect(),
+        None => ICO_SIZES.iter().map(|&size| (size, None, None)).collect(),
+    };
+    let frames = render_icon_frames(cli, doc, sizes, from_w, from_h)?;
+    fs::write(output, ico::encode_ico(&frames)?).context("write ico output")?;
+    let sizes: Vec<String> = frames.iter().map(|f| f.size.to_string()).collect();
+    println!("输出: {}（{}）", output.display(), sizes.join(", "));
+    Ok(())
+}
+
+/// Render the document at the standard Apple sizes ([`ICNS_ICONS`]) and
+/// pack the images into one `.icns`
+fn write_icns(
+    cli: &Cli,
+    doc: &roxmltree::Document,
+    from_w: f64,
+    from_h: f64,
+    output: &Path,
+) -> Result<()> {
+    if cli.scale.is_some() || cli.to.is_some() || cli.to_height.is_some() {
+        bail!(
+            ".icns 输出使用固定的 Apple 尺寸（16 到 1024），不能使用 --scale、--to 或 --to-height"
+        );
+    }
+    // The @2x images share their pixel size with the next 1x one
+    let mut sizes: Vec<u32> = ICNS_ICONS.iter().map(|&(_, size, _)| size).collect();
+    sizes.sort_unstable();
+    sizes.dedup();
+    let frames = render_icon_frames(
+        cli,
+        doc,
+        sizes.into_iter().map(|size| (size, None, None)).collect(),
+        from_w,
+        from_h,
+    )?;
+    let entries: Vec<icns::Entry> = ICNS_ICONS
+        .iter()
+        .map(|&(kind, size, _)| {
+            let frame = frames.iter().find(|f| f.size == size);
+            icns::Entry {
+                kind: *kind,
+                png: &frame.expect("rendered every size above").png,
+            }
+        })
+        .collect();
+    fs::write(output, icns::encode_icns(&entries)?).context("write icns output")?;
+    let names: Vec<&str> = ICNS_ICONS.iter().map(|&(_, _, name)| name).collect();
+    println!("输出: {}（{}）", output.display(), names.join(", "));
+    Ok(())
+}
+
+/// The document scaled and rendered into a square PNG at each size, with
+/// the size's precision and fix-stroke overrides
+fn render_icon_frames(
+    cli: &Cli,
+    doc: &roxmltree::Document,
+    sizes: Vec<(u32, Option<usize>, Option<bool>)>,
+    from_w: f64,
+    from_h: f64,
+) -> Result<Vec<ico::Frame>> {
+    check_fonts(cli, doc)?;
+    sizes
+        .into_iter()
+        .map(|(size, precision, fix_stroke)| {
+            let pixmap = render_icon(cli, doc, size, precision, fix_stroke, from_w, from_h)?;
+            let png = pixmap.encode_png().context("encode icon frame")?;
+            Ok(ico::Frame { size, png })
+        })
+        .collect()
+}
+
+/// The document scaled so its longer side fills a `size` px square and
+/// rendered there
+fn render_icon(
+    cli: &Cli,
+    doc: &roxmltree::Document,
+    size: u32,
+    precision: Option<usize>,
+    fix_stroke: Option<bool>,
+    from_w: f64,
+    from_h: f64,
+) -> Result<resvg::tiny_skia::Pixmap> {
+    let ctx = ScaleCtx {
+        scale: size as f64 / from_w.max(from_h),
+        precision: precision.unwrap_or(cli.precision),
+        fix_stroke: fix_stroke.unwrap_or(cli.fix_stroke),
+        exact: cli.exact,
+        stroke_exponent: cli.stroke_compensation,
     };
+    let scaled_svg = write_svg_with_options(doc, &ctx, &cli.walk_options())?;
+    render_svg_to_square_pixmap(&scaled_svg, size.max(1))
+}
+
+/// Write `mipmap-<density>/ic_launcher.png` for every density into
+/// --out-dir (or the current directory), and with --adaptive-icon the
+/// adaptive foreground layer as `ic_launcher_foreground.svg`
+fn write_android_icons(
+    cli: &Cli,
+    doc: &roxmltree::Document,
+    from_w: f64,
+    from_h: f64,
+) -> Result<()> {
+    let out_dir = Path::new(cli.out_dir.as_deref().unwrap_or("."));
+    check_fonts(cli, doc)?;
+    println!("输出:");
+    for &(density, size) in MIPMAP_DENSITIES {
+        let dir = out_dir.join(format!("mipmap-{}", density));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("ic_launcher.png");
+        let pixmap = render_icon(cli, doc, size, None, None, from_w, from_h)?;
+        let png = pixmap.encode_png().context("encode launcher icon")?;
+        fs::write(&path, png).with_context(|| format!("write {}", path.display()))?;
+        println!("  {}", path.display());
+    }
+    if cli.adaptive_icon {
+        let path = out_dir.join("ic_launcher_foreground.svg");
+        let svg = android::adaptive_foreground(doc, cli.precision, cli.dpi)?;
+        fs::write(&path, svg).with_context(|| format!("write {}", path.display()))?;
+        println!("  {}", path.display());
+    }
+    Ok(())
+}
+
+/// Write the favicon bundle into --out-dir (or the current directory) and
+/// print the `<link>` tags for it. The touch and maskable icons are
+/// flattened onto white, as home screens show no transparency.
+fn write_favicons(cli: &Cli, doc: &roxmltree::Document, from_w: f64, from_h: f64) -> Result<()> {
+    let out_dir = Path::new(cli.out_dir.as_deref().unwrap_or("."));
     fs::create_dir_all(out_dir)?;
+    let write = |name: &str, data: Vec<u8>| -> Result<()> {
+        let path = out_dir.join(name);
+        fs::write(&path, data).with_context(|| format!("write {}", path.display()))?;
+        println!("  {}", path.display());
+        Ok(())
+    };
+    let sizes = FAVICON_SIZES.iter().map(|&s| (s, None, None)).collect();
+    let frames = render_icon_frames(cli, doc, sizes, from_w, from_h)?;
+    println!("输出:");
+    write("favicon.ico", ico::encode_ico(&frames)?)?;
+    for frame in frames {
+        write(&favicon::png_name(frame.size), frame.png)?;
+    }
+    let touch = render_icon(cli, doc, APPLE_TOUCH_SIZE, None, None, from_w, from_h)?;
+    write(
+        "apple-touch-icon.png",
+        encode_opaque_png(&touch, [255, 255, 255])?,
+    )?;
+    let maskable_svg = favicon::maskable_document(doc, cli.dpi)?;
+    let maskable = roxmltree::Document::parse(&maskable_svg)?;
+    let (mask_w, mask_h) = (from_w / MASKABLE_SAFE_ZONE, from_h / MASKABLE_SAFE_ZONE);
+    for &size in MASKABLE_SIZES {
+        let pixmap = render_icon(cli, &maskable, size, None, None, mask_w, mask_h)?;
+        write(
+            &favicon::maskable_name(size),
+            encode_opaque_png(&pixmap, [255, 255, 255])?,
+        )?;
+    }
+    write("site.webmanifest", favicon::webmanifest().into_bytes())?;
+    println!("在 HTML 的 <head> 中加入:");
+    println!("{}", favicon::link_tags());
+    Ok(())
+}
 
-    let svg_out = out_dir.join("icon.svg");
-    fs::write(&svg_out, &scaled_svg)?;
+/// Write `AppIcon.appiconset/` into --out-dir (or the current directory):
+/// one PNG per distinct pixel size and the `Contents.json` naming them. The
+/// App Store icon is flattened onto white, since it may not have alpha.
+fn write_ios_appicon(cli: &Cli, doc: &roxmltree::Document, from_w: f64, from_h: f64) -> Result<()> {
+    let dir = Path::new(cli.out_dir.as_deref().unwrap_or(".")).join("AppIcon.appiconset");
+    fs::create_dir_all(&dir)?;
+    check_fonts(cli, doc)?;
+    let mut written: Vec<String> = Vec::new();
+    for icon in IOS_APP_ICONS {
+        let name = icon.filename();
+        if written.contains(&name) {
+            continue;
+        }
+        let pixmap = render_icon(cli, doc, icon.pixels(), None, None, from_w, from_h)?;
+        let png = if icon.is_marketing() {
+            encode_opaque_png(&pixmap, [255, 255, 255])?
+        } else {
+            pixmap.encode_png().context("encode app icon")?
+        };
+        fs::write(dir.join(&name), png).with_context(|| format!("write {}", name))?;
+        written.push(name);
+    }
+    let contents = dir.join("Contents.json");
+    fs::write(&contents, ios::contents_json(IOS_APP_ICONS)).context("write Contents.json")?;
+    println!(
+        "输出: {}（{} 个 PNG 和 Contents.json）",
+        dir.display(),
+        written.len()
+    );
+    Ok(())
+}
 
-    let png_out = out_dir.join("icon.png");
+/// Write a rendered pixmap in one of the raster formats
+fn save_raster(cli: &Cli, format: ExportFormat, raster: &mut Raster, path: &Path) -> Result<()> {
+    match format {
+        ExportFormat::Png => save_png(cli, raster, path),
+        ExportFormat::Webp => write_webp(raster.pixmap()?, path),
+        ExportFormat::Rgba => write_raw(raster.pixmap()?, cli.pixel_order, cli.raw_header, path),
+        ExportFormat::Bmp => write_bmp(raster.pixmap()?, path),
+        ExportFormat::CArray => write_c_source(raster.pixmap()?, Some(cli.pixel_order), path),
+        ExportFormat::Lvgl => write_c_source(raster.pixmap()?, None, path),
+        ExportFormat::Svg | ExportFormat::Pdf => unreachable!("not a raster format"),
+    }
+}
 
-    render_svg_to_png(&scaled_svg, 128, 128, &png_out)?;
+/// Write a rendered PNG, palette-quantized when --png-colors is set and
+/// served from (or stored in) the --render-cache
+fn save_png(cli: &Cli, raster: &mut Raster, path: &Path) -> Result<()> {
+    let cache = match &cli.render_cache {
+        Some(dir) => Some(RenderCache::open(dir.as_deref())?),
+        None => None,
+    };
+    let key = RenderCache::key(
+        raster.svg,
+        raster.width,
+        raster.height,
+        &format!("png-colors={:?}", cli.png_colors),
+    );
+    if let Some(data) = cache.as_ref().and_then(|c| c.get(&key)) {
+        return fs::write(path, data).context("write png output");
+    }
+    let pixels = raster.width as u64 * raster.height as u64;
+    if cli.png_colors.is_none() && raster.pixmap.is_none() && pixels > MAX_PIXMAP_PIXELS {
+        // Poster-sized output: render and encode in strips
+        render_svg_to_png_tiled(raster.svg, raster.width, raster.height, path)?;
+    } else {
+        let pixmap = raster.pixmap()?;
+        match cli.png_colors {
+            Some(colors) => write_indexed_png(pixmap, colors.into(), path)?,
+            None => pixmap.save_png(path).context("write png output")?,
+        }
+    }
+    if let Some(cache) = &cache {
+        cache.put(&key, &fs::read(path)?)?;
+    }
+    Ok(())
+}
 
-    println!("VSCode icon generated:");
-    println!("  {}", svg_out.display());
-    println!("  {}", png_out.display());
+/// Whether a target size matches the source size closely enough that
+/// scaling would only churn the formatting
+fn is_same_size(target: f64, source: f64) -> bool {
+    (target - source).abs() <= 1e-6 * source.abs().max(1.0)
+}
 
+/// Close the writer, rounding the viewBox when --round-viewbox is set and
+/// sorting attributes when --canonical is set
+fn finish_output(cli: &Cli, doc: &roxmltree::Document, writer: XmlWriter) -> Result<String> {
+    let mut svg = finish_document(doc, writer);
+    if cli.round_viewbox {
+        svg = viewbox::round_view_box(&roxmltree::Document::parse(&svg)?, cli.dpi)?;
+    }
+    if cli.canonical {
+        canonicalize(&svg)
+    } else {
+        Ok(svg)
+    }
+}
+
+/// Like [`finish_output`], writing into `out`
+fn write_output(
+    cli: &Cli,
+    doc: &roxmltree::Document,
+    writer: XmlWriter,
+    out: &mut impl Write,
+) -> Result<()> {
+    if cli.canonical || cli.round_viewbox {
+        out.write_all(finish_output(cli, doc, writer)?.as_bytes())?;
+    } else {
+        finish_document_to(doc, writer, out)?;
+    }
     Ok(())
 }
 
-fn render_svg_to_png(svg_data: &str, width: u32, height: u32, out_path: &Path) -> Result<()> {
-    let opt = usvg::Options::default();
-    let tree = usvg::Tree::from_str(svg_data, &opt).context("parse svg for rendering")?;
+/// The scaling walk into a fresh writer, recorded as the "scale" stage
+fn scale_document(
+    doc: &roxmltree::Document,
+    styles: &StyleSheet,
+    ctx: &ScaleCtx,
+    opts: &WalkOptions,
+    timings: &mut Timings,
+) -> Result<XmlWriter> {
+    timings.time("scale", || {
+        let mut writer = XmlWriter::new(xmlwriter::Options::default());
+        svg::walk_with_styles(doc.root_element(), &mut writer, ctx, opts, styles)?;
+        Ok(writer)
+    })
+}
 
-    let size = tree.size();
-    if size.width() <= 0.0 || size.height() <= 0.0 {
-        bail!("svg has zero size");
+/// Report elements whose scaled geometry diverges from the source and fail if any do
+fn verify_output(cli: &Cli, input_svg: &str, scaled_svg: &str, display_scale: f64) -> Result<()> {
+    let found = compare_geometry(input_svg, scaled_svg, display_scale, cli.verify_tolerance)?;
+    if found.is_empty() {
+        eprintln!("几何校验通过");
+        return Ok(());
+    }
+    for d in &found {
+        eprintln!("几何不一致: {}", d);
     }
+    Err(Failure::Verify.error(format!("几何校验失败: {} 处不一致", found.len())))
+}
+
+/// Report attributes that do not survive scaling by S and back by 1/S
+fn self_check(cli: &Cli, doc: &roxmltree::Document, ctx: &ScaleCtx) -> Result<()> {
+    // The root size options rewrite width/height one way only
+    let opts = WalkOptions {
+        root_size: RootSize::Keep,
+        ..cli.walk_options()
+    };
+    let issues = check_round_trip(doc, ctx, &opts)?;
+    if issues.is_empty() {
+        eprintln!("往返校验通过");
+        return Ok(());
+    }
+    for issue in &issues {
+        eprintln!("无法还原: {}", issue);
+    }
+    Err(Failure::Verify.error(format!("往返校验失败: {} 个属性无法还原", issues.len())))
+}
 
-    let sx = width as f32 / size.width();
-    let sy = height as f32 / size.height();
-    let transform = usvg::Transform::from_scale(sx, sy);
+fn stream_pipeline(cli: &Cli, job: Job) -> Result<()> {
+    let target = cli.to.as_deref().map(parse_targets).transpose()?;
+    let target = target.as_ref().map(|t| &t[0]);
+    if cli.scale.is_none() && target.is_none() {
+        bail!("流式模式需要指定 --scale 或 --to");
+    }
+    if target.is_some_and(|t| t.height.is_some()) {
+        bail!("流式模式不支持 --to WxH，请只给出宽度");
+    }
+    // Without --from, the root start tag gives the original width before
+    // anything is written
+    let resolve = |root: &StreamRoot| -> Result<ScaleCtx> {
+        let scale = match (cli.scale, target) {
+            (Some(s), _) => s,
+            (None, Some(target)) => {
+                let from = match cli.from {
+                    Some(from) => from.width,
+                    None => {
+                        let from = root
+                            .width
+                            .as_deref()
+                            .and_then(|w| svg::length_to_px(w, cli.dpi))
+                            .or(root.view_box.map(|vb| vb.width))
+                            .filter(|&w| w > 0.0)
+                            .context("根元素没有可用的 width 或 viewBox，流式模式请指定 --from")?;
+                        eprintln!("自动检测到原始尺寸: {}", from);
+                        from
+                    }
+                };
+                target.px(cli.dpi) / from
+            }
+            (None, None) => unreachable!("checked above"),
+        };
+        scale::check_scale(scale, false).context("算出的缩放比例不可用，请检查 --to 和 --from")?;
+        Ok(ScaleCtx {
+            scale,
+            precision: target.and_then(|t| t.precision).unwrap_or(cli.precision),
+            fix_stroke: target.and_then(|t| t.fix_stroke).unwrap_or(cli.fix_stroke),
+            exact: cli.exact,
+            stroke_exponent: cli.stroke_compensation,
+        })
+    };
 
-    let mut pixmap = tiny_skia::Pixmap::new(width, height).context("create target pixmap")?;
+    let input: Box<dyn BufRead> = match job.input_path() {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(std::io::Cursor::new(job.read_input()?)),
+    };
+    if let Some(output) = job.output {
+        if let Some(format) = ExportFormat::from_raster_path(output) {
+            bail!(
+                "流式模式不支持 {} 输出",
+                format.extension().to_ascii_uppercase()
+            );
+        }
+        scale_stream_with(input, BufWriter::new(File::create(output)?), resolve)?;
+        println!("输出: {}", output);
+    } else {
+        scale_stream_with(input, BufWriter::new(std::io::stdout().lock()), resolve)?;
+    }
+    Ok(())
+}
 
-    let mut pixmap_mut = pixmap.as_mut();
-    resvg::render(&tree, transform, &mut pixmap_mut);
+/// One size of an SVG + PNG icon set
+struct IconFiles {
+    size: u32,
+    scale: f64,
+    svg: PathBuf,
+    png: PathBuf,
+}
 
-    pixmap.save_png(out_path).context("write png output")?;
+/// Write each icon as a scaled SVG and a PNG rendered from it, returning
+/// the manifest entries of both
+fn write_svg_png_icons(
+    cli: &Cli,
+    doc: &roxmltree::Document,
+    icons: &[IconFiles],
+    fix_stroke: bool,
+    timings: &mut Timings,
+) -> Result<Vec<manifest::Entry>> {
+    let styles = timings.time("scale", || Ok(StyleSheet::collect(doc.root_element())))?;
+    check_fonts(cli, doc)?;
+    let mut entries = Vec::with_capacity(icons.len() * 2);
+    for icon in icons {
+        let ctx = ScaleCtx {
+            scale: icon.scale,
+            precision: cli.precision,
+            fix_stroke,
+            exact: cli.exact,
+            stroke_exponent: cli.stroke_compensation,
+        };
+        let writer = scale_document(doc, &styles, &ctx, &cli.walk_options(), timings)?;
+        let scaled_svg = timings.time("serialize", || finish_output(cli, doc, writer))?;
+        timings.time("serialize", || Ok(fs::write(&icon.svg, &scaled_svg)?))?;
+        timings.time("render", || {
+            save_png(
+                cli,
+                &mut Raster::new(&scaled_svg, icon.size, icon.size),
+                &icon.png,
+            )
+        })?;
+        let view_box = output_view_box(&scaled_svg);
+        for (path, format) in [(&icon.svg, "svg"), (&icon.png, "png")] {
+            entries.push(manifest::Entry {
+                path: path.clone(),
+                format,
+                width: icon.size as f64,
+                height: icon.size as f64,
+                view_box: view_box.clone().filter(|_| format == "svg"),
+            });
+        }
+    }
+    Ok(entries)
+}
 
+/// Write the extension icon set with `store`'s names into --out-dir (by
+/// default `icons`) and print the manifest's `icons` entry for it
+fn write_extension_icons(
+    cli: &Cli,
+    doc: &roxmltree::Document,
+    from_w: f64,
+    from_h: f64,
+    store: Store,
+) -> Result<()> {
+    let dir = cli.out_dir.as_deref().unwrap_or("icons");
+    let out_dir = Path::new(dir);
+    fs::create_dir_all(out_dir)?;
+    let icons: Vec<IconFiles> = EXTENSION_SIZES
+        .iter()
+        .map(|&size| IconFiles {
+            size,
+            scale: size as f64 / from_w.max(from_h),
+            svg: out_dir.join(store.file_name(size, "svg")),
+            png: out_dir.join(store.file_name(size, "png")),
+        })
+        .collect();
+    let entries = write_svg_png_icons(cli, doc, &icons, cli.fix_stroke, &mut Timings::default())?;
+    println!("输出:");
+    for entry in &entries {
+        println!("  {}", entry.path.display());
+    }
+    if cli.manifest {
+        println!("  {}", manifest::write(out_dir, &entries)?.display());
+    }
+    println!("在扩展的 manifest.json 中加入:");
+    println!("{}", store.manifest_icons(dir));
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
     use std::time::{SystemTime, UNIX_EPOCH};
+    use svg_scale::json::{self, Value};
+
+    const ICON: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="12" viewBox="0 0 24 12"><rect width="24" height="12"/></svg>"#;
 
-    fn tmp_png_path() -> std::path::PathBuf {
-        let mut path = std::env::temp_dir();
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        path.push(format!("svg-scale-test-{}.png", nanos));
-        path
+    /// A fresh directory for one test under the system temp dir
+    fn scratch_dir(name: &str) -> Result<PathBuf> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let dir = std::env::temp_dir().join(format!("svg-scale-{}-{}", name, nanos));
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
     }
 
-    fn read_png_dimensions(data: &[u8]) -> Result<(u32, u32)> {
-        const PNG_SIG: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
-        if data.len() < 33 || data[0..8] != PNG_SIG {
-            bail!("invalid png signature");
-        }
+    fn write_file(path: &Path, text: &str) -> Result<String> {
+        fs::create_dir_all(path.parent().context("no parent")?)?;
+        fs::write(path, text)?;
+        Ok(path.to_string_lossy().into_owned())
+    }
 
-        let chunk_type = &data[12..16];
-        if chunk_type != b"IHDR" {
-            bail!("missing IHDR chunk");
-        }
+    fn run_cli(args: &[&str]) -> Result<()> {
+        run(std::iter::once("svg-scale")
+            .chain(args.iter().copied())
+            .map(Into::into))
+    }
+
+    #[test]
+    fn negative_factors_parse_in_the_space_separated_form() -> Result<()> {
+        let parse = |args: &[&str]| -> Result<Cli> {
+            let mut cli = Cli::try_parse_from(args)?;
+            check_scale_factors(&mut cli)?;
+            Ok(cli)
+        };
+        let cli = parse(&[
+            "svg-scale",
+            "-i",
+            "a.svg",
+            "--scale",
+            "-1",
+            "--allow-negative-scale",
+        ])?;
+        assert_eq!((cli.scale, cli.mirror), (Some(1.0), (true, true)));
+        let cli = parse(&[
+            "svg-scale",
+            "-i",
+            "a.svg",
+            "--scale-x",
+            "-2",
+            "--scale-y",
+            "0.5",
+            "--allow-negative-scale",
+        ])?;
+        assert_eq!((cli.scale_x, cli.mirror), (Some(2.0), (true, false)));
+        let Err(err) = parse(&["svg-scale", "-i", "a.svg", "--scale", "-1"]) else {
+            bail!("a negative --scale needs --allow-negative-scale");
+        };
+        assert!(err.to_string().contains("为负"), "got: {err:#}");
+        Ok(())
+    }
 
-        let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
-        let height = u32::from_be_bytes([data[20], data[21], data[22], data[23]]);
-        Ok((width, height))
+    #[test]
+    fn fit_modes_keep_the_drawing_uniform() {
+        // A box twice as wide, relative to the source, as it is tall
+        assert_eq!(Reshape::for_ratio(2.0, None), Reshape::Stretch(0.5));
+        assert_eq!(
+            Reshape::for_ratio(2.0, Some(FitMode::Stretch)),
+            Reshape::Stretch(0.5)
+        );
+        assert_eq!(
+            Reshape::for_ratio(2.0, Some(FitMode::Meet)),
+            Reshape::Frame(2.0, 1.0)
+        );
+        assert_eq!(
+            Reshape::for_ratio(2.0, Some(FitMode::Slice)),
+            Reshape::Frame(1.0, 0.5)
+        );
+        assert_eq!(
+            Reshape::for_ratio(0.5, Some(FitMode::Meet)),
+            Reshape::Frame(1.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn keep_going_finishes_the_batch_and_exits_partial() -> Result<()> {
+        let dir = scratch_dir("keep-going")?;
+        let bad = write_file(&dir.join("bad.svg"), "<svg")?;
+        let good = write_file(&dir.join("a.svg"), ICON)?;
+        let out = dir.join("out").to_string_lossy().into_owned();
+        let args = ["-i", &bad, "-i", &good, "--out-dir", &out, "--to", "16"];
+
+        let Err(err) = run_cli(&args) else {
+            bail!("a broken input fails the batch");
+        };
+        assert_eq!(exit::code_of(&err), Failure::Parse.code(), "got: {err:#}");
+        assert!(format!("{err:#}").contains("bad.svg"), "got: {err:#}");
+        assert!(!dir.join("out/a-16.svg").exists());
+
+        let Err(err) = run_cli(&[&args[..], &["--keep-going"]].concat()) else {
+            bail!("--keep-going still reports the failure");
+        };
+        assert_eq!(exit::code_of(&err), Failure::Partial.code());
+        assert_eq!(err.to_string(), "1 / 2 个输入失败");
+        assert!(dir.join("out/a-16.svg").exists());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_lists_every_generated_size() -> Result<()> {
+        let dir = scratch_dir("cli-manifest")?;
+        let input = write_file(&dir.join("a.svg"), ICON)?;
+        let out = dir.join("out").to_string_lossy().into_owned();
+        run_cli(&[
+            "-i",
+            &input,
+            "--out-dir",
+            &out,
+            "--to",
+            "16,32",
+            "--manifest",
+        ])?;
+        let manifest = json::parse(&fs::read_to_string(dir.join("out/manifest.json"))?)?;
+        let Some(Value::Array(files)) = manifest.get("files") else {
+            bail!("no files in {manifest}");
+        };
+        let listed: Vec<_> = files
+            .iter()
+            .map(|f| {
+                let field = |k| f.get(k).cloned().unwrap_or(Value::Null);
+                (field("file"), field("width"), field("height"))
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("a-16.svg".into(), 16.0.into(), 8.0.into()),
+                ("a-32.svg".into(), 32.0.into(), 16.0.into()),
+            ]
+        );
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unpaired_outputs_are_a_usage_error() -> Result<()> {
+        let Err(err) = run_cli(&["-i", "a.svg", "-i", "b.svg", "-o", "a-out.svg"]) else {
+            bail!("two inputs need two outputs");
+        };
+        let kind = err.downcast_ref::<clap::Error>().map(clap::Error::kind);
+        assert_eq!(kind, Some(ErrorKind::WrongNumberOfValues), "got: {err:#}");
+        Ok(())
+    }
+
+    #[test]
+    fn inputs_with_the_same_output_name_are_rejected() -> Result<()> {
+        let dir = scratch_dir("collide")?;
+        let a = write_file(&dir.join("x/a.svg"), ICON)?;
+        let b = write_file(&dir.join("y/a.svg"), ICON)?;
+        let out = dir.join("out").to_string_lossy().into_owned();
+        let Err(err) = run_cli(&["-i", &a, "-i", &b, "--out-dir", &out, "--to", "16"]) else {
+            bail!("both inputs would write out/a-16.svg");
+        };
+        assert!(err.to_string().contains("同名文件"), "got: {err:#}");
+        assert!(!dir.join("out").exists());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn input_dir_keeps_relative_folders() -> Result<()> {
+        let dir = scratch_dir("input-dir")?;
+        write_file(&dir.join("src/a.svg"), ICON)?;
+        write_file(&dir.join("src/sub/a.svg"), ICON)?;
+        let src = dir.join("src").to_string_lossy().into_owned();
+        let out = dir.join("out").to_string_lossy().into_owned();
+        run_cli(&["--input-dir", &src, "--out-dir", &out, "--to", "16"])?;
+        assert!(dir.join("out/a-16.svg").exists());
+        assert!(dir.join("out/sub/a-16.svg").exists());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn to_height_names_outputs_by_height() -> Result<()> {
+        let dir = scratch_dir("to-height")?;
+        let input = write_file(&dir.join("a.svg"), ICON)?;
+        let out = dir.join("out").to_string_lossy().into_owned();
+        run_cli(&["-i", &input, "--out-dir", &out, "--to-height", "6,12"])?;
+        let small = fs::read_to_string(dir.join("out/a-6.svg"))?;
+        assert!(small.contains(r#"width="12" height="6""#), "got: {small}");
+        assert!(dir.join("out/a-12.svg").exists());
+        fs::remove_dir_all(&dir)?;
+        Ok(())
     }
 
     #[test]
-    fn render_png_writes_expected_dimensions() -> Result<()> {
-        let svg = r#"<svg width="10" height="20" xmlns="http://www.w3.org/2000/svg">
-  <rect x="0" y="0" width="10" height="20" fill="red"/>
-</svg>"#;
-        let out_path = tmp_png_path();
-        render_svg_to_png(svg, 30, 60, &out_path)?;
-
-        let data = fs::read(&out_path)?;
-        let (w, h) = read_png_dimensions(&data)?;
-        fs::remove_file(&out_path)?;
-
-        assert_eq!((w, h), (30, 60));
+    fn to_wxh_stretches_nested_viewports_with_the_drawing() -> Result<()> {
+        let dir = scratch_dir("to-wxh")?;
+        let input = write_file(
+            &dir.join("a.svg"),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 20 20"><svg width="20" height="20" viewBox="0 0 20 20"><rect width="10" height="10"/></svg></svg>"#,
+        )?;
+        let output = dir.join("a-40x20.svg").to_string_lossy().into_owned();
+        run_cli(&[
+            "-i",
+            &input,
+            "-o",
+            &output,
+            "--to",
+            "40x20",
+            "--fit-mode",
+            "stretch",
+        ])?;
+        let out = fs::read_to_string(&output)?;
+        assert!(
+            out.contains(r#"<svg width="40" height="20" viewBox="0 0 40 20">"#),
+            "expected the nested viewBox stretched, got: {out}"
+        );
+        assert!(
+            out.contains(r#"<rect width="20" height="10"/>"#),
+            "got: {out}"
+        );
+        fs::remove_dir_all(&dir)?;
         Ok(())
     }
 }
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use svg_scale::hash::sha256_hex;
+use svg_scale::json::Value;
+
+/// One generated file, listed in `manifest.json` by `--manifest`
+pub struct Entry {
+    pub path: PathBuf,
+    pub format: &'static str,
+    pub width: f64,
+    pub height: f64,
+    /// Root viewBox of an SVG output; `None` for rasters
+    pub view_box: Option<String>,
+}
+
+/// Module format written by `--metadata`
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum MetadataFormat {
+    Ts,
+    Json,
+}
+
+/// Write an asset catalog for design systems into `dir`: `assets.json`, or
+/// `assets.ts` exporting the same list with a type. Each asset has its
+/// name (the file stem), path, format, size and viewBox.
+pub fn write_metadata(dir: &Path, entries: &[Entry], format: MetadataFormat) -> Result<PathBuf> {
+    let assets: Vec<Value> = entries
+        .iter()
+        .map(|entry| {
+            let name = entry.path.file_stem().unwrap_or_default().to_string_lossy();
+            let file = entry.path.strip_prefix(dir).unwrap_or(&entry.path);
+            Value::Object(vec![
+                ("name".to_string(), name.as_ref().into()),
+                ("path".to_string(), file.to_string_lossy().as_ref().into()),
+                ("format".to_string(), entry.format.into()),
+                ("width".to_string(), entry.width.into()),
+                ("height".to_string(), entry.height.into()),
+                (
+                    "viewBox".to_string(),
+                    entry.view_box.as_deref().map_or(Value::Null, Value::from),
+                ),
+            ])
+        })
+        .collect();
+    let list = Value::Array(assets);
+    let (path, text) = match format {
+        MetadataFormat::Json => (dir.join("assets.json"), format!("{}\n", list)),
+        MetadataFormat::Ts => (
+            dir.join("assets.ts"),
+            format!(
+                "// Generated by svg-scale; do not edit.\n\
+                 export interface Asset {{\n  name: string;\n  path: string;\n  format: string;\n  \
+                 width: number;\n  height: number;\n  viewBox: string | null;\n}}\n\n\
+                 export const assets: Asset[] = {};\n\nexport default assets;\n",
+                list
+            ),
+        ),
+    };
+    fs::write(&path, text).with_context(|| format!("write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Write `manifest.json` into `dir`, listing each entry with its byte size and
+/// SHA-256 read back from disk. Paths are relative to `dir` where possible.
+pub fn write(dir: &Path, entries: &[Entry]) -> Result<PathBuf> {
+    let mut files = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let data =
+            fs::read(&entry.path).with_context(|| format!("read {}", entry.path.display()))?;
+        let name = entry.path.strip_prefix(dir).unwrap_or(&entry.path);
+        files.push(Value::Object(vec![
+            ("file".to_string(), name.to_string_lossy().as_ref().into()),
+            ("format".to_string(), entry.format.into()),
+            ("width".to_string(), entry.width.into()),
+            ("height".to_string(), entry.height.into()),
+            ("bytes".to_string(), (data.len() as f64).into()),
+            ("sha256".to_string(), sha256_hex(&data).into()),
+        ]));
+    }
+    let manifest = Value::Object(vec![("files".to_string(), Value::Array(files))]);
+    let path = dir.join("manifest.json");
+    fs::write(&path, format!("{}\n", manifest))
+        .with_context(|| format!("write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn lists_files_relative_to_dir_with_hash() -> Result<()> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let dir = std::env::temp_dir().join(format!("svg-scale-manifest-{}", nanos));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("icon-16.svg"), "abc")?;
+        let path = write(
+            &dir,
+            &[Entry {
+                path: dir.join("icon-16.svg"),
+                format: "svg",
+                width: 16.0,
+                height: 8.5,
+                view_box: None,
+            }],
+        )?;
+        let text = fs::read_to_string(&path)?;
+        fs::remove_dir_all(&dir)?;
+        assert_eq!(
+            text,
+            "{\"files\":[{\"file\":\"icon-16.svg\",\"format\":\"svg\",\"width\":16,\"height\":8.5,\"bytes\":3,\"sha256\":\"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\"}]}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn metadata_lists_assets_as_json_and_typescript() -> Result<()> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let dir = std::env::temp_dir().join(format!("svg-scale-metadata-{}", nanos));
+        fs::create_dir_all(&dir)?;
+        let entries = [Entry {
+            path: dir.join("icon-16.svg"),
+            format: "svg",
+            width: 16.0,
+            height: 16.0,
+            view_box: Some("0 0 16 16".to_string()),
+        }];
+        let json = fs::read_to_string(write_metadata(&dir, &entries, MetadataFormat::Json)?)?;
+        let ts = fs::read_to_string(write_metadata(&dir, &entries, MetadataFormat::Ts)?)?;
+        fs::remove_dir_all(&dir)?;
+        let listed = r#"[{"name":"icon-16","path":"icon-16.svg","format":"svg","width":16,"height":16,"viewBox":"0 0 16 16"}]"#;
+        assert_eq!(json, format!("{}\n", listed));
+        assert!(ts.contains(&format!("export const assets: Asset[] = {};", listed)));
+        assert!(ts.contains("viewBox: string | null;"));
+        Ok(())
+    }
+}
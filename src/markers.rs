@@ -0,0 +1,154 @@
+//! How scaling changes markers relative to the paths they decorate.
+//!
+//! A marker in the default `markerUnits="strokeWidth"` is sized by the
+//! stroke width of the path using it, so anything that scales
+//! `stroke-width` differently from the geometry resizes the marker
+//! against its path: a `non-scaling-stroke` that is kept, one that
+//! `--fix-stroke` removes, and `--stroke-compensation`.
+
+use roxmltree::Node;
+
+use crate::scale::ScaleCtx;
+use crate::svg::{css_property, VectorEffects};
+
+const MARKER_PROPERTIES: &[&str] = &["marker-start", "marker-mid", "marker-end", "marker"];
+
+/// How a marker's size changes relative to its path
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkerChange {
+    /// The stroke keeps its width (`non-scaling-stroke`), so the marker
+    /// keeps its size while the path scales
+    KeepsSize,
+    /// `--fix-stroke` removed `non-scaling-stroke`: the marker kept its
+    /// size on screen before and now scales with the path
+    NowScales,
+    /// `--stroke-compensation` scales the stroke, and with it the marker,
+    /// by this factor relative to the path
+    Compensated(f64),
+}
+
+/// One path whose markers change size relative to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerEffect {
+    /// The referencing element, e.g. `<path id="arrow">`
+    pub element: String,
+    /// Ids of the markers in stroke-width units it uses
+    pub markers: Vec<String>,
+    pub change: MarkerChange,
+}
+
+/// Elements whose stroke-width-sized markers scale differently from their
+/// geometry under `ctx`, in document order
+pub fn marker_effects(doc: &roxmltree::Document, ctx: &ScaleCtx) -> Vec<MarkerEffect> {
+    if (ctx.scale - 1.0).abs() < 1e-9 {
+        return Vec::new();
+    }
+    let compensation = ctx.for_stroke().scale / ctx.scale;
+    doc.descendants()
+        .filter(|n| n.is_element())
+        .filter_map(|node| {
+            let markers = stroke_width_markers(node);
+            if markers.is_empty() {
+                return None;
+            }
+            let effects = VectorEffects::from_values(
+                node.attribute("vector-effect")
+                    .map(str::to_string)
+                    .into_iter()
+                    .chain(css_property(node, "vector-effect"))
+                    .collect::<Vec<_>>()
+                    .iter()
+                    .map(String::as_str),
+            );
+            let change = match (effects.non_scaling_stroke, ctx.fix_stroke) {
+                (true, false) => MarkerChange::KeepsSize,
+                (true, true) => MarkerChange::NowScales,
+                _ if (compensation - 1.0).abs() > 1e-6 => MarkerChange::Compensated(compensation),
+                _ => return None,
+            };
+            Some(MarkerEffect {
+                element: label(node),
+                markers,
+                change,
+            })
+        })
+        .collect()
+}
+
+/// Ids of the markers `node` references whose `markerUnits` are the
+/// default `strokeWidth`
+fn stroke_width_markers(node: Node) -> Vec<String> {
+    let mut ids: Vec<String> = MARKER_PROPERTIES
+        .iter()
+        .flat_map(|&name| {
+            node.attribute(name)
+                .map(str::to_string)
+                .into_iter()
+                .chain(css_property(node, name))
+        })
+        .filter_map(|value| {
+            let id = value.trim().strip_prefix("url(")?.strip_suffix(')')?;
+            Some(
+                id.trim()
+                    .trim_matches(['"', '\''])
+                    .strip_prefix('#')?
+                    .to_string(),
+            )
+        })
+        .filter(|id| {
+            node.document().descendants().any(|m| {
+                m.has_tag_name("marker")
+                    && m.attribute("id") == Some(id)
+                    && m.attribute("markerUnits") != Some("userSpaceOnUse")
+            })
+        })
+        .collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+fn label(node: Node) -> String {
+    match node.attribute("id") {
+        Some(id) => format!("<{} id=\"{}\">", node.tag_name().name(), id),
+        None => format!("<{}>", node.tag_name().name()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARROWS: &str = r##"<svg xmlns="http://www.w3.org/2000/svg"><marker id="tip"/><marker id="dot" markerUnits="userSpaceOnUse"/><path id="a" d="M0 0H9" marker-end="url(#tip)" vector-effect="non-scaling-stroke"/><path d="M0 0H9" style="marker-start:url(#dot)" vector-effect="non-scaling-stroke"/><line id="b" x2="9" style="marker: url('#tip')"/></svg>"##;
+
+    fn ctx(fix_stroke: bool, stroke_exponent: Option<f64>) -> ScaleCtx {
+        ScaleCtx {
+            scale: 0.25,
+            fix_stroke,
+            stroke_exponent,
+            ..ScaleCtx::default()
+        }
+    }
+
+    #[test]
+    fn stroke_width_markers_follow_the_stroke_handling() -> anyhow::Result<()> {
+        let doc = roxmltree::Document::parse(ARROWS)?;
+        assert_eq!(
+            marker_effects(&doc, &ctx(false, None)),
+            [MarkerEffect {
+                element: r#"<path id="a">"#.into(),
+                markers: vec!["tip".into()],
+                change: MarkerChange::KeepsSize,
+            }]
+        );
+        assert_eq!(
+            marker_effects(&doc, &ctx(true, None))[0].change,
+            MarkerChange::NowScales
+        );
+        let compensated = marker_effects(&doc, &ctx(true, Some(0.5)));
+        assert_eq!(compensated.len(), 2);
+        assert_eq!(compensated[1].element, r#"<line id="b">"#);
+        assert_eq!(compensated[1].change, MarkerChange::Compensated(2.0));
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::fs;
+
+use svg_scale::scale;
+use svg_scale::viewbox::normalize_viewbox;
+use svg_scale::{scale_svg, ScaleOptions, DEFAULT_DPI};
+
+#[derive(Args)]
+pub struct NormalizeArgs {
+    /// 输入 SVG 文件
+    pub input: String,
+
+    /// 输出文件（默认输出到 stdout）
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// 归一化后再按比例缩放，支持 0.5、2/3、150% 等表达式
+    #[arg(long, value_parser = scale::parse_scale_factor)]
+    pub scale: Option<f64>,
+
+    /// 归一化后再缩放到目标尺寸
+    #[arg(long)]
+    pub to: Option<f64>,
+
+    /// 原始尺寸（可选）
+    #[arg(long)]
+    pub from: Option<f64>,
+
+    #[arg(long, default_value = "4", value_parser = scale::parse_precision)]
+    pub precision: usize,
+
+    /// 移除 non-scaling-stroke
+    #[arg(long)]
+    pub fix_stroke: bool,
+}
+
+pub fn run(args: &NormalizeArgs) -> Result<()> {
+    let input_svg =
+        fs::read_to_string(&args.input).with_context(|| format!("read {}", args.input))?;
+    let doc = roxmltree::Document::parse(&input_svg)?;
+    let mut out = normalize_viewbox(&doc, args.precision)?;
+
+    if args.scale.is_some() || args.to.is_some() {
+        out = scale_svg(
+            &out,
+            &ScaleOptions {
+                scale: args.scale,
+                to: args.to,
+                from: args.from,
+                precision: args.precision,
+                fix_stroke: args.fix_stroke,
+                dpi: DEFAULT_DPI,
+                exact: false,
+            },
+        )?;
+    }
+
+    if let Some(output) = &args.output {
+        fs::write(output, &out)?;
+        println!("输出: {}", output);
+    } else {
+        println!("{}", out);
+    }
+    Ok(())
+}
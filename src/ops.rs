@@ -0,0 +1,292 @@
+//! Ordered document operations for `--ops`.
+//!
+//! Each operation rewrites the in-memory document and hands the text to the
+//! next one, so a whole chain runs in one invocation. Intermediate results
+//! keep 12 decimals; only `snap` rounds on purpose, and the final scaling
+//! pass applies the requested precision once.
+
+use anyhow::{bail, Context, Result};
+use std::fmt;
+
+use crate::optimize::{self, OptimizeOptions};
+use crate::scale::{self, ScaleCtx};
+use crate::viewbox::{self, EXACT_PRECISION};
+
+/// One step of an operation pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Crop the viewBox to the drawn content, strokes included
+    Trim,
+    /// Grow the viewBox by this many user units on every side
+    Pad(f64),
+    /// Scale everything by a factor
+    Scale(f64),
+    /// Scale so the document is this wide
+    To(f64),
+    /// Round coordinates to multiples of this step
+    Snap(f64),
+    /// Move a non-zero viewBox origin to `0 0`
+    Normalize,
+    /// Drop unused defs, hidden and editor content, and empty groups
+    Optimize,
+    /// Sort attributes into a fixed order
+    Canonical,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Trim => f.write_str("trim"),
+            Op::Pad(v) => write!(f, "pad={}", v),
+            Op::Scale(v) => write!(f, "scale={}", v),
+            Op::To(v) => write!(f, "to={}", v),
+            Op::Snap(v) => write!(f, "snap={}", v),
+            Op::Normalize => f.write_str("normalize"),
+            Op::Optimize => f.write_str("optimize"),
+            Op::Canonical => f.write_str("canonical"),
+        }
+    }
+}
+
+impl std::str::FromStr for Op {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, arg) = match s.split_once('=') {
+            Some((name, arg)) => (name.trim(), Some(arg.trim())),
+            None => (s.trim(), None),
+        };
+        let number = |arg: Option<&str>| -> Result<f64> {
+            let arg = arg.with_context(|| format!("{} needs a value, e.g. {}=2", name, name))?;
+            match arg.parse::<f64>() {
+                Ok(v) if v.is_finite() => Ok(v),
+                _ => bail!("invalid {} value: {}", name, arg),
+            }
+        };
+        let positive = |arg: Option<&str>| -> Result<f64> {
+            match number(arg)? {
+                v if v > 0.0 => Ok(v),
+                _ => bail!("{} must be positive", name),
+            }
+        };
+        let op = match name {
+            "trim" => Op::Trim,
+            "pad" => Op::Pad(number(arg)?),
+            "scale" => {
+                let factor =
+                    scale::parse_scale_expr(arg.context("scale needs a value, e.g. scale=0.5")?)?;
+                scale::check_scale(factor, false)?;
+                Op::Scale(factor)
+            }
+            "to" => Op::To(positive(arg)?),
+            "snap" => Op::Snap(match arg {
+                Some(_) => positive(arg)?,
+                None => 1.0,
+            }),
+            "normalize" => Op::Normalize,
+            "optimize" => Op::Optimize,
+            "canonical" => Op::Canonical,
+            _ => bail!(
+                "unknown operation: {} (expected trim, pad, scale, to, snap, normalize, optimize or canonical)",
+                name
+            ),
+        };
+        let takes_value = matches!(op, Op::Pad(_) | Op::Scale(_) | Op::To(_) | Op::Snap(_));
+        if arg.is_some() && !takes_value {
+            bail!("{} takes no value", name);
+        }
+        Ok(op)
+    }
+}
+
+/// Comma-separated operations, run in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpList(pub Vec<Op>);
+
+impl std::str::FromStr for OpList {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let ops = s
+            .split(',')
+            .filter(|part| !part.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Op>>>()?;
+        if ops.is_empty() {
+            bail!("empty operation list");
+        }
+        Ok(OpList(ops))
+    }
+}
+
+/// Run `ops` over the document text in order; the result is unindented
+pub fn run_ops(svg_text: &str, ops: &[Op], dpi: f64) -> Result<String> {
+    let mut text = svg_text.to_string();
+    for op in ops {
+        let doc = roxmltree::Document::parse(&text)?;
+        let out = apply(&doc, *op, dpi).with_context(|| format!("operation {} failed", op))?;
+        text = crate::strip_indentation(&out)?;
+    }
+    Ok(text)
+}
+
+fn apply(doc: &roxmltree::Document, op: Op, dpi: f64) -> Result<String> {
+    match op {
+        Op::Trim => trim(doc, dpi),
+        Op::Pad(amount) => viewbox::pad_view_box(doc, amount, dpi),
+        Op::Scale(scale) => scaled(doc, scale, EXACT_PRECISION),
+        Op::To(size) => {
+            let from = crate::get_svg_size(doc, dpi).context("未能从SVG检测到尺寸")?;
+            scaled(doc, size / from, EXACT_PRECISION)
+        }
+        Op::Snap(step) => {
+            // Scaling by 1/step at precision 0 rounds to the grid in
+            // step units; scaling back is then exact
+            let grid = scaled(doc, 1.0 / step, 0)?;
+            scaled(&roxmltree::Document::parse(&grid)?, step, EXACT_PRECISION)
+        }
+        Op::Normalize => viewbox::normalize_viewbox(doc, EXACT_PRECISION),
+        Op::Optimize => optimize::optimize(
+            doc,
+            &OptimizeOptions {
+                compact_precision: None,
+                ..OptimizeOptions::default()
+            },
+        ),
+        Op::Canonical => crate::canonicalize(doc.input_text()),
+    }
+}
+
+fn scaled(doc: &roxmltree::Document, scale: f64, precision: usize) -> Result<String> {
+    let ctx = ScaleCtx {
+        scale,
+        precision,
+        ..ScaleCtx::default()
+    };
+    crate::write_svg(doc, &ctx)
+}
+
+fn trim(doc: &roxmltree::Document, dpi: f64) -> Result<String> {
+    let content = content_view_box(doc, dpi)?;
+    Ok(viewbox::crop_view_box(doc, content, dpi))
+}
+
+/// Width and height of what the document draws, stroke included, in
+/// pixels at the document's own size
+pub fn content_size(doc: &roxmltree::Document, dpi: f64) -> Result<(f64, f64)> {
+    let content = content_view_box(doc, dpi)?;
+    let factor = viewbox::display_factor(doc, dpi);
+    Ok((content.width * factor, content.height * factor))
+}
+
+/// The drawn bounds, stroke included, in user units
+#[cfg(feature = "render")]
+pub fn content_view_box(doc: &roxmltree::Document, dpi: f64) -> Result<viewbox::ViewBox> {
+    use resvg::usvg;
+
+    // With the root sized in proportion to its viewBox, canvas pixels are
+    // user units times the display factor, shifted by the viewBox origin
+    let current = viewbox::current_view_box(doc, dpi)?;
+    let factor = viewbox::display_factor(doc, dpi);
+    let framed = viewbox::crop_view_box(doc, current, dpi);
+    let tree =
+        usvg::Tree::from_str(&framed, &usvg::Options::default()).context("parse svg for trim")?;
+    let Some(bbox) = content_box(tree.root()).filter(|r| r.width() > 0.0 && r.height() > 0.0)
+    else {
+        bail!("nothing to measure: the document draws nothing");
+    };
+    Ok(viewbox::ViewBox {
+        x: current.x + bbox.x() as f64 / factor,
+        y: current.y + bbox.y() as f64 / factor,
+        width: bbox.width() as f64 / factor,
+        height: bbox.height() as f64 / factor,
+    })
+}
+
+/// Union of the leaves' canvas boxes. usvg does not fill in the boxes of
+/// the group it wraps around the viewBox transform, so groups are not
+/// trusted.
+#[cfg(feature = "render")]
+fn content_box(group: &resvg::usvg::Group) -> Option<resvg::usvg::Rect> {
+    use resvg::usvg::{Node, Rect};
+
+    group
+        .children()
+        .iter()
+        .filter_map(|node| match node {
+            Node::Group(group) => content_box(group),
+            node => Some(node.abs_stroke_bounding_box()),
+        })
+        .reduce(|a, b| {
+            Rect::from_ltrb(
+                a.left().min(b.left()),
+                a.top().min(b.top()),
+                a.right().max(b.right()),
+                a.bottom().max(b.bottom()),
+            )
+            .unwrap_or(a)
+        })
+}
+
+#[cfg(not(feature = "render"))]
+pub fn content_view_box(_doc: &roxmltree::Document, _dpi: f64) -> Result<viewbox::ViewBox> {
+    bail!("measuring the content needs the render feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_operation_lists() -> Result<()> {
+        let list: OpList = "trim, pad=2,scale=1/2,snap,snap=0.5,to=16".parse()?;
+        assert_eq!(
+            list.0,
+            vec![
+                Op::Trim,
+                Op::Pad(2.0),
+                Op::Scale(0.5),
+                Op::Snap(1.0),
+                Op::Snap(0.5),
+                Op::To(16.0)
+            ]
+        );
+        assert!("pad".parse::<OpList>().is_err());
+        assert!("trim=1".parse::<OpList>().is_err());
+        assert!("spin".parse::<OpList>().is_err());
+        assert!("snap=0".parse::<OpList>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn runs_operations_in_order() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100" viewBox="0 0 100 100"><rect x="10.3" y="20.6" width="30" height="40"/></svg>"#;
+        let ops: OpList = "pad=2,scale=0.5,snap".parse()?;
+        let out = run_ops(input, &ops.0, 96.0)?;
+        assert!(
+            out.contains(r#"width="52" height="52" viewBox="-1 -1 52 52""#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<rect x="5" y="10" width="15" height="20"/>"#),
+            "got: {out}"
+        );
+        // Passes hand on unindented text instead of stacking whitespace
+        assert!(!out.contains("\n    \n"), "got: {out}");
+        Ok(())
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn trim_crops_to_the_content() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="48" height="48" viewBox="0 0 24 24"><rect x="4" y="6" width="10" height="8"/></svg>"#;
+        let out = run_ops(input, &[Op::Trim], 96.0)?;
+        assert!(
+            out.contains(r#"width="20" height="16" viewBox="4 6 10 8""#),
+            "got: {out}"
+        );
+        let doc = roxmltree::Document::parse(input)?;
+        assert_eq!(content_size(&doc, 96.0)?, (20.0, 16.0));
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+//! SVGO-style cleanup passes behind `svg-scale optimize`.
+//!
+//! Each pass can be turned off on its own. Removal never touches an element
+//! whose subtree holds something that is still referenced (`url(#id)`,
+//! `href="#id"`, `<style>` id selectors, SMIL `begin`/`end`), so dropping
+//! defs or hidden elements cannot break the rendering.
+
+use anyhow::Result;
+use roxmltree::{Node, NodeId};
+use std::collections::HashSet;
+use xmlwriter::XmlWriter;
+
+use crate::path::{compact_path, strip_leading_zero};
+use crate::scale::ScaleCtx;
+use crate::svg;
+
+/// Namespaces written by editors (Inkscape, Sketch, Illustrator, ...) and by
+/// the RDF metadata they embed; none of them affects the rendering
+const EDITOR_NAMESPACES: &[&str] = &[
+    "http://www.inkscape.org/namespaces/inkscape",
+    "http://sodipodi.sourceforge.net/DTD/sodipodi-0.dtd",
+    "http://www.bohemiancoding.com/sketch/ns",
+    "http://ns.adobe.com/AdobeIllustrator/10.0/",
+    "http://ns.adobe.com/AdobeSVGViewerExtensions/3.0/",
+    "http://ns.adobe.com/Extensibility/1.0/",
+    "http://ns.adobe.com/Flows/1.0/",
+    "http://ns.adobe.com/Graphs/1.0/",
+    "http://ns.adobe.com/ImageReplacement/1.0/",
+    "http://ns.adobe.com/SaveForWeb/1.0/",
+    "http://ns.adobe.com/Variables/1.0/",
+    "http://ns.adobe.com/XPath/1.0/",
+    "http://www.serif.com/",
+    "http://www.vector.evaxdesign.sk",
+    "http://creativecommons.org/ns#",
+    "http://purl.org/dc/elements/1.1/",
+    "http://www.w3.org/1999/02/22-rdf-syntax-ns#",
+];
+
+/// Elements that only render through a reference
+const REFERENCED_ONLY_TAGS: &[&str] = &[
+    "clipPath",
+    "filter",
+    "linearGradient",
+    "marker",
+    "mask",
+    "pattern",
+    "radialGradient",
+    "symbol",
+];
+
+const ANIMATION_TAGS: &[&str] = &["animate", "animateMotion", "animateTransform", "set"];
+
+/// Attributes that look numeric but are names or versions, not quantities
+const VERBATIM_ATTRS: &[&str] = &["id", "class", "version"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizeOptions {
+    /// Drop unreferenced `<defs>` content, gradients, clip paths, ...
+    pub prune_defs: bool,
+    /// Drop `display="none"` elements and shapes with a zero size
+    pub remove_hidden: bool,
+    /// Drop editor namespaces, their elements and attributes, and `<metadata>`
+    pub strip_editor_data: bool,
+    /// Unwrap groups without attributes and drop empty ones
+    pub collapse_groups: bool,
+    /// Round numbers to this many decimals and compact path data
+    pub compact_precision: Option<usize>,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            prune_defs: true,
+            remove_hidden: true,
+            strip_editor_data: true,
+            collapse_groups: true,
+            compact_precision: Some(4),
+        }
+    }
+}
+
+/// Run the enabled cleanup passes over `doc` and serialize the result
+pub fn optimize(doc: &roxmltree::Document, opts: &OptimizeOptions) -> Result<String> {
+    let removed = removed_nodes(doc, opts);
+    let ctx = opts.compact_precision.map(|precision| ScaleCtx {
+        precision,
+        ..ScaleCtx::default()
+    });
+    let pass = Pass {
+        opts,
+        removed: &removed,
+        ctx: ctx.as_ref(),
+    };
+    let mut w = XmlWriter::new(xmlwriter::Options::default());
+    pass.write(doc.root_element(), &mut w)?;
+    Ok(crate::finish_document_with_namespaces(doc, w, &|ns| {
+        !(opts.strip_editor_data && EDITOR_NAMESPACES.contains(&ns.uri()))
+    }))
+}
+
+fn is_editor_element(node: Node) -> bool {
+    node.tag_name()
+        .namespace()
+        .is_some_and(|ns| EDITOR_NAMESPACES.contains(&ns))
+        || node.tag_name().name() == "metadata"
+}
+
+fn is_editor_attr(attr: &roxmltree::Attribute) -> bool {
+    attr.namespace()
+        .is_some_and(|ns| EDITOR_NAMESPACES.contains(&ns))
+}
+
+/// Ids referenced from anywhere outside `removed`
+fn referenced_ids<'a>(doc: &'a roxmltree::Document, removed: &HashSet<NodeId>) -> HashSet<&'a str> {
+    let mut ids = HashSet::new();
+    for node in doc.descendants().filter(|n| !is_removed(*n, removed)) {
+        if node.is_text() && node.parent().is_some_and(|p| p.has_tag_name("style")) {
+            let text = node.text().unwrap_or("");
+            ids.extend(url_refs(text));
+            ids.extend(text.split('#').skip(1).map(|s| {
+                let end = s
+                    .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+                    .unwrap_or(s.len());
+                &s[..end]
+            }));
+        }
+        for attr in node.attributes() {
+            let value = attr.value();
+            match attr.name() {
+                "href" => ids.extend(value.strip_prefix('#')),
+                "begin" | "end" => ids.extend(
+                    value
+                        .split(';')
+                        .filter_map(|t| t.trim().split_once('.'))
+                        .map(|(id, _)| id),
+                ),
+                _ => {}
+            }
+            ids.extend(url_refs(value));
+        }
+    }
+    ids
+}
+
+/// Fragment ids of every `url(#id)` in `s`
+fn url_refs(s: &str) -> impl Iterator<Item = &str> {
+    s.split("url(").skip(1).filter_map(|rest| {
+        let inner = rest.split(')').next()?.trim();
+        inner
+            .trim_matches(|c| c == '"' || c == '\'')
+            .strip_prefix('#')
+    })
+}
+
+fn is_removed(node: Node, removed: &HashSet<NodeId>) -> bool {
+    node.ancestors().any(|a| removed.contains(&a.id()))
+}
+
+/// Elements to drop, iterated until removals stop freeing further ones
+fn removed_nodes(doc: &roxmltree::Document, opts: &OptimizeOptions) -> HashSet<NodeId> {
+    let mut removed = HashSet::new();
+    loop {
+        let refs = referenced_ids(doc, &removed);
+        let newly: Vec<NodeId> = doc
+            .root_element()
+            .descendants()
+            .skip(1)
+            .filter(|n| n.is_element() && !is_removed(*n, &removed))
+            .filter(|n| should_remove(*n, opts, &refs, &removed))
+            .map(|n| n.id())
+            .collect();
+        if newly.is_empty() {
+            return removed;
+        }
+        removed.extend(newly);
+    }
+}
+
+fn should_remove(
+    node: Node,
+    opts: &OptimizeOptions,
+    refs: &HashSet<&str>,
+    removed: &HashSet<NodeId>,
+) -> bool {
+    if opts.strip_editor_data && is_editor_element(node) {
+        return true;
+    }
+    let still_needed = node.descendants().any(|d| {
+        d.attribute("id").is_some_and(|id| refs.contains(id))
+            || ANIMATION_TAGS.contains(&d.tag_name().name())
+    });
+    if still_needed {
+        return false;
+    }
+    let tag = node.tag_name().name();
+    let parent_tag = node.parent_element().map(|p| p.tag_name().name());
+    let has_live_children = node
+        .children()
+        .any(|c| c.is_element() && !removed.contains(&c.id()));
+
+    if opts.prune_defs {
+        let in_defs = parent_tag == Some("defs") && !matches!(tag, "style" | "script");
+        if in_defs || REFERENCED_ONLY_TAGS.contains(&tag) || (tag == "defs" && !has_live_children) {
+            return true;
+        }
+    }
+    if opts.remove_hidden && (is_display_none(node) || is_zero_size(node)) {
+        return true;
+    }
+    opts.collapse_groups && tag == "g" && !has_live_children
+}
+
+fn is_display_none(node: Node) -> bool {
+    let inline = node
+        .attribute("style")
+        .map(svg::parse_style)
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .find(|(k, _)| k == "display")
+        .map(|(_, v)| v);
+    inline.as_deref().or(node.attribute("display")) == Some("none")
+}
+
+fn is_zero_size(node: Node) -> bool {
+    let is_zero = |name| {
+        node.attribute(name)
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            == Some(0.0)
+    };
+    let is_blank = |name| node.attribute(name).is_none_or(|v| v.trim().is_empty());
+    match node.tag_name().name() {
+        "rect" => is_zero("width") || is_zero("height"),
+        "circle" => is_zero("r"),
+        "ellipse" => is_zero("rx") || is_zero("ry"),
+        "path" => is_blank("d"),
+        "polyline" | "polygon" => is_blank("points"),
+        _ => false,
+    }
+}
+
+struct Pass<'a> {
+    opts: &'a OptimizeOptions,
+    removed: &'a HashSet<NodeId>,
+    ctx: Option<&'a ScaleCtx>,
+}
+
+impl Pass<'_> {
+    fn write(&self, node: Node, w: &mut XmlWriter) -> Result<()> {
+        match node.node_type() {
+            roxmltree::NodeType::Element => {
+                if self.removed.contains(&node.id()) {
+                    return Ok(());
+                }
+                let attrs: Vec<_> = node
+                    .attributes()
+                    .filter(|a| !(self.opts.strip_editor_data && is_editor_attr(a)))
+                    .collect();
+                let unwrap = self.opts.collapse_groups
+                    && node.has_tag_name("g")
+                    && attrs.is_empty()
+                    && node.parent_element().is_some();
+                if !unwrap {
+                    w.start_element(node.tag_name().name());
+                    for attr in &attrs {
+                        let name = svg::qualified_attr_name(node, attr);
+                        match self.compact(&name, attr.value())? {
+                            Some(v) => w.write_attribute(&name, &v),
+                            None => w.write_attribute(&name, attr.value()),
+                        }
+                    }
+                }
+                for child in node.children() {
+                    self.write(child, w)?;
+                }
+                if !unwrap {
+                    w.end_element();
+                }
+            }
+            roxmltree::NodeType::Text if !svg::is_indentation(node) => {
+                w.write_text(node.text().unwrap_or(""));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The compacted form of a numeric attribute, `None` to keep it as is
+    fn compact(&self, name: &str, value: &str) -> Result<Option<String>> {
+        let Some(ctx) = self.ctx else {
+            return Ok(None);
+        };
+        if name == "d" {
+            return compact_path(value, ctx).map(Some);
+        }
+        if VERBATIM_ATTRS.contains(&name) || name.contains(':') {
+            return Ok(None);
+        }
+        let nums: Option<Vec<f64>> = value
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.parse().ok())
+            .collect();
+        Ok(nums.filter(|n| !n.is_empty()).map(|nums| {
+            let mut out = String::with_capacity(value.len());
+            for (i, v) in nums.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                let mut num = ctx.fmt(v);
+                strip_leading_zero(&mut num);
+                out.push_str(&num);
+            }
+            out
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str, opts: &OptimizeOptions) -> Result<String> {
+        optimize(&roxmltree::Document::parse(input)?, opts)
+    }
+
+    #[test]
+    fn prunes_unused_defs_and_keeps_referenced_ones() -> Result<()> {
+        let input = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+<defs>
+  <linearGradient id="used" xlink:href="#base"/>
+  <linearGradient id="base"/>
+  <linearGradient id="unused" xlink:href="#chain"/>
+  <linearGradient id="chain"/>
+</defs>
+<rect fill="url(#used)" width="1" height="1"/>
+<rect display="none" width="1" height="1"/>
+<rect width="0" height="1"/>
+</svg>"##;
+        let out = run(input, &OptimizeOptions::default())?;
+        assert!(out.contains(r#"id="used""#));
+        assert!(out.contains(r#"id="base""#));
+        assert!(!out.contains("unused"));
+        assert!(!out.contains("chain"), "freed by removing its only user");
+        assert!(!out.contains("display"));
+        assert!(!out.contains(r#"width="0""#));
+        Ok(())
+    }
+
+    #[test]
+    fn strips_editor_data_and_collapses_groups() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" xmlns:sodipodi="http://sodipodi.sourceforge.net/DTD/sodipodi-0.dtd">
+<sodipodi:namedview id="view"/>
+<metadata>editor</metadata>
+<g inkscape:label="Layer 1" inkscape:groupmode="layer"><g><path d="M 0.50 10.0 L 20 30.00001"/></g><g/></g>
+<g id="keep" opacity="0.50"><rect x="0.25" width="1" height="1"/></g>
+</svg>"#;
+        let out = run(input, &OptimizeOptions::default())?;
+        assert!(!out.contains("inkscape"));
+        assert!(!out.contains("sodipodi"));
+        assert!(!out.contains("metadata"));
+        assert!(out.contains(r#"<path d="M.5 10L20 30"/>"#), "{}", out);
+        assert!(out.contains(r#"opacity=".5""#));
+        assert!(out.contains(r#"x=".25""#));
+        assert_eq!(out.matches("<g").count(), 1, "{}", out);
+        Ok(())
+    }
+
+    #[test]
+    fn passes_can_be_turned_off() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><defs><linearGradient id="a"/></defs><g><rect x="0.50" width="1" height="1"/></g></svg>"#;
+        let opts = OptimizeOptions {
+            prune_defs: false,
+            collapse_groups: false,
+            compact_precision: None,
+            ..OptimizeOptions::default()
+        };
+        let out = run(input, &opts)?;
+        assert!(out.contains(r#"id="a""#));
+        assert!(out.contains("<g>"));
+        assert!(out.contains(r#"x="0.50""#));
+        Ok(())
+    }
+}
@@ -5,19 +5,7 @@ use nom::{
     multi::many0, number::complete::double, IResult,
 };
 pub fn scale_path(d: &str, ctx: &ScaleCtx) -> Result<String> {
-    let (rest, parts) = match parse_parts(d) {
-        Ok(v) => v,
-        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
-            let pos = d.len().saturating_sub(e.input.len());
-            return Err(anyhow::anyhow!(format_path_error(d, pos)));
-        }
-        Err(_) => return Err(anyhow::anyhow!("invalid path data")),
-    };
-    if !rest.is_empty() {
-        let pos = d.len().saturating_sub(rest.len());
-        return Err(anyhow::anyhow!(format_path_error(d, pos)));
-    }
-
+    let parts = parse_checked(d)?;
     let mut cmd: Option<char> = None;
     let mut param_index: usize = 0;
     let mut out = String::with_capacity(d.len());
@@ -39,7 +27,7 @@ pub fn scale_path(d: &str, ctx: &ScaleCtx) -> Result<String> {
                     _ => true,
                 };
                 if should_scale {
-                    out.push_str(&ctx.fmt(val * ctx.scale));
+                    ctx.push_scaled(&mut out, val);
                 } else {
                     out.push_str(raw);
                 }
@@ -51,6 +39,224 @@ pub fn scale_path(d: &str, ctx: &ScaleCtx) -> Result<String> {
     Ok(out)
 }
 
+/// Scale path data by `kx` horizontally and `ky` vertically, formatted with
+/// the context's precision (its `scale` is not used). Arc radii and
+/// rotations are recomputed so the stretched arc is the image of the
+/// original one.
+pub fn stretch_path(d: &str, kx: f64, ky: f64, ctx: &ScaleCtx) -> Result<String> {
+    let parts = parse_checked(d)?;
+    let mut cmd: Option<char> = None;
+    let mut param_index: usize = 0;
+    let mut out = String::with_capacity(d.len());
+    // Start in `out` and values of the arc radii and rotation being read
+    let mut arc: (usize, [f64; 3]) = (0, [0.0; 3]);
+
+    for part in parts {
+        match part {
+            Part::Sep(s) => out.push_str(s),
+            Part::Cmd(c) => {
+                cmd = Some(c);
+                param_index = 0;
+                out.push(c);
+            }
+            Part::Num { raw, val } => {
+                match (cmd, param_index % 7) {
+                    (Some('A' | 'a'), i @ 0..=2) => {
+                        if i == 0 {
+                            arc.0 = out.len();
+                        }
+                        arc.1[i] = val;
+                        out.push_str(raw);
+                        if i == 2 {
+                            let (rx, ry, angle) = stretch_arc(arc.1, kx, ky);
+                            out.truncate(arc.0);
+                            ctx.push_fmt(&mut out, rx);
+                            out.push(' ');
+                            ctx.push_fmt(&mut out, ry);
+                            out.push(' ');
+                            ctx.push_fmt(&mut out, angle);
+                        }
+                    }
+                    (Some('A' | 'a'), 3 | 4) => out.push_str(raw),
+                    (Some('A' | 'a'), i) => {
+                        ctx.push_fmt(&mut out, val * if i == 5 { kx } else { ky })
+                    }
+                    (Some('H' | 'h'), _) => ctx.push_fmt(&mut out, val * kx),
+                    (Some('V' | 'v'), _) => ctx.push_fmt(&mut out, val * ky),
+                    _ => {
+                        let k = if param_index.is_multiple_of(2) {
+                            kx
+                        } else {
+                            ky
+                        };
+                        ctx.push_fmt(&mut out, val * k);
+                    }
+                }
+                param_index = param_index.saturating_add(1);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Radii and rotation (degrees) of the ellipse `[rx, ry, angle]` stretched by
+/// `kx`, `ky`
+fn stretch_arc([rx, ry, angle]: [f64; 3], kx: f64, ky: f64) -> (f64, f64, f64) {
+    if kx == ky || angle % 180.0 == 0.0 {
+        return ((rx * kx).abs(), (ry * ky).abs(), angle);
+    }
+    if angle % 90.0 == 0.0 {
+        return ((rx * ky).abs(), (ry * kx).abs(), angle);
+    }
+    // The stretched ellipse is the unit circle under K * R(angle) * diag(rx, ry);
+    // its radii and rotation are that matrix's singular values and left
+    // rotation
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (m00, m01, m10, m11) = (kx * cos * rx, -kx * sin * ry, ky * sin * rx, ky * cos * ry);
+    let (e, f) = ((m00 + m11) / 2.0, (m00 - m11) / 2.0);
+    let (g, h) = ((m10 + m01) / 2.0, (m10 - m01) / 2.0);
+    let (q, r) = (e.hypot(h), f.hypot(g));
+    let rotation = (g.atan2(f) + h.atan2(e)) / 2.0;
+    ((q + r).abs(), (q - r).abs(), rotation.to_degrees())
+}
+
+/// Absolute end and control points of path data, in drawing order. Curves
+/// stay inside the hull of their points; arcs may bulge past their ends.
+pub fn path_points(d: &str) -> Result<Vec<(f64, f64)>> {
+    let mut points = Vec::new();
+    let mut cmd: Option<char> = None;
+    let mut args: Vec<f64> = Vec::with_capacity(7);
+    let (mut current, mut start) = ((0.0, 0.0), (0.0, 0.0));
+
+    for part in parse_checked(d)? {
+        match part {
+            Part::Sep(_) => {}
+            Part::Cmd(c) => {
+                cmd = Some(c);
+                args.clear();
+                if c == 'Z' || c == 'z' {
+                    current = start;
+                }
+            }
+            Part::Num { val, .. } => {
+                let Some(c) = cmd else { continue };
+                let arity = match c.to_ascii_uppercase() {
+                    'H' | 'V' => 1,
+                    'M' | 'L' | 'T' => 2,
+                    'S' | 'Q' => 4,
+                    'C' => 6,
+                    'A' => 7,
+                    _ => continue,
+                };
+                args.push(val);
+                if args.len() < arity {
+                    continue;
+                }
+                let (ox, oy) = if c.is_ascii_lowercase() {
+                    current
+                } else {
+                    (0.0, 0.0)
+                };
+                let at = |i: usize| (args[i] + ox, args[i + 1] + oy);
+                match c.to_ascii_uppercase() {
+                    'H' => current.0 = args[0] + ox,
+                    'V' => current.1 = args[0] + oy,
+                    'A' => current = at(5),
+                    _ => {
+                        points.extend((0..arity - 2).step_by(2).map(at));
+                        current = at(arity - 2);
+                    }
+                }
+                points.push(current);
+                if c == 'M' || c == 'm' {
+                    // Further pairs after a moveto are linetos
+                    start = current;
+                    cmd = Some(if c == 'm' { 'l' } else { 'L' });
+                }
+                args.clear();
+            }
+        }
+    }
+
+    Ok(points)
+}
+
+/// Rewrite path data in its shortest form: numbers rounded to the context's
+/// precision without leading zeros, separators only where a number would
+/// otherwise run into the previous one, and repeated commands implied
+pub fn compact_path(d: &str, ctx: &ScaleCtx) -> Result<String> {
+    let parts = parse_checked(d)?;
+
+    let mut cmd: Option<char> = None;
+    let mut param_index: usize = 0;
+    let mut out = String::with_capacity(d.len());
+    let mut last_num = String::new();
+    let mut after_num = false;
+
+    for part in parts {
+        match part {
+            Part::Sep(_) => {}
+            Part::Cmd(c) => {
+                // A repeated command is implied by more parameters, except
+                // that extra moveto pairs mean lineto and closepath has none
+                if cmd != Some(c) || matches!(c, 'M' | 'm' | 'Z' | 'z') {
+                    out.push(c);
+                    after_num = false;
+                }
+                cmd = Some(c);
+                param_index = 0;
+            }
+            Part::Num { raw, val } => {
+                let is_flag = matches!(cmd, Some('A' | 'a')) && matches!(param_index % 7, 3 | 4);
+                let mut num = String::new();
+                if is_flag {
+                    num.push_str(raw);
+                } else {
+                    ctx.push_scaled(&mut num, val);
+                    strip_leading_zero(&mut num);
+                }
+                let joins =
+                    num.starts_with('-') || (num.starts_with('.') && last_num.contains('.'));
+                if after_num && !joins {
+                    out.push(' ');
+                }
+                out.push_str(&num);
+                last_num = num;
+                after_num = true;
+                param_index = param_index.saturating_add(1);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// `0.5` -> `.5`, `-0.5` -> `-.5`
+pub fn strip_leading_zero(num: &mut String) {
+    let sign = usize::from(num.starts_with('-'));
+    if num[sign..].starts_with("0.") {
+        num.remove(sign);
+    }
+}
+
+/// Tokenize path data, failing with the position of the first bad token
+fn parse_checked(d: &str) -> Result<Vec<Part<'_>>> {
+    let (rest, parts) = match parse_parts(d) {
+        Ok(v) => v,
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            let pos = d.len().saturating_sub(e.input.len());
+            return Err(anyhow::anyhow!(format_path_error(d, pos)));
+        }
+        Err(_) => return Err(anyhow::anyhow!("invalid path data")),
+    };
+    if !rest.is_empty() {
+        let pos = d.len().saturating_sub(rest.len());
+        return Err(anyhow::anyhow!(format_path_error(d, pos)));
+    }
+    Ok(parts)
+}
+
 fn format_path_error(input: &str, pos: usize) -> String {
     let start = pos.saturating_sub(10);
     let end = (pos + 10).min(input.len());
@@ -146,8 +352,7 @@ mod tests {
     fn arc_flags_should_not_be_scaled() -> Result<()> {
         let ctx = ScaleCtx {
             scale: 2.0,
-            precision: 4,
-            fix_stroke: false,
+            ..ScaleCtx::default()
         };
         let input = "M10 10 A 5 5 0 0 1 20 20";
         let out = scale_path(input, &ctx)?;
@@ -155,12 +360,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn path_points_are_absolute() -> Result<()> {
+        assert_eq!(
+            path_points("M10 10 h5 v5 c1 1 2 2 3 3 20 20 Z l1 1")?,
+            [
+                (10.0, 10.0),
+                (15.0, 10.0),
+                (15.0, 15.0),
+                (16.0, 16.0),
+                (17.0, 17.0),
+                (18.0, 18.0),
+                (11.0, 11.0)
+            ]
+        );
+        assert_eq!(path_points("m1 1 2 2")?, [(1.0, 1.0), (3.0, 3.0)]);
+        Ok(())
+    }
+
     #[test]
     fn large_path_scales_without_panic() -> Result<()> {
         let ctx = ScaleCtx {
             scale: 1.25,
-            precision: 4,
-            fix_stroke: false,
+            ..ScaleCtx::default()
         };
         let mut d = String::from("M0 0");
         for i in 1..1000 {
@@ -176,7 +398,7 @@ mod tests {
         let ctx = ScaleCtx {
             scale: 2.0,
             precision: 6,
-            fix_stroke: false,
+            ..ScaleCtx::default()
         };
         let input = "M-0.5e-2 1E2 L+.25 -3.5e1";
         let out = scale_path(input, &ctx)?;
@@ -188,8 +410,7 @@ mod tests {
     fn path_numbers_with_tight_packing() -> Result<()> {
         let ctx = ScaleCtx {
             scale: 2.0,
-            precision: 4,
-            fix_stroke: false,
+            ..ScaleCtx::default()
         };
         let input = "M10-20L.5-.25";
         let out = scale_path(input, &ctx)?;
@@ -201,8 +422,7 @@ mod tests {
     fn arc_flags_remain_unscaled_in_mixed_numbers() -> Result<()> {
         let ctx = ScaleCtx {
             scale: 3.0,
-            precision: 4,
-            fix_stroke: false,
+            ..ScaleCtx::default()
         };
         let input = "M0 0 A1.5e1 2.5 0 1 0 10 -20";
         let out = scale_path(input, &ctx)?;
@@ -212,11 +432,7 @@ mod tests {
 
     #[test]
     fn path_invalid_trailing_garbage_fails() {
-        let ctx = ScaleCtx {
-            scale: 1.0,
-            precision: 4,
-            fix_stroke: false,
-        };
+        let ctx = ScaleCtx::default();
         let err = scale_path("M10e", &ctx).unwrap_err();
         assert!(err.to_string().contains("invalid path data at char"));
         assert!(err.to_string().contains("invalid number"));
@@ -224,13 +440,41 @@ mod tests {
 
     #[test]
     fn path_invalid_command_fails() {
-        let ctx = ScaleCtx {
-            scale: 1.0,
-            precision: 4,
-            fix_stroke: false,
-        };
+        let ctx = ScaleCtx::default();
         let err = scale_path("X10 20", &ctx).unwrap_err();
         assert!(err.to_string().contains("invalid path data at char"));
         assert!(err.to_string().contains("invalid command"));
     }
+
+    #[test]
+    fn compact_path_drops_redundant_characters() -> Result<()> {
+        let ctx = ScaleCtx {
+            precision: 2,
+            ..ScaleCtx::default()
+        };
+        let input = "M 10.000, 20 L 0.5 -0.25 L 0.126 0.5 Z M 0 0 A 5 5 0 0 1 10 10";
+        let out = compact_path(input, &ctx)?;
+        assert_eq!(out, "M10 20L.5-.25.13.5ZM0 0A5 5 0 0 1 10 10");
+        assert!(compact_path("M10e", &ctx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn stretch_path_scales_axes_separately() -> Result<()> {
+        let ctx = ScaleCtx::default();
+        assert_eq!(
+            stretch_path("M10 10 H20 V30 l5,5 A 5 5 0 0 1 20 20z", 2.0, 0.5, &ctx)?,
+            "M20 5 H40 V15 l10,2.5 A 10 2.5 0 0 1 40 10z"
+        );
+        assert_eq!(
+            stretch_path("M0 0 A10 4 90 1 0 8 0", 1.0, 2.0, &ctx)?,
+            "M0 0 A20 4 90 1 0 8 0"
+        );
+        // A 45° circle of radius 1 stretched 2x upright stays upright
+        assert_eq!(
+            stretch_path("M0 0a1 1 45 0 0 1 1", 1.0, 2.0, &ctx)?,
+            "M0 0a2 1 90 0 0 1 2"
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,206 @@
+//! Minimal PDF writer for raster pages.
+//!
+//! Every page shows one image drawn over the whole page: the colour channels
+//! go into a Flate-compressed `DeviceRGB` image and the alpha channel into
+//! its soft mask, so transparent icons stay transparent in viewers. Pages are
+//! compressed when they are created, which keeps a long multi-page document
+//! cheap to hold in memory until it is written.
+
+use anyhow::{Context, Result};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// CSS pixels per inch, for converting page sizes to PDF points
+const PX_PER_INCH: f64 = 96.0;
+
+/// One page of a PDF document
+#[derive(Debug, Clone)]
+pub struct Page {
+    /// Page size in points (1/72 inch)
+    pub width_pt: f64,
+    pub height_pt: f64,
+    image_width: u32,
+    image_height: u32,
+    rgb: Vec<u8>,
+    alpha: Vec<u8>,
+}
+
+impl Page {
+    /// Build a page of `width_px` x `height_px` CSS pixels showing the
+    /// straight (not premultiplied) RGBA `image`, which may have a higher
+    /// resolution than the page
+    pub fn from_rgba(
+        rgba: &[u8],
+        image_width: u32,
+        image_height: u32,
+        width_px: f64,
+        height_px: f64,
+    ) -> Result<Self> {
+        let (rgb, alpha): (Vec<[u8; 3]>, Vec<u8>) = rgba
+            .chunks_exact(4)
+            .map(|p| ([p[0], p[1], p[2]], p[3]))
+            .unzip();
+        Ok(Page {
+            width_pt: width_px * 72.0 / PX_PER_INCH,
+            height_pt: height_px * 72.0 / PX_PER_INCH,
+            image_width,
+            image_height,
+            rgb: deflate(rgb.as_flattened())?,
+            alpha: deflate(&alpha)?,
+        })
+    }
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).context("compress pdf image")?;
+    encoder.finish().context("compress pdf image")
+}
+
+/// Object writer that remembers where each object starts for the xref table
+struct Writer {
+    out: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl Writer {
+    fn object(&mut self, id: usize, body: &str) {
+        self.begin(id);
+        self.out.extend_from_slice(body.as_bytes());
+        self.out.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        self.begin(id);
+        self.out.extend_from_slice(
+            format!("<< {} /Length {} >>\nstream\n", dict, data.len()).as_bytes(),
+        );
+        self.out.extend_from_slice(data);
+        self.out.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn begin(&mut self, id: usize) {
+        self.offsets[id - 1] = self.out.len();
+        self.out
+            .extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+    }
+}
+
+/// Serialize `pages` into one PDF document, in order
+pub fn write_pdf(pages: &[Page]) -> Vec<u8> {
+    // 1: catalog, 2: page tree, then page, contents, image and mask per page
+    let object_count = 2 + pages.len() * 4;
+    let mut w = Writer {
+        out: b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec(),
+        offsets: vec![0; object_count],
+    };
+    let page_id = |i: usize| 3 + i * 4;
+
+    w.object(1, "<< /Type /Catalog /Pages 2 0 R >>");
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", page_id(i)))
+        .collect();
+    w.object(
+        2,
+        &format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+    );
+    for (i, page) in pages.iter().enumerate() {
+        let id = page_id(i);
+        let (width, height) = (fmt_num(page.width_pt), fmt_num(page.height_pt));
+        w.object(
+            id,
+            &format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                width,
+                height,
+                id + 2,
+                id + 1
+            ),
+        );
+        let contents = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q", width, height);
+        w.stream(id + 1, "", contents.as_bytes());
+        let image = format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /BitsPerComponent 8",
+            page.image_width, page.image_height
+        );
+        w.stream(
+            id + 2,
+            &format!(
+                "{} /ColorSpace /DeviceRGB /SMask {} 0 R /Filter /FlateDecode",
+                image,
+                id + 3
+            ),
+            &page.rgb,
+        );
+        w.stream(
+            id + 3,
+            &format!("{} /ColorSpace /DeviceGray /Filter /FlateDecode", image),
+            &page.alpha,
+        );
+    }
+
+    let xref = w.out.len();
+    let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", object_count + 1);
+    for offset in &w.offsets {
+        table.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    table.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        object_count + 1,
+        xref
+    ));
+    w.out.extend_from_slice(table.as_bytes());
+    w.out
+}
+
+fn fmt_num(v: f64) -> String {
+    let s = format!("{:.3}", v);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack
+            .windows(needle.len())
+            .filter(|w| *w == needle)
+            .count()
+    }
+
+    #[test]
+    fn writes_one_page_per_image_at_the_page_size() -> Result<()> {
+        let red = [255, 0, 0, 255].repeat(4);
+        let pages = vec![
+            Page::from_rgba(&red, 2, 2, 16.0, 16.0)?,
+            Page::from_rgba(&red, 2, 2, 96.0, 48.0)?,
+        ];
+        let pdf = write_pdf(&pages);
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert_eq!(count(&pdf, b"/Type /Page "), 2);
+        assert_eq!(count(&pdf, b"/Count 2"), 1);
+        assert_eq!(count(&pdf, b"/MediaBox [0 0 12 12]"), 1);
+        assert_eq!(count(&pdf, b"/MediaBox [0 0 72 36]"), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn xref_points_at_each_object() -> Result<()> {
+        let pdf = write_pdf(&[Page::from_rgba(&[0; 4], 1, 1, 1.0, 1.0)?]);
+        let xref = pdf.windows(5).rposition(|w| w == b"xref\n").unwrap();
+        let table = std::str::from_utf8(&pdf[xref..])?;
+        for (i, line) in table.lines().skip(3).take(6).enumerate() {
+            let offset: usize = line[..10].parse()?;
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Median-cut palette quantization for indexed-color PNG output.
+//!
+//! Colors are counted first, so an icon with few distinct colors keeps them
+//! exactly and only busier images (anti-aliased edges, gradients) are reduced.
+//! Fully transparent pixels all share one palette entry.
+
+use std::collections::HashMap;
+
+/// An image reduced to a palette
+#[derive(Debug, Clone, PartialEq)]
+pub struct Indexed {
+    /// Straight RGBA palette, entries with transparency first
+    pub palette: Vec<[u8; 4]>,
+    /// One palette index per pixel, row by row
+    pub indices: Vec<u8>,
+}
+
+type Bucket = Vec<([u8; 4], u64)>;
+
+/// Reduce straight RGBA pixels to at most `max_colors` (2 to 256) palette
+/// entries
+pub fn quantize(rgba: &[u8], max_colors: usize) -> Indexed {
+    let pixels: Vec<[u8; 4]> = rgba
+        .chunks_exact(4)
+        .map(|p| {
+            if p[3] == 0 {
+                [0; 4]
+            } else {
+                [p[0], p[1], p[2], p[3]]
+            }
+        })
+        .collect();
+    let mut counts: HashMap<[u8; 4], u64> = HashMap::new();
+    for p in &pixels {
+        *counts.entry(*p).or_default() += 1;
+    }
+    let mut colors: Bucket = counts.into_iter().collect();
+    colors.sort_unstable();
+
+    let mut buckets = vec![colors];
+    while buckets.len() < max_colors.clamp(2, 256) {
+        // Split the bucket with the widest channel, weighted by its pixel count
+        let widest = buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range * pixel_count(b))
+            })
+            .max_by_key(|&(_, _, weight)| weight);
+        let Some((i, channel, _)) = widest else {
+            break;
+        };
+        let mut bucket = buckets.swap_remove(i);
+        bucket.sort_unstable_by_key(|(c, _)| c[channel]);
+        let half = pixel_count(&bucket).div_ceil(2);
+        let mut seen = 0;
+        let split = bucket
+            .iter()
+            .position(|(_, n)| {
+                seen += n;
+                seen >= half
+            })
+            .map_or(1, |k| (k + 1).clamp(1, bucket.len() - 1));
+        let rest = bucket.split_off(split);
+        buckets.push(bucket);
+        buckets.push(rest);
+    }
+
+    let mut entries: Vec<([u8; 4], Bucket)> = buckets.into_iter().map(|b| (mean(&b), b)).collect();
+    // tRNS only has to list entries up to the last non-opaque one
+    entries.sort_by_key(|(c, _)| c[3] == 255);
+    let mut lookup = HashMap::new();
+    for (index, (_, bucket)) in entries.iter().enumerate() {
+        for (color, _) in bucket {
+            lookup.insert(*color, index as u8);
+        }
+    }
+    Indexed {
+        palette: entries.into_iter().map(|(c, _)| c).collect(),
+        indices: pixels.iter().map(|p| lookup[p]).collect(),
+    }
+}
+
+fn pixel_count(bucket: &Bucket) -> u64 {
+    bucket.iter().map(|(_, n)| n).sum()
+}
+
+/// The channel with the largest value range in `bucket`, and that range
+fn widest_channel(bucket: &Bucket) -> (usize, u64) {
+    (0..4)
+        .map(|channel| {
+            let values = bucket.iter().map(|(c, _)| c[channel]);
+            let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+            (channel, range as u64)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+/// Pixel-count weighted average of the colors in `bucket`
+fn mean(bucket: &Bucket) -> [u8; 4] {
+    let total = pixel_count(bucket).max(1);
+    let mut sums = [0u64; 4];
+    for (color, n) in bucket {
+        for (sum, v) in sums.iter_mut().zip(color) {
+            *sum += *v as u64 * n;
+        }
+    }
+    sums.map(|s| ((s + total / 2) / total) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_few_colors_exactly() {
+        let rgba = [
+            [255, 0, 0, 255],
+            [0, 0, 0, 0],
+            [255, 0, 0, 255],
+            [9, 9, 9, 0],
+        ]
+        .concat();
+        let image = quantize(&rgba, 16);
+        assert_eq!(image.palette, vec![[0, 0, 0, 0], [255, 0, 0, 255]]);
+        assert_eq!(image.indices, vec![1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn limits_the_palette_size() {
+        let rgba: Vec<u8> = (0..=255u8).flat_map(|v| [v, 255 - v, 0, 255]).collect();
+        let image = quantize(&rgba, 4);
+        assert_eq!(image.palette.len(), 4);
+        assert_eq!(image.indices.len(), 256);
+        // Neighbouring shades land in the same entry
+        assert_eq!(image.indices[0], image.indices[1]);
+        assert_ne!(image.indices[0], image.indices[255]);
+    }
+}
@@ -0,0 +1,513 @@
+use anyhow::{bail, Context, Result};
+use resvg::{tiny_skia, usvg};
+use std::path::Path;
+
+/// Rasterize SVG text to a PNG file of exactly `width` x `height` pixels
+pub fn render_svg_to_png(svg_data: &str, width: u32, height: u32, out_path: &Path) -> Result<()> {
+    let pixmap = render_svg_to_pixmap(svg_data, width, height)?;
+    pixmap.save_png(out_path).context("write png output")?;
+    Ok(())
+}
+
+/// Rasterize SVG text to a lossless WebP file of exactly `width` x `height` pixels
+pub fn render_svg_to_webp(svg_data: &str, width: u32, height: u32, out_path: &Path) -> Result<()> {
+    crate::webp::check_dimensions(width, height)?;
+    let pixmap = render_svg_to_pixmap(svg_data, width, height)?;
+    write_webp(&pixmap, out_path)
+}
+
+/// Encode a rendered pixmap as lossless WebP
+pub fn write_webp(pixmap: &tiny_skia::Pixmap, out_path: &Path) -> Result<()> {
+    let rgba = straight_rgba(pixmap);
+    let data = crate::webp::encode_lossless(&rgba, pixmap.width(), pixmap.height())?;
+    std::fs::write(out_path, data).context("write webp output")?;
+    Ok(())
+}
+
+/// Dump a rendered pixmap as raw pixels (see [`crate::bitmap::encode_raw`])
+pub fn write_raw(
+    pixmap: &tiny_skia::Pixmap,
+    order: crate::bitmap::PixelOrder,
+    header: bool,
+    out_path: &Path,
+) -> Result<()> {
+    let data = crate::bitmap::encode_raw(
+        &straight_rgba(pixmap),
+        pixmap.width(),
+        pixmap.height(),
+        order,
+        header,
+    );
+    std::fs::write(out_path, data).context("write raw output")?;
+    Ok(())
+}
+
+/// Encode a rendered pixmap as a 32-bit BMP
+pub fn write_bmp(pixmap: &tiny_skia::Pixmap, out_path: &Path) -> Result<()> {
+    let data = crate::bitmap::encode_bmp(&straight_rgba(pixmap), pixmap.width(), pixmap.height());
+    std::fs::write(out_path, data).context("write bmp output")?;
+    Ok(())
+}
+
+/// Write a rendered pixmap as C source: `out_path` gets the `.c` file and
+/// the same path with an `.h` extension the header. `order` selects a plain
+/// byte array; `None` writes an LVGL image descriptor.
+pub fn write_c_source(
+    pixmap: &tiny_skia::Pixmap,
+    order: Option<crate::bitmap::PixelOrder>,
+    out_path: &Path,
+) -> Result<()> {
+    let stem = out_path
+        .file_stem()
+        .context("c source output needs a file name")?
+        .to_string_lossy();
+    let name = crate::bitmap::c_identifier(&stem);
+    let header_path = out_path.with_extension("h");
+    let header_file = header_path
+        .file_name()
+        .expect("has a file stem")
+        .to_string_lossy();
+    let rgba = straight_rgba(pixmap);
+    let (width, height) = (pixmap.width(), pixmap.height());
+    let code = match order {
+        Some(order) => {
+            crate::bitmap::encode_c_array(&rgba, width, height, order, &name, &header_file)
+        }
+        None => crate::bitmap::encode_lvgl(&rgba, width, height, &name, &header_file),
+    };
+    std::fs::write(&header_path, code.header).context("write c header output")?;
+    std::fs::write(out_path, code.source).context("write c source output")?;
+    Ok(())
+}
+
+/// Encode a rendered pixmap as an indexed-color PNG with at most
+/// `max_colors` palette entries, packed to 1, 2 or 4 bits per pixel when the
+/// palette is small enough
+pub fn write_indexed_png(
+    pixmap: &tiny_skia::Pixmap,
+    max_colors: usize,
+    out_path: &Path,
+) -> Result<()> {
+    let image = crate::quantize::quantize(&straight_rgba(pixmap), max_colors);
+    let (depth, bits) = match image.palette.len() {
+        0..=2 => (png::BitDepth::One, 1),
+        3..=4 => (png::BitDepth::Two, 2),
+        5..=16 => (png::BitDepth::Four, 4),
+        _ => (png::BitDepth::Eight, 8),
+    };
+    let width = pixmap.width() as usize;
+    let stride = (width * bits).div_ceil(8);
+    let mut data = vec![0u8; stride * pixmap.height() as usize];
+    for (row, indices) in data
+        .chunks_exact_mut(stride)
+        .zip(image.indices.chunks(width))
+    {
+        for (x, &index) in indices.iter().enumerate() {
+            let bit = x * bits;
+            row[bit / 8] |= index << (8 - bits - bit % 8);
+        }
+    }
+
+    let file = std::fs::File::create(out_path).context("write png output")?;
+    let mut encoder = png::Encoder::new(
+        std::io::BufWriter::new(file),
+        pixmap.width(),
+        pixmap.height(),
+    );
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_compression(png::Compression::Best);
+    encoder.set_palette(
+        image
+            .palette
+            .iter()
+            .flat_map(|c| [c[0], c[1], c[2]])
+            .collect::<Vec<u8>>(),
+    );
+    let alpha: Vec<u8> = image
+        .palette
+        .iter()
+        .map(|c| c[3])
+        .take_while(|&a| a < 255)
+        .collect();
+    if !alpha.is_empty() {
+        encoder.set_trns(alpha);
+    }
+    let mut writer = encoder.write_header().context("write png output")?;
+    writer.write_image_data(&data).context("write png output")?;
+    writer.finish().context("write png output")?;
+    Ok(())
+}
+
+/// Encode a rendered pixmap as an RGB PNG without an alpha channel,
+/// composited over `background`
+pub fn encode_opaque_png(pixmap: &tiny_skia::Pixmap, background: [u8; 3]) -> Result<Vec<u8>> {
+    // Premultiplied channels composite as c + bg * (1 - a)
+    let rgb: Vec<u8> = pixmap
+        .data()
+        .chunks_exact(4)
+        .flat_map(|px| {
+            let under = 255 - px[3] as u32;
+            [0, 1, 2].map(|i| (px[i] as u32 + (background[i] as u32 * under + 127) / 255) as u8)
+        })
+        .collect();
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, pixmap.width(), pixmap.height());
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().context("encode png")?;
+    writer.write_image_data(&rgb).context("encode png")?;
+    writer.finish().context("encode png")?;
+    Ok(out)
+}
+
+/// Longest image side a PDF page is rendered at
+const PDF_MAX_IMAGE_SIDE: u32 = 2048;
+
+/// Rasterize SVG text to a PDF page of `width` x `height` pixels.
+///
+/// The image is rendered at up to 4x the page size (capped at
+/// [`PDF_MAX_IMAGE_SIDE`]) so small icons stay sharp when a reviewer zooms in.
+pub fn render_svg_to_pdf_page(svg_data: &str, width: u32, height: u32) -> Result<crate::pdf::Page> {
+    let oversample = (PDF_MAX_IMAGE_SIDE / width.max(height).max(1)).clamp(1, 4);
+    let pixmap = render_svg_to_pixmap(svg_data, width * oversample, height * oversample)?;
+    crate::pdf::Page::from_rgba(
+        &straight_rgba(&pixmap),
+        pixmap.width(),
+        pixmap.height(),
+        width as f64,
+        height as f64,
+    )
+}
+
+/// Pixmap pixels as straight (not premultiplied) RGBA bytes
+fn straight_rgba(pixmap: &tiny_skia::Pixmap) -> Vec<u8> {
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect()
+}
+
+/// Font families the document's text asks for that the renderer cannot
+/// provide, in first-use order. Empty when the document has no visible text.
+///
+/// Families come from `font-family` attributes, `style` properties and
+/// `<style>` rules; text without any falls back to the renderer's default
+/// `serif`. A missing family means [`render_svg_to_pixmap`] would draw
+/// fallback glyphs, or nothing at all when no fonts are loaded.
+pub fn unavailable_font_families(doc: &roxmltree::Document) -> Vec<String> {
+    let has_text = doc.descendants().any(|n| {
+        n.has_tag_name("text")
+            && n.descendants()
+                .any(|t| t.is_text() && !t.text().unwrap_or("").trim().is_empty())
+    });
+    if !has_text {
+        return Vec::new();
+    }
+
+    let mut lists = Vec::new();
+    for node in doc.descendants().filter(|n| n.is_element()) {
+        if let Some(v) = node.attribute("font-family") {
+            lists.push(v.to_string());
+        }
+        let style = node.attribute("style").unwrap_or("");
+        let css = if node.has_tag_name("style") {
+            node.text().unwrap_or("")
+        } else {
+            ""
+        };
+        for decl in [style]
+            .into_iter()
+            .chain(css.split(['{', '}']).skip(1).step_by(2))
+        {
+            lists.extend(
+                crate::svg::parse_style(decl)
+                    .into_iter()
+                    .filter(|(k, _)| k == "font-family")
+                    .map(|(_, v)| v),
+            );
+        }
+    }
+    if lists.is_empty() {
+        lists.push("serif".to_string());
+    }
+
+    let fontdb = usvg::Options::default().fontdb;
+    let mut missing: Vec<String> = Vec::new();
+    for list in &lists {
+        for name in list.split(',') {
+            let name = name.trim().trim_matches(['"', '\'']).trim();
+            let family = match name {
+                "" => continue,
+                "serif" => usvg::fontdb::Family::Serif,
+                "sans-serif" => usvg::fontdb::Family::SansSerif,
+                "monospace" => usvg::fontdb::Family::Monospace,
+                "cursive" => usvg::fontdb::Family::Cursive,
+                "fantasy" => usvg::fontdb::Family::Fantasy,
+                _ => usvg::fontdb::Family::Name(name),
+            };
+            let query = usvg::fontdb::Query {
+                families: &[family],
+                ..Default::default()
+            };
+            if fontdb.query(&query).is_none() && !missing.iter().any(|m| m == name) {
+                missing.push(name.to_string());
+            }
+        }
+    }
+    missing
+}
+
+/// Largest pixmap rendered in one piece; bigger PNGs are rendered in strips
+/// of at most this many pixels by [`render_svg_to_png_tiled`]
+pub const MAX_PIXMAP_PIXELS: u64 = 1 << 24;
+
+/// Parse SVG text and compute the transform that maps it onto `width` x
+/// `height` pixels
+fn render_tree(svg_data: &str, width: u32, height: u32) -> Result<(usvg::Tree, usvg::Transform)> {
+    let opt = usvg::Options::default();
+    let tree = usvg::Tree::from_str(svg_data, &opt).context("parse svg for rendering")?;
+
+    let size = tree.size();
+    if size.width() <= 0.0 || size.height() <= 0.0 {
+        bail!("svg has zero size");
+    }
+
+    let sx = width as f32 / size.width();
+    let sy = height as f32 / size.height();
+    Ok((tree, usvg::Transform::from_scale(sx, sy)))
+}
+
+/// Rasterize SVG text to a PNG file of `width` x `height` pixels in
+/// horizontal strips of at most [`MAX_PIXMAP_PIXELS`], encoding each strip
+/// as soon as it is drawn, so poster-sized outputs need bounded memory. The
+/// pixels match [`render_svg_to_png`] up to anti-aliasing rounding at edges.
+pub fn render_svg_to_png_tiled(
+    svg_data: &str,
+    width: u32,
+    height: u32,
+    out_path: &Path,
+) -> Result<()> {
+    let rows = (MAX_PIXMAP_PIXELS / width.max(1) as u64).clamp(1, height.max(1) as u64) as u32;
+    render_png_strips(svg_data, width, height, rows, out_path)
+}
+
+fn render_png_strips(
+    svg_data: &str,
+    width: u32,
+    height: u32,
+    rows: u32,
+    out_path: &Path,
+) -> Result<()> {
+    let (tree, transform) = render_tree(svg_data, width, height)?;
+    let file = std::fs::File::create(out_path).context("write png output")?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().context("write png output")?;
+    let mut stream = writer.stream_writer().context("write png output")?;
+
+    let mut strip = tiny_skia::Pixmap::new(width, rows).context("create strip pixmap")?;
+    let mut line = Vec::with_capacity(width as usize * 4);
+    for top in (0..height).step_by(rows as usize) {
+        strip.fill(tiny_skia::Color::TRANSPARENT);
+        let shifted = transform.post_translate(0.0, -(top as f32));
+        resvg::render(&tree, shifted, &mut strip.as_mut());
+        let drawn = (rows.min(height - top) * width) as usize;
+        for row in strip.pixels()[..drawn].chunks_exact(width as usize) {
+            line.clear();
+            line.extend(row.iter().flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            }));
+            std::io::Write::write_all(&mut stream, &line).context("write png output")?;
+        }
+    }
+    stream.finish().context("write png output")?;
+    Ok(())
+}
+
+/// Rasterize SVG text to an in-memory pixmap of exactly `width` x `height` pixels
+pub fn render_svg_to_pixmap(svg_data: &str, width: u32, height: u32) -> Result<tiny_skia::Pixmap> {
+    let (tree, transform) = render_tree(svg_data, width, height)?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).context("create target pixmap")?;
+
+    let mut pixmap_mut = pixmap.as_mut();
+    resvg::render(&tree, transform, &mut pixmap_mut);
+
+    Ok(pixmap)
+}
+
+/// Rasterize SVG text into a `size` x `size` pixmap, fitted and centered
+/// so a non-square drawing keeps its proportions
+pub fn render_svg_to_square_pixmap(svg_data: &str, size: u32) -> Result<tiny_skia::Pixmap> {
+    let (tree, _) = render_tree(svg_data, size, size)?;
+    let tree_size = tree.size();
+    let scale = size as f32 / tree_size.width().max(tree_size.height());
+    let dx = (size as f32 - tree_size.width() * scale) / 2.0;
+    let dy = (size as f32 - tree_size.height() * scale) / 2.0;
+
+    let mut pixmap = tiny_skia::Pixmap::new(size, size).context("create target pixmap")?;
+    let transform = usvg::Transform::from_row(scale, 0.0, 0.0, scale, dx, dy);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_png_path() -> std::path::PathBuf {
+        let mut path = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        path.push(format!("svg-scale-test-{}.png", nanos));
+        path
+    }
+
+    fn read_png_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+        const PNG_SIG: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+        if data.len() < 33 || data[0..8] != PNG_SIG {
+            bail!("invalid png signature");
+        }
+
+        let chunk_type = &data[12..16];
+        if chunk_type != b"IHDR" {
+            bail!("missing IHDR chunk");
+        }
+
+        let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
+        let height = u32::from_be_bytes([data[20], data[21], data[22], data[23]]);
+        Ok((width, height))
+    }
+
+    #[test]
+    fn render_png_writes_expected_dimensions() -> Result<()> {
+        let svg = r#"<svg width="10" height="20" xmlns="http://www.w3.org/2000/svg">
+  <rect x="0" y="0" width="10" height="20" fill="red"/>
+</svg>"#;
+        let out_path = tmp_png_path();
+        render_svg_to_png(svg, 30, 60, &out_path)?;
+
+        let data = fs::read(&out_path)?;
+        let (w, h) = read_png_dimensions(&data)?;
+        fs::remove_file(&out_path)?;
+
+        assert_eq!((w, h), (30, 60));
+        Ok(())
+    }
+
+    #[test]
+    fn indexed_png_uses_a_packed_palette() -> Result<()> {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="8">
+<rect width="4" height="8" fill="red"/>
+</svg>"#;
+        let pixmap = render_svg_to_pixmap(svg, 8, 8)?;
+        let out = tmp_png_path();
+        write_indexed_png(&pixmap, 16, &out)?;
+        let data = fs::read(&out)?;
+        let _ = fs::remove_file(&out);
+        assert_eq!(read_png_dimensions(&data)?, (8, 8));
+        // IHDR bit depth and color type: 1-bit indexed for red + transparent
+        assert_eq!((data[24], data[25]), (1, 3));
+        assert!(data.windows(4).any(|w| w == b"tRNS"));
+        Ok(())
+    }
+
+    #[test]
+    fn lists_font_families_text_cannot_render_with() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+<style>.t { font-family: 'Inter', sans-serif }</style>
+<text class="t" font-family="Roboto, Inter">Hi</text>
+<text style="font-family:monospace"> </text>
+</svg>"#,
+        )?;
+        assert_eq!(
+            unavailable_font_families(&doc),
+            vec!["Inter", "sans-serif", "Roboto", "monospace"]
+        );
+        let no_text = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" font-family="Inter"><rect/></svg>"#,
+        )?;
+        assert!(unavailable_font_families(&no_text).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn strips_match_a_single_rendering() -> Result<()> {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+<circle cx="5" cy="5" r="4" fill="red" fill-opacity="0.5"/>
+</svg>"#;
+        let (whole, striped) = (tmp_png_path(), tmp_png_path());
+        render_svg_to_png(svg, 37, 23, &whole)?;
+        render_png_strips(svg, 37, 23, 5, &striped)?;
+        let decode = |path: &Path| -> Result<Vec<u8>> {
+            let mut reader = png::Decoder::new(fs::File::open(path)?).read_info()?;
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut buf)?;
+            buf.truncate(info.buffer_size());
+            Ok(buf)
+        };
+        let (a, b) = (decode(&whole)?, decode(&striped)?);
+        let _ = (fs::remove_file(&whole), fs::remove_file(&striped));
+        assert_eq!(a.len(), 37 * 23 * 4);
+        // Edge coverage may round differently once shifted into a strip,
+        // by at most one supersample; everything else is identical
+        let same = a
+            .chunks_exact(4)
+            .zip(b.chunks_exact(4))
+            .filter(|(x, y)| x == y)
+            .count();
+        assert!(same * 20 >= 37 * 23 * 19, "only {} pixels match", same);
+        let premultiplied = |p: &[u8]| {
+            p[..3]
+                .iter()
+                .map(|&c| c as u32 * p[3] as u32 / 255)
+                .collect::<Vec<_>>()
+        };
+        for (pa, pb) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+            assert!(pa[3].abs_diff(pb[3]) <= 32, "{:?} vs {:?}", pa, pb);
+            for (ca, cb) in premultiplied(pa).into_iter().zip(premultiplied(pb)) {
+                assert!(ca.abs_diff(cb) <= 32, "{:?} vs {:?}", pa, pb);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn square_rendering_centers_a_wide_drawing() -> Result<()> {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+<rect width="20" height="10" fill="red"/>
+</svg>"#;
+        let pixmap = render_svg_to_square_pixmap(svg, 16)?;
+        assert_eq!((pixmap.width(), pixmap.height()), (16, 16));
+        let alpha = |x: u32, y: u32| pixmap.pixel(x, y).map_or(0, |p| p.alpha());
+        assert_eq!((alpha(8, 0), alpha(8, 15)), (0, 0));
+        assert_eq!((alpha(0, 8), alpha(15, 8)), (255, 255));
+        Ok(())
+    }
+
+    #[test]
+    fn opaque_png_drops_alpha_over_the_background() -> Result<()> {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="2" height="1">
+<rect width="1" height="1" fill="#000" fill-opacity="0.5"/>
+</svg>"##;
+        let png = encode_opaque_png(&render_svg_to_pixmap(svg, 2, 1)?, [255, 255, 255])?;
+        let mut reader = png::Decoder::new(png.as_slice()).read_info()?;
+        let mut rgb = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgb)?;
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(rgb[..6], [127, 127, 127, 255, 255, 255]);
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+//! Round-trip consistency check: scale by S, then by 1/S, and compare with the source.
+//!
+//! Attributes that come back different reveal where scaling is lossy at the
+//! chosen precision (rounding drift, transform lists collapsed into matrices,
+//! dropped values), which matters for in-place workflows that rescale the
+//! same file repeatedly.
+
+use anyhow::Result;
+use std::fmt;
+
+use crate::scale::ScaleCtx;
+use crate::svg::WalkOptions;
+
+/// How an attribute failed to survive the round trip
+#[derive(Debug, Clone, PartialEq)]
+pub enum Loss {
+    /// Numbers moved by more than the precision allows; holds the largest drift
+    Drift(f64),
+    /// The non-numeric structure changed, e.g. a transform list became a matrix
+    Structure,
+    /// The attribute disappeared
+    Removed,
+    /// The attribute was not in the source
+    Added,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundTripIssue {
+    /// 1-based line of the element in the source
+    pub line: u32,
+    pub element: String,
+    pub attribute: String,
+    pub original: String,
+    pub round_trip: String,
+    pub loss: Loss,
+}
+
+impl fmt::Display for RoundTripIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {} <{}> {}: ",
+            self.line, self.element, self.attribute
+        )?;
+        match &self.loss {
+            Loss::Drift(d) => write!(
+                f,
+                "drifts by {} (\"{}\" -> \"{}\")",
+                d, self.original, self.round_trip
+            ),
+            Loss::Structure => write!(
+                f,
+                "changes form (\"{}\" -> \"{}\")",
+                self.original, self.round_trip
+            ),
+            Loss::Removed => write!(f, "removed (was \"{}\")", self.original),
+            Loss::Added => write!(f, "added (\"{}\")", self.round_trip),
+        }
+    }
+}
+
+/// Scale `doc` by `ctx.scale` and back with the same options, then list the
+/// attributes whose values differ from the source by more than one unit in
+/// the last kept decimal place
+pub fn check_round_trip(
+    doc: &roxmltree::Document,
+    ctx: &ScaleCtx,
+    opts: &WalkOptions,
+) -> Result<Vec<RoundTripIssue>> {
+    let forward = crate::write_svg_with_options(doc, ctx, opts)?;
+    let forward_doc = roxmltree::Document::parse(&forward)?;
+    let back_ctx = ScaleCtx {
+        scale: 1.0 / ctx.scale,
+        ..*ctx
+    };
+    let back = crate::write_svg_with_options(&forward_doc, &back_ctx, opts)?;
+    let back_doc = roxmltree::Document::parse(&back)?;
+
+    let tolerance = 10f64.powi(-(ctx.precision as i32)) * (1.0 + 1e-9);
+    let mut issues = Vec::new();
+    let originals = doc.root_element().descendants().filter(|n| n.is_element());
+    let round_trips = back_doc
+        .root_element()
+        .descendants()
+        .filter(|n| n.is_element());
+    for (orig, back) in originals.zip(round_trips) {
+        let line = doc.text_pos_at(orig.range().start).row;
+        let mut report = |attribute: &str, original: &str, round_trip: &str, loss| {
+            issues.push(RoundTripIssue {
+                line,
+                element: orig.tag_name().name().to_string(),
+                attribute: attribute.to_string(),
+                original: original.to_string(),
+                round_trip: round_trip.to_string(),
+                loss,
+            })
+        };
+        let find = |node: roxmltree::Node<'_, '_>, attr: &roxmltree::Attribute| {
+            node.attributes()
+                .find(|a| a.namespace() == attr.namespace() && a.name() == attr.name())
+                .map(|a| a.value().to_string())
+        };
+        for attr in orig.attributes() {
+            match find(back, &attr) {
+                Some(value) => {
+                    if let Some(loss) = compare_values(attr.value(), &value, tolerance) {
+                        report(attr.name(), attr.value(), &value, loss);
+                    }
+                }
+                None => report(attr.name(), attr.value(), "", Loss::Removed),
+            }
+        }
+        for attr in back.attributes() {
+            if find(orig, &attr).is_none() {
+                report(attr.name(), "", attr.value(), Loss::Added);
+            }
+        }
+    }
+    Ok(issues)
+}
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Num(f64),
+    Text(&'a str),
+}
+
+/// Split a value into numbers and the text between them, ignoring
+/// whitespace and commas so that only meaningful changes are reported
+fn tokenize(value: &str) -> Vec<Token<'_>> {
+    let bytes = value.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut text_start = None;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let starts_number = c.is_ascii_digit()
+            || (matches!(c, b'-' | b'+' | b'.')
+                && bytes
+                    .get(i + 1)
+                    .is_some_and(|n| n.is_ascii_digit() || (*n == b'.' && c != b'.')));
+        // Digits inside identifiers such as `url(#a1)` belong to the text
+        if starts_number && text_start.is_none_or(|s| !is_ident_tail(&bytes[s..i])) {
+            let end = number_end(bytes, i);
+            if let Ok(n) = value[i..end].parse() {
+                if let Some(s) = text_start.take() {
+                    tokens.push(Token::Text(&value[s..i]));
+                }
+                tokens.push(Token::Num(n));
+                i = end;
+                continue;
+            }
+        }
+        if c.is_ascii_whitespace() || c == b',' {
+            if let Some(s) = text_start.take() {
+                tokens.push(Token::Text(&value[s..i]));
+            }
+        } else if text_start.is_none() {
+            text_start = Some(i);
+        }
+        i += 1;
+    }
+    if let Some(s) = text_start {
+        tokens.push(Token::Text(&value[s..]));
+    }
+    tokens
+}
+
+fn is_ident_tail(text: &[u8]) -> bool {
+    text.last()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_' || *c == b'#')
+        && !text.iter().all(|c| c.is_ascii_alphabetic())
+}
+
+fn number_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start;
+    if matches!(bytes[i], b'-' | b'+') {
+        i += 1;
+    }
+    let mut seen_dot = false;
+    while i < bytes.len() && (bytes[i].is_ascii_digit() || (bytes[i] == b'.' && !seen_dot)) {
+        seen_dot |= bytes[i] == b'.';
+        i += 1;
+    }
+    if i < bytes.len() && matches!(bytes[i], b'e' | b'E') {
+        let mut j = i + 1;
+        if j < bytes.len() && matches!(bytes[j], b'-' | b'+') {
+            j += 1;
+        }
+        if j < bytes.len() && bytes[j].is_ascii_digit() {
+            while j < bytes.len() && bytes[j].is_ascii_digit() {
+                j += 1;
+            }
+            i = j;
+        }
+    }
+    i
+}
+
+fn compare_values(original: &str, round_trip: &str, tolerance: f64) -> Option<Loss> {
+    if original == round_trip {
+        return None;
+    }
+    let (a, b) = (tokenize(original), tokenize(round_trip));
+    if a.len() != b.len() {
+        return Some(Loss::Structure);
+    }
+    let mut drift: f64 = 0.0;
+    for pair in a.iter().zip(&b) {
+        match pair {
+            (Token::Num(x), Token::Num(y)) => drift = drift.max((x - y).abs()),
+            (Token::Text(x), Token::Text(y)) if x == y => {}
+            _ => return Some(Loss::Structure),
+        }
+    }
+    (drift > tolerance).then_some(Loss::Drift(drift))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str, scale: f64, precision: usize) -> Result<Vec<RoundTripIssue>> {
+        let doc = roxmltree::Document::parse(input)?;
+        let ctx = ScaleCtx {
+            scale,
+            precision,
+            ..ScaleCtx::default()
+        };
+        check_round_trip(&doc, &ctx, &WalkOptions::default())
+    }
+
+    #[test]
+    fn tokens_split_numbers_from_text() {
+        assert_eq!(
+            tokenize("translate(2,-3.5) url(#a1)"),
+            vec![
+                Token::Text("translate("),
+                Token::Num(2.0),
+                Token::Num(-3.5),
+                Token::Text(")"),
+                Token::Text("url(#a1)"),
+            ]
+        );
+        assert_eq!(
+            tokenize("M1e2-.5L3 4"),
+            vec![
+                Token::Text("M"),
+                Token::Num(100.0),
+                Token::Num(-0.5),
+                Token::Text("L"),
+                Token::Num(3.0),
+                Token::Num(4.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn lossless_round_trip_has_no_issues() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24"><path d="M1 2L3.5 4" stroke-width="2" transform="translate(1 2)"/></svg>"#;
+        let issues = check(input, 2.0, 4)?;
+        assert!(issues.is_empty(), "got: {issues:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn lossy_rounding_is_flagged_with_line() -> Result<()> {
+        let input = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"24\" height=\"24\">\n<rect x=\"1.23\" width=\"10\"/></svg>";
+        let issues = check(input, 0.1, 2)?;
+        let rect: Vec<_> = issues.iter().filter(|i| i.element == "rect").collect();
+        assert_eq!(rect.len(), 1, "got: {issues:?}");
+        assert_eq!(rect[0].line, 2);
+        assert_eq!(rect[0].attribute, "x");
+        assert!(matches!(rect[0].loss, Loss::Drift(_)), "got: {issues:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn collapsed_transform_is_a_structural_change() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24"><g transform="rotate(30)"><path d="M1 1L2 2"/></g></svg>"#;
+        let issues = check(input, 2.0, 4)?;
+        assert!(
+            issues
+                .iter()
+                .any(|i| i.attribute == "transform" && i.loss == Loss::Structure),
+            "got: {issues:?}"
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,429 @@
+use anyhow::{bail, Result};
+use nom::{
+    branch::alt,
+    character::complete::{char, one_of, space0},
+    combinator::{all_consuming, map, opt},
+    multi::many0,
+    number::complete::double,
+    sequence::{delimited, pair, preceded, terminated},
+    IResult,
+};
+
 pub struct ScaleCtx {
     pub scale: f64,
     pub precision: usize,
     pub fix_stroke: bool,
+    /// Multiply in decimal instead of binary floating point (`--exact`)
+    pub exact: bool,
+    /// Scale `stroke-width` by `scale^e` instead of `scale`
+    /// (`--stroke-compensation`), so thin lines stay balanced across sizes
+    pub stroke_exponent: Option<f64>,
+}
+
+/// Scale 1 at the CLI's default precision, with every other policy off
+impl Default for ScaleCtx {
+    fn default() -> Self {
+        ScaleCtx {
+            scale: 1.0,
+            precision: 4,
+            fix_stroke: false,
+            exact: false,
+            stroke_exponent: None,
+        }
+    }
 }
 
 impl ScaleCtx {
+    /// The context `stroke-width` values are scaled with
+    pub fn for_stroke(&self) -> ScaleCtx {
+        ScaleCtx {
+            scale: match self.stroke_exponent {
+                Some(e) => self.scale.powf(e),
+                None => self.scale,
+            },
+            ..*self
+        }
+    }
+
     pub fn fmt(&self, v: f64) -> String {
-        let s = format!("{:.*}", self.precision, v);
-        s.trim_end_matches('0').trim_end_matches('.').to_string()
+        let mut s = String::with_capacity(24);
+        push_fixed(&mut s, v, self.precision);
+        s
+    }
+
+    /// Append `v` formatted like [`ScaleCtx::fmt`] without allocating
+    pub fn push_fmt(&self, out: &mut String, v: f64) {
+        push_fixed(out, v, self.precision);
+    }
+
+    /// `v * scale`, formatted like [`ScaleCtx::fmt`]
+    pub fn fmt_scaled(&self, v: f64) -> String {
+        let mut s = String::with_capacity(24);
+        self.push_scaled(&mut s, v);
+        s
+    }
+
+    /// Append `v * scale`; with `exact` the product is computed in decimal
+    pub fn push_scaled(&self, out: &mut String, v: f64) {
+        if !(self.exact && push_exact_product(out, v, self.scale, self.precision)) {
+            push_fixed(out, v * self.scale, self.precision);
+        }
+    }
+}
+
+/// `v` as `(negative, mantissa, exponent)` using its shortest round-trip digits
+fn to_decimal(v: f64) -> Option<(bool, u128, i32)> {
+    if !v.is_finite() {
+        return None;
+    }
+    let s = format!("{:e}", v.abs());
+    let (digits, exp) = s.split_once('e')?;
+    let mut exp: i32 = exp.parse().ok()?;
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    exp -= frac.len() as i32;
+    let mantissa = format!("{}{}", int, frac).parse().ok()?;
+    Some((v.is_sign_negative(), mantissa, exp))
+}
+
+/// Append `v * scale` computed exactly on the decimal digits of both and
+/// rounded half away from zero; returns false if the values are out of range
+fn push_exact_product(out: &mut String, v: f64, scale: f64, precision: usize) -> bool {
+    let (Some((neg_v, m_v, e_v)), Some((neg_s, m_s, e_s))) = (to_decimal(v), to_decimal(scale))
+    else {
+        return false;
+    };
+    if precision > 30 {
+        return false;
+    }
+    // Shortest digits have at most 17 significant figures, so the product fits in u128
+    let Some(product) = m_v.checked_mul(m_s) else {
+        return false;
+    };
+    let exp = e_v + e_s;
+    let precision_i = precision as i32;
+
+    // Integer count of 10^-precision units
+    let units = if exp >= -precision_i {
+        let shift = (exp + precision_i) as u32;
+        match 10u128
+            .checked_pow(shift)
+            .and_then(|p| product.checked_mul(p))
+        {
+            Some(u) => u,
+            None => return false,
+        }
+    } else {
+        let drop = (-precision_i - exp) as u32;
+        match 10u128.checked_pow(drop) {
+            Some(p) => {
+                let (q, r) = (product / p, product % p);
+                if r >= p - r {
+                    q + 1
+                } else {
+                    q
+                }
+            }
+            // Everything is below the rounding position
+            None => 0,
+        }
+    };
+
+    if units != 0 && neg_v != neg_s {
+        out.push('-');
+    }
+    let p10 = 10u128.pow(precision as u32);
+    out.push_str(&(units / p10).to_string());
+    let frac = units % p10;
+    if frac != 0 {
+        let digits = format!("{:0width$}", frac, width = precision);
+        out.push('.');
+        out.push_str(digits.trim_end_matches('0'));
+    }
+    true
+}
+
+const POW10: [f64; 16] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15,
+];
+
+/// Append `v` rounded to `precision` decimals with trailing zeros removed,
+/// e.g. `1.5`, `-0.25`, `12`; precision is capped at [`MAX_PRECISION`]
+pub fn push_fixed(out: &mut String, v: f64, precision: usize) {
+    let precision = precision.min(MAX_PRECISION);
+    if let Some(&p10) = POW10.get(precision) {
+        let x = v.abs() * p10;
+        // Exact halves need the correctly rounded slow path
+        if x < 4e15 && ((x - x.floor()) - 0.5).abs() > 1e-6 {
+            let n = x.round() as u64;
+            if v.is_sign_negative() {
+                out.push('-');
+            }
+            let p10 = p10 as u64;
+            push_u64(out, n / p10);
+            let frac = n % p10;
+            if frac != 0 {
+                out.push('.');
+                let mut digits = [b'0'; 16];
+                let mut f = frac;
+                for d in digits[..precision].iter_mut().rev() {
+                    *d = b'0' + (f % 10) as u8;
+                    f /= 10;
+                }
+                let digits = &digits[..precision];
+                let end = digits.iter().rposition(|&d| d != b'0').map_or(0, |i| i + 1);
+                out.extend(digits[..end].iter().map(|&d| d as char));
+            }
+            return;
+        }
+    }
+    let s = format!("{:.*}", precision, v);
+    if s.contains('.') {
+        out.push_str(s.trim_end_matches('0').trim_end_matches('.'));
+    } else {
+        out.push_str(&s);
+    }
+}
+
+/// Append the shortest representation that parses back to exactly `v`
+pub fn push_shortest(out: &mut String, v: f64) {
+    use std::fmt::Write;
+    let _ = write!(out, "{}", v);
+}
+
+fn push_u64(out: &mut String, mut n: u64) {
+    let mut digits = [0u8; 20];
+    let mut i = digits.len();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    out.extend(digits[i..].iter().map(|&d| d as char));
+}
+
+fn factor(input: &str) -> IResult<&str, f64> {
+    preceded(
+        space0,
+        alt((
+            map(preceded(char('-'), factor), |v| -v),
+            delimited(char('('), expr, preceded(space0, char(')'))),
+            map(
+                pair(double, opt(preceded(space0, char('%')))),
+                |(v, pct)| {
+                    if pct.is_some() {
+                        v / 100.0
+                    } else {
+                        v
+                    }
+                },
+            ),
+        )),
+    )(input)
+}
+
+fn term(input: &str) -> IResult<&str, f64> {
+    let (input, first) = factor(input)?;
+    let (input, rest) = many0(pair(preceded(space0, one_of("*/")), factor))(input)?;
+    let v = rest.into_iter().fold(
+        first,
+        |acc, (op, v)| if op == '*' { acc * v } else { acc / v },
+    );
+    Ok((input, v))
+}
+
+fn expr(input: &str) -> IResult<&str, f64> {
+    let (input, first) = term(input)?;
+    let (input, rest) = many0(pair(preceded(space0, one_of("+-")), term))(input)?;
+    let v = rest.into_iter().fold(
+        first,
+        |acc, (op, v)| if op == '+' { acc + v } else { acc - v },
+    );
+    Ok((input, v))
+}
+
+/// Largest factor magnitude accepted: beyond it coordinates keep no useful
+/// precision and rasters cannot be allocated
+pub const MAX_SCALE: f64 = 1e6;
+
+/// Smallest factor magnitude accepted; the drawing would vanish below it
+pub const MIN_SCALE: f64 = 1e-6;
+
+/// Reject factors that cannot produce a usable drawing: zero, non-finite,
+/// out of [`MIN_SCALE`]..=[`MAX_SCALE`] in magnitude, or negative unless
+/// mirroring is allowed
+pub fn check_scale(scale: f64, allow_negative: bool) -> Result<()> {
+    if !scale.is_finite() {
+        bail!("scale factor is not a finite number: {}", scale);
+    }
+    if scale == 0.0 {
+        bail!("scale factor is zero, which collapses the drawing to a point");
+    }
+    if scale < 0.0 && !allow_negative {
+        bail!("negative scale factor {} would mirror the drawing", scale);
+    }
+    if !(MIN_SCALE..=MAX_SCALE).contains(&scale.abs()) {
+        bail!(
+            "scale factor {:e} is outside {:e}..={:e}",
+            scale,
+            MIN_SCALE,
+            MAX_SCALE
+        );
+    }
+    Ok(())
+}
+
+/// Parse a scale factor such as `0.5`, `2/3`, `150%` or `(16+8)/512`
+pub fn parse_scale_expr(input: &str) -> Result<f64> {
+    match all_consuming(terminated(expr, space0))(input) {
+        Ok((_, v)) if v.is_finite() => Ok(v),
+        Ok(_) => Err(anyhow::anyhow!("scale is not a finite number: {}", input)),
+        Err(_) => Err(anyhow::anyhow!("invalid scale expression: {}", input)),
+    }
+}
+
+/// Most decimals a value is written with: an `f64` holds at most 17
+/// significant digits, so further ones are noise
+pub const MAX_PRECISION: usize = 17;
+
+/// Reject decimal counts above [`MAX_PRECISION`]
+pub fn check_precision(precision: usize) -> Result<()> {
+    if precision > MAX_PRECISION {
+        bail!(
+            "precision {} is above the maximum of {} decimals",
+            precision,
+            MAX_PRECISION
+        );
+    }
+    Ok(())
+}
+
+/// Parse a decimal count for `--precision`; it must pass [`check_precision`]
+pub fn parse_precision(input: &str) -> Result<usize> {
+    let precision = input
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid precision: {}", input))?;
+    check_precision(precision)?;
+    Ok(precision)
+}
+
+/// [`parse_scale_expr`] for options that take no mirroring; the factor
+/// must pass [`check_scale`]
+pub fn parse_scale_factor(input: &str) -> Result<f64> {
+    let scale = parse_scale_expr(input)?;
+    check_scale(scale, false)?;
+    Ok(scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_expressions_evaluate() -> Result<()> {
+        assert_eq!(parse_scale_expr("0.5")?, 0.5);
+        assert_eq!(parse_scale_expr("2/3")?, 2.0 / 3.0);
+        assert_eq!(parse_scale_expr("150%")?, 1.5);
+        assert_eq!(parse_scale_expr(" (16 + 16) / 512 ")?, 0.0625);
+        assert_eq!(parse_scale_expr("1-0.25*2")?, 0.5);
+        assert_eq!(parse_scale_expr("-50%")?, -0.5);
+        Ok(())
+    }
+
+    #[test]
+    fn unusable_scale_factors_are_rejected() {
+        assert!(check_scale(0.5, false).is_ok());
+        assert!(check_scale(0.0, true).is_err());
+        assert!(check_scale(-2.0, false).is_err());
+        assert!(check_scale(-2.0, true).is_ok());
+        assert!(check_scale(1e7, false).is_err());
+        assert!(check_scale(1e-9, false).is_err());
+        assert!(check_scale(f64::INFINITY, true).is_err());
+        assert!(parse_scale_factor("0").is_err());
+        assert_eq!(parse_scale_factor("1/4").ok(), Some(0.25));
+    }
+
+    #[test]
+    fn scale_expressions_reject_garbage() {
+        assert!(parse_scale_expr("2/").is_err());
+        assert!(parse_scale_expr("abc").is_err());
+        assert!(parse_scale_expr("1/0").is_err());
+    }
+
+    fn reference_fixed(v: f64, precision: usize) -> String {
+        let s = format!("{:.*}", precision, v);
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s
+        }
+    }
+
+    #[test]
+    fn fast_fixed_formatting_matches_std() {
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut values = vec![0.0, -0.0, 0.5, 0.125, 2.5, -1.25, 120.0, 1e-9, -1e-9, 1e300];
+        for _ in 0..20_000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let mantissa = (seed % 2_000_000) as f64 - 1_000_000.0;
+            values.push(mantissa / POW10[(seed >> 40) as usize % 8]);
+        }
+        for precision in 0..=8 {
+            for &v in &values {
+                let mut fast = String::new();
+                push_fixed(&mut fast, v, precision);
+                assert_eq!(fast, reference_fixed(v, precision), "v={v} p={precision}");
+            }
+        }
+        let mut s = String::new();
+        push_shortest(&mut s, 0.1 + 0.2);
+        assert_eq!(s.parse::<f64>().ok(), Some(0.1 + 0.2));
+    }
+
+    #[test]
+    fn exact_products_avoid_binary_noise() {
+        let ctx = ScaleCtx {
+            scale: 3.0,
+            precision: 17,
+            exact: true,
+            ..ScaleCtx::default()
+        };
+        assert_eq!(ctx.fmt_scaled(0.1), "0.3");
+        assert_eq!(
+            ScaleCtx {
+                exact: false,
+                stroke_exponent: None,
+                ..ctx
+            }
+            .fmt_scaled(0.1),
+            "0.30000000000000004"
+        );
+
+        let halves = ScaleCtx {
+            scale: 0.5,
+            precision: 2,
+            exact: true,
+            ..ScaleCtx::default()
+        };
+        assert_eq!(halves.fmt_scaled(0.25), "0.13");
+        assert_eq!(halves.fmt_scaled(-0.25), "-0.13");
+        assert_eq!(halves.fmt_scaled(-0.001), "0");
+        assert_eq!(halves.fmt_scaled(1e20), "50000000000000000000");
+        assert_eq!(halves.fmt_scaled(240.0), "120");
+    }
+
+    #[test]
+    fn precision_is_bounded() {
+        let mut out = String::new();
+        push_fixed(&mut out, 0.1, 20_000_000);
+        assert_eq!(out, "0.10000000000000001");
+        assert_eq!(parse_precision("17").ok(), Some(17));
+        assert!(parse_precision("18").is_err());
+        assert!(parse_precision("-1").is_err());
     }
 }
@@ -0,0 +1,272 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::UNIX_EPOCH;
+use std::{fs, thread};
+
+use svg_scale::scale::{self, ScaleCtx};
+
+#[derive(Args, Clone)]
+pub struct ServeArgs {
+    /// 输入 SVG 文件
+    pub input: String,
+
+    /// 监听端口
+    #[arg(long, default_value = "8000")]
+    pub port: u16,
+
+    /// 预览尺寸，如 16,32,48,128
+    #[arg(long, default_value = "16,32,48,128")]
+    pub sizes: String,
+
+    /// 原始尺寸（可选）
+    #[arg(long)]
+    pub from: Option<f64>,
+
+    #[arg(long, default_value = "4", value_parser = scale::parse_precision)]
+    pub precision: usize,
+
+    /// 移除 non-scaling-stroke
+    #[arg(long)]
+    pub fix_stroke: bool,
+}
+
+pub fn run(args: &ServeArgs) -> Result<()> {
+    let sizes = parse_sizes(&args.sizes)?;
+    let listener = TcpListener::bind(("127.0.0.1", args.port))
+        .with_context(|| format!("bind port {}", args.port))?;
+    println!("预览服务器已启动: http://127.0.0.1:{}/", args.port);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("连接失败: {}", e);
+                continue;
+            }
+        };
+        let args = args.clone();
+        let sizes = sizes.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &args, &sizes) {
+                eprintln!("请求处理失败: {:#}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn parse_sizes(input: &str) -> Result<Vec<f64>> {
+    let sizes: Vec<f64> = input
+        .split(',')
+        .map(|s| s.trim().parse())
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("invalid sizes: {}", input))?;
+    if sizes.is_empty() {
+        bail!("invalid sizes: {}", input);
+    }
+    Ok(sizes)
+}
+
+fn handle_connection(mut stream: TcpStream, args: &ServeArgs, sizes: &[f64]) -> Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the remaining headers; the body is never used.
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let (status, content_type, body) = match route(path, args, sizes) {
+        Ok(Some((content_type, body))) => ("200 OK", content_type, body),
+        Ok(None) => (
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            "not found".to_string(),
+        ),
+        Err(e) => (
+            "500 Internal Server Error",
+            "text/plain; charset=utf-8",
+            format!("{:#}", e),
+        ),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body.as_bytes())?;
+    Ok(())
+}
+
+type Response = (&'static str, String);
+
+/// Resolve a request path to a response; `None` means 404
+fn route(path: &str, args: &ServeArgs, sizes: &[f64]) -> Result<Option<Response>> {
+    let path = path.split('?').next().unwrap_or(path);
+    let resp = match path {
+        "/" => ("text/html; charset=utf-8", page_html(&args.input, sizes)),
+        "/version" => ("text/plain; charset=utf-8", source_version(&args.input)?),
+        "/source.svg" => ("image/svg+xml", fs::read_to_string(&args.input)?),
+        _ => {
+            let Some(size) = path
+                .strip_prefix("/scaled/")
+                .and_then(|p| p.strip_suffix(".svg"))
+                .and_then(|p| p.parse::<f64>().ok())
+            else {
+                return Ok(None);
+            };
+            ("image/svg+xml", scaled_svg(args, size)?)
+        }
+    };
+    Ok(Some(resp))
+}
+
+/// Modification time of the source, polled by the page to trigger reloads
+fn source_version(input: &str) -> Result<String> {
+    let modified = fs::metadata(input)?.modified()?;
+    let nanos = modified
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok(nanos.to_string())
+}
+
+fn scaled_svg(args: &ServeArgs, size: f64) -> Result<String> {
+    let input_svg = fs::read_to_string(&args.input)?;
+    let doc = roxmltree::Document::parse(&input_svg)?;
+    let from_size = match args
+        .from
+        .or_else(|| svg_scale::get_svg_size(&doc, svg_scale::DEFAULT_DPI))
+    {
+        Some(s) => s,
+        None => bail!("未能从SVG检测到尺寸，请使用 --from 指定原始尺寸"),
+    };
+    let scale = size / from_size;
+    scale::check_scale(scale, false)?;
+    let ctx = ScaleCtx {
+        scale,
+        precision: args.precision,
+        fix_stroke: args.fix_stroke,
+        ..ScaleCtx::default()
+    };
+    svg_scale::write_svg(&doc, &ctx)
+}
+
+fn page_html(input: &str, sizes: &[f64]) -> String {
+    let mut cells = String::new();
+    cells.push_str(
+        "<figure><img src=\"/source.svg\" class=\"source\"><figcaption>source</figcaption></figure>\n",
+    );
+    for size in sizes {
+        cells.push_str(&format!(
+            "<figure><img src=\"/scaled/{size}.svg\" width=\"{size}\" height=\"{size}\"><figcaption>{size}px</figcaption></figure>\n"
+        ));
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>svg-scale: {title}</title>
+<style>
+body {{ font-family: sans-serif; background: #f4f4f4; }}
+main {{ display: flex; flex-wrap: wrap; align-items: flex-end; gap: 24px; }}
+figure {{ margin: 0; text-align: center; }}
+img {{ background: #fff; image-rendering: pixelated; }}
+img.source {{ max-width: 256px; max-height: 256px; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<main>
+{cells}</main>
+<script>
+let version = null;
+setInterval(async () => {{
+  const v = await fetch("/version").then(r => r.text()).catch(() => version);
+  if (version !== null && v !== version) location.reload();
+  version = v;
+}}, 1000);
+</script>
+</body>
+</html>
+"#,
+        title = escape_html(input),
+        cells = cells
+    )
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_lists_every_preview_size() {
+        let html = page_html("icons/<gear>.svg", &[16.0, 32.0]);
+        assert!(html.contains(r#"src="/scaled/16.svg""#), "got: {html}");
+        assert!(html.contains(r#"src="/scaled/32.svg""#), "got: {html}");
+        assert!(html.contains("icons/&lt;gear&gt;.svg"), "got: {html}");
+    }
+
+    #[test]
+    fn unknown_route_is_not_found() {
+        let args = ServeArgs {
+            input: "missing.svg".to_string(),
+            port: 0,
+            sizes: "16".to_string(),
+            from: None,
+            precision: 4,
+            fix_stroke: false,
+        };
+        assert!(route("/nope", &args, &[16.0]).unwrap().is_none());
+    }
+
+    #[test]
+    fn degenerate_scales_are_rejected() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("svg-scale-serve-{}.svg", std::process::id()));
+        fs::write(
+            &path,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="0"/>"#,
+        )?;
+        let args = ServeArgs {
+            input: path.to_string_lossy().into_owned(),
+            port: 0,
+            sizes: "16".to_string(),
+            from: None,
+            precision: 4,
+            fix_stroke: false,
+        };
+        let detected = scaled_svg(&args, 16.0);
+        let given = scaled_svg(
+            &ServeArgs {
+                from: Some(0.0),
+                ..args.clone()
+            },
+            16.0,
+        );
+        fs::remove_file(&path)?;
+        for result in [detected, given] {
+            let Err(err) = result else {
+                bail!("an infinite scale must not reach the walker");
+            };
+            assert!(err.to_string().contains("finite"), "got: {err:#}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Icon sheets split into one document per icon for `--split-by`.
+//!
+//! Each outermost element matching the selectors becomes its own document:
+//! the root element, the resource elements beside the icon (`<defs>`,
+//! `<style>`, gradients, symbols, ...) and the icon itself with the groups
+//! around it, cropped to the icon's drawn bounds. Other graphics on the
+//! sheet, such as labels and guides, are left out.
+
+use anyhow::{bail, Context, Result};
+use roxmltree::Node;
+use xmlwriter::XmlWriter;
+
+use crate::ops::{self, Op};
+use crate::svg::{self, SelectorList};
+
+/// Elements that draw nothing themselves and may be referenced by the icon
+const RESOURCE_TAGS: &[&str] = &[
+    "defs",
+    "style",
+    "symbol",
+    "linearGradient",
+    "radialGradient",
+    "pattern",
+    "clipPath",
+    "mask",
+    "filter",
+    "marker",
+];
+
+/// One icon cut out of a sheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Piece {
+    /// `id` of the matched element, if it has one
+    pub id: Option<String>,
+    pub svg: String,
+}
+
+/// Cut every outermost element matching `selectors` out into its own
+/// document, in document order
+pub fn split_document(
+    doc: &roxmltree::Document,
+    selectors: &SelectorList,
+    dpi: f64,
+) -> Result<Vec<Piece>> {
+    let root = doc.root_element();
+    let matches: Vec<Node> = root
+        .descendants()
+        .skip(1)
+        .filter(|n| n.is_element() && selectors.matches(*n))
+        .filter(|n| {
+            !n.ancestors()
+                .skip(1)
+                .any(|a| a != root && selectors.matches(a))
+        })
+        .collect();
+    if matches.is_empty() {
+        bail!("no element matches the split selectors");
+    }
+    matches
+        .into_iter()
+        .enumerate()
+        .map(|(i, icon)| {
+            let id = icon.attribute("id").map(str::to_string);
+            let label = id.clone().unwrap_or_else(|| format!("#{}", i + 1));
+            let extracted = extract(doc, icon);
+            let svg = ops::run_ops(&extracted, &[Op::Trim], dpi)
+                .with_context(|| format!("icon {}", label))?;
+            Ok(Piece { id, svg })
+        })
+        .collect()
+}
+
+/// The document with only `icon` and the resources left beside it
+fn extract(doc: &roxmltree::Document, icon: Node) -> String {
+    let root = doc.root_element();
+    let mut w = XmlWriter::new(xmlwriter::Options::default());
+    w.start_element(root.tag_name().name());
+    for attr in root.attributes() {
+        w.write_attribute(&svg::qualified_attr_name(root, &attr), attr.value());
+    }
+    write_kept(root, icon, &mut w);
+    w.end_element();
+    crate::finish_document(doc, w)
+}
+
+fn write_kept(parent: Node, icon: Node, w: &mut XmlWriter) {
+    for c in parent.children().filter(Node::is_element) {
+        if c == icon || RESOURCE_TAGS.contains(&c.tag_name().name()) {
+            svg::write_verbatim(c, w);
+        } else if icon.ancestors().any(|a| a == c) {
+            w.start_element(c.tag_name().name());
+            for attr in c.attributes() {
+                w.write_attribute(&svg::qualified_attr_name(c, &attr), attr.value());
+            }
+            write_kept(c, icon, w);
+            w.end_element();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="0 0 100 50"><defs><linearGradient id="g"><stop offset="0"/></linearGradient></defs><text x="0" y="48">sheet</text><g id="icon-a"><rect x="10" y="10" width="20" height="20" fill="url(#g)"/></g><g transform="translate(50,0)"><g id="icon-b"><circle cx="10" cy="20" r="5"/></g></g></svg>"##;
+
+    #[test]
+    fn extract_keeps_the_icon_and_its_resources() -> Result<()> {
+        let doc = roxmltree::Document::parse(SHEET)?;
+        let icon = doc
+            .descendants()
+            .find(|n| n.attribute("id") == Some("icon-b"))
+            .unwrap();
+        let out = extract(&doc, icon);
+        assert!(out.contains(r#"<linearGradient id="g">"#), "got: {out}");
+        assert!(
+            out.contains(r#"<g transform="translate(50,0)">"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<circle cx="10" cy="20" r="5"/>"#),
+            "got: {out}"
+        );
+        assert!(
+            !out.contains("icon-a") && !out.contains("<text"),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "render")]
+    #[test]
+    fn splits_matching_groups_cropped_to_their_bounds() -> Result<()> {
+        let doc = roxmltree::Document::parse(SHEET)?;
+        let pieces = split_document(&doc, &"g[id^=icon-]".parse()?, 96.0)?;
+        let ids: Vec<_> = pieces.iter().map(|p| p.id.as_deref()).collect();
+        assert_eq!(ids, [Some("icon-a"), Some("icon-b")]);
+        assert!(
+            pieces[0]
+                .svg
+                .contains(r#"width="20" height="20" viewBox="10 10 20 20""#),
+            "got: {}",
+            pieces[0].svg
+        );
+        assert!(
+            pieces[1]
+                .svg
+                .contains(r#"width="10" height="10" viewBox="55 15 10 10""#),
+            "got: {}",
+            pieces[1].svg
+        );
+        assert!(split_document(&doc, &"#none".parse()?, 96.0).is_err());
+        Ok(())
+    }
+}
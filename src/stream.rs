@@ -0,0 +1,435 @@
+//! Streaming rewrite for documents too large to hold as a DOM.
+//!
+//! The input is tokenized on the fly and every start tag is rewritten as
+//! soon as it is complete, so memory stays bounded by the largest single
+//! tag. Only the per-element rules of [`crate::svg`] apply: `<style>`
+//! sheets, selector filters and hooks need the whole tree and are ignored.
+//! The scale may be chosen once the root start tag has been read (see
+//! [`scale_stream_with`]), which is all `--to` needs to detect the size.
+
+use anyhow::{bail, Context, Result};
+use std::borrow::Cow;
+use std::io::{BufRead, Write};
+
+use crate::scale::ScaleCtx;
+use crate::svg::{self, ElementCtx, TransformMode};
+use crate::viewbox::ViewBox;
+
+/// Scaling state inherited from the open ancestors
+#[derive(Clone, Copy, Default)]
+struct Frame {
+    non_translate_transform: bool,
+    skip_scale: bool,
+    /// Open `<svg>` elements, the root included
+    svg_depth: u32,
+}
+
+/// Size attributes of the root `<svg>`, as read from its start tag
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamRoot {
+    pub width: Option<String>,
+    pub height: Option<String>,
+    pub view_box: Option<ViewBox>,
+}
+
+/// Scale an SVG document read from `input`, writing the result to `output`
+pub fn scale_stream(input: impl BufRead, output: impl Write, ctx: &ScaleCtx) -> Result<()> {
+    scale_stream_with(input, output, |_| Ok(ScaleCtx { ..*ctx }))
+}
+
+/// Like [`scale_stream`], with the context chosen by `resolve` from the root
+/// start tag, before anything of the root is written
+pub fn scale_stream_with(
+    mut input: impl BufRead,
+    mut output: impl Write,
+    resolve: impl FnOnce(&StreamRoot) -> Result<ScaleCtx>,
+) -> Result<()> {
+    let mut resolve = Some(resolve);
+    let mut resolved = None;
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut text = Vec::new();
+    loop {
+        text.clear();
+        if input.read_until(b'<', &mut text)? == 0 {
+            break;
+        }
+        let at_markup = text.last() == Some(&b'<');
+        if at_markup {
+            text.pop();
+        }
+        output.write_all(&text)?;
+        if !at_markup {
+            break;
+        }
+
+        let markup = read_markup(&mut input)?;
+        if markup.starts_with('/') {
+            stack.pop();
+            write!(output, "<{}>", markup)?;
+        } else if markup.starts_with(['!', '?']) {
+            write!(output, "<{}>", markup)?;
+        } else {
+            if let Some(resolve) = resolve.take() {
+                resolved = Some(resolve(&root_of(&markup)?)?);
+            }
+            let ctx = resolved
+                .as_ref()
+                .expect("resolved with the first start tag");
+            let parent = stack.last().copied().unwrap_or_default();
+            let (tag, frame) = rewrite_start_tag(&markup, parent, ctx)?;
+            output.write_all(tag.as_bytes())?;
+            if !markup.ends_with('/') {
+                stack.push(frame);
+            }
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Read up to and including the `>` closing the current markup, returning
+/// everything between `<` and `>`
+fn read_markup(input: &mut impl BufRead) -> Result<String> {
+    let mut buf = Vec::new();
+    loop {
+        if input.read_until(b'>', &mut buf)? == 0 || buf.last() != Some(&b'>') {
+            bail!("unexpected end of input inside markup");
+        }
+        if markup_is_complete(&buf[..buf.len() - 1]) {
+            buf.pop();
+            return String::from_utf8(buf).context("markup is not valid UTF-8");
+        }
+    }
+}
+
+/// Whether a `>` following `body` closes the markup (it may also appear in
+/// quoted values, comments, CDATA sections or a DOCTYPE internal subset)
+fn markup_is_complete(body: &[u8]) -> bool {
+    if body.starts_with(b"!--") {
+        return body.len() >= 5 && body.ends_with(b"--");
+    }
+    if body.starts_with(b"![CDATA[") {
+        return body.ends_with(b"]]");
+    }
+    if body.starts_with(b"?") {
+        return body.ends_with(b"?");
+    }
+    let mut quote = None;
+    let mut depth = 0i32;
+    for &c in body {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'') => quote = Some(c),
+            (None, b'[') => depth += 1,
+            (None, b']') => depth -= 1,
+            _ => {}
+        }
+    }
+    quote.is_none() && (depth <= 0 || !body.starts_with(b"!"))
+}
+
+fn root_of(markup: &str) -> Result<StreamRoot> {
+    let (_, attrs, _) = parse_start_tag(markup)?;
+    let attr = |name: &str| {
+        attrs
+            .iter()
+            .find(|(k, _, _)| *k == name)
+            .map(|(_, v, _)| v.clone())
+    };
+    Ok(StreamRoot {
+        width: attr("width"),
+        height: attr("height"),
+        view_box: attr("viewBox").as_deref().and_then(ViewBox::parse),
+    })
+}
+
+/// Attribute name, unescaped value and the quote character it used
+type RawAttr<'a> = (&'a str, String, char);
+
+/// Split a start tag body into name, attributes and whether it is self-closing
+fn parse_start_tag(markup: &str) -> Result<(&str, Vec<RawAttr<'_>>, bool)> {
+    let (body, self_closing) = match markup.strip_suffix('/') {
+        Some(b) => (b, true),
+        None => (markup, false),
+    };
+    let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+    let name = &body[..name_end];
+    let mut rest = &body[name_end..];
+    let mut attrs = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        let eq = rest
+            .find('=')
+            .with_context(|| format!("invalid attribute in <{}>: {}", name, rest))?;
+        let key = rest[..eq].trim_end();
+        rest = rest[eq + 1..].trim_start();
+        let quote = rest
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .with_context(|| format!("unquoted attribute {} in <{}>", key, name))?;
+        let end = rest[1..]
+            .find(quote)
+            .with_context(|| format!("unterminated attribute {} in <{}>", key, name))?;
+        attrs.push((key, unescape(&rest[1..1 + end]), quote));
+        rest = &rest[end + 2..];
+    }
+    Ok((name, attrs, self_closing))
+}
+
+fn rewrite_start_tag(markup: &str, parent: Frame, ctx: &ScaleCtx) -> Result<(String, Frame)> {
+    let (tag_name, attrs, self_closing) = parse_start_tag(markup)?;
+    let attr = |name: &str| {
+        attrs
+            .iter()
+            .find(|(k, _, _)| *k == name)
+            .map(|(_, v, _)| v.as_str())
+    };
+    let node_id = attr("id").unwrap_or("");
+    let local_name = tag_name.rsplit(':').next().unwrap_or(tag_name);
+
+    let (skip_scale_due_to_units, skip_children_due_to_content_units) =
+        svg::units_skip_flags(local_name, attr);
+    let style_props = svg::parse_style(attr("style").unwrap_or(""));
+
+    let mut has_non_translate_transform = false;
+    let transforms = attr("transform").into_iter().chain(
+        style_props
+            .iter()
+            .filter(|(k, _)| k == "transform")
+            .map(|(_, v)| v.as_str()),
+    );
+    for t in transforms {
+        has_non_translate_transform |= svg::has_non_translate_transform(t)
+            .with_context(|| format!("transform parse failed on <{}>", local_name))?;
+    }
+    let effects = svg::VectorEffects::from_values(
+        attr("vector-effect").into_iter().chain(
+            style_props
+                .iter()
+                .filter(|(k, _)| k == "vector-effect")
+                .map(|(_, v)| v.as_str()),
+        ),
+    );
+    let has_non_scaling_stroke = effects.non_scaling_stroke;
+    let keeps_size = effects.non_scaling_size && !ctx.fix_stroke;
+
+    let skip_scale_self = parent.skip_scale || skip_scale_due_to_units;
+    let style_prop = |name: &str| {
+        style_props
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    };
+    let transform_mode = TransformMode::of_element(
+        parent.non_translate_transform || skip_scale_self,
+        has_non_translate_transform,
+        style_prop("transform-origin").or(attr("transform-origin")),
+        style_prop("transform-box").or(attr("transform-box")),
+    );
+    let folds_transform = transform_mode.folds(has_non_translate_transform);
+    // <view> is in the root's user space, whatever lies in between
+    let is_root_view = local_name == "view" && parent.svg_depth == 1;
+    let el = ElementCtx {
+        tag_name: local_name,
+        id: node_id,
+        skip_scale: !is_root_view
+            && (parent.non_translate_transform || folds_transform || skip_scale_self || keeps_size),
+    };
+
+    let mut out = format!("<{}", tag_name);
+    for (k, v, quote) in &attrs {
+        let nv = if ctx.fix_stroke && *k == "vector-effect" {
+            match svg::fix_vector_effect(v) {
+                Some(nv) => Cow::Owned(nv),
+                None => continue,
+            }
+        } else if *k == "style" {
+            let mut props = Vec::with_capacity(style_props.len());
+            for (sk, sv) in &style_props {
+                if ctx.fix_stroke && sk == "vector-effect" {
+                    if let Some(nv) = svg::fix_vector_effect(sv) {
+                        props.push((sk.clone(), nv));
+                    }
+                    continue;
+                }
+                let scaled = svg::scale_style_value(
+                    sk,
+                    sv,
+                    ctx,
+                    skip_scale_self
+                        || parent.non_translate_transform
+                        || folds_transform
+                        || keeps_size,
+                    has_non_scaling_stroke,
+                    transform_mode,
+                )?;
+                props.push((sk.clone(), scaled));
+            }
+            Cow::Owned(svg::serialize_style(&props))
+        } else {
+            svg::scale_attribute_value(
+                &el,
+                k,
+                v,
+                ctx,
+                skip_scale_self,
+                has_non_scaling_stroke,
+                transform_mode,
+            )?
+        };
+        out.push(' ');
+        out.push_str(k);
+        out.push('=');
+        out.push(*quote);
+        out.push_str(&escape(&nv, *quote));
+        out.push(*quote);
+    }
+    out.push_str(if self_closing { "/>" } else { ">" });
+
+    let frame = Frame {
+        non_translate_transform: parent.non_translate_transform || folds_transform,
+        skip_scale: if local_name == "filter" {
+            parent.skip_scale || skip_children_due_to_content_units
+        } else {
+            skip_scale_self || skip_children_due_to_content_units
+        },
+        svg_depth: parent.svg_depth + u32::from(local_name == "svg"),
+    };
+    Ok((out, frame))
+}
+
+fn unescape(v: &str) -> String {
+    if !v.contains('&') {
+        return v.to_string();
+    }
+    let mut out = String::with_capacity(v.len());
+    let mut rest = v;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|h| u32::from_str_radix(h, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(|r| r.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape(v: &str, quote: char) -> String {
+    let mut out = String::with_capacity(v.len());
+    for c in v.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' if quote == '"' => out.push_str("&quot;"),
+            '\'' if quote == '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(input: &str, scale: f64) -> Result<String> {
+        let mut out = Vec::new();
+        let ctx = ScaleCtx {
+            scale,
+            ..ScaleCtx::default()
+        };
+        scale_stream(input.as_bytes(), &mut out, &ctx)?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn stream_rewrites_attributes_and_keeps_everything_else() -> Result<()> {
+        let input = concat!(
+            "<?xml version=\"1.0\"?>\n<!-- a > b -->\n",
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 100 100\">\n",
+            "  <title>1 &lt; 2</title>\n",
+            "  <rect x='10' width=\"40\" style=\"stroke-width: 4\" data-note=\"a&quot;b\"/>\n",
+            "  <path d=\"M10 10 L20 20\"/>\n",
+            "</svg>\n",
+        );
+        let out = stream(input, 0.5)?;
+        let expected = concat!(
+            "<?xml version=\"1.0\"?>\n<!-- a > b -->\n",
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 50 50\">\n",
+            "  <title>1 &lt; 2</title>\n",
+            "  <rect x='5' width=\"20\" style=\"stroke-width:2\" data-note=\"a&quot;b\"/>\n",
+            "  <path d=\"M5 5 L10 10\"/>\n",
+            "</svg>\n",
+        );
+        assert_eq!(out, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn stream_respects_ancestor_transforms_and_units() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><g transform="scale(2)"><path d="M10 10"/></g><path d="M10 10"/><clipPath clipPathUnits="objectBoundingBox"><rect width="0.5"/></clipPath><g transform="rotate(45)"><view viewBox="0 0 10 10"/></g></svg>"#;
+        let out = stream(input, 0.5)?;
+        assert!(
+            out.contains(r#"<g transform="scale(1)"><path d="M10 10"/></g><path d="M5 5"/>"#),
+            "got: {out}"
+        );
+        assert!(out.contains(r#"<rect width="0.5"/>"#), "got: {out}");
+        assert!(out.contains(r#"<view viewBox="0 0 5 5"/>"#), "got: {out}");
+        Ok(())
+    }
+
+    #[test]
+    fn stream_reports_truncated_markup() {
+        assert!(stream(r#"<svg><rect width="1""#, 0.5).is_err());
+    }
+
+    #[test]
+    fn stream_picks_the_scale_from_the_root_tag() -> Result<()> {
+        let input = "<?xml version=\"1.0\"?>\n<svg width=\"200\" viewBox=\"0 0 100 100\"><rect width=\"40\"/></svg>";
+        let mut seen = None;
+        let mut out = Vec::new();
+        scale_stream_with(input.as_bytes(), &mut out, |root| {
+            seen = Some(root.clone());
+            Ok(ScaleCtx {
+                scale: 0.25,
+                ..ScaleCtx::default()
+            })
+        })?;
+        let root = seen.context("resolved")?;
+        assert_eq!(root.width.as_deref(), Some("200"));
+        assert_eq!(root.view_box.map(|vb| vb.width), Some(100.0));
+        assert!(String::from_utf8(out)?
+            .ends_with(r#"<svg width="50" viewBox="0 0 25 25"><rect width="10"/></svg>"#));
+        Ok(())
+    }
+}
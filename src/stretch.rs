@@ -0,0 +1,419 @@
+//! Non-uniform scaling for `--scale-x` / `--scale-y`.
+//!
+//! The document is stretched by the ratio of the two factors before the
+//! usual uniform pass, so the walker never needs separate factors. Path
+//! data, points, positions and sizes are rewritten per axis, circles become
+//! ellipses and transforms are conjugated, so a rotation turns into the
+//! matching shear. The same goes for geometry set through `style` or
+//! `<style>` rules, which is written inline with each stretched element.
+//! Nested viewports and patterns with a `viewBox` have it stretched along
+//! with their content, so `preserveAspectRatio` sees the original proportions.
+//! Stroke widths, font sizes and other lengths without a direction are left
+//! to the uniform pass. Content in `objectBoundingBox` units already follows
+//! the stretched box; marker content and filter primitives are kept in their
+//! own systems.
+
+use anyhow::{Context, Result};
+use roxmltree::Node;
+use xmlwriter::XmlWriter;
+
+use crate::path::stretch_path;
+use crate::scale::ScaleCtx;
+use crate::svg::{self, scale_length_value, scale_number_list, StyleSheet};
+use crate::transform::stretch_transform_value;
+use crate::viewbox::{ViewBox, EXACT_PRECISION};
+
+/// Axis an attribute measures along
+#[derive(Clone, Copy, PartialEq)]
+enum Axis {
+    X,
+    Y,
+}
+
+fn axis_of(tag_name: &str, name: &str) -> Option<Axis> {
+    match name {
+        "x" | "x1" | "x2" | "cx" | "fx" | "width" | "dx" | "refX" => Some(Axis::X),
+        "y" | "y1" | "y2" | "cy" | "fy" | "height" | "dy" | "refY" => Some(Axis::Y),
+        "rx" if tag_name != "radialGradient" => Some(Axis::X),
+        "ry" => Some(Axis::Y),
+        _ => None,
+    }
+}
+
+/// The document stretched by `kx` horizontally and `ky` vertically, with
+/// full precision
+pub fn stretch_document(doc: &roxmltree::Document, kx: f64, ky: f64) -> Result<String> {
+    let mut w = XmlWriter::new(xmlwriter::Options {
+        indent: xmlwriter::Indent::None,
+        ..xmlwriter::Options::default()
+    });
+    let stretch = Stretch {
+        kx,
+        ky,
+        styles: StyleSheet::collect(doc.root_element()),
+    };
+    stretch.write_root(doc.root_element(), &mut w)?;
+    Ok(crate::finish_document(doc, w))
+}
+
+struct Stretch {
+    kx: f64,
+    ky: f64,
+    styles: StyleSheet,
+}
+
+impl Stretch {
+    fn ctx(&self, k: f64) -> ScaleCtx {
+        ScaleCtx {
+            scale: k,
+            precision: EXACT_PRECISION,
+            ..ScaleCtx::default()
+        }
+    }
+
+    fn factor(&self, axis: Axis) -> f64 {
+        match axis {
+            Axis::X => self.kx,
+            Axis::Y => self.ky,
+        }
+    }
+
+    fn stretch_view_box(&self, v: &str) -> String {
+        match ViewBox::parse(v) {
+            Some(vb) => ViewBox {
+                x: vb.x * self.kx,
+                y: vb.y * self.ky,
+                width: vb.width * self.kx,
+                height: vb.height * self.ky,
+            }
+            .format(&self.ctx(1.0)),
+            None => v.to_string(),
+        }
+    }
+
+    /// `node`'s style from its inline `style` and the `<style>` rules, with
+    /// geometry stretched; `None` when neither sets anything to stretch
+    fn stretched_style(&self, node: Node, is_circle: bool) -> Result<Option<String>> {
+        let props = self.styles.computed_style(node);
+        let tag_name = node.tag_name().name();
+        let stretches = |k: &str| k == "transform" || axis_of(tag_name, k).is_some();
+        // A circle turned ellipse no longer matches `circle` rules
+        if !props
+            .iter()
+            .any(|(k, _)| stretches(k) || (is_circle && k == "r"))
+        {
+            return Ok(None);
+        }
+        let has = |name: &str| props.iter().any(|(k, _)| k == name);
+        let mut out = Vec::with_capacity(props.len() + 1);
+        for (k, v) in &props {
+            let context = || format!("stretch style {} on <{}>", k, tag_name);
+            let x = || scale_length_value(v, &self.ctx(self.kx)).with_context(context);
+            let y = || scale_length_value(v, &self.ctx(self.ky)).with_context(context);
+            match k.as_str() {
+                "transform" => {
+                    let t = stretch_transform_value(v, self.kx, self.ky, EXACT_PRECISION)
+                        .with_context(context)?;
+                    out.push((k.clone(), t));
+                }
+                "r" if is_circle => {
+                    out.push(("rx".to_string(), x()?));
+                    out.push(("ry".to_string(), y()?));
+                }
+                "rx" | "ry" if tag_name == "rect" && !has(if k == "rx" { "ry" } else { "rx" }) => {
+                    out.push(("rx".to_string(), x()?));
+                    out.push(("ry".to_string(), y()?));
+                }
+                _ => match axis_of(tag_name, k) {
+                    Some(Axis::X) => out.push((k.clone(), x()?)),
+                    Some(Axis::Y) => out.push((k.clone(), y()?)),
+                    None => out.push((k.clone(), v.clone())),
+                },
+            }
+        }
+        Ok(Some(svg::serialize_style(&out)))
+    }
+
+    fn write_root(&self, root: Node, w: &mut XmlWriter) -> Result<()> {
+        w.start_element(root.tag_name().name());
+        let style = self.stretched_style(root, false)?;
+        for attr in root.attributes() {
+            let name = svg::qualified_attr_name(root, &attr);
+            let value = match &*name {
+                "width" => scale_length_value(attr.value(), &self.ctx(self.kx))?,
+                "height" => scale_length_value(attr.value(), &self.ctx(self.ky))?,
+                "viewBox" => self.stretch_view_box(attr.value()),
+                "style" if style.is_some() => continue,
+                _ => attr.value().to_string(),
+            };
+            w.write_attribute(&name, &value);
+        }
+        if let Some(style) = &style {
+            w.write_attribute("style", style);
+        }
+        for c in root.children() {
+            self.write_node(c, w)?;
+        }
+        w.end_element();
+        Ok(())
+    }
+
+    fn write_node(&self, node: Node, w: &mut XmlWriter) -> Result<()> {
+        if !node.is_element() {
+            if node.is_text() {
+                w.write_text(node.text().unwrap_or(""));
+            }
+            return Ok(());
+        }
+        let tag_name = node.tag_name().name();
+        let user_space = |units: &str, default: bool| match node.attribute(units) {
+            Some(v) => v == "userSpaceOnUse",
+            None => default,
+        };
+        let (skip_self, skip_children) = match tag_name {
+            "clipPath" => {
+                let bbox = !user_space("clipPathUnits", true);
+                (bbox, bbox)
+            }
+            "mask" => (
+                !user_space("maskUnits", false),
+                !user_space("maskContentUnits", true),
+            ),
+            // A viewBox overrides `patternContentUnits`
+            "pattern" => (
+                !user_space("patternUnits", false),
+                !node.has_attribute("viewBox") && !user_space("patternContentUnits", true),
+            ),
+            "linearGradient" | "radialGradient" => {
+                let bbox = !user_space("gradientUnits", false);
+                (bbox, bbox)
+            }
+            "filter" => (!user_space("filterUnits", false), true),
+            "marker" | "font" => (true, true),
+            _ => (false, false),
+        };
+        if !skip_self
+            && (matches!(tag_name, "linearGradient" | "radialGradient")
+                || (tag_name == "pattern" && !skip_children && !node.has_attribute("viewBox")))
+        {
+            // User-space paint is stretched as a whole by its own transform,
+            // since a radial gradient's `r` has no direction
+            self.write_paint_server(node, w)?;
+            return Ok(());
+        }
+        if skip_self && skip_children {
+            svg::write_verbatim(node, w);
+            return Ok(());
+        }
+
+        let is_circle = tag_name == "circle" && self.kx != self.ky;
+        w.start_element(if is_circle { "ellipse" } else { tag_name });
+        let style = match skip_self {
+            true => None,
+            false => self.stretched_style(node, is_circle)?,
+        };
+        for attr in node.attributes() {
+            let name = svg::qualified_attr_name(node, &attr);
+            let v = attr.value();
+            // A viewBox is stretched with its content, which keeps the
+            // `preserveAspectRatio` fit of the original and so the stretch;
+            // the pattern's own system holds even when its tile is in
+            // bounding-box units
+            if name == "viewBox" && !skip_children {
+                w.write_attribute(&name, &self.stretch_view_box(v));
+                continue;
+            }
+            if name == "patternTransform" && !skip_children {
+                let t = stretch_transform_value(v, self.kx, self.ky, EXACT_PRECISION)
+                    .with_context(|| format!("stretch {} on <{}>", name, tag_name))?;
+                w.write_attribute(&name, &t);
+                continue;
+            }
+            if skip_self {
+                w.write_attribute(&name, v);
+                continue;
+            }
+            let context = || format!("stretch {} on <{}>", name, tag_name);
+            match &*name {
+                "d" | "points" if matches!(tag_name, "path" | "polygon" | "polyline") => {
+                    let d =
+                        stretch_path(v, self.kx, self.ky, &self.ctx(1.0)).with_context(context)?;
+                    w.write_attribute(&name, &d);
+                }
+                "transform" => {
+                    let t = stretch_transform_value(v, self.kx, self.ky, EXACT_PRECISION)
+                        .with_context(context)?;
+                    w.write_attribute(&name, &t);
+                }
+                "style" if style.is_some() => {}
+                "r" if is_circle => {
+                    w.write_attribute("rx", &scale_length_value(v, &self.ctx(self.kx))?);
+                    w.write_attribute("ry", &scale_length_value(v, &self.ctx(self.ky))?);
+                }
+                // One rounded-corner radius stands for both
+                "rx" | "ry"
+                    if tag_name == "rect"
+                        && !node.has_attribute(if name == "rx" { "ry" } else { "rx" }) =>
+                {
+                    w.write_attribute("rx", &scale_length_value(v, &self.ctx(self.kx))?);
+                    w.write_attribute("ry", &scale_length_value(v, &self.ctx(self.ky))?);
+                }
+                _ => match axis_of(tag_name, &name) {
+                    Some(axis) if matches!(tag_name, "text" | "tspan") => {
+                        w.write_attribute(
+                            &name,
+                            &scale_number_list(v, &self.ctx(self.factor(axis))),
+                        );
+                    }
+                    Some(axis) => {
+                        let scaled = scale_length_value(v, &self.ctx(self.factor(axis)))
+                            .with_context(context)?;
+                        w.write_attribute(&name, &scaled);
+                    }
+                    None => w.write_attribute(&name, v),
+                },
+            }
+        }
+        if let Some(style) = &style {
+            w.write_attribute("style", style);
+        }
+        for c in node.children() {
+            if skip_children {
+                match c.is_element() {
+                    true => svg::write_verbatim(c, w),
+                    false => w.write_text(c.text().unwrap_or("")),
+                }
+            } else {
+                self.write_node(c, w)?;
+            }
+        }
+        w.end_element();
+        Ok(())
+    }
+
+    fn write_paint_server(&self, node: Node, w: &mut XmlWriter) -> Result<()> {
+        let transform_name = if node.has_tag_name("pattern") {
+            "patternTransform"
+        } else {
+            "gradientTransform"
+        };
+        w.start_element(node.tag_name().name());
+        for attr in node.attributes() {
+            let name = svg::qualified_attr_name(node, &attr);
+            if name != transform_name {
+                w.write_attribute(&name, attr.value());
+            }
+        }
+        let stretch = format!(
+            "scale({} {})",
+            self.ctx(1.0).fmt(self.kx),
+            self.ctx(1.0).fmt(self.ky)
+        );
+        match node.attribute(transform_name) {
+            Some(t) => w.write_attribute(transform_name, &format!("{} {}", stretch, t)),
+            None => w.write_attribute(transform_name, &stretch),
+        }
+        for c in node.children() {
+            match c.is_element() {
+                true => svg::write_verbatim(c, w),
+                false => w.write_text(c.text().unwrap_or("")),
+            }
+        }
+        w.end_element();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretches_geometry_per_axis() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24"><rect x="2" y="4" width="10" height="6" rx="1"/><circle cx="12" cy="12" r="4" stroke-width="2"/><path d="M0 0 L10 10" transform="translate(1 2)"/><text x="1 2" y="3">a</text></svg>"#,
+        )?;
+        let out = stretch_document(&doc, 2.0, 1.0)?;
+        assert!(
+            out.contains(r#"width="48" height="24" viewBox="0 0 48 24""#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<rect x="4" y="4" width="20" height="6" rx="2" ry="1"/>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<ellipse cx="24" cy="12" rx="8" ry="4" stroke-width="2"/>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<path d="M0 0 L20 10" transform="translate(2,2)"/>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<text x="2 4" y="3">a</text>"#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn user_space_paint_is_stretched_by_its_transform() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><radialGradient id="r" gradientUnits="userSpaceOnUse" r="5" gradientTransform="rotate(10)"/><linearGradient id="b" x2="1"/><clipPath clipPathUnits="objectBoundingBox"><rect width="0.5" height="1"/></clipPath></svg>"#,
+        )?;
+        let out = stretch_document(&doc, 1.0, 3.0)?;
+        assert!(
+            out.contains(r#"<radialGradient id="r" gradientUnits="userSpaceOnUse" r="5" gradientTransform="scale(1 3) rotate(10)"/>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<linearGradient id="b" x2="1"/>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<rect width="0.5" height="1"/>"#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn nested_view_boxes_are_stretched_with_their_content() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 40 20"><svg width="20" height="20" viewBox="0 0 20 20"><rect width="10" height="10"/></svg><symbol id="s" viewBox="0 0 10 10"><circle cx="5" cy="5" r="5"/></symbol><pattern id="p" width="0.5" height="0.5" viewBox="0 0 4 4"><rect x="1" width="2" height="2"/></pattern></svg>"#,
+        )?;
+        let out = stretch_document(&doc, 2.0, 1.0)?;
+        assert!(
+            out.contains(r#"<svg width="40" height="20" viewBox="0 0 40 20"><rect width="20" height="10"/></svg>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<symbol id="s" viewBox="0 0 20 10"><ellipse cx="10" cy="5" rx="10" ry="5"/></symbol>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<pattern id="p" width="0.5" height="0.5" viewBox="0 0 8 4"><rect x="2" width="4" height="2"/></pattern>"#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn style_geometry_is_stretched_inline() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><style>circle { r: 2px; fill: red }</style><rect style="x: 1px; transform: translate(1, 2); opacity: 0.5"/><circle cx="5" cy="5"/><path style="fill: blue"/></svg>"#,
+        )?;
+        let out = stretch_document(&doc, 3.0, 1.0)?;
+        assert!(
+            out.contains(r#"<rect style="x:3px; transform:translate(3,2); opacity:0.5"/>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<ellipse cx="15" cy="5" style="rx:6px; ry:2px; fill:red"/>"#),
+            "got: {out}"
+        );
+        assert!(out.contains(r#"<path style="fill: blue"/>"#), "got: {out}");
+        Ok(())
+    }
+}
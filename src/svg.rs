@@ -1,14 +1,56 @@
 use crate::{
+    bounds, data_uri,
+    explain::{Decision, Recorder},
+    filter,
     path::scale_path,
-    scale::ScaleCtx,
-    transform::{parse_transform_list, scale_transform_value},
+    scale::{check_scale, parse_scale_expr, ScaleCtx},
+    transform::{conjugate_transform_value, parse_transform_list, scale_transform_value},
+    viewbox::ViewBox,
 };
 use anyhow::{Context, Result};
 use roxmltree::Node;
+use std::borrow::Cow;
+use std::io::{self, Write};
 use xmlwriter::XmlWriter;
 
+/// `vector-effect` keywords that change how an element reacts to scaling.
+/// SVG 2 allows a list, e.g. `non-scaling-stroke non-rotation`; `non-rotation`
+/// and `fixed-position` only cancel rotation/translation and need no handling.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct VectorEffects {
+    /// Stroke widths are in screen units
+    pub non_scaling_stroke: bool,
+    /// The element's own geometry is in screen units
+    pub non_scaling_size: bool,
+}
+
+impl VectorEffects {
+    pub(crate) fn from_values<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut effects = VectorEffects::default();
+        for keyword in values.into_iter().flat_map(str::split_whitespace) {
+            match keyword {
+                "non-scaling-stroke" => effects.non_scaling_stroke = true,
+                "non-scaling-size" => effects.non_scaling_size = true,
+                _ => {}
+            }
+        }
+        effects
+    }
+}
+
+/// The `vector-effect` value kept by `--fix-stroke`, which bakes the screen
+/// units into scaled geometry: `non-scaling-*` keywords are dropped, the rest
+/// kept; `None` when nothing is left
+pub(crate) fn fix_vector_effect(value: &str) -> Option<String> {
+    let kept: Vec<&str> = value
+        .split_whitespace()
+        .filter(|k| !matches!(*k, "non-scaling-stroke" | "non-scaling-size" | "none"))
+        .collect();
+    (!kept.is_empty()).then(|| kept.join(" "))
+}
+
 /// Check if transform contains any non-translate components
-fn has_non_translate_transform(transform: &str) -> Result<bool> {
+pub(crate) fn has_non_translate_transform(transform: &str) -> Result<bool> {
     let list = parse_transform_list(transform)?;
     Ok(list.iter().any(|t| t.name != "translate"))
 }
@@ -26,26 +68,285 @@ struct SimpleSelector {
     element: Option<String>,
     id: Option<String>,
     classes: Vec<String>,
+    attrs: Vec<AttrSelector>,
+}
+
+/// `[name]`, `[name=value]`, `[name^=value]`, `[name$=value]` or
+/// `[name*=value]`
+#[derive(Debug, Clone)]
+struct AttrSelector {
+    name: String,
+    op: AttrOp,
+    value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttrOp {
+    Exists,
+    Equals,
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+impl AttrSelector {
+    fn parse(inner: &str) -> Option<Self> {
+        let inner = inner.trim();
+        let Some(eq) = inner.find('=') else {
+            return is_simple_ident(inner).then(|| AttrSelector {
+                name: inner.to_string(),
+                op: AttrOp::Exists,
+                value: String::new(),
+            });
+        };
+        let (name, op) = match inner[..eq].trim_end() {
+            n if n.ends_with('^') => (&n[..n.len() - 1], AttrOp::Prefix),
+            n if n.ends_with('$') => (&n[..n.len() - 1], AttrOp::Suffix),
+            n if n.ends_with('*') => (&n[..n.len() - 1], AttrOp::Contains),
+            n => (n, AttrOp::Equals),
+        };
+        let name = name.trim();
+        if !is_simple_ident(name) {
+            return None;
+        }
+        let value = inner[eq + 1..].trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+            .unwrap_or(value);
+        Some(AttrSelector {
+            name: name.to_string(),
+            op,
+            value: value.to_string(),
+        })
+    }
+
+    fn matches(&self, node: Node) -> bool {
+        let Some(actual) = node.attributes().find(|a| a.name() == self.name) else {
+            return false;
+        };
+        let actual = actual.value();
+        match self.op {
+            AttrOp::Exists => true,
+            AttrOp::Equals => actual == self.value,
+            AttrOp::Prefix => !self.value.is_empty() && actual.starts_with(&self.value),
+            AttrOp::Suffix => !self.value.is_empty() && actual.ends_with(&self.value),
+            AttrOp::Contains => !self.value.is_empty() && actual.contains(&self.value),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum SelectorRelation {
     Descendant,
     Child,
+    /// `A + B`: B directly follows A
+    Adjacent,
+    /// `A ~ B`: B follows A among the same parent's children
+    Sibling,
 }
 
 #[derive(Debug, Clone)]
 struct StyleSelector {
+    /// Left-hand side of the relation: an ancestor, or a preceding sibling
     ancestor: Option<SimpleSelector>,
     relation: Option<SelectorRelation>,
     target: SimpleSelector,
 }
 
+/// Element being emitted, as seen by an [`AttributeHook`]
+#[derive(Debug, Clone, Copy)]
+pub struct ElementCtx<'a> {
+    pub tag_name: &'a str,
+    pub id: &'a str,
+    /// Geometry scaling is suppressed here (non-translate transform in scope,
+    /// `objectBoundingBox` units, ...)
+    pub skip_scale: bool,
+}
+
+/// How a hooked attribute should be treated
+#[derive(Debug, Clone, PartialEq)]
+pub enum Handling {
+    /// Fall through to the next hook, then to the built-in handling
+    Default,
+    /// Emit the value unchanged
+    Keep,
+    /// Emit this value instead
+    Replace(String),
+    /// Scale with the given semantics, unless `ElementCtx::skip_scale` is set
+    Scale(ValueKind),
+}
+
+/// Scaling semantics for an attribute value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// A single length with an optional unit
+    Length,
+    /// A number list such as `stroke-dasharray`
+    List,
+    /// A number list scaled by `1 / scale`, such as `baseFrequency`
+    Inverse,
+}
+
+impl std::str::FromStr for ValueKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "length" => Ok(ValueKind::Length),
+            "list" => Ok(ValueKind::List),
+            "inverse" => Ok(ValueKind::Inverse),
+            _ => Err(anyhow::anyhow!(
+                "unknown value kind: {} (expected length, list or inverse)",
+                s
+            )),
+        }
+    }
+}
+
+/// Per-attribute callback: `(element, attribute name, value) -> Handling`
+pub type AttributeHook = Box<dyn Fn(&ElementCtx, &str, &str) -> Handling + Send + Sync>;
+
+/// Parse a `NAME[:length|list|inverse]` spec; the kind defaults to `length`.
+/// Prefixed names such as `inkscape:cx` are kept whole.
+pub fn parse_scale_attr(spec: &str) -> Result<(String, ValueKind)> {
+    if let Some((name, kind)) = spec.rsplit_once(':') {
+        if let Ok(kind) = kind.parse::<ValueKind>() {
+            if name.is_empty() {
+                return Err(anyhow::anyhow!("missing attribute name: {}", spec));
+            }
+            return Ok((name.to_string(), kind));
+        }
+    }
+    if spec.is_empty() {
+        return Err(anyhow::anyhow!("missing attribute name"));
+    }
+    Ok((spec.to_string(), ValueKind::Length))
+}
+
+/// Hook scaling each listed attribute with its given semantics
+pub fn scale_attr_hook(attrs: Vec<(String, ValueKind)>) -> AttributeHook {
+    Box::new(move |_, name, _| {
+        attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, kind)| Handling::Scale(*kind))
+            .unwrap_or(Handling::Default)
+    })
+}
+
+fn apply_handling(handling: Handling, v: &str, ctx: &ScaleCtx, skip_scale: bool) -> Result<String> {
+    match handling {
+        Handling::Default | Handling::Keep => Ok(v.to_string()),
+        Handling::Replace(nv) => Ok(nv),
+        Handling::Scale(_) if skip_scale => Ok(v.to_string()),
+        Handling::Scale(ValueKind::Length) => scale_length_value(v, ctx),
+        Handling::Scale(ValueKind::List) => Ok(scale_number_list(v, ctx)),
+        Handling::Scale(ValueKind::Inverse) => Ok(scale_number_list_inverse(v, ctx)),
+    }
+}
+
 fn scale_transform_all(v: &str, scale: f64, precision: usize) -> Result<String> {
     scale_transform_value(v, scale, precision)
 }
 
-fn parse_style(input: &str) -> Vec<(String, String)> {
+/// How an element's own `transform` is rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TransformMode {
+    /// Fold the scale into the transform; content under a non-translate
+    /// transform stays unscaled
+    Fold,
+    /// Keep the linear part and scale only the translation while the content
+    /// scales, so a reference box (`transform-box`) or `transform-origin`
+    /// moves with it
+    Conjugate,
+    /// The element lives in unscaled content; leave the transform as written
+    Keep,
+}
+
+impl TransformMode {
+    pub(crate) fn of_element(
+        inside_unscaled: bool,
+        has_non_translate_transform: bool,
+        transform_origin: Option<&str>,
+        transform_box: Option<&str>,
+    ) -> Self {
+        if inside_unscaled {
+            TransformMode::Keep
+        } else if has_non_translate_transform
+            && (transform_origin.is_some() || transform_box.is_some_and(|b| b.trim() != "view-box"))
+        {
+            TransformMode::Conjugate
+        } else {
+            TransformMode::Fold
+        }
+    }
+
+    /// Whether the element's content is left unscaled because of its own transform
+    pub(crate) fn folds(self, has_non_translate_transform: bool) -> bool {
+        self == TransformMode::Fold && has_non_translate_transform
+    }
+
+    fn apply<'v>(self, v: &'v str, ctx: &ScaleCtx) -> Result<Cow<'v, str>> {
+        match self {
+            TransformMode::Keep => Ok(Cow::Borrowed(v)),
+            TransformMode::Fold => scale_transform_all(v, ctx.scale, ctx.precision).map(Cow::Owned),
+            TransformMode::Conjugate => {
+                conjugate_transform_value(v, ctx.scale, ctx.precision).map(Cow::Owned)
+            }
+        }
+    }
+}
+
+/// Elements whose `x`/`y`/`dx`/`dy`/`rotate` are per-glyph lists
+fn is_text_positioning(tag_name: &str) -> bool {
+    matches!(tag_name, "text" | "tspan" | "tref" | "altGlyph")
+}
+
+/// SVG 2 `refX`/`refY` keywords naming an edge or the center of the box
+fn is_ref_keyword(v: &str) -> bool {
+    matches!(v.trim(), "left" | "center" | "right" | "top" | "bottom")
+}
+
+/// Numeric value of a `refX`/`refY` keyword on a marker: an edge or the
+/// center of its viewBox, or of `markerWidth`/`markerHeight` without one
+pub(crate) fn resolve_ref_keyword(node: Node, attr: &str, keyword: &str) -> Option<f64> {
+    let fraction = match (attr, keyword.trim()) {
+        (_, "center") => 0.5,
+        ("refX", "left") | ("refY", "top") => 0.0,
+        ("refX", "right") | ("refY", "bottom") => 1.0,
+        _ => return None,
+    };
+    let (start, size) = match node.attribute("viewBox").and_then(ViewBox::parse) {
+        Some(vb) if attr == "refX" => (vb.x, vb.width),
+        Some(vb) => (vb.y, vb.height),
+        None => {
+            let size_attr = if attr == "refX" {
+                "markerWidth"
+            } else {
+                "markerHeight"
+            };
+            let size = node
+                .attribute(size_attr)
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(3.0);
+            (0.0, size)
+        }
+    };
+    Some(start + fraction * size)
+}
+
+/// Scale the absolute lengths of a `transform-origin`; keywords and
+/// percentages resolve against a reference box that already scales
+fn scale_transform_origin(v: &str, ctx: &ScaleCtx) -> Result<String> {
+    let parts: Vec<String> = v
+        .split_whitespace()
+        .map(|part| scale_length_value(part, ctx))
+        .collect::<Result<_>>()?;
+    Ok(parts.join(" "))
+}
+
+pub(crate) fn parse_style(input: &str) -> Vec<(String, String)> {
     let mut out = Vec::new();
     for part in input.split(';') {
         let part = part.trim();
@@ -84,6 +385,23 @@ fn split_num_and_unit(token: &str) -> (&str, &str) {
     (num, unit)
 }
 
+/// Convert an absolute length such as `24`, `10mm` or `18pt` to px at `dpi`.
+/// Relative lengths (`%`, `em`, ...) yield `None`.
+pub fn length_to_px(value: &str, dpi: f64) -> Option<f64> {
+    let (num_part, unit) = split_num_and_unit(value.trim());
+    let num: f64 = num_part.parse().ok()?;
+    let per_unit = match unit.trim() {
+        "" | "px" => 1.0,
+        "in" => dpi,
+        "cm" => dpi / 2.54,
+        "mm" => dpi / 25.4,
+        "pt" => dpi / 72.0,
+        "pc" => dpi / 6.0,
+        _ => return None,
+    };
+    Some(num * per_unit)
+}
+
 fn scale_number_token(token: &str, ctx: &ScaleCtx) -> Option<String> {
     let t = token.trim();
     if t.is_empty() {
@@ -101,14 +419,14 @@ fn scale_number_token(token: &str, ctx: &ScaleCtx) -> Option<String> {
         return None;
     }
     let num: f64 = num_part.parse().ok()?;
-    let mut out = ctx.fmt(num * ctx.scale);
+    let mut out = ctx.fmt_scaled(num);
     if !unit.is_empty() {
         out.push_str(unit);
     }
     Some(out)
 }
 
-fn scale_number_list(value: &str, ctx: &ScaleCtx) -> String {
+pub(crate) fn scale_number_list(value: &str, ctx: &ScaleCtx) -> String {
     let mut out = String::with_capacity(value.len());
     let mut buf = String::new();
 
@@ -125,7 +443,8 @@ fn scale_number_list(value: &str, ctx: &ScaleCtx) -> String {
     };
 
     for c in value.chars() {
-        if is_num_char(c) || c.is_ascii_alphabetic() {
+        // `%` stays in the token so percentages are recognized and kept
+        if is_num_char(c) || c.is_ascii_alphabetic() || c == '%' {
             buf.push(c);
         } else {
             flush_buf(&mut out, &mut buf);
@@ -136,6 +455,80 @@ fn scale_number_list(value: &str, ctx: &ScaleCtx) -> String {
     out
 }
 
+/// CSS basic shapes whose length arguments scale with the geometry
+const BASIC_SHAPES: &[&str] = &["circle(", "ellipse(", "inset(", "polygon("];
+
+/// Scale the lengths in a `clip-path` basic shape such as
+/// `circle(40px at 20px 20px)`; `url(#id)` references, keywords and
+/// percentages are kept
+fn scale_basic_shape<'v>(value: &'v str, ctx: &ScaleCtx) -> Cow<'v, str> {
+    let t = value.trim_start();
+    let is_shape = BASIC_SHAPES.iter().any(|shape| {
+        t.get(..shape.len())
+            .is_some_and(|p| p.eq_ignore_ascii_case(shape))
+    });
+    if is_shape {
+        Cow::Owned(scale_number_list(value, ctx))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Scale the offsets in a legacy `clip: rect(top, right, bottom, left)`;
+/// `auto` (as the whole value or one offset) is kept
+fn scale_clip_rect<'v>(value: &'v str, ctx: &ScaleCtx) -> Cow<'v, str> {
+    let is_rect = value
+        .trim_start()
+        .get(..5)
+        .is_some_and(|p| p.eq_ignore_ascii_case("rect("));
+    if is_rect {
+        Cow::Owned(scale_number_list(value, ctx))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Precision of values resolved to numbers before they are scaled
+const RESOLVED_PRECISION: usize = 12;
+
+/// Replace percentages in a `stroke-dasharray` with user units. They are
+/// relative to the normalized diagonal `sqrt((w² + h²) / 2)` of the nearest
+/// `<svg>` viewport (its viewBox, else its numeric width/height); `None`
+/// when there is nothing to resolve or no known viewport.
+pub(crate) fn resolve_dash_percentages(value: &str, node: Node, ctx: &ScaleCtx) -> Option<String> {
+    if !value.contains('%') {
+        return None;
+    }
+    let viewport = node.ancestors().find(|n| n.tag_name().name() == "svg")?;
+    let (width, height) = match viewport.attribute("viewBox").and_then(ViewBox::parse) {
+        Some(vb) => (vb.width, vb.height),
+        None => (
+            viewport.attribute("width")?.trim().parse::<f64>().ok()?,
+            viewport.attribute("height")?.trim().parse::<f64>().ok()?,
+        ),
+    };
+    let reference = ((width * width + height * height) / 2.0).sqrt();
+
+    let mut out = String::with_capacity(value.len());
+    for (i, part) in value.split(',').enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let mut first = true;
+        for token in part.split_whitespace() {
+            if !first || part.starts_with(char::is_whitespace) {
+                out.push(' ');
+            }
+            first = false;
+            match token.strip_suffix('%').and_then(|n| n.parse::<f64>().ok()) {
+                Some(pct) => ctx.push_fmt(&mut out, pct / 100.0 * reference),
+                None => out.push_str(token),
+            }
+        }
+    }
+    Some(out)
+}
+
 fn scale_number_list_inverse(value: &str, ctx: &ScaleCtx) -> String {
     if ctx.scale == 0.0 {
         return value.to_string();
@@ -154,6 +547,8 @@ fn scale_number_list_inverse(value: &str, ctx: &ScaleCtx) -> String {
                 scale: inv,
                 precision: ctx.precision,
                 fix_stroke: ctx.fix_stroke,
+                exact: ctx.exact,
+                stroke_exponent: ctx.stroke_exponent,
             },
         ) {
             out.push_str(&scaled);
@@ -164,7 +559,8 @@ fn scale_number_list_inverse(value: &str, ctx: &ScaleCtx) -> String {
     };
 
     for c in value.chars() {
-        if is_num_char(c) || c.is_ascii_alphabetic() {
+        // `%` stays in the token so percentages are recognized and kept
+        if is_num_char(c) || c.is_ascii_alphabetic() || c == '%' {
             buf.push(c);
         } else {
             flush_buf(&mut out, &mut buf);
@@ -175,7 +571,7 @@ fn scale_number_list_inverse(value: &str, ctx: &ScaleCtx) -> String {
     out
 }
 
-fn scale_length_value(val: &str, ctx: &ScaleCtx) -> Result<String> {
+pub(crate) fn scale_length_value(val: &str, ctx: &ScaleCtx) -> Result<String> {
     let t = val.trim();
     if t.is_empty() {
         return Ok(val.to_string());
@@ -194,7 +590,7 @@ fn scale_length_value(val: &str, ctx: &ScaleCtx) -> Result<String> {
     let num: f64 = num_part
         .parse()
         .with_context(|| format!("invalid length: {}", val))?;
-    let mut out = ctx.fmt(num * ctx.scale);
+    let mut out = ctx.fmt_scaled(num);
     if !unit.is_empty() {
         out.push_str(unit);
     }
@@ -234,19 +630,24 @@ fn parse_simple_selector(sel: &str) -> Option<SimpleSelector> {
     if sel.is_empty() {
         return None;
     }
-    if sel.contains(['>', '+', '~', '[', ']', ':']) {
+    if sel.contains(['>', '+', '~', ':']) {
         return None;
     }
 
     let mut element: Option<String> = None;
     let mut id: Option<String> = None;
     let mut classes: Vec<String> = Vec::new();
+    let mut attrs: Vec<AttrSelector> = Vec::new();
     let mut i = 0;
     let bytes = sel.as_bytes();
 
     while i < bytes.len() {
         let c = bytes[i] as char;
-        if c == '.' || c == '#' {
+        if c == '[' {
+            let end = i + sel[i..].find(']')?;
+            attrs.push(AttrSelector::parse(&sel[i + 1..end])?);
+            i = end + 1;
+        } else if c == '.' || c == '#' {
             let kind = c;
             i += 1;
             let start = i;
@@ -297,7 +698,7 @@ fn parse_simple_selector(sel: &str) -> Option<SimpleSelector> {
         }
     }
 
-    if element.is_none() && id.is_none() && classes.is_empty() {
+    if element.is_none() && id.is_none() && classes.is_empty() && attrs.is_empty() {
         return None;
     }
 
@@ -305,6 +706,7 @@ fn parse_simple_selector(sel: &str) -> Option<SimpleSelector> {
         element,
         id,
         classes,
+        attrs,
     })
 }
 
@@ -313,8 +715,13 @@ fn parse_selector(s: &str) -> Option<StyleSelector> {
     if sel.is_empty() {
         return None;
     }
-    if sel.contains('>') {
-        let mut parts: Vec<&str> = sel.split('>').map(|p| p.trim()).collect();
+    let combinators = [
+        ('>', SelectorRelation::Child),
+        ('+', SelectorRelation::Adjacent),
+        ('~', SelectorRelation::Sibling),
+    ];
+    if let Some(&(combinator, relation)) = combinators.iter().find(|(c, _)| sel.contains(*c)) {
+        let mut parts: Vec<&str> = sel.split(combinator).map(|p| p.trim()).collect();
         parts.retain(|p| !p.is_empty());
         if parts.len() != 2 {
             return None;
@@ -323,7 +730,7 @@ fn parse_selector(s: &str) -> Option<StyleSelector> {
         let target = parse_simple_selector(parts[1])?;
         return Some(StyleSelector {
             ancestor: Some(ancestor),
-            relation: Some(SelectorRelation::Child),
+            relation: Some(relation),
             target,
         });
     }
@@ -355,8 +762,8 @@ fn selector_specificity_simple(sel: &SimpleSelector) -> u32 {
     if sel.id.is_some() {
         score += 100;
     }
-    if !sel.classes.is_empty() {
-        score += 10 * sel.classes.len() as u32;
+    if !sel.classes.is_empty() || !sel.attrs.is_empty() {
+        score += 10 * (sel.classes.len() + sel.attrs.len()) as u32;
     }
     if sel.element.is_some() {
         score += 1;
@@ -372,20 +779,131 @@ fn selector_specificity(sel: &StyleSelector) -> u32 {
     score
 }
 
+/// One top-level statement of a stylesheet, as byte ranges into its text
+#[derive(Debug, Clone, PartialEq)]
+struct CssItem {
+    /// Selectors of a rule, or `@name ...` of an at-rule
+    prelude: std::ops::Range<usize>,
+    /// Text between the braces; `None` for statements like `@import ...;`
+    block: Option<std::ops::Range<usize>>,
+}
+
+/// Index just past the string or comment starting at `i`, if one does
+fn skip_css_string_or_comment(b: &[u8], i: usize) -> Option<usize> {
+    match b[i] {
+        q @ (b'"' | b'\'') => {
+            let mut j = i + 1;
+            while j < b.len() && b[j] != q {
+                j += if b[j] == b'\\' { 2 } else { 1 };
+            }
+            Some((j + 1).min(b.len()))
+        }
+        b'/' if b.get(i + 1) == Some(&b'*') => {
+            let end = b[i + 2..]
+                .windows(2)
+                .position(|w| w == b"*/")
+                .map_or(b.len(), |p| i + 2 + p + 2);
+            Some(end)
+        }
+        _ => None,
+    }
+}
+
+/// Split a stylesheet into top-level rules and at-rules, matching nested
+/// braces so that blocks like `@media { ... }` stay whole
+fn css_items(input: &str) -> Vec<CssItem> {
+    let b = input.as_bytes();
+    let mut items = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < b.len() {
+            if b[i].is_ascii_whitespace() {
+                i += 1;
+            } else if b[i] == b'/' && b.get(i + 1) == Some(&b'*') {
+                i = skip_css_string_or_comment(b, i).unwrap_or(b.len());
+            } else {
+                break;
+            }
+        }
+        if i >= b.len() {
+            break;
+        }
+        let start = i;
+        let is_at_rule = b[i] == b'@';
+        let mut j = i;
+        while j < b.len() && b[j] != b'{' && b[j] != b'}' && !(is_at_rule && b[j] == b';') {
+            j = skip_css_string_or_comment(b, j).unwrap_or(j + 1);
+        }
+        if j >= b.len() {
+            break;
+        }
+        match b[j] {
+            b';' => {
+                items.push(CssItem {
+                    prelude: start..j,
+                    block: None,
+                });
+                i = j + 1;
+            }
+            // Stray closing brace: skip it
+            b'}' => i = j + 1,
+            _ => {
+                let mut depth = 1;
+                let mut k = j + 1;
+                while k < b.len() {
+                    if let Some(next) = skip_css_string_or_comment(b, k) {
+                        k = next;
+                        continue;
+                    }
+                    match b[k] {
+                        b'{' => depth += 1,
+                        b'}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    k += 1;
+                }
+                items.push(CssItem {
+                    prelude: start..j,
+                    block: Some(j + 1..k.min(b.len())),
+                });
+                i = k + 1;
+            }
+        }
+    }
+    items
+}
+
+/// At-rules whose block holds ordinary rules that only apply conditionally
+/// (or in a separate cascade layer), so they are scaled in place instead of
+/// being applied to elements
+fn is_group_at_rule(prelude: &str) -> bool {
+    let name = prelude[1..]
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .unwrap_or("");
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "media" | "supports" | "container" | "layer" | "document" | "-moz-document"
+    )
+}
+
 fn parse_css_rules(input: &str) -> Vec<StyleRule> {
-    let cleaned = strip_css_comments(input);
     let mut rules = Vec::new();
-    let mut i = 0;
     let mut order: u32 = 0;
-    while let Some(open) = cleaned[i..].find('{') {
-        let open_idx = i + open;
-        let selector_text = cleaned[i..open_idx].trim();
-        let rest = &cleaned[open_idx + 1..];
-        let Some(close) = rest.find('}') else {
-            break;
+    for item in css_items(input) {
+        let selector_text = strip_css_comments(&input[item.prelude]);
+        let selector_text = selector_text.trim();
+        // At-rules (@font-face, @media, ...) never apply to elements directly
+        let Some(block) = item.block.filter(|_| !selector_text.starts_with('@')) else {
+            order = order.saturating_add(1);
+            continue;
         };
-        let body = rest[..close].trim();
-        let props = parse_style(body);
+        let props = parse_style(strip_css_comments(&input[block]).trim());
         if !selector_text.is_empty() && !props.is_empty() {
             for sel in selector_text.split(',') {
                 if let Some(selector) = parse_selector(sel) {
@@ -399,12 +917,95 @@ fn parse_css_rules(input: &str) -> Vec<StyleRule> {
                 }
             }
         }
-        i = open_idx + 1 + close + 1;
         order = order.saturating_add(1);
     }
     rules
 }
 
+/// Rewrite `<style>` text: rules inside grouping at-rules (`@media`,
+/// `@supports`, ...) get their values scaled in place, since they are not
+/// applied to elements; everything else, including `@font-face` and
+/// `@import`, is emitted exactly as written
+fn scale_stylesheet<'t>(input: &'t str, ctx: &ScaleCtx) -> Result<Cow<'t, str>> {
+    let mut out = String::new();
+    let mut copied = 0;
+    for item in css_items(input) {
+        let Some(block) = item.block else {
+            continue;
+        };
+        let prelude = input[item.prelude].trim_start();
+        if !prelude.starts_with('@') || !is_group_at_rule(prelude) {
+            continue;
+        }
+        let inner = &input[block.clone()];
+        let scaled = scale_group_rules(inner, ctx)?;
+        if scaled != inner {
+            out.push_str(&input[copied..block.start]);
+            out.push_str(&scaled);
+            copied = block.end;
+        }
+    }
+    if copied == 0 {
+        return Ok(Cow::Borrowed(input));
+    }
+    out.push_str(&input[copied..]);
+    Ok(Cow::Owned(out))
+}
+
+/// Scale the declarations of every rule in a group block, recursing into
+/// nested groups
+fn scale_group_rules(input: &str, ctx: &ScaleCtx) -> Result<String> {
+    let mut out = String::with_capacity(input.len());
+    let mut copied = 0;
+    for item in css_items(input) {
+        let Some(block) = item.block else {
+            continue;
+        };
+        let prelude = input[item.prelude].trim_start();
+        let scaled = if !prelude.starts_with('@') {
+            scale_declarations(&input[block.clone()], ctx)?
+        } else if is_group_at_rule(prelude) {
+            scale_group_rules(&input[block.clone()], ctx)?
+        } else {
+            continue;
+        };
+        out.push_str(&input[copied..block.start]);
+        out.push_str(&scaled);
+        copied = block.end;
+    }
+    out.push_str(&input[copied..]);
+    Ok(out)
+}
+
+/// Scale the values of `prop: value; ...`, keeping the surrounding whitespace
+/// and `!important`
+fn scale_declarations(body: &str, ctx: &ScaleCtx) -> Result<String> {
+    let mut out = String::with_capacity(body.len());
+    for (i, decl) in body.split(';').enumerate() {
+        if i > 0 {
+            out.push(';');
+        }
+        let Some((key, value)) = decl.split_once(':') else {
+            out.push_str(decl);
+            continue;
+        };
+        let value_start = value.len() - value.trim_start().len();
+        let trimmed = value.trim();
+        let (plain, important) = match trimmed.strip_suffix("!important") {
+            Some(v) => (v.trim_end(), &trimmed[v.trim_end().len()..]),
+            None => (trimmed, ""),
+        };
+        let scaled = scale_style_value(key.trim(), plain, ctx, false, false, TransformMode::Fold)?;
+        out.push_str(key);
+        out.push(':');
+        out.push_str(&value[..value_start]);
+        out.push_str(&scaled);
+        out.push_str(important);
+        out.push_str(&value[value_start + trimmed.len()..]);
+    }
+    Ok(out)
+}
+
 fn collect_style_rules(root: Node) -> Vec<StyleRule> {
     let mut rules = Vec::new();
     for n in root.descendants() {
@@ -418,7 +1019,7 @@ fn collect_style_rules(root: Node) -> Vec<StyleRule> {
     rules
 }
 
-fn serialize_style(props: &[(String, String)]) -> String {
+pub(crate) fn serialize_style(props: &[(String, String)]) -> String {
     let mut s = String::new();
     for (i, (k, v)) in props.iter().enumerate() {
         if i > 0 {
@@ -474,7 +1075,7 @@ fn matches_simple_selector(sel: &SimpleSelector, node: Node) -> bool {
             }
         }
     }
-    true
+    sel.attrs.iter().all(|a| a.matches(node))
 }
 
 fn matches_selector(sel: &StyleSelector, node: Node) -> bool {
@@ -489,6 +1090,17 @@ fn matches_selector(sel: &StyleSelector, node: Node) -> bool {
                 }
                 return false;
             }
+            Some(SelectorRelation::Adjacent) => {
+                return node
+                    .prev_sibling_element()
+                    .is_some_and(|prev| matches_simple_selector(anc, prev));
+            }
+            Some(SelectorRelation::Sibling) => {
+                return node
+                    .prev_siblings()
+                    .skip(1)
+                    .any(|prev| prev.is_element() && matches_simple_selector(anc, prev));
+            }
             _ => {
                 for a in node.ancestors().skip(1) {
                     if a.is_element() && matches_simple_selector(anc, a) {
@@ -517,26 +1129,39 @@ fn collect_matching_style_props(rules: &[StyleRule], node: Node) -> Vec<(String,
     props
 }
 
-fn scale_style_value(
+pub(crate) fn scale_style_value(
     key: &str,
     val: &str,
     ctx: &ScaleCtx,
     skip_scale: bool,
     has_non_scaling_stroke: bool,
+    transform_mode: TransformMode,
 ) -> Result<String> {
     match key {
-        "transform" => scale_transform_all(val, ctx.scale, ctx.precision)
+        "transform" => transform_mode
+            .apply(val, ctx)
+            .map(Cow::into_owned)
             .with_context(|| format!("transform scale failed in style: {}", val)),
+        "transform-origin" if transform_mode == TransformMode::Conjugate && !skip_scale => {
+            scale_transform_origin(val, ctx)
+                .with_context(|| format!("invalid transform-origin in style: {}", val))
+        }
+        "refX" | "refY" if is_ref_keyword(val) => Ok(val.to_string()),
         "stroke-width" | "width" | "height" | "x" | "y" | "z" | "cx" | "cy" | "r" | "rx" | "ry"
         | "x1" | "y1" | "x2" | "y2" | "font-size" | "letter-spacing" | "stroke-dashoffset"
         | "dx" | "dy" | "markerWidth" | "markerHeight" | "refX" | "refY" | "surfaceScale"
-        | "pointsAtX" | "pointsAtY" | "pointsAtZ" => {
+        | "pointsAtX" | "pointsAtY" | "pointsAtZ" | "baseline-shift" | "word-spacing" => {
             if skip_scale {
                 return Ok(val.to_string());
             }
             if key == "stroke-width" && has_non_scaling_stroke && !ctx.fix_stroke {
                 return Ok(val.to_string());
             }
+            let ctx = if key == "stroke-width" {
+                &ctx.for_stroke()
+            } else {
+                ctx
+            };
             scale_length_value(val, ctx)
                 .with_context(|| format!("invalid {} in style: {}", key, val))
         }
@@ -549,7 +1174,8 @@ fn scale_style_value(
             }
             Ok(scale_number_list(val, ctx))
         }
-        "stdDeviation" | "radius" | "kernelUnitLength" => {
+        // Legacy `new x y w h` region; keywords pass through unchanged
+        "stdDeviation" | "radius" | "kernelUnitLength" | "enable-background" => {
             if skip_scale {
                 return Ok(val.to_string());
             }
@@ -559,83 +1185,1031 @@ fn scale_style_value(
             if skip_scale {
                 return Ok(val.to_string());
             }
-            Ok(scale_number_list_inverse(val, ctx))
+            Ok(filter::scale_base_frequency(val, ctx))
+        }
+        "clip-path" if !skip_scale => Ok(scale_basic_shape(val, ctx).into_owned()),
+        "clip" if !skip_scale => Ok(scale_clip_rect(val, ctx).into_owned()),
+        // Keywords (`center`, `cover`, `auto`, ...) and percentages are kept
+        "mask-position" | "mask-size" | "-webkit-mask-position" | "-webkit-mask-size"
+            if !skip_scale =>
+        {
+            Ok(scale_number_list(val, ctx))
         }
         _ => Ok(val.to_string()),
     }
 }
 
-fn walk_impl(
-    node: Node,
-    w: &mut XmlWriter,
-    ctx: &ScaleCtx,
-    ancestor_has_non_translate_transform: bool,
-    ancestor_skip_scale: bool,
-    style_rules: &[StyleRule],
-) -> Result<()> {
-    match node.node_type() {
-        roxmltree::NodeType::Element => {
-            let tag_name = node.tag_name().name();
-            let node_id = node.attribute("id").unwrap_or("");
-            w.start_element(tag_name);
+/// Whether `*Units="objectBoundingBox"`-style attributes stop scaling of the
+/// element itself and of its children
+pub(crate) fn units_skip_flags<'a>(
+    tag_name: &str,
+    attr: impl Fn(&str) -> Option<&'a str>,
+) -> (bool, bool) {
+    // SVG font glyphs and metrics are in font units (`units-per-em`); the
+    // scaled `font-size` of the text using them already resizes them
+    if tag_name == "font" {
+        return (true, true);
+    }
+    let units_attr = if tag_name == "clipPath" {
+        attr("clipPathUnits")
+    } else if tag_name == "mask" {
+        attr("maskUnits")
+    } else if tag_name == "linearGradient" || tag_name == "radialGradient" {
+        attr("gradientUnits")
+    } else if tag_name == "pattern" {
+        attr("patternUnits")
+    } else if tag_name == "filter" {
+        attr("filterUnits")
+    } else if tag_name == "marker" {
+        attr("markerUnits")
+    } else {
+        None
+    };
+    let skip_self = matches!(units_attr, Some("objectBoundingBox"))
+        || (tag_name == "marker"
+            && (matches!(units_attr, Some("strokeWidth")) || units_attr.is_none()));
+    // A pattern's viewBox defines its content coordinates and overrides
+    // `patternContentUnits`; only the tile is in user space
+    let skip_children = if tag_name == "pattern" {
+        matches!(attr("patternContentUnits"), Some("objectBoundingBox"))
+            || attr("viewBox").is_some()
+    } else if tag_name == "filter" {
+        matches!(attr("primitiveUnits"), Some("objectBoundingBox"))
+    } else if tag_name == "marker" {
+        matches!(attr("markerUnits"), Some("strokeWidth"))
+    } else {
+        false
+    };
+    (skip_self, skip_children)
+}
+
+/// The gradient an `href` on a gradient points at, whose attributes it
+/// inherits
+fn gradient_template<'a, 'i>(node: Node<'a, 'i>) -> Option<Node<'a, 'i>> {
+    let is_gradient =
+        |n: &Node| n.has_tag_name("linearGradient") || n.has_tag_name("radialGradient");
+    if !is_gradient(&node) {
+        return None;
+    }
+    let href = node.attributes().find(|a| a.name() == "href")?.value();
+    let id = href.trim().strip_prefix('#')?;
+    node.document()
+        .descendants()
+        .find(|n| is_gradient(n) && n.attribute("id") == Some(id))
+}
+
+/// An attribute of a gradient, inherited through its `href` chain if it
+/// does not set it itself
+fn gradient_attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    let mut current = node;
+    // Bounded, since an `href` cycle is not an error in the source
+    for _ in 0..16 {
+        if let Some(v) = current.attribute(name) {
+            return Some(v);
+        }
+        current = gradient_template(current)?;
+    }
+    None
+}
 
-            let units_attr = if tag_name == "clipPath" {
-                node.attribute("clipPathUnits")
-            } else if tag_name == "mask" {
-                node.attribute("maskUnits")
-            } else if tag_name == "linearGradient" || tag_name == "radialGradient" {
-                node.attribute("gradientUnits")
-            } else if tag_name == "pattern" {
-                node.attribute("patternUnits")
-            } else if tag_name == "filter" {
-                node.attribute("filterUnits")
-            } else if tag_name == "marker" {
-                node.attribute("markerUnits")
+/// Geometry a gradient inherits from a template in different
+/// `gradientUnits`: without its own copy, the template's values would
+/// scale by the template's units instead of the gradient's
+fn inherited_gradient_geometry<'a>(node: Node<'a, '_>) -> Vec<(&'static str, &'a str)> {
+    let Some(template) = gradient_template(node) else {
+        return Vec::new();
+    };
+    let is_bbox = |n| gradient_attr(n, "gradientUnits") == Some("objectBoundingBox");
+    if is_bbox(node) == is_bbox(template) {
+        return Vec::new();
+    }
+    let names: &[&'static str] = if node.has_tag_name("linearGradient") {
+        &["x1", "y1", "x2", "y2", "gradientTransform"]
+    } else {
+        &["cx", "cy", "r", "fx", "fy", "fr", "gradientTransform"]
+    };
+    names
+        .iter()
+        .filter(|name| !node.has_attribute(**name))
+        .filter_map(|&name| Some((name, gradient_attr(template, name)?)))
+        .collect()
+}
+
+/// The `<mpath>` child of an `<animateMotion>` and the path it references
+fn motion_track<'a, 'i>(anim: Node<'a, 'i>) -> Option<(Node<'a, 'i>, Node<'a, 'i>)> {
+    let mpath = anim.children().find(|c| c.has_tag_name("mpath"))?;
+    let href = mpath
+        .attributes()
+        .find(|a| a.name() == "href")?
+        .value()
+        .trim()
+        .strip_prefix('#')?;
+    let track = anim
+        .document()
+        .descendants()
+        .find(|n| n.attribute("id") == Some(href))?;
+    track.has_attribute("d").then_some((mpath, track))
+}
+
+/// Whether the walk scales a path's `d` where it stands: no folded
+/// transform and no unit-driven skipping on it or its ancestors. Only
+/// attributes are consulted, not `<style>` rules.
+fn scales_in_place(node: Node) -> bool {
+    node.ancestors().filter(Node::is_element).all(|n| {
+        let transform = n.attribute("transform");
+        let non_translate =
+            transform.is_some_and(|t| has_non_translate_transform(t).unwrap_or(false));
+        let mode = TransformMode::of_element(
+            false,
+            non_translate,
+            n.attribute("transform-origin"),
+            n.attribute("transform-box"),
+        );
+        let (skip_self, skip_children) =
+            units_skip_flags(n.tag_name().name(), |name| n.attribute(name));
+        let skips_children = if n.has_tag_name("filter") {
+            skip_children
+        } else {
+            skip_self || skip_children
+        };
+        !mode.folds(non_translate) && (n == node || !skips_children)
+    })
+}
+
+/// Built-in scaling of one attribute value; `el.skip_scale` covers
+/// ancestor transforms, `skip_scale_self` only unit-driven skipping
+pub(crate) fn scale_attribute_value<'v>(
+    el: &ElementCtx,
+    k: &str,
+    v: &'v str,
+    ctx: &ScaleCtx,
+    skip_scale_self: bool,
+    has_non_scaling_stroke: bool,
+    transform_mode: TransformMode,
+) -> Result<Cow<'v, str>> {
+    match k {
+        "d" => {
+            // Only skip scaling if there's a non-translate transform in ancestry
+            // (translate doesn't affect path coordinate space)
+            if el.skip_scale {
+                Ok(Cow::Borrowed(v))
             } else {
-                None
-            };
-            let skip_scale_due_to_units = matches!(units_attr, Some("objectBoundingBox"))
-                || (tag_name == "marker"
-                    && (matches!(units_attr, Some("strokeWidth")) || units_attr.is_none()));
-            let skip_children_due_to_content_units = if tag_name == "pattern" {
-                matches!(
-                    node.attribute("patternContentUnits"),
-                    Some("objectBoundingBox")
-                )
-            } else if tag_name == "filter" {
-                matches!(node.attribute("primitiveUnits"), Some("objectBoundingBox"))
-            } else if tag_name == "marker" {
-                matches!(node.attribute("markerUnits"), Some("strokeWidth"))
+                scale_path(v, ctx).map(Cow::Owned).with_context(|| {
+                    if el.id.is_empty() {
+                        format!("scale path failed on <{}>", el.tag_name)
+                    } else {
+                        format!("scale path failed on <{} id=\"{}\">", el.tag_name, el.id)
+                    }
+                })
+            }
+        }
+
+        // A bare coordinate list, so path tokenizing splits `1-2` and `1e-3`
+        // the same way browsers do
+        "points" if matches!(el.tag_name, "polygon" | "polyline") => {
+            if el.skip_scale {
+                Ok(Cow::Borrowed(v))
             } else {
-                false
-            };
+                scale_path(v, ctx).map(Cow::Owned).with_context(|| {
+                    if el.id.is_empty() {
+                        format!("invalid points on <{}>: {}", el.tag_name, v)
+                    } else {
+                        format!(
+                            "invalid points on <{} id=\"{}\">: {}",
+                            el.tag_name, el.id, v
+                        )
+                    }
+                })
+            }
+        }
 
-            let mut rule_style_props = collect_matching_style_props(style_rules, node);
+        // Keywords name a position in the marker's own box, whatever its units
+        "refX" | "refY" if is_ref_keyword(v) => Ok(Cow::Borrowed(v)),
 
-            let style_attr = node.attributes().find(|attr| attr.name() == "style");
-            let style_value = style_attr.map(|a| a.value()).unwrap_or("");
-            let inline_style_props = parse_style(style_value);
-            merge_style_props(&mut rule_style_props, &inline_style_props);
+        // Text positions are per-glyph lists; `rotate` holds per-glyph angles
+        // on the same elements and must never be scaled
+        "x" | "y" | "dx" | "dy" if is_text_positioning(el.tag_name) => {
+            if el.skip_scale {
+                Ok(Cow::Borrowed(v))
+            } else {
+                Ok(Cow::Owned(scale_number_list(v, ctx)))
+            }
+        }
+        "rotate" if is_text_positioning(el.tag_name) => Ok(Cow::Borrowed(v)),
 
-            // Check if this element has transform
-            let transform_attr = node.attributes().find(|attr| attr.name() == "transform");
-            let has_style_transform = rule_style_props.iter().any(|(k, _)| k == "transform");
-            let has_transform = transform_attr.is_some() || has_style_transform;
-            let transform_value = transform_attr.map(|a| a.value()).unwrap_or("");
-            let style_transform_value = rule_style_props
-                .iter()
-                .find(|(k, _)| k == "transform")
-                .map(|(_, v)| v.as_str())
-                .unwrap_or("");
+        // An image primitive's subregion (`x/y/width/height`) scales like any
+        // other primitive's, so it stays over the filtered element; the
+        // referenced element or file is scaled (or not) on its own and the
+        // reference itself is kept
+        "href" | "xlink:href" if el.tag_name == "feImage" => Ok(Cow::Borrowed(v)),
 
-            let has_non_scaling_stroke = node
-                .attributes()
-                .find(|attr| attr.name() == "vector-effect")
-                .map(|attr| attr.value() == "non-scaling-stroke")
-                .unwrap_or(false)
-                || rule_style_props
-                    .iter()
-                    .any(|(k, v)| k == "vector-effect" && v == "non-scaling-stroke");
+        // A convolution kernel is counted in cells of `kernelUnitLength`,
+        // which is the only length here: the matrix weights, its size, the
+        // divisor, the bias and the target cell never change
+        "kernelMatrix" | "order" | "divisor" | "bias" | "targetX" | "targetY"
+            if el.tag_name == "feConvolveMatrix" =>
+        {
+            Ok(Cow::Borrowed(v))
+        }
+
+        // Motion paths and positions are coordinates in the animated
+        // element's parent space; `keyPoints` are fractions of the path
+        // length and `rotate` is an angle or `auto`, so neither changes
+        "path" if el.tag_name == "animateMotion" => {
+            if el.skip_scale {
+                Ok(Cow::Borrowed(v))
+            } else {
+                scale_path(v, ctx)
+                    .map(Cow::Owned)
+                    .with_context(|| format!("scale path failed on <{}>", el.tag_name))
+            }
+        }
+        "values" | "from" | "to" | "by" if el.tag_name == "animateMotion" => {
+            if el.skip_scale {
+                Ok(Cow::Borrowed(v))
+            } else {
+                Ok(Cow::Owned(scale_number_list(v, ctx)))
+            }
+        }
+
+        "stroke-width" | "width" | "height" | "x" | "y" | "z" | "cx" | "cy" | "r" | "rx" | "ry"
+        | "x1" | "y1" | "x2" | "y2" | "font-size" | "letter-spacing" | "stroke-dashoffset"
+        | "fx" | "fy" | "fr" | "dx" | "dy" | "markerWidth" | "markerHeight" | "refX" | "refY"
+        | "surfaceScale" | "pointsAtX" | "pointsAtY" | "pointsAtZ" | "baseline-shift"
+        | "word-spacing" => {
+            if el.skip_scale || (k == "stroke-width" && has_non_scaling_stroke && !ctx.fix_stroke) {
+                Ok(Cow::Borrowed(v))
+            } else {
+                let ctx = if k == "stroke-width" {
+                    &ctx.for_stroke()
+                } else {
+                    ctx
+                };
+                scale_length_value(v, ctx).map(Cow::Owned).with_context(|| {
+                    if el.id.is_empty() {
+                        format!("invalid {} on <{}>: {}", k, el.tag_name, v)
+                    } else {
+                        format!("invalid {} on <{} id=\"{}\">: {}", k, el.tag_name, el.id, v)
+                    }
+                })
+            }
+        }
+        "stroke-dasharray" | "stdDeviation" | "radius" | "scale" | "kernelUnitLength"
+        | "enable-background" => {
+            if el.skip_scale || v.trim().eq_ignore_ascii_case("none") {
+                Ok(Cow::Borrowed(v))
+            } else {
+                Ok(Cow::Owned(scale_number_list(v, ctx)))
+            }
+        }
+        "baseFrequency" => {
+            if el.skip_scale {
+                Ok(Cow::Borrowed(v))
+            } else {
+                Ok(Cow::Owned(filter::scale_base_frequency(v, ctx)))
+            }
+        }
+        "clip-path" if !el.skip_scale => Ok(scale_basic_shape(v, ctx)),
+        "clip" if !el.skip_scale => Ok(scale_clip_rect(v, ctx)),
+        "gradientTransform" | "patternTransform" => {
+            if skip_scale_self {
+                Ok(Cow::Borrowed(v))
+            } else {
+                scale_transform_all(v, ctx.scale, ctx.precision)
+                    .map(Cow::Owned)
+                    .with_context(|| {
+                        if el.id.is_empty() {
+                            format!("transform scale failed on <{}>", el.tag_name)
+                        } else {
+                            format!(
+                                "transform scale failed on <{} id=\"{}\">",
+                                el.tag_name, el.id
+                            )
+                        }
+                    })
+            }
+        }
+
+        // A viewBox maps the element's content coordinates onto its viewport
+        // (nested <svg>, <symbol>, <marker>, <pattern>, <view>); it tracks the
+        // content, so it stays put wherever the children are left unscaled
+        "viewBox" => {
+            if el.skip_scale {
+                return Ok(Cow::Borrowed(v));
+            }
+            let mut out = String::with_capacity(v.len());
+            for n in v
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|n| !n.is_empty())
+            {
+                let val: f64 = n.parse().with_context(|| {
+                    if el.id.is_empty() {
+                        format!("invalid viewBox on <{}>: {}", el.tag_name, n)
+                    } else {
+                        format!(
+                            "invalid viewBox on <{} id=\"{}\">: {}",
+                            el.tag_name, el.id, n
+                        )
+                    }
+                })?;
+                if !out.is_empty() {
+                    out.push(' ');
+                }
+                ctx.push_scaled(&mut out, val);
+            }
+            Ok(Cow::Owned(out))
+        }
+
+        "transform" => transform_mode.apply(v, ctx).with_context(|| {
+            if el.id.is_empty() {
+                format!("transform scale failed on <{}>", el.tag_name)
+            } else {
+                format!(
+                    "transform scale failed on <{} id=\"{}\">",
+                    el.tag_name, el.id
+                )
+            }
+        }),
+
+        "transform-origin" if transform_mode == TransformMode::Conjugate && !el.skip_scale => {
+            scale_transform_origin(v, ctx)
+                .map(Cow::Owned)
+                .with_context(|| {
+                    if el.id.is_empty() {
+                        format!("invalid transform-origin on <{}>: {}", el.tag_name, v)
+                    } else {
+                        format!(
+                            "invalid transform-origin on <{} id=\"{}\">: {}",
+                            el.tag_name, el.id, v
+                        )
+                    }
+                })
+        }
+
+        _ => Ok(Cow::Borrowed(v)),
+    }
+}
+
+/// Attribute name including its namespace prefix, if any
+pub(crate) fn qualified_attr_name<'a>(
+    node: Node,
+    attr: &roxmltree::Attribute<'a, '_>,
+) -> Cow<'a, str> {
+    let local_name = attr.name();
+    if let Some(ns_uri) = attr.namespace() {
+        // Look up the prefix for this namespace URI
+        if let Some(prefix) = node.lookup_prefix(ns_uri) {
+            return Cow::Owned(format!("{}:{}", prefix, local_name));
+        }
+    }
+    Cow::Borrowed(local_name)
+}
+
+/// Where the walker writes markup: an [`XmlWriter`], or [`Discard`] when only
+/// the walk's side effects are wanted
+pub(crate) trait MarkupSink {
+    fn start_element(&mut self, name: &str);
+    fn write_attribute(&mut self, name: &str, value: &str);
+    fn write_text(&mut self, text: &str);
+    fn end_element(&mut self);
+}
+
+impl MarkupSink for XmlWriter {
+    fn start_element(&mut self, name: &str) {
+        XmlWriter::start_element(self, name);
+    }
+
+    fn write_attribute(&mut self, name: &str, value: &str) {
+        XmlWriter::write_attribute(self, name, value);
+    }
+
+    fn write_text(&mut self, text: &str) {
+        XmlWriter::write_text(self, text);
+    }
+
+    fn end_element(&mut self) {
+        XmlWriter::end_element(self);
+    }
+}
+
+/// A sink that drops everything written to it
+pub(crate) struct Discard;
+
+impl MarkupSink for Discard {
+    fn start_element(&mut self, _: &str) {}
+    fn write_attribute(&mut self, _: &str, _: &str) {}
+    fn write_text(&mut self, _: &str) {}
+    fn end_element(&mut self) {}
+}
+
+/// A sink laying markup out like a default [`XmlWriter`], but writing it into
+/// `out` as the walk goes instead of collecting the document in memory.
+/// `root_attrs` is written verbatim at the end of the first start tag; the
+/// first I/O error is kept and returned by [`StreamWriter::finish`].
+pub(crate) struct StreamWriter<W: Write> {
+    out: W,
+    /// Open elements with whether they have children yet
+    open: Vec<(String, bool)>,
+    in_start_tag: bool,
+    started: bool,
+    root_attrs: Option<String>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> StreamWriter<W> {
+    pub(crate) fn new(out: W, root_attrs: String) -> Self {
+        StreamWriter {
+            out,
+            open: Vec::new(),
+            in_start_tag: false,
+            started: false,
+            root_attrs: Some(root_attrs),
+            error: None,
+        }
+    }
+
+    /// Close the open elements and flush `out`
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        while !self.open.is_empty() {
+            self.end_element();
+        }
+        self.push(b"\n");
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        self.out.flush()
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        if self.error.is_none() {
+            if let Err(e) = self.out.write_all(bytes) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// Write `text` with every `from` byte replaced by `to`
+    fn push_escaped(&mut self, text: &str, from: u8, to: &[u8]) {
+        for (i, part) in text.as_bytes().split(|&b| b == from).enumerate() {
+            if i > 0 {
+                self.push(to);
+            }
+            self.push(part);
+        }
+    }
+
+    fn push_indent(&mut self) {
+        for _ in 0..self.open.len() {
+            self.push(b"    ");
+        }
+    }
+
+    fn push_root_attrs(&mut self) {
+        if let Some(attrs) = self.root_attrs.take() {
+            self.push(attrs.as_bytes());
+        }
+    }
+
+    /// Finish a pending start tag before the element's first child
+    fn open_start_tag(&mut self) {
+        if self.in_start_tag {
+            self.push_root_attrs();
+            self.push(b">");
+            if let Some(last) = self.open.last_mut() {
+                last.1 = true;
+            }
+            self.in_start_tag = false;
+        }
+    }
+
+    /// Start a new line and indent it, except before the first node
+    fn new_node(&mut self) {
+        self.open_start_tag();
+        if self.started {
+            self.push(b"\n");
+        }
+        self.started = true;
+        self.push_indent();
+    }
+}
+
+impl<W: Write> MarkupSink for StreamWriter<W> {
+    fn start_element(&mut self, name: &str) {
+        self.new_node();
+        self.push(b"<");
+        self.push(name.as_bytes());
+        self.open.push((name.to_string(), false));
+        self.in_start_tag = true;
+    }
+
+    fn write_attribute(&mut self, name: &str, value: &str) {
+        self.push(b" ");
+        self.push(name.as_bytes());
+        self.push(b"=\"");
+        self.push_escaped(value, b'"', b"&quot;");
+        self.push(b"\"");
+    }
+
+    fn write_text(&mut self, text: &str) {
+        self.new_node();
+        self.push_escaped(text, b'<', b"&lt;");
+    }
+
+    fn end_element(&mut self) {
+        let Some((name, has_children)) = self.open.pop() else {
+            return;
+        };
+        if has_children {
+            self.push(b"\n");
+            self.push_indent();
+            self.push(b"</");
+            self.push(name.as_bytes());
+            self.push(b">");
+        } else {
+            self.push_root_attrs();
+            self.push(b"/>");
+        }
+        self.in_start_tag = false;
+    }
+}
+
+/// Emit a subtree exactly as it appears in the source
+pub(crate) fn write_verbatim<W: MarkupSink + ?Sized>(node: Node, w: &mut W) {
+    match node.node_type() {
+        roxmltree::NodeType::Element => {
+            w.start_element(node.tag_name().name());
+            for attr in node.attributes() {
+                w.write_attribute(&qualified_attr_name(node, &attr), attr.value());
+            }
+            for c in node.children() {
+                write_verbatim(c, w);
+            }
+            w.end_element();
+        }
+        roxmltree::NodeType::Text => {
+            w.write_text(node.text().unwrap_or(""));
+        }
+        _ => {}
+    }
+}
+
+/// Elements removed from SVG 2 that `--drop-deprecated` leaves out
+const DEPRECATED_TAGS: &[&str] = &[
+    "altGlyph",
+    "altGlyphDef",
+    "altGlyphItem",
+    "animateColor",
+    "color-profile",
+    "cursor",
+    "font",
+    "font-face",
+    "glyphRef",
+    "hkern",
+    "tref",
+    "vkern",
+];
+
+/// Extensions whose `requiredExtensions` URI counts as supported, as in browsers
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "http://www.w3.org/1999/xhtml",
+    "http://www.w3.org/1998/Math/MathML",
+];
+
+/// Whether `node` is the first element child of its `<switch>` whose
+/// conditional processing attributes all pass.
+///
+/// `requiredFeatures` is obsolete in SVG 2 and passes unless empty;
+/// `systemLanguage` passes when one of its tags equals `lang` or one of the
+/// two is a `-`-delimited prefix of the other (`en` matches `en-US`).
+fn is_switch_winner(node: Node, lang: &str) -> bool {
+    let passes = |n: &Node| {
+        let features = n
+            .attribute("requiredFeatures")
+            .is_none_or(|v| !v.trim().is_empty());
+        let extensions = n.attribute("requiredExtensions").is_none_or(|v| {
+            let mut uris = v.split_whitespace().peekable();
+            uris.peek().is_some() && uris.all(|u| SUPPORTED_EXTENSIONS.contains(&u))
+        });
+        let language = n.attribute("systemLanguage").is_none_or(|v| {
+            v.split(',')
+                .map(str::trim)
+                .any(|tag| language_matches(tag, lang))
+        });
+        features && extensions && language
+    };
+    node.parent()
+        .and_then(|p| p.children().filter(|c| c.is_element()).find(passes))
+        == Some(node)
+}
+
+fn language_matches(tag: &str, lang: &str) -> bool {
+    let is_prefix = |short: &str, long: &str| {
+        long.len() > short.len()
+            && long.as_bytes()[short.len()] == b'-'
+            && long[..short.len()].eq_ignore_ascii_case(short)
+    };
+    !tag.is_empty()
+        && (tag.eq_ignore_ascii_case(lang) || is_prefix(tag, lang) || is_prefix(lang, tag))
+}
+
+/// Geometry attributes in `--canonical` order, after `id` and `class`
+const CANONICAL_GEOMETRY: &[&str] = &[
+    "x",
+    "y",
+    "x1",
+    "y1",
+    "x2",
+    "y2",
+    "cx",
+    "cy",
+    "r",
+    "rx",
+    "ry",
+    "fx",
+    "fy",
+    "fr",
+    "width",
+    "height",
+    "viewBox",
+    "preserveAspectRatio",
+    "points",
+    "d",
+    "transform",
+];
+
+/// Sort key for `--canonical`: `id`, `class`, geometry in
+/// [`CANONICAL_GEOMETRY`] order, everything else (presentation attributes,
+/// references, ...) alphabetically, and `style` last
+fn canonical_rank(name: &str) -> (u8, usize, &str) {
+    match name {
+        "id" => (0, 0, name),
+        "class" => (1, 0, name),
+        "style" => (4, 0, name),
+        _ => match CANONICAL_GEOMETRY.iter().position(|&g| g == name) {
+            Some(i) => (2, i, name),
+            None => (3, 0, name),
+        },
+    }
+}
+
+/// Emit a subtree like [`write_verbatim`], with every element's attributes
+/// sorted by [`canonical_rank`]
+pub(crate) fn write_canonical(node: Node, w: &mut XmlWriter) {
+    write_reindented(node, w, true);
+}
+
+/// Emit a subtree like [`write_verbatim`], dropping the source indentation
+/// so the writer's own is the only one left; `sort` orders attributes by
+/// [`canonical_rank`]
+pub(crate) fn write_reindented(node: Node, w: &mut XmlWriter, sort: bool) {
+    match node.node_type() {
+        roxmltree::NodeType::Element => {
+            w.start_element(node.tag_name().name());
+            let mut attrs: Vec<_> = node
+                .attributes()
+                .map(|a| (qualified_attr_name(node, &a), a.value()))
+                .collect();
+            if sort {
+                attrs.sort_by(|(a, _), (b, _)| canonical_rank(a).cmp(&canonical_rank(b)));
+            }
+            for (name, value) in attrs {
+                w.write_attribute(&name, value);
+            }
+            for c in node.children() {
+                write_reindented(c, w, sort);
+            }
+            w.end_element();
+        }
+        roxmltree::NodeType::Text if !is_indentation(node) => {
+            w.write_text(node.text().unwrap_or(""));
+        }
+        _ => {}
+    }
+}
+
+/// Elements whose whitespace text is content rather than indentation
+pub(crate) const TEXT_CONTENT_TAGS: &[&str] = &[
+    "text", "tspan", "textPath", "style", "script", "title", "desc",
+];
+
+/// Whitespace-only text between elements, which the writer re-creates
+pub(crate) fn is_indentation(node: Node) -> bool {
+    node.text().unwrap_or("").trim().is_empty()
+        && !node
+            .parent_element()
+            .is_some_and(|p| TEXT_CONTENT_TAGS.contains(&p.tag_name().name()))
+}
+
+/// How the root `width`/`height` are emitted
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RootSize {
+    /// Scale them like any other length
+    #[default]
+    Keep,
+    /// Drop them so the document fills its container, adding a viewBox if missing
+    Responsive,
+    /// Write them from the (scaled) viewBox, for consumers that require concrete dimensions
+    Explicit,
+    /// Leave them unscaled so only the coordinate density changes, adding a
+    /// viewBox if missing
+    Preserve,
+    /// Write the given size in a physical unit (`mm`, `in`, ...), adding a
+    /// viewBox if missing so the scaled user units map onto it at any DPI
+    Physical {
+        width: f64,
+        height: f64,
+        unit: &'static str,
+    },
+}
+
+/// Root attribute changes requested by [`RootSize`]; every variant drops
+/// the original `width`/`height`
+enum RootRewrite {
+    DropSize,
+    /// Synthesize `0 0 W H` from the unscaled root size
+    AddViewBox(f64, f64),
+    /// Write `width`/`height` from the unscaled viewBox size
+    SetSize(f64, f64),
+    /// Write the original `width`/`height`, plus a viewBox synthesized from
+    /// the unscaled root size if there was none
+    KeepSize(Option<(f64, f64)>),
+    /// Write `width`/`height` with a unit, plus a viewBox synthesized from
+    /// the unscaled root size if there was none
+    Physical {
+        width: f64,
+        height: f64,
+        unit: &'static str,
+        view_box: Option<(f64, f64)>,
+    },
+}
+
+fn root_rewrite(root: Node, mode: RootSize) -> Option<RootRewrite> {
+    match mode {
+        RootSize::Keep => None,
+        RootSize::Responsive => {
+            if root.attribute("viewBox").and_then(ViewBox::parse).is_some() {
+                return Some(RootRewrite::DropSize);
+            }
+            let width = root_length(root, "width")?;
+            let height = root_length(root, "height")?;
+            Some(RootRewrite::AddViewBox(width, height))
+        }
+        RootSize::Explicit => {
+            let vb = root.attribute("viewBox").and_then(ViewBox::parse)?;
+            Some(RootRewrite::SetSize(vb.width, vb.height))
+        }
+        RootSize::Preserve => {
+            if root.attribute("viewBox").and_then(ViewBox::parse).is_some() {
+                return Some(RootRewrite::KeepSize(None));
+            }
+            let width = root_length(root, "width")?;
+            let height = root_length(root, "height")?;
+            Some(RootRewrite::KeepSize(Some((width, height))))
+        }
+        RootSize::Physical {
+            width,
+            height,
+            unit,
+        } => {
+            let view_box = match root.attribute("viewBox").and_then(ViewBox::parse) {
+                Some(_) => None,
+                None => Some((root_length(root, "width")?, root_length(root, "height")?)),
+            };
+            Some(RootRewrite::Physical {
+                width,
+                height,
+                unit,
+                view_box,
+            })
+        }
+    }
+}
+
+/// State shared by the whole walk
+struct WalkEnv<'a> {
+    style_rules: &'a [StyleRule],
+    opts: &'a WalkOptions,
+    explain: Option<&'a Recorder<'a>>,
+}
+
+/// Flags that can stop an element's own values from being scaled
+struct SkipReasons {
+    ancestor_transform: bool,
+    units: bool,
+    own_transform: bool,
+    keeps_size: bool,
+    non_scaling_stroke: bool,
+}
+
+impl SkipReasons {
+    /// Why `v` came out as `nv`; `would_scale` tells whether the value
+    /// changes at all when scaled without any of the skip flags
+    fn decide(&self, k: &str, v: &str, nv: &str, would_scale: impl FnOnce() -> bool) -> Decision {
+        if nv != v {
+            return Decision::Scaled;
+        }
+        if !would_scale() {
+            let (num, unit) = split_num_and_unit(v.trim());
+            return if v.contains('%') || (!num.is_empty() && !is_supported_unit(unit.trim())) {
+                Decision::UnsupportedUnit
+            } else {
+                Decision::NotGeometry
+            };
+        }
+        if self.ancestor_transform {
+            Decision::AncestorTransform
+        } else if self.units {
+            Decision::ObjectBoundingBox
+        } else if self.own_transform {
+            Decision::OwnTransform
+        } else if self.keeps_size {
+            Decision::NonScalingSize
+        } else if self.non_scaling_stroke && k == "stroke-width" {
+            Decision::NonScalingStroke
+        } else {
+            // Rounds back to the same value at this scale
+            Decision::Scaled
+        }
+    }
+}
+
+/// A scale factor that changes any value depending on the scale, for
+/// asking whether an attribute would have been scaled
+fn probe_ctx(ctx: &ScaleCtx) -> ScaleCtx {
+    ScaleCtx {
+        scale: if ctx.scale == 2.0 { 3.0 } else { 2.0 },
+        ..*ctx
+    }
+}
+
+fn walk_impl(
+    node: Node,
+    w: &mut dyn MarkupSink,
+    ctx: &ScaleCtx,
+    ancestor_has_non_translate_transform: bool,
+    ancestor_skip_scale: bool,
+    inside_only: bool,
+    env: &WalkEnv,
+) -> Result<()> {
+    match node.node_type() {
+        roxmltree::NodeType::Element => {
+            let tag_name = node.tag_name().name();
+            let node_id = node.attribute("id").unwrap_or("");
+            let is_root = node.parent().is_some_and(|p| p.is_root());
+
+            if let Some(lang) = &env.opts.switch_language {
+                let in_switch = node
+                    .parent_element()
+                    .is_some_and(|p| p.has_tag_name("switch"));
+                if in_switch && !is_switch_winner(node, lang) {
+                    return Ok(());
+                }
+            }
+
+            if !is_root && env.opts.drop_deprecated && DEPRECATED_TAGS.contains(&tag_name) {
+                // altGlyph holds the fallback text itself; keep that
+                if tag_name == "altGlyph" {
+                    for c in node.children() {
+                        walk_impl(
+                            c,
+                            w,
+                            ctx,
+                            ancestor_has_non_translate_transform,
+                            ancestor_skip_scale,
+                            inside_only,
+                            env,
+                        )?;
+                    }
+                }
+                return Ok(());
+            }
+
+            if !is_root && env.opts.exclude.as_ref().is_some_and(|s| s.matches(node)) {
+                if let Some(r) = env.explain {
+                    for el in node.descendants().filter(|n| r.wants(*n)) {
+                        for attr in el.attributes() {
+                            let k = qualified_attr_name(el, &attr);
+                            r.record(el, k, attr.value(), Some(attr.value()), Decision::Excluded);
+                        }
+                    }
+                }
+                write_verbatim(node, w);
+                return Ok(());
+            }
+
+            // The unmatched ancestors of an --only match keep their
+            // transforms, so the match makes up for what scaling them would
+            // have moved it by
+            let matches_only =
+                !inside_only && env.opts.only.as_ref().is_some_and(|s| s.matches(node));
+            let mut anchor_shift = None;
+            if matches_only
+                && !is_root
+                && !ancestor_has_non_translate_transform
+                && !ancestor_skip_scale
+            {
+                add_shift(&mut anchor_shift, only_anchor_shift(node, ctx.scale));
+            }
+
+            // Subtrees matched by --scale-for are scaled by their factor on
+            // top of the surrounding one, about the center of what they draw
+            // so they stay in place; the last matching rule wins
+            let override_ctx;
+            let ctx = match env
+                .opts
+                .scale_overrides
+                .iter()
+                .rev()
+                .find(|(sel, _)| sel.matches(node))
+            {
+                Some((_, factor)) => {
+                    override_ctx = ScaleCtx {
+                        scale: ctx.scale * factor,
+                        ..*ctx
+                    };
+                    if !is_root && !ancestor_has_non_translate_transform && !ancestor_skip_scale {
+                        add_shift(
+                            &mut anchor_shift,
+                            override_anchor_shift(node, ctx.scale, *factor),
+                        );
+                    }
+                    &override_ctx
+                }
+                None => ctx,
+            };
+
+            let inside_only = inside_only || matches_only;
+            if !is_root && !inside_only {
+                // Outside every --only match: keep this element as-is but keep
+                // looking for matches below it
+                w.start_element(tag_name);
+                let recorder = env.explain.filter(|r| r.wants(node));
+                for attr in node.attributes() {
+                    let k = qualified_attr_name(node, &attr);
+                    if let Some(r) = recorder {
+                        r.record(
+                            node,
+                            &*k,
+                            attr.value(),
+                            Some(attr.value()),
+                            Decision::OutsideOnly,
+                        );
+                    }
+                    w.write_attribute(&k, attr.value());
+                }
+                for c in node.children() {
+                    walk_impl(
+                        c,
+                        w,
+                        ctx,
+                        ancestor_has_non_translate_transform,
+                        ancestor_skip_scale,
+                        false,
+                        env,
+                    )?;
+                }
+                w.end_element();
+                return Ok(());
+            }
+
+            w.start_element(tag_name);
+
+            // Gradients inherit `gradientUnits` through `href`
+            let (skip_scale_due_to_units, skip_children_due_to_content_units) =
+                units_skip_flags(tag_name, |name| match name {
+                    "gradientUnits" => gradient_attr(node, name),
+                    _ => node.attribute(name),
+                });
+
+            let mut rule_style_props = collect_matching_style_props(env.style_rules, node);
+
+            let style_attr = node.attributes().find(|attr| attr.name() == "style");
+            let style_value = style_attr.map(|a| a.value()).unwrap_or("");
+            let inline_style_props = parse_style(style_value);
+            merge_style_props(&mut rule_style_props, &inline_style_props);
+
+            // Check if this element has transform
+            let transform_attr = node.attributes().find(|attr| attr.name() == "transform");
+            let has_style_transform = rule_style_props.iter().any(|(k, _)| k == "transform");
+            let has_transform = transform_attr.is_some() || has_style_transform;
+            let transform_value = transform_attr.map(|a| a.value()).unwrap_or("");
+            let style_transform_value = rule_style_props
+                .iter()
+                .find(|(k, _)| k == "transform")
+                .map(|(_, v)| v.as_str())
+                .unwrap_or("");
+
+            let effects = VectorEffects::from_values(
+                node.attribute("vector-effect").into_iter().chain(
+                    rule_style_props
+                        .iter()
+                        .filter(|(k, _)| k == "vector-effect")
+                        .map(|(_, v)| v.as_str()),
+                ),
+            );
+            let has_non_scaling_stroke = effects.non_scaling_stroke;
+            // Screen-sized geometry stays as it is, like under a folded transform
+            let keeps_size = effects.non_scaling_size && !ctx.fix_stroke;
 
             // Check if this element has a non-translate transform
             let has_non_translate_transform = if has_transform {
@@ -678,169 +2252,345 @@ fn walk_impl(
                 skip_scale_self || skip_children_due_to_content_units
             };
 
-            for attr in node.attributes() {
-                let local_name = attr.name();
-                // Construct full attribute name with namespace prefix if present
-                let k = if let Some(ns_uri) = attr.namespace() {
-                    // Look up the prefix for this namespace URI
-                    if let Some(prefix) = node.lookup_prefix(ns_uri) {
-                        format!("{}:{}", prefix, local_name)
-                    } else {
-                        local_name.to_string()
-                    }
-                } else {
-                    local_name.to_string()
-                };
-                let v = attr.value();
-
-                if k == "style" {
+            let style_prop = |name: &str| {
+                rule_style_props
+                    .iter()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.as_str())
+            };
+            let transform_mode = TransformMode::of_element(
+                ancestor_has_non_translate_transform || skip_scale_self,
+                has_non_translate_transform,
+                style_prop("transform-origin").or(node.attribute("transform-origin")),
+                style_prop("transform-box").or(node.attribute("transform-box")),
+            );
+            let folds_transform = transform_mode.folds(has_non_translate_transform);
+
+            // A <view> viewBox is in the user space of its nearest <svg>, not
+            // of its parent, so transforms and units in between do not apply
+            let is_root_view = tag_name == "view"
+                && node
+                    .ancestors()
+                    .skip(1)
+                    .find(|a| a.has_tag_name("svg"))
+                    .is_some_and(|svg| svg.parent().is_some_and(|p| p.is_root()));
+            let element_ctx = ElementCtx {
+                tag_name,
+                id: node_id,
+                skip_scale: !is_root_view
+                    && (ancestor_has_non_translate_transform
+                        || folds_transform
+                        || skip_scale_self
+                        || keeps_size),
+            };
+
+            let root_rewrite = if is_root {
+                root_rewrite(node, env.opts.root_size)
+            } else {
+                None
+            };
+
+            let exact_ctx = ScaleCtx {
+                precision: RESOLVED_PRECISION,
+                ..*ctx
+            };
+            // A track scaled under a different context than the motion would
+            // move the element too far or too little; inline its data as
+            // `path`, scaled for the motion, and drop the <mpath>
+            let inlined_track = if tag_name == "animateMotion" {
+                motion_track(node)
+                    .filter(|(_, track)| scales_in_place(*track) == element_ctx.skip_scale)
+            } else {
+                None
+            };
+
+            let pattern_view_box = tag_name == "pattern" && node.has_attribute("viewBox");
+            let recorder = env.explain.filter(|r| r.wants(node));
+            let reasons = SkipReasons {
+                ancestor_transform: ancestor_has_non_translate_transform,
+                units: skip_scale_self,
+                own_transform: folds_transform,
+                keeps_size,
+                non_scaling_stroke: has_non_scaling_stroke && !ctx.fix_stroke,
+            };
+            for attr in node.attributes() {
+                let k = qualified_attr_name(node, &attr);
+                // Values rewritten into plain numbers before the usual scaling;
+                // kept at full precision so they are only rounded once
+                let resolved = if env.opts.bake_marker_refs
+                    && tag_name == "marker"
+                    && (k == "refX" || k == "refY")
+                {
+                    resolve_ref_keyword(node, &k, attr.value()).map(|n| exact_ctx.fmt(n))
+                } else if env.opts.resolve_dash_percentages && k == "stroke-dasharray" {
+                    resolve_dash_percentages(attr.value(), node, &exact_ctx)
+                } else if env.opts.recurse_embedded
+                    && tag_name == "image"
+                    && (k == "href" || k == "xlink:href")
+                    && !element_ctx.skip_scale
+                {
+                    scale_embedded_svg(attr.value(), ctx)
+                        .with_context(|| format!("embedded SVG in <{}>", tag_name))?
+                } else if tag_name == "feTurbulence"
+                    && k == "baseFrequency"
+                    && !element_ctx.skip_scale
+                {
+                    filter::stitched_base_frequency(node, attr.value()).map(|(fx, fy)| {
+                        filter::format_base_frequency(attr.value(), fx, fy, &exact_ctx)
+                    })
+                } else {
+                    None
+                };
+                let v = resolved.as_deref().unwrap_or(attr.value());
+
+                if k == "style" || (k == "path" && inlined_track.is_some()) {
                     continue;
                 }
 
-                if ctx.fix_stroke && k == "vector-effect" {
+                if root_rewrite.is_some() && (k == "width" || k == "height") {
+                    if let Some(r) = recorder {
+                        let result = match root_rewrite {
+                            Some(RootRewrite::SetSize(width, height)) => {
+                                Some(ctx.fmt_scaled(if k == "width" { width } else { height }))
+                            }
+                            Some(RootRewrite::KeepSize(_)) => Some(v.to_string()),
+                            Some(RootRewrite::Physical {
+                                width,
+                                height,
+                                unit,
+                                ..
+                            }) => Some(format!(
+                                "{}{}",
+                                ctx.fmt(if k == "width" { width } else { height }),
+                                unit
+                            )),
+                            _ => None,
+                        };
+                        r.record(node, &*k, v, result.as_deref(), Decision::RootSize);
+                    }
                     continue;
                 }
 
-                let nv = match k.as_str() {
-                    "d" => {
-                        // Only skip scaling if there's a non-translate transform in ancestry
-                        // (translate doesn't affect path coordinate space)
-                        if ancestor_has_non_translate_transform
-                            || has_non_translate_transform
-                            || skip_scale_self
-                        {
-                            Ok(v.to_string())
-                        } else {
-                            scale_path(v, ctx).with_context(|| {
-                                if node_id.is_empty() {
-                                    format!("scale path failed on <{}>", tag_name)
-                                } else {
-                                    format!(
-                                        "scale path failed on <{} id=\"{}\">",
-                                        tag_name, node_id
-                                    )
-                                }
-                            })
-                        }
+                // Maps the unscaled content onto the tile, which scales itself
+                if pattern_view_box && k == "viewBox" {
+                    if let Some(r) = recorder {
+                        r.record(node, &*k, v, Some(v), Decision::PatternViewBox);
                     }
+                    w.write_attribute(&k, v);
+                    continue;
+                }
 
-                    "stroke-width" | "width" | "height" | "x" | "y" | "z" | "cx" | "cy" | "r"
-                    | "rx" | "ry" | "x1" | "y1" | "x2" | "y2" | "font-size" | "letter-spacing"
-                    | "stroke-dashoffset" | "fx" | "fy" | "dx" | "dy" | "markerWidth"
-                    | "markerHeight" | "refX" | "refY" | "surfaceScale" | "pointsAtX"
-                    | "pointsAtY" | "pointsAtZ" => {
-                        if ancestor_has_non_translate_transform
-                            || has_non_translate_transform
-                            || skip_scale_self
-                            || (k == "stroke-width" && has_non_scaling_stroke && !ctx.fix_stroke)
-                        {
-                            Ok(v.to_string())
-                        } else {
-                            scale_length_value(v, ctx).with_context(|| {
-                                if node_id.is_empty() {
-                                    format!("invalid {} on <{}>: {}", k, tag_name, v)
-                                } else {
-                                    format!(
-                                        "invalid {} on <{} id=\"{}\">: {}",
-                                        k, tag_name, node_id, v
-                                    )
-                                }
-                            })
-                        }
-                    }
-                    "stroke-dasharray" | "stdDeviation" | "radius" | "scale"
-                    | "kernelUnitLength" => {
-                        if ancestor_has_non_translate_transform
-                            || has_non_translate_transform
-                            || skip_scale_self
-                            || v.trim().eq_ignore_ascii_case("none")
-                        {
-                            Ok(v.to_string())
-                        } else {
-                            Ok(scale_number_list(v, ctx))
-                        }
-                    }
-                    "baseFrequency" => {
-                        if ancestor_has_non_translate_transform
-                            || has_non_translate_transform
-                            || skip_scale_self
-                        {
-                            Ok(v.to_string())
-                        } else {
-                            Ok(scale_number_list_inverse(v, ctx))
-                        }
+                if ctx.fix_stroke && k == "vector-effect" {
+                    let nv = fix_vector_effect(v);
+                    if let Some(r) = recorder {
+                        r.record(node, &*k, v, nv.as_deref(), Decision::FixStroke);
                     }
-                    "gradientTransform" | "patternTransform" => {
-                        if skip_scale_self {
-                            Ok(v.to_string())
-                        } else {
-                            scale_transform_all(v, ctx.scale, ctx.precision).with_context(|| {
-                                if node_id.is_empty() {
-                                    format!("transform scale failed on <{}>", tag_name)
-                                } else {
-                                    format!(
-                                        "transform scale failed on <{} id=\"{}\">",
-                                        tag_name, node_id
-                                    )
-                                }
-                            })
-                        }
+                    if let Some(nv) = nv {
+                        w.write_attribute(&k, &nv);
                     }
+                    continue;
+                }
 
-                    "viewBox" => {
-                        let parts: Result<Vec<String>> = v
-                            .split_whitespace()
-                            .map(|n| {
-                                let val: f64 = n.parse().with_context(|| {
-                                    if node_id.is_empty() {
-                                        format!("invalid viewBox on <{}>: {}", tag_name, n)
-                                    } else {
-                                        format!(
-                                            "invalid viewBox on <{} id=\"{}\">: {}",
-                                            tag_name, node_id, n
-                                        )
-                                    }
-                                })?;
-                                Ok(ctx.fmt(val * ctx.scale))
-                            })
-                            .collect();
-                        Ok(parts?.join(" "))
+                // The noise pattern, not its size; no hook may scale these
+                if tag_name == "feTurbulence" && filter::is_turbulence_constant(&k) {
+                    if let Some(r) = recorder {
+                        r.record(node, &*k, v, Some(v), Decision::NotGeometry);
                     }
+                    w.write_attribute(&k, v);
+                    continue;
+                }
 
-                    "transform" => {
-                        scale_transform_all(v, ctx.scale, ctx.precision).with_context(|| {
+                let handling = env
+                    .opts
+                    .hooks
+                    .iter()
+                    .map(|hook| hook(&element_ctx, &k, v))
+                    .find(|h| *h != Handling::Default)
+                    .unwrap_or(Handling::Default);
+                if handling != Handling::Default {
+                    let nv = apply_handling(handling, v, ctx, element_ctx.skip_scale)
+                        .with_context(|| {
                             if node_id.is_empty() {
-                                format!("transform scale failed on <{}>", tag_name)
+                                format!("invalid {} on <{}>: {}", k, tag_name, v)
                             } else {
-                                format!(
-                                    "transform scale failed on <{} id=\"{}\">",
-                                    tag_name, node_id
-                                )
+                                format!("invalid {} on <{} id=\"{}\">: {}", k, tag_name, node_id, v)
                             }
-                        })
+                        })?;
+                    if let Some(r) = recorder {
+                        r.record(node, &*k, attr.value(), Some(&nv), Decision::Hook);
                     }
+                    w.write_attribute(&k, &nv);
+                    continue;
+                }
 
-                    _ => Ok(v.to_string()),
-                };
+                let mut nv = scale_attribute_value(
+                    &element_ctx,
+                    &k,
+                    v,
+                    ctx,
+                    skip_scale_self,
+                    has_non_scaling_stroke,
+                    transform_mode,
+                )?;
+                if let Some((tx, ty)) = anchor_shift.filter(|_| k == "transform") {
+                    if !has_style_transform {
+                        nv = format!("translate({} {}) {}", ctx.fmt(tx), ctx.fmt(ty), nv).into();
+                    }
+                }
+                if let Some(r) = recorder {
+                    let decision = reasons.decide(&k, v, &nv, || {
+                        let free = ElementCtx {
+                            skip_scale: false,
+                            ..element_ctx
+                        };
+                        let probe = scale_attribute_value(
+                            &free,
+                            &k,
+                            v,
+                            &probe_ctx(ctx),
+                            false,
+                            false,
+                            TransformMode::Fold,
+                        );
+                        probe.is_ok_and(|p| p != v)
+                    });
+                    r.record(node, &*k, attr.value(), Some(&nv), decision);
+                }
+
+                w.write_attribute(&k, &nv);
+            }
+            if let Some((tx, ty)) = anchor_shift.filter(|_| !has_transform) {
+                w.write_attribute(
+                    "transform",
+                    &format!("translate({} {})", ctx.fmt(tx), ctx.fmt(ty)),
+                );
+            }
+            for (k, v) in inherited_gradient_geometry(node) {
+                let nv = scale_attribute_value(
+                    &element_ctx,
+                    k,
+                    v,
+                    ctx,
+                    skip_scale_self,
+                    false,
+                    transform_mode,
+                )?;
+                w.write_attribute(k, &nv);
+            }
 
-                w.write_attribute(&k, &nv?);
+            match root_rewrite {
+                Some(RootRewrite::AddViewBox(width, height))
+                | Some(RootRewrite::KeepSize(Some((width, height)))) => {
+                    let vb = ViewBox {
+                        x: 0.0,
+                        y: 0.0,
+                        width: width * ctx.scale,
+                        height: height * ctx.scale,
+                    };
+                    w.write_attribute("viewBox", &vb.format(ctx));
+                }
+                Some(RootRewrite::SetSize(width, height)) => {
+                    w.write_attribute("width", &ctx.fmt_scaled(width));
+                    w.write_attribute("height", &ctx.fmt_scaled(height));
+                }
+                Some(RootRewrite::Physical {
+                    width,
+                    height,
+                    unit,
+                    view_box,
+                }) => {
+                    if let Some((vb_width, vb_height)) = view_box {
+                        let vb = ViewBox {
+                            x: 0.0,
+                            y: 0.0,
+                            width: vb_width * ctx.scale,
+                            height: vb_height * ctx.scale,
+                        };
+                        w.write_attribute("viewBox", &vb.format(ctx));
+                    }
+                    w.write_attribute("width", &format!("{}{}", ctx.fmt(width), unit));
+                    w.write_attribute("height", &format!("{}{}", ctx.fmt(height), unit));
+                }
+                Some(RootRewrite::DropSize) | Some(RootRewrite::KeepSize(None)) | None => {}
+            }
+            if let Some(RootRewrite::KeepSize(_)) = root_rewrite {
+                for k in ["width", "height"] {
+                    if let Some(v) = node.attribute(k) {
+                        w.write_attribute(k, v);
+                    }
+                }
             }
 
             if !rule_style_props.is_empty() {
                 let mut new_props = Vec::with_capacity(rule_style_props.len());
-                for (sk, sv) in rule_style_props {
+                for (sk, mut sv) in rule_style_props {
+                    // CSS root sizes follow the same --responsive/--keep-size rules
+                    // as the attributes
+                    let style_key = || format!("style:{}", sk);
+                    if is_root && (sk == "width" || sk == "height") {
+                        match root_rewrite {
+                            Some(RootRewrite::DropSize)
+                            | Some(RootRewrite::AddViewBox(..))
+                            | Some(RootRewrite::Physical { .. }) => {
+                                if let Some(r) = recorder {
+                                    r.record(node, style_key(), &sv, None, Decision::RootSize);
+                                }
+                                continue;
+                            }
+                            Some(RootRewrite::KeepSize(_)) => {
+                                if let Some(r) = recorder {
+                                    r.record(node, style_key(), &sv, Some(&sv), Decision::RootSize);
+                                }
+                                new_props.push((sk, sv));
+                                continue;
+                            }
+                            _ => {}
+                        }
+                    }
+                    if env.opts.resolve_dash_percentages && sk == "stroke-dasharray" {
+                        if let Some(resolved) = resolve_dash_percentages(&sv, node, &exact_ctx) {
+                            sv = resolved;
+                        }
+                    }
                     if ctx.fix_stroke && sk == "vector-effect" {
+                        let nv = fix_vector_effect(&sv);
+                        if let Some(r) = recorder {
+                            r.record(node, style_key(), &sv, nv.as_deref(), Decision::FixStroke);
+                        }
+                        if let Some(nv) = nv {
+                            new_props.push((sk, nv));
+                        }
                         continue;
                     }
-                    let scaled = scale_style_value(
+                    let mut scaled = scale_style_value(
                         &sk,
                         &sv,
                         ctx,
                         skip_scale_self
                             || ancestor_has_non_translate_transform
-                            || has_non_translate_transform,
+                            || folds_transform
+                            || keeps_size,
                         has_non_scaling_stroke,
+                        transform_mode,
                     )?;
+                    if let Some((tx, ty)) = anchor_shift.filter(|_| sk == "transform") {
+                        let (tx, ty) = (ctx.fmt(tx), ctx.fmt(ty));
+                        scaled = format!("translate({}px, {}px) {}", tx, ty, scaled);
+                    }
+                    if let Some(r) = recorder {
+                        let decision = reasons.decide(&sk, &sv, &scaled, || {
+                            let probe = scale_style_value(
+                                &sk,
+                                &sv,
+                                &probe_ctx(ctx),
+                                false,
+                                false,
+                                TransformMode::Fold,
+                            );
+                            probe.is_ok_and(|p| p != sv)
+                        });
+                        r.record(node, style_key(), &sv, Some(&scaled), decision);
+                    }
                     new_props.push((sk, scaled));
                 }
                 if !new_props.is_empty() {
@@ -849,31 +2599,303 @@ fn walk_impl(
                 }
             }
 
-            // Pass down whether there's a non-translate transform in the ancestry
-            for c in node.children() {
-                walk_impl(
-                    c,
-                    w,
+            if let Some((_, track)) = inlined_track {
+                let d = track.attribute("d").unwrap_or("");
+                let nv = scale_attribute_value(
+                    &element_ctx,
+                    "path",
+                    d,
                     ctx,
-                    ancestor_has_non_translate_transform || has_non_translate_transform,
-                    child_skip_scale,
-                    style_rules,
+                    skip_scale_self,
+                    false,
+                    transform_mode,
                 )?;
+                w.write_attribute("path", &nv);
+            }
+
+            // Pass down whether there's a non-translate transform in the ancestry
+            for c in node.children() {
+                if inlined_track.is_some_and(|(mpath, _)| mpath == c) {
+                    continue;
+                }
+                // Motion happens in this element's parent space, outside its
+                // own transform
+                let (ancestor_transform, skip_scale) = if c.has_tag_name("animateMotion") {
+                    (ancestor_has_non_translate_transform, skip_scale_self)
+                } else {
+                    (
+                        ancestor_has_non_translate_transform || folds_transform,
+                        child_skip_scale,
+                    )
+                };
+                walk_impl(c, w, ctx, ancestor_transform, skip_scale, inside_only, env)?;
             }
 
             w.end_element();
         }
         roxmltree::NodeType::Text => {
-            w.write_text(node.text().unwrap_or(""));
+            let text = node.text().unwrap_or("");
+            if node
+                .parent()
+                .is_some_and(|p| p.tag_name().name() == "style")
+            {
+                w.write_text(&scale_stylesheet(text, ctx)?);
+            } else {
+                w.write_text(text);
+            }
         }
         _ => {}
     }
     Ok(())
 }
 
+/// Comma-separated list of selectors, using the same subset as `<style>` rules
+#[derive(Debug, Clone)]
+pub struct SelectorList(Vec<StyleSelector>);
+
+impl SelectorList {
+    pub fn matches(&self, node: Node) -> bool {
+        self.0.iter().any(|sel| matches_selector(sel, node))
+    }
+}
+
+impl std::str::FromStr for SelectorList {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut list = Vec::new();
+        for part in s.split(',') {
+            let sel = parse_selector(part)
+                .ok_or_else(|| anyhow::anyhow!("unsupported selector: {}", part.trim()))?;
+            list.push(sel);
+        }
+        Ok(SelectorList(list))
+    }
+}
+
+/// Settings for [`walk_with_options`] beyond the scale factor itself
+#[derive(Default)]
+pub struct WalkOptions {
+    /// Consulted for every attribute before the built-in handling; the first
+    /// hook returning something other than [`Handling::Default`] wins
+    pub hooks: Vec<AttributeHook>,
+    /// Matching subtrees are emitted unscaled
+    pub exclude: Option<SelectorList>,
+    /// When set, only matching subtrees (and the root element itself) are
+    /// scaled; a translation on each match keeps it where a whole-document
+    /// scale would put it, since its ancestors' transforms are left alone
+    pub only: Option<SelectorList>,
+    /// Matching subtrees are scaled by this factor on top of the
+    /// document-wide one, about the center of what they draw
+    pub scale_overrides: Vec<(SelectorList, f64)>,
+    pub root_size: RootSize,
+    /// Replace SVG 2 `refX`/`refY` keywords on markers with numbers
+    pub bake_marker_refs: bool,
+    /// Turn `stroke-dasharray` percentages into user units before scaling
+    pub resolve_dash_percentages: bool,
+    /// Leave out elements removed in SVG 2 (`cursor`, SVG fonts, `tref`,
+    /// ...); `altGlyph` is replaced by its text
+    pub drop_deprecated: bool,
+    /// When set, `<switch>` elements keep only the child that a user agent
+    /// with this language (e.g. `en-US`) would render
+    pub switch_language: Option<String>,
+    /// Scale SVG documents embedded in `<image>` as `data:` URIs along with
+    /// the rest, re-encoded the way they were
+    pub recurse_embedded: bool,
+}
+
+/// A `data:image/svg+xml` URI with its document scaled by `ctx` and encoded
+/// as before; `None` for other references
+fn scale_embedded_svg(uri: &str, ctx: &ScaleCtx) -> Result<Option<String>> {
+    if data_uri::media_type(uri).as_deref() != Some("image/svg+xml") {
+        return Ok(None);
+    }
+    let Some(text) = data_uri::decode(uri)? else {
+        return Ok(None);
+    };
+    let doc = roxmltree::Document::parse(&text)?;
+    let opts = WalkOptions {
+        recurse_embedded: true,
+        ..WalkOptions::default()
+    };
+    let scaled = crate::write_svg_with_options(&doc, ctx, &opts)?;
+    Ok(Some(data_uri::encode_like(uri, &scaled)))
+}
+
+/// Translation, in the scaled user space of `node`'s parent, that keeps the
+/// center of what `node` draws in place when its subtree is scaled by
+/// `factor` more than the parent's `scale`: its bounds center, else the
+/// origin its transform maps to
+fn override_anchor_shift(node: Node, scale: f64, factor: f64) -> Option<(f64, f64)> {
+    let (x, y) = match bounds::element_bounds(node, crate::DEFAULT_DPI) {
+        Some(b) => (b.x + b.width / 2.0, b.y + b.height / 2.0),
+        None => {
+            let m = crate::transform::transform_to_matrix(
+                &parse_transform_list(node.attribute("transform").unwrap_or("")).ok()?,
+            )
+            .ok()?;
+            (m[4], m[5])
+        }
+    };
+    let k = scale * (1.0 - factor);
+    // `+ 0.0` turns a -0 into 0, which would otherwise print as `-0`
+    (x * k != 0.0 || y * k != 0.0).then_some((x * k + 0.0, y * k + 0.0))
+}
+
+/// Translation, in the user space of `node`'s parent, that puts a subtree
+/// matched by `--only` where scaling the whole document would. Its unmatched
+/// ancestors' transforms `A` stay as they are, so the subtree, scaled by `S`,
+/// needs `A⁻¹·S·A·S⁻¹`, which is a pure translation for a uniform `S`
+fn only_anchor_shift(node: Node, scale: f64) -> Option<(f64, f64)> {
+    let mut m = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+    for a in node.ancestors().skip(1).filter(|a| a.is_element()) {
+        if a.parent().is_some_and(|p| p.is_root()) {
+            break;
+        }
+        let t = crate::transform::transform_to_matrix(
+            &parse_transform_list(a.attribute("transform").unwrap_or("")).ok()?,
+        )
+        .ok()?;
+        m = crate::transform::mat_mul(t, m);
+    }
+    let [a, b, c, d, e, f] = m;
+    let det = a * d - b * c;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let k = (scale - 1.0) / det;
+    // Rotation matrices leave rounding noise where a term should vanish,
+    // which would otherwise print as `-0`
+    let snap = |v: f64| if v.abs() < 1e-9 { 0.0 } else { v };
+    let (x, y) = (snap((d * e - c * f) * k), snap((a * f - b * e) * k));
+    (x != 0.0 || y != 0.0).then_some((x, y))
+}
+
+fn add_shift(shift: &mut Option<(f64, f64)>, extra: Option<(f64, f64)>) {
+    if let Some((x, y)) = extra {
+        let (tx, ty) = shift.unwrap_or_default();
+        *shift = Some((tx + x, ty + y));
+    }
+}
+
+/// Parse a `SELECTORS=FACTOR` spec for [`WalkOptions::scale_overrides`]
+pub fn parse_scale_override(spec: &str) -> Result<(SelectorList, f64)> {
+    let (sel, factor) = spec
+        .rsplit_once('=')
+        .ok_or_else(|| anyhow::anyhow!("expected SELECTORS=FACTOR, got: {}", spec))?;
+    let factor = parse_scale_expr(factor)?;
+    check_scale(factor, false)?;
+    Ok((sel.parse()?, factor))
+}
+
 pub fn walk(node: Node, w: &mut XmlWriter, ctx: &ScaleCtx) -> Result<()> {
-    let style_rules = collect_style_rules(node);
-    walk_impl(node, w, ctx, false, false, &style_rules)
+    walk_with_options(node, w, ctx, &WalkOptions::default())
+}
+
+pub fn walk_with_options(
+    node: Node,
+    w: &mut XmlWriter,
+    ctx: &ScaleCtx,
+    opts: &WalkOptions,
+) -> Result<()> {
+    walk_with_styles(node, w, ctx, opts, &StyleSheet::collect(node))
+}
+
+/// `<style>` rules of a document, collected once and shared between walks
+/// at different scales
+pub struct StyleSheet(Vec<StyleRule>);
+
+impl StyleSheet {
+    pub fn collect(root: Node) -> Self {
+        StyleSheet(collect_style_rules(root))
+    }
+
+    /// What the rules set on `node`, with its inline `style` on top
+    pub(crate) fn computed_style(&self, node: Node) -> Vec<(String, String)> {
+        let mut props = collect_matching_style_props(&self.0, node);
+        merge_style_props(
+            &mut props,
+            &parse_style(node.attribute("style").unwrap_or("")),
+        );
+        props
+    }
+}
+
+/// The value of a CSS property on `node` from its inline `style` or, failing
+/// that, from the document's `<style>` rules; attributes are not consulted
+pub fn css_property(node: Node, name: &str) -> Option<String> {
+    let inline = node
+        .attribute("style")
+        .map(parse_style)
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .find(|(k, _)| k == name);
+    if let Some((_, v)) = inline {
+        return Some(v);
+    }
+    let rules = collect_style_rules(node.document().root_element());
+    collect_matching_style_props(&rules, node)
+        .into_iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v)
+}
+
+/// A root `width`/`height` in px: CSS wins over the attribute, as in browsers.
+/// User units are CSS px, so physical units always convert at 96dpi here.
+fn root_length(root: Node, name: &str) -> Option<f64> {
+    css_property(root, name)
+        .and_then(|v| length_to_px(&v, crate::DEFAULT_DPI))
+        .or_else(|| length_to_px(root.attribute(name)?, crate::DEFAULT_DPI))
+}
+
+/// Like [`walk_with_options`], reusing rules collected by [`StyleSheet::collect`]
+pub fn walk_with_styles(
+    node: Node,
+    w: &mut XmlWriter,
+    ctx: &ScaleCtx,
+    opts: &WalkOptions,
+    styles: &StyleSheet,
+) -> Result<()> {
+    let env = WalkEnv {
+        style_rules: &styles.0,
+        opts,
+        explain: None,
+    };
+    walk_impl(node, w, ctx, false, false, opts.only.is_none(), &env)
+}
+
+/// Like [`walk_with_styles`], writing into any [`MarkupSink`]
+pub(crate) fn walk_into(
+    node: Node,
+    w: &mut dyn MarkupSink,
+    ctx: &ScaleCtx,
+    opts: &WalkOptions,
+    styles: &StyleSheet,
+) -> Result<()> {
+    let env = WalkEnv {
+        style_rules: &styles.0,
+        opts,
+        explain: None,
+    };
+    walk_impl(node, w, ctx, false, false, opts.only.is_none(), &env)
+}
+
+/// Like [`walk_with_styles`], recording attribute decisions into `recorder`
+pub(crate) fn walk_recorded(
+    node: Node,
+    w: &mut dyn MarkupSink,
+    ctx: &ScaleCtx,
+    opts: &WalkOptions,
+    styles: &StyleSheet,
+    recorder: &Recorder,
+) -> Result<()> {
+    let env = WalkEnv {
+        style_rules: &styles.0,
+        opts,
+        explain: Some(recorder),
+    };
+    walk_impl(node, w, ctx, false, false, opts.only.is_none(), &env)
 }
 
 #[cfg(test)]
@@ -882,906 +2904,1899 @@ mod tests {
     use crate::scale::ScaleCtx;
 
     fn render_scaled_svg(input: &str, scale: f64) -> Result<String> {
+        render_with_options(input, scale, &WalkOptions::default())
+    }
+
+    fn render_with_options(input: &str, scale: f64, opts: &WalkOptions) -> Result<String> {
         let doc = roxmltree::Document::parse(input)?;
         let mut writer = XmlWriter::new(xmlwriter::Options::default());
-        walk(
+        walk_with_options(
             doc.root_element(),
             &mut writer,
             &ScaleCtx {
                 scale,
-                precision: 4,
-                fix_stroke: false,
+                ..ScaleCtx::default()
             },
+            opts,
         )?;
         Ok(writer.end_document())
     }
 
     #[test]
-    fn transform_scale_should_be_scaled_when_path_is_not() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="scale(2)"/></svg>"#;
+    fn transform_scale_should_be_scaled_when_path_is_not() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="scale(2)"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        let ok =
+            out.contains(r#"transform="scale(1)""#) || out.contains(r#"transform="scale(1,1)""#);
+        assert!(ok, "expected scaled transform, got: {out}");
+        Ok(())
+    }
+
+    #[test]
+    fn transform_matrix_should_scale_all_components() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="matrix(2,0,0,2,10,20)"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"transform="matrix(1,0,0,1,5,10)""#),
+            "expected scaled matrix, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn transform_combo_translate_rotate_scale() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="translate(10,20) rotate(30 5 6) scale(2)"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"transform="matrix("#)
+                || out.contains(r#"transform="translate(5,10) rotate(30,2.5,3) scale(1)""#)
+                || out.contains(r#"transform="translate(5,10) rotate(30,2.5,3) scale(1,1)""#),
+            "expected scaled transform combo, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn transform_combo_matrix_and_translate() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="matrix(1,2,3,4,10,20) translate(6 8)"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"transform="matrix("#)
+                || out.contains(r#"transform="matrix(0.5,1,1.5,2,5,10) translate(3,4)""#),
+            "expected scaled matrix + translate, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn transform_rotate_without_center_is_unchanged() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="rotate(30)"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"transform="matrix("#) || out.contains(r#"transform="rotate(30)""#),
+            "expected rotate angle unchanged or matrix, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn transform_skew_is_unchanged() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="skewX(30) skewY(10)"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"transform="matrix("#)
+                || out.contains(r#"transform="skewX(30) skewY(10)""#),
+            "expected skew angles unchanged or matrix, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn nested_transforms_scale_correctly() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><g transform="translate(10,20)"><g transform="scale(2)"><path d="M10 0 L20 0"/></g></g></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"transform="translate(5,10)""#),
+            "expected translated parent to scale, got: {out}"
+        );
+        assert!(
+            out.contains(r#"transform="scale(1)""#) || out.contains(r#"transform="scale(1,1)""#),
+            "expected scaled child transform, got: {out}"
+        );
+        assert!(
+            out.contains(r#"d="M10 0 L20 0""#),
+            "expected path not to be double-scaled under scale(), got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multi_element_integration() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg">
+            <g transform="translate(10,20)">
+                <rect x="5" y="6" width="10" height="12"/>
+            </g>
+            <circle cx="8" cy="9" r="4" transform="rotate(45 8 9)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"transform="translate(5,10)""#),
+            "expected group translate scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x="2.5""#) && out.contains(r#"y="3""#),
+            "expected rect position scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="5""#) && out.contains(r#"height="6""#),
+            "expected rect size scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"transform="matrix("#)
+                || out.contains(r#"transform="rotate(45,4,4.5)""#),
+            "expected rotate center scaled or matrix, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn non_scaling_stroke_preserves_stroke_width() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" vector-effect="non-scaling-stroke" stroke-width="2"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"stroke-width="2""#),
+            "expected stroke-width unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"vector-effect="non-scaling-stroke""#),
+            "expected vector-effect preserved, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn style_attributes_are_scaled() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect style="x:10; y:20; width:30; height:40; stroke-width:2"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"style="x:5; y:10; width:15; height:20; stroke-width:1""#),
+            "expected style numeric values scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn style_non_scaling_stroke_preserves_width() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" style="vector-effect:non-scaling-stroke; stroke-width:2"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"style="vector-effect:non-scaling-stroke; stroke-width:2""#),
+            "expected style stroke-width unchanged, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn style_transform_is_scaled_and_prevents_path_double_scale() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" style="transform:scale(2)"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"style="transform:scale(1)""#)
+                || out.contains(r#"style="transform:scale(1,1)""#)
+                || out.contains(r#"style="transform:matrix("#),
+            "expected style transform scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"d="M10 0 L20 0""#),
+            "expected path not double-scaled under style transform, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stylesheet_rules_are_applied_and_scaled() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <style>
+                rect { width: 30; height: 40; }
+                .big { x: 10; y: 20; }
+                #solo { stroke-width: 2; }
+            </style>
+            <rect id="solo" class="big"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"style="#),
+            "expected style attribute, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width:15"#),
+            "expected width scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"height:20"#),
+            "expected height scaled, got: {out}"
+        );
+        assert!(out.contains(r#"x:5"#), "expected x scaled, got: {out}");
+        assert!(out.contains(r#"y:10"#), "expected y scaled, got: {out}");
+        assert!(
+            out.contains(r#"stroke-width:1"#),
+            "expected stroke-width scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stroke_dasharray_and_offset_scale() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M0 0 L10 0" stroke-dasharray="4, 2 1" stroke-dashoffset="3"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"stroke-dasharray="2, 1 0.5""#),
+            "expected dasharray scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"stroke-dashoffset="1.5""#),
+            "expected dashoffset scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn font_size_and_letter_spacing_scale_in_style() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><text style="font-size:16; letter-spacing:2">Hi</text></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"style="font-size:8; letter-spacing:1"#),
+            "expected font properties scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn gradient_and_pattern_attributes_scale() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <linearGradient id="g1" x1="0" y1="0" x2="100" y2="200" gradientTransform="translate(10,20) scale(2)"/>
+                <radialGradient id="g2" cx="50" cy="60" r="40" fx="10" fy="20"/>
+                <pattern id="p1" x="5" y="6" width="70" height="80" patternTransform="translate(4 8)"/>
+            </defs>
+            <rect width="100" height="100" fill="url(#g1)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"x1="0""#) && out.contains(r#"y1="0""#),
+            "expected gradient x1/y1 scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x2="50""#) && out.contains(r#"y2="100""#),
+            "expected gradient x2/y2 scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"cx="25""#) && out.contains(r#"cy="30""#) && out.contains(r#"r="20""#),
+            "expected radial gradient scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"fx="5""#) && out.contains(r#"fy="10""#),
+            "expected focal point scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="35""#) && out.contains(r#"height="40""#),
+            "expected pattern size scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"patternTransform="matrix("#)
+                || out.contains(r#"patternTransform="translate(2,4)""#),
+            "expected pattern transform scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"gradientTransform="matrix("#)
+                || out.contains(r#"gradientTransform="translate(5,10) scale(1)"#),
+            "expected gradient transform scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mask_attributes_scale_in_user_space() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <mask id="m1" maskUnits="userSpaceOnUse" x="10" y="20" width="100" height="120">
+                <rect x="10" y="20" width="30" height="40"/>
+            </mask>
+            <rect width="200" height="200" mask="url(#m1)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"x="5""#) && out.contains(r#"y="10""#),
+            "expected mask x/y scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="50""#) && out.contains(r#"height="60""#),
+            "expected mask width/height scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn animate_motion_track_is_scaled_once() -> Result<()> {
+        let input = r##"<svg xmlns="http://www.w3.org/2000/svg"><defs><path id="track" d="M0 0 L100 0"/></defs><circle r="4"><animateMotion dur="2s" keyPoints="0;0.5;1" keyTimes="0;0.5;1" rotate="auto"><mpath href="#track"/></animateMotion></circle></svg>"##;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(out.contains(r#"d="M0 0 L50 0""#), "got: {out}");
+        assert!(
+            out.contains(
+                r#"<animateMotion dur="2s" keyPoints="0;0.5;1" keyTimes="0;0.5;1" rotate="auto">"#
+            ),
+            "got: {out}"
+        );
+        assert!(out.contains(r##"<mpath href="#track"/>"##), "got: {out}");
+        Ok(())
+    }
+
+    #[test]
+    fn animate_motion_inlines_a_track_scaled_elsewhere() -> Result<()> {
+        // The track's own rotation folds its scale into the transform, but
+        // the motion uses its bare data in the circle's parent space
+        let input = r##"<svg xmlns="http://www.w3.org/2000/svg"><path id="track" transform="rotate(45)" d="M0 0 L100 0"/><circle r="4"><animateMotion dur="2s" rotate="auto-reverse"><mpath href="#track"/></animateMotion></circle></svg>"##;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(out.contains(r#"d="M0 0 L100 0""#), "got: {out}");
+        assert!(
+            out.contains(r#"<animateMotion dur="2s" rotate="auto-reverse" path="M0 0 L50 0"/>"#),
+            "got: {out}"
+        );
+
+        // Under a folded group the motion stays in unscaled units, while a
+        // track in <defs> is scaled
+        let input = r##"<svg xmlns="http://www.w3.org/2000/svg"><defs><path id="track" d="M0 0 L100 0"/></defs><g transform="rotate(30)"><circle r="4"><animateMotion dur="2s"><mpath href="#track"/></animateMotion></circle></g></svg>"##;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(out.contains(r#"d="M0 0 L50 0""#), "got: {out}");
+        assert!(
+            out.contains(r#"<animateMotion dur="2s" path="M0 0 L100 0"/>"#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn animate_motion_coordinates_ignore_the_animated_elements_transform() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="10" height="10" transform="rotate(30)"><animateMotion path="M0 0 L100 0" values="0,0;100,20" keyPoints="0;1" keyTimes="0;1"/></rect></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"path="M0 0 L50 0" values="0,0;50,10" keyPoints="0;1""#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clip_path_object_bounding_box_is_not_scaled() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <clipPath id="c1" clipPathUnits="objectBoundingBox">
+                <rect x="0.1" y="0.2" width="0.5" height="0.6"/>
+            </clipPath>
+            <rect width="200" height="200" clip-path="url(#c1)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"x="0.1""#) && out.contains(r#"y="0.2""#),
+            "expected clipPath rect coords unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="0.5""#) && out.contains(r#"height="0.6""#),
+            "expected clipPath rect size unchanged, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn gradient_percent_values_are_preserved() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <linearGradient id="g1" x1="0%" y1="0%" x2="100%" y2="100%"/>
+                <radialGradient id="g2" cx="50%" cy="60%" r="40%"/>
+            </defs>
+            <rect width="100" height="100" fill="url(#g1)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"x1="0%""#) && out.contains(r#"y1="0%""#),
+            "expected linear gradient percents preserved, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x2="100%""#) && out.contains(r#"y2="100%""#),
+            "expected linear gradient percents preserved, got: {out}"
+        );
+        assert!(
+            out.contains(r#"cx="50%""#)
+                && out.contains(r#"cy="60%""#)
+                && out.contains(r#"r="40%""#),
+            "expected radial gradient percents preserved, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn gradient_object_bounding_box_is_not_scaled() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <linearGradient id="g1" gradientUnits="objectBoundingBox" x1="0.1" y1="0.2" x2="0.9" y2="1"/>
+                <radialGradient id="g2" gradientUnits="objectBoundingBox" cx="0.5" cy="0.6" r="0.4" fx="0.2" fy="0.3"/>
+            </defs>
+            <rect width="100" height="100" fill="url(#g1)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"x1="0.1""#) && out.contains(r#"y1="0.2""#),
+            "expected linear gradient values unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x2="0.9""#) && out.contains(r#"y2="1""#),
+            "expected linear gradient values unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"cx="0.5""#)
+                && out.contains(r#"cy="0.6""#)
+                && out.contains(r#"r="0.4""#),
+            "expected radial gradient values unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"fx="0.2""#) && out.contains(r#"fy="0.3""#),
+            "expected focal point unchanged, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_content_units_object_bounding_box_skips_child_scaling() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <pattern id="p1" patternUnits="userSpaceOnUse" patternContentUnits="objectBoundingBox" x="10" y="20" width="40" height="50">
+                    <rect x="0.1" y="0.2" width="0.5" height="0.6"/>
+                </pattern>
+            </defs>
+            <rect width="100" height="100" fill="url(#p1)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"x="5""#) && out.contains(r#"y="10""#),
+            "expected pattern x/y scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="20""#) && out.contains(r#"height="25""#),
+            "expected pattern size scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x="0.1""#) && out.contains(r#"y="0.2""#),
+            "expected child rect coords unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="0.5""#) && out.contains(r#"height="0.6""#),
+            "expected child rect size unchanged, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn length_units_are_scaled_and_preserved() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="10mm" height="8pt" x="1cm" y="2in"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"width="5mm""#) && out.contains(r#"height="4pt""#),
+            "expected mm/pt scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x="0.5cm""#) && out.contains(r#"y="1in""#),
+            "expected cm/in scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dasharray_units_are_scaled_and_preserved() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M0 0 L10 0" stroke-dasharray="2pt 4pt,1mm"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"stroke-dasharray="1pt 2pt,0.5mm""#),
+            "expected dasharray units scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn filter_object_bounding_box_is_not_scaled() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <filter id="f1" filterUnits="objectBoundingBox" x="0.1" y="0.2" width="0.5" height="0.6">
+                    <feOffset dx="10" dy="20"/>
+                </filter>
+            </defs>
+            <rect width="100" height="100" filter="url(#f1)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"x="0.1""#) && out.contains(r#"y="0.2""#),
+            "expected filter region unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="0.5""#) && out.contains(r#"height="0.6""#),
+            "expected filter region unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"dx="5""#) && out.contains(r#"dy="10""#),
+            "expected feOffset scaled in user space, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn filter_primitives_scale_in_user_space() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <filter id="f2" x="10" y="20" width="100" height="120">
+                    <feGaussianBlur stdDeviation="4 2"/>
+                    <feOffset dx="10" dy="20"/>
+                    <feMorphology radius="6"/>
+                </filter>
+            </defs>
+            <rect width="100" height="100" filter="url(#f2)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"x="5""#) && out.contains(r#"y="10""#),
+            "expected filter region scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="50""#) && out.contains(r#"height="60""#),
+            "expected filter size scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"stdDeviation="2 1""#),
+            "expected stdDeviation scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"dx="5""#) && out.contains(r#"dy="10""#),
+            "expected feOffset scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"radius="3""#),
+            "expected feMorphology radius scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn marker_units_stroke_width_skips_scaling() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <marker id="m1" markerUnits="strokeWidth" markerWidth="10" markerHeight="8" refX="2" refY="3">
+                    <rect x="1" y="2" width="3" height="4"/>
+                </marker>
+            </defs>
+            <path d="M0 0 L10 0" marker-end="url(#m1)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"markerWidth="10""#)
+                && out.contains(r#"markerHeight="8""#)
+                && out.contains(r#"refX="2""#)
+                && out.contains(r#"refY="3""#),
+            "expected marker attributes unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x="1""#) && out.contains(r#"y="2""#),
+            "expected marker child coords unchanged, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="3""#) && out.contains(r#"height="4""#),
+            "expected marker child size unchanged, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn marker_user_space_scales() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <marker id="m2" markerUnits="userSpaceOnUse" markerWidth="10" markerHeight="8" refX="2" refY="3">
+                    <rect x="4" y="6" width="10" height="12"/>
+                </marker>
+            </defs>
+            <path d="M0 0 L10 0" marker-end="url(#m2)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"markerWidth="5""#)
+                && out.contains(r#"markerHeight="4""#)
+                && out.contains(r#"refX="1""#)
+                && out.contains(r#"refY="1.5""#),
+            "expected marker attributes scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x="2""#) && out.contains(r#"y="3""#),
+            "expected marker child coords scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"width="5""#) && out.contains(r#"height="6""#),
+            "expected marker child size scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn filter_drop_shadow_and_displacement_scale() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <filter id="f3" x="10" y="20" width="100" height="120">
+                    <feDropShadow dx="4" dy="6" stdDeviation="5"/>
+                    <feDisplacementMap scale="8"/>
+                </filter>
+            </defs>
+            <rect width="100" height="100" filter="url(#f3)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"dx="2""#) && out.contains(r#"dy="3""#),
+            "expected drop shadow offset scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"stdDeviation="2.5""#),
+            "expected drop shadow blur scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"scale="4""#),
+            "expected displacement scale scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fe_image_subregion_scales_with_primitive_units() -> Result<()> {
+        let input = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100">
+<circle id="dot" cx="20" cy="20" r="10"/>
+<filter id="user" filterUnits="userSpaceOnUse" x="0" y="0" width="100" height="100">
+<feImage href="#dot" x="10" y="10" width="50" height="50"/>
+<feImage xlink:href="tex.png" x="5%" y="5" width="20" height="20" preserveAspectRatio="none"/>
+</filter>
+<filter id="bbox" primitiveUnits="objectBoundingBox">
+<feImage href="#dot" x="0.1" y="0.1" width="0.5" height="0.5"/>
+</filter>
+</svg>"##;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r##"<feImage href="#dot" x="5" y="5" width="25" height="25"/>"##),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r#"<feImage xlink:href="tex.png" x="5%" y="2.5" width="10" height="10" preserveAspectRatio="none"/>"#),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r##"<feImage href="#dot" x="0.1" y="0.1" width="0.5" height="0.5"/>"##),
+            "got: {out}"
+        );
+        assert!(out.contains(r#"<circle id="dot" cx="10" cy="10" r="5"/>"#));
+        Ok(())
+    }
+
+    #[test]
+    fn convolve_matrix_scales_only_its_kernel_unit_length() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><filter id="f"><feConvolveMatrix order="3 2" kernelMatrix="1 2 1 0 0 0" divisor="4" bias="0.5" targetX="1" targetY="0" kernelUnitLength="2" edgeMode="wrap"/></filter></svg>"#;
+        let out = render_scaled_svg(input, 3.0)?;
+        assert!(
+            out.contains(r#"<feConvolveMatrix order="3 2" kernelMatrix="1 2 1 0 0 0" divisor="4" bias="0.5" targetX="1" targetY="0" kernelUnitLength="6" edgeMode="wrap"/>"#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn filter_kernel_unit_length_and_surface_scale() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <filter id="f4" x="10" y="20" width="100" height="120">
+                    <feDiffuseLighting surfaceScale="5" kernelUnitLength="2 4"/>
+                </filter>
+            </defs>
+            <rect width="100" height="100" filter="url(#f4)"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"surfaceScale="2.5""#),
+            "expected surfaceScale scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"kernelUnitLength="1 2""#),
+            "expected kernelUnitLength scaled, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stylesheet_specificity_overrides() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <style>
+                rect { width: 20; }
+                .big { width: 30; }
+                #solo { width: 40; }
+                rect.big { height: 20; }
+                rect#solo { height: 40; }
+            </style>
+            <rect id="solo" class="big"/>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"width:20"#),
+            "expected id width scaled to 20, got: {out}"
+        );
+        assert!(
+            out.contains(r#"height:20"#),
+            "expected id+element height scaled to 20, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stylesheet_descendant_selector_applies() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <style>
+                g .inner { width: 30; }
+            </style>
+            <g>
+                <rect class="inner"/>
+            </g>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"width:15"#),
+            "expected descendant rule to apply, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stylesheet_child_selector_applies() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <style>
+                g > .inner { width: 30; }
+            </style>
+            <g>
+                <rect class="inner"/>
+            </g>
+        </svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"width:15"#),
+            "expected child rule to apply, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn filter_light_positions_scale() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <filter id="f5" x="10" y="20" width="100" height="120">
+                    <fePointLight x="10" y="20" z="30"/>
+                    <feSpotLight x="5" y="6" z="7" pointsAtX="8" pointsAtY="9" pointsAtZ="10"/>
+                </filter>
+            </defs>
+            <rect width="100" height="100" filter="url(#f5)"/>
+        </svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
-        let ok =
-            out.contains(r#"transform="scale(1)""#) || out.contains(r#"transform="scale(1,1)""#);
-        assert!(ok, "expected scaled transform, got: {out}");
+        assert!(
+            out.contains(r#"x="5""#) && out.contains(r#"y="10""#) && out.contains(r#"z="15""#),
+            "expected point light scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"pointsAtX="4""#)
+                && out.contains(r#"pointsAtY="4.5""#)
+                && out.contains(r#"pointsAtZ="5""#),
+            "expected spot light points scaled, got: {out}"
+        );
         Ok(())
     }
 
     #[test]
-    fn transform_matrix_should_scale_all_components() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="matrix(2,0,0,2,10,20)"/></svg>"#;
+    fn marker_orient_is_preserved() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <marker id="m4" orient="auto" markerWidth="10" markerHeight="8" refX="2" refY="3">
+                    <rect x="1" y="2" width="3" height="4"/>
+                </marker>
+            </defs>
+            <path d="M0 0 L10 0" marker-end="url(#m4)"/>
+        </svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"transform="matrix(1,0,0,1,5,10)""#),
-            "expected scaled matrix, got: {out}"
+            out.contains(r#"orient="auto""#),
+            "expected orient preserved, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn transform_combo_translate_rotate_scale() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="translate(10,20) rotate(30 5 6) scale(2)"/></svg>"#;
+    fn marker_orient_angle_is_preserved() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <marker id="m5" orient="45" markerWidth="10" markerHeight="8" refX="2" refY="3">
+                    <rect x="1" y="2" width="3" height="4"/>
+                </marker>
+            </defs>
+            <path d="M0 0 L10 0" marker-end="url(#m5)"/>
+        </svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"transform="matrix("#)
-                || out.contains(r#"transform="translate(5,10) rotate(30,2.5,3) scale(1)""#)
-                || out.contains(r#"transform="translate(5,10) rotate(30,2.5,3) scale(1,1)""#),
-            "expected scaled transform combo, got: {out}"
+            out.contains(r#"orient="45""#),
+            "expected orient angle preserved, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn transform_combo_matrix_and_translate() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="matrix(1,2,3,4,10,20) translate(6 8)"/></svg>"#;
+    fn turbulence_base_frequency_scales_inverse() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <filter id="f6">
+                    <feTurbulence baseFrequency="0.05 0.1"/>
+                </filter>
+            </defs>
+            <rect width="100" height="100" filter="url(#f6)"/>
+        </svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"transform="matrix("#)
-                || out.contains(r#"transform="matrix(0.5,1,1.5,2,5,10) translate(3,4)""#),
-            "expected scaled matrix + translate, got: {out}"
+            out.contains(r#"baseFrequency="0.1 0.2""#),
+            "expected baseFrequency inverse scaled, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn transform_rotate_without_center_is_unchanged() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="rotate(30)"/></svg>"#;
+    fn marker_default_units_stroke_width_skips_scaling() -> Result<()> {
+        let input = r#"
+        <svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <marker id="m3" markerWidth="10" markerHeight="8" refX="2" refY="3">
+                    <rect x="1" y="2" width="3" height="4"/>
+                </marker>
+            </defs>
+            <path d="M0 0 L10 0" marker-end="url(#m3)"/>
+        </svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"transform="matrix("#) || out.contains(r#"transform="rotate(30)""#),
-            "expected rotate angle unchanged or matrix, got: {out}"
+            out.contains(r#"markerWidth="10""#)
+                && out.contains(r#"markerHeight="8""#)
+                && out.contains(r#"refX="2""#)
+                && out.contains(r#"refY="3""#),
+            "expected default marker units to skip scaling, got: {out}"
+        );
+        assert!(
+            out.contains(r#"x="1""#) && out.contains(r#"y="2""#),
+            "expected marker child coords unchanged, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn transform_skew_is_unchanged() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="skewX(30) skewY(10)"/></svg>"#;
+    fn transform_scientific_e_notation_is_supported() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="scale(1E2) translate(1e1,2E1) rotate(3e1 4E0 5e0) matrix(1E0,0,0,1e0,1E1,2e1)"/></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
             out.contains(r#"transform="matrix("#)
-                || out.contains(r#"transform="skewX(30) skewY(10)""#),
-            "expected skew angles unchanged or matrix, got: {out}"
+                || out.contains(
+                    r#"transform="scale(50) translate(5,10) rotate(30,2,2.5) matrix(0.5,0,0,0.5,5,10)""#
+                ),
+            "expected scientific notation to parse, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn nested_transforms_scale_correctly() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><g transform="translate(10,20)"><g transform="scale(2)"><path d="M10 0 L20 0"/></g></g></svg>"#;
+    fn viewbox_scientific_e_notation_is_supported() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 1E2 2e2"><path d="M10 0 L20 0"/></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"transform="translate(5,10)""#),
-            "expected translated parent to scale, got: {out}"
+            out.contains(r#"viewBox="0 0 50 100""#),
+            "expected viewBox scaled with scientific notation, got: {out}"
         );
+        Ok(())
+    }
+
+    #[test]
+    fn fixture_basic_svg_scales() -> Result<()> {
+        let input = include_str!("../tests/fixtures/basic.svg");
+        let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"transform="scale(1)""#) || out.contains(r#"transform="scale(1,1)""#),
-            "expected scaled child transform, got: {out}"
+            out.contains(r#"viewBox="0 0 50 50""#),
+            "expected viewBox scaled, got: {out}"
         );
+        assert!(out.contains(r#"x="5""#) && out.contains(r#"y="10""#));
+        assert!(out.contains(r#"width="15""#) && out.contains(r#"height="20""#));
+        assert!(out.contains(r#"stroke-width="1""#));
         assert!(
-            out.contains(r#"d="M10 0 L20 0""#),
-            "expected path not to be double-scaled under scale(), got: {out}"
+            out.contains(r#"cx="25""#) && out.contains(r#"cy="30""#) && out.contains(r#"r="5""#)
         );
         Ok(())
     }
 
     #[test]
-    fn multi_element_integration() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg">
-            <g transform="translate(10,20)">
-                <rect x="5" y="6" width="10" height="12"/>
-            </g>
-            <circle cx="8" cy="9" r="4" transform="rotate(45 8 9)"/>
-        </svg>"#;
+    fn fixture_complex_svg_scales() -> Result<()> {
+        let input = include_str!("../tests/fixtures/complex.svg");
         let out = render_scaled_svg(input, 0.5)?;
+        assert!(
+            out.contains(r#"viewBox="0 0 100 50""#),
+            "expected viewBox scaled, got: {out}"
+        );
         assert!(
             out.contains(r#"transform="translate(5,10)""#),
-            "expected group translate scaled, got: {out}"
+            "expected translate scaled, got: {out}"
         );
         assert!(
-            out.contains(r#"x="2.5""#) && out.contains(r#"y="3""#),
-            "expected rect position scaled, got: {out}"
+            out.contains(r#"stroke-width="2""#),
+            "expected non-scaling stroke to remain, got: {out}"
         );
         assert!(
-            out.contains(r#"width="5""#) && out.contains(r#"height="6""#),
-            "expected rect size scaled, got: {out}"
+            out.contains(r#"transform="scale(1)""#) || out.contains(r#"transform="scale(1,1)""#),
+            "expected scale transformed, got: {out}"
         );
         assert!(
             out.contains(r#"transform="matrix("#)
-                || out.contains(r#"transform="rotate(45,4,4.5)""#),
+                || out.contains(r#"transform="rotate(45,20,25)""#),
             "expected rotate center scaled or matrix, got: {out}"
         );
+        assert!(
+            out.contains(r#"A 2.5 2.5 0 1 0 5 5""#),
+            "expected arc scaled correctly, got: {out}"
+        );
         Ok(())
     }
 
     #[test]
-    fn non_scaling_stroke_preserves_stroke_width() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" vector-effect="non-scaling-stroke" stroke-width="2"/></svg>"#;
+    fn fixture_skew_and_matrix_svg_scales() -> Result<()> {
+        let input = include_str!("../tests/fixtures/skew-matrix.svg");
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"stroke-width="2""#),
-            "expected stroke-width unchanged, got: {out}"
+            out.contains(r#"viewBox="0 0 60 30""#),
+            "expected viewBox scaled, got: {out}"
         );
         assert!(
-            out.contains(r#"vector-effect="non-scaling-stroke""#),
-            "expected vector-effect preserved, got: {out}"
+            out.contains(r#"transform="matrix("#)
+                || out.contains(r#"transform="skewX(30) skewY(10)""#),
+            "expected skew transform preserved or matrix, got: {out}"
+        );
+        assert!(
+            out.contains(r#"transform="matrix(0.5,1,1.5,2,5,10)""#),
+            "expected matrix scaled, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn style_attributes_are_scaled() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect style="x:10; y:20; width:30; height:40; stroke-width:2"/></svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn matrix_with_mirror_is_treated_as_non_translate() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><g transform="matrix(1,0,0,-1,0,216)"><path d="M10 0 L20 0"/></g></svg>"#;
+        let out = render_scaled_svg(input, 0.25)?;
         assert!(
-            out.contains(r#"style="x:5; y:10; width:15; height:20; stroke-width:1""#),
-            "expected style numeric values scaled, got: {out}"
+            out.contains(r#"transform="matrix(0.25,0,0,-0.25,0,54)""#),
+            "expected matrix scaled, got: {out}"
+        );
+        assert!(
+            out.contains(r#"d="M10 0 L20 0""#),
+            "expected path not double-scaled, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn style_non_scaling_stroke_preserves_width() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" style="vector-effect:non-scaling-stroke; stroke-width:2"/></svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn attribute_hooks_can_scale_and_veto() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect data-x="10" width="30" height="40"/><g transform="scale(2)"><rect data-x="10"/></g></svg>"#;
+        let opts = WalkOptions {
+            hooks: vec![Box::new(|el: &ElementCtx, name: &str, _: &str| {
+                match (el.tag_name, name) {
+                    (_, "data-x") => Handling::Scale(ValueKind::Length),
+                    ("rect", "width") => Handling::Keep,
+                    _ => Handling::Default,
+                }
+            })],
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.5, &opts)?;
         assert!(
-            out.contains(r#"style="vector-effect:non-scaling-stroke; stroke-width:2""#),
-            "expected style stroke-width unchanged, got: {out}"
+            out.contains(r#"<rect data-x="5" width="30" height="20"/>"#),
+            "expected hooked attributes, got: {out}"
+        );
+        assert!(
+            out.contains(r#"<rect data-x="10"/>"#),
+            "expected hook scaling to respect ancestor transform, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn style_transform_is_scaled_and_prevents_path_double_scale() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" style="transform:scale(2)"/></svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
-        assert!(
-            out.contains(r#"style="transform:scale(1)""#)
-                || out.contains(r#"style="transform:scale(1,1)""#)
-                || out.contains(r#"style="transform:matrix("#),
-            "expected style transform scaled, got: {out}"
+    fn scale_attr_specs_parse_kind_suffix() -> Result<()> {
+        assert_eq!(
+            parse_scale_attr("data-x")?,
+            ("data-x".to_string(), ValueKind::Length)
         );
-        assert!(
-            out.contains(r#"d="M10 0 L20 0""#),
-            "expected path not double-scaled under style transform, got: {out}"
+        assert_eq!(
+            parse_scale_attr("data-pts:list")?,
+            ("data-pts".to_string(), ValueKind::List)
+        );
+        assert_eq!(
+            parse_scale_attr("inkscape:cx")?,
+            ("inkscape:cx".to_string(), ValueKind::Length)
+        );
+        assert_eq!(
+            parse_scale_attr("my:freq:inverse")?,
+            ("my:freq".to_string(), ValueKind::Inverse)
         );
+        assert!(parse_scale_attr(":list").is_err());
         Ok(())
     }
 
     #[test]
-    fn stylesheet_rules_are_applied_and_scaled() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <style>
-                rect { width: 30; height: 40; }
-                .big { x: 10; y: 20; }
-                #solo { stroke-width: 2; }
-            </style>
-            <rect id="solo" class="big"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
-        assert!(
-            out.contains(r#"style="#),
-            "expected style attribute, got: {out}"
-        );
+    fn scale_attr_hook_scales_listed_attributes() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><g data-pts="10 20,30" data-other="10"/></svg>"#;
+        let opts = WalkOptions {
+            hooks: vec![scale_attr_hook(vec![(
+                "data-pts".to_string(),
+                ValueKind::List,
+            )])],
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.5, &opts)?;
         assert!(
-            out.contains(r#"width:15"#),
-            "expected width scaled, got: {out}"
+            out.contains(r#"data-pts="5 10,15" data-other="10""#),
+            "expected only listed attribute scaled, got: {out}"
         );
+        Ok(())
+    }
+
+    #[test]
+    fn exclude_selector_keeps_subtree_unscaled() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100"><rect width="40"/><g id="watermark" transform="translate(80,80)"><rect width="10" style="height:10"/></g></svg>"#;
+        let opts = WalkOptions {
+            exclude: Some("#watermark".parse()?),
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.5, &opts)?;
         assert!(
-            out.contains(r#"height:20"#),
-            "expected height scaled, got: {out}"
+            out.contains(r#"viewBox="0 0 50 50""#) && out.contains(r#"<rect width="20"/>"#),
+            "expected the rest of the document scaled, got: {out}"
         );
-        assert!(out.contains(r#"x:5"#), "expected x scaled, got: {out}");
-        assert!(out.contains(r#"y:10"#), "expected y scaled, got: {out}");
         assert!(
-            out.contains(r#"stroke-width:1"#),
-            "expected stroke-width scaled, got: {out}"
+            out.contains(r#"transform="translate(80,80)""#)
+                && out.contains(r#"<rect width="10" style="height:10"/>"#),
+            "expected excluded subtree unchanged, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn stroke_dasharray_and_offset_scale() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M0 0 L10 0" stroke-dasharray="4, 2 1" stroke-dashoffset="3"/></svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn only_selector_scales_matching_subtrees() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100"><g><rect class="glyph" width="40"/><rect width="40" x="4"/></g></svg>"#;
+        let opts = WalkOptions {
+            only: Some(".glyph".parse()?),
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.5, &opts)?;
+        assert!(
+            out.contains(r#"viewBox="0 0 50 50""#),
+            "expected root still scaled, got: {out}"
+        );
         assert!(
-            out.contains(r#"stroke-dasharray="2, 1 0.5""#),
-            "expected dasharray scaled, got: {out}"
+            out.contains(r#"<rect class="glyph" width="20"/>"#),
+            "expected matched element scaled, got: {out}"
         );
         assert!(
-            out.contains(r#"stroke-dashoffset="1.5""#),
-            "expected dashoffset scaled, got: {out}"
+            out.contains(r#"<rect width="40" x="4"/>"#),
+            "expected unmatched element unchanged, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn font_size_and_letter_spacing_scale_in_style() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><text style="font-size:16; letter-spacing:2">Hi</text></svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
-        assert!(
-            out.contains(r#"style="font-size:8; letter-spacing:1"#),
-            "expected font properties scaled, got: {out}"
-        );
+    fn selector_list_rejects_unsupported_selectors() {
+        assert!("rect:hover".parse::<SelectorList>().is_err());
+        assert!("#a, .b".parse::<SelectorList>().is_ok());
+    }
+
+    #[test]
+    fn selector_list_matches_attribute_selectors() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><g id="icon-home"/><g id="home-icon" data-x=""/><rect id="icon-x"/></svg>"#,
+        )?;
+        let ids = |sel: &str| -> Result<Vec<&str>> {
+            let list: SelectorList = sel.parse()?;
+            Ok(doc
+                .descendants()
+                .filter(|n| n.is_element() && list.matches(*n))
+                .filter_map(|n| n.attribute("id"))
+                .collect())
+        };
+        assert_eq!(ids("g[id^=icon-]")?, ["icon-home"]);
+        assert_eq!(ids("[id$='-icon']")?, ["home-icon"]);
+        assert_eq!(ids("[id*=con-]")?, ["icon-home", "icon-x"]);
+        assert_eq!(ids("g[data-x]")?, ["home-icon"]);
+        assert_eq!(ids(r#"[id="icon-x"]"#)?, ["icon-x"]);
+        assert!("g[id~=icon]".parse::<SelectorList>().is_err());
+        assert!("g[id".parse::<SelectorList>().is_err());
         Ok(())
     }
 
     #[test]
-    fn gradient_and_pattern_attributes_scale() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <linearGradient id="g1" x1="0" y1="0" x2="100" y2="200" gradientTransform="translate(10,20) scale(2)"/>
-                <radialGradient id="g2" cx="50" cy="60" r="40" fx="10" fy="20"/>
-                <pattern id="p1" x="5" y="6" width="70" height="80" patternTransform="translate(4 8)"/>
-            </defs>
-            <rect width="100" height="100" fill="url(#g1)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn scale_override_applies_to_matching_subtree() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100"><rect width="40"/><g id="badge"><circle r="8"/></g><circle id="dot" cx="85" cy="85" r="10"/><g id="moved" transform="translate(50 0)"><rect x="-5" y="-5" width="10" height="10"/></g></svg>"#;
+        let opts = WalkOptions {
+            scale_overrides: vec![parse_scale_override("#badge,#dot,#moved=2")?],
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.25, &opts)?;
         assert!(
-            out.contains(r#"x1="0""#) && out.contains(r#"y1="0""#),
-            "expected gradient x1/y1 scaled, got: {out}"
+            out.contains(r#"<rect width="10"/>"#),
+            "expected document-wide scale elsewhere, got: {out}"
         );
         assert!(
-            out.contains(r#"x2="50""#) && out.contains(r#"y2="100""#),
-            "expected gradient x2/y2 scaled, got: {out}"
+            out.contains(r#"<circle r="4"/>"#),
+            "expected the factor on top of the document's inside badge, got: {out}"
         );
+        // Centered at 21.25 on the 25 px canvas as before, twice the size
         assert!(
-            out.contains(r#"cx="25""#) && out.contains(r#"cy="30""#) && out.contains(r#"r="20""#),
-            "expected radial gradient scaled, got: {out}"
+            out.contains(r#"<circle id="dot" cx="42.5" cy="42.5" r="5" transform="translate(-21.25 -21.25)"/>"#),
+            "expected the badge to stay where it was, got: {out}"
         );
         assert!(
-            out.contains(r#"fx="5""#) && out.contains(r#"fy="10""#),
-            "expected focal point scaled, got: {out}"
+            out.contains(r#"<g id="moved" transform="translate(-12.5 0) translate(25,0)">"#),
+            "got: {out}"
         );
+        assert!(parse_scale_override("#badge").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn responsive_root_drops_size_and_keeps_view_box() -> Result<()> {
+        let opts = WalkOptions {
+            root_size: RootSize::Responsive,
+            ..Default::default()
+        };
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="0 0 100 50"><rect width="40"/></svg>"#;
+        let out = render_with_options(input, 0.5, &opts)?;
         assert!(
-            out.contains(r#"width="35""#) && out.contains(r#"height="40""#),
-            "expected pattern size scaled, got: {out}"
+            out.contains(r#"<svg viewBox="0 0 50 25">"#),
+            "expected width/height dropped, got: {out}"
         );
+        assert!(out.contains(r#"<rect width="20"/>"#), "got: {out}");
+
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="1in"><rect width="40"/></svg>"#;
+        let out = render_with_options(input, 0.5, &opts)?;
         assert!(
-            out.contains(r#"patternTransform="matrix("#)
-                || out.contains(r#"patternTransform="translate(2,4)""#),
-            "expected pattern transform scaled, got: {out}"
+            out.contains(r#"<svg viewBox="0 0 50 48">"#),
+            "expected viewBox synthesized from the root size, got: {out}"
         );
+
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100%" height="50"/>"#;
+        let out = render_with_options(input, 0.5, &opts)?;
         assert!(
-            out.contains(r#"gradientTransform="matrix("#)
-                || out.contains(r#"gradientTransform="translate(5,10) scale(1)"#),
-            "expected gradient transform scaled, got: {out}"
+            out.contains(r#"width="100%" height="25""#),
+            "expected size kept when no viewBox can be derived, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn mask_attributes_scale_in_user_space() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <mask id="m1" maskUnits="userSpaceOnUse" x="10" y="20" width="100" height="120">
-                <rect x="10" y="20" width="30" height="40"/>
-            </mask>
-            <rect width="200" height="200" mask="url(#m1)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn explicit_size_is_derived_from_view_box() -> Result<()> {
+        let opts = WalkOptions {
+            root_size: RootSize::Explicit,
+            ..Default::default()
+        };
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100%" viewBox="10 10 100 50"><rect width="40"/></svg>"#;
+        let out = render_with_options(input, 0.5, &opts)?;
         assert!(
-            out.contains(r#"x="5""#) && out.contains(r#"y="10""#),
-            "expected mask x/y scaled, got: {out}"
+            out.contains(r#"<svg viewBox="5 5 50 25" width="50" height="25">"#),
+            "expected width/height from the scaled viewBox, got: {out}"
         );
+
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"/>"#;
+        let out = render_with_options(input, 0.5, &opts)?;
         assert!(
-            out.contains(r#"width="50""#) && out.contains(r#"height="60""#),
-            "expected mask width/height scaled, got: {out}"
+            out.contains(r#"width="50" height="25""#),
+            "expected existing size scaled without a viewBox, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn clip_path_object_bounding_box_is_not_scaled() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <clipPath id="c1" clipPathUnits="objectBoundingBox">
-                <rect x="0.1" y="0.2" width="0.5" height="0.6"/>
-            </clipPath>
-            <rect width="200" height="200" clip-path="url(#c1)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn preserved_root_size_only_changes_coordinate_density() -> Result<()> {
+        let opts = WalkOptions {
+            root_size: RootSize::Preserve,
+            ..Default::default()
+        };
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24"><rect width="12"/></svg>"#;
+        let out = render_with_options(input, 10.0, &opts)?;
         assert!(
-            out.contains(r#"x="0.1""#) && out.contains(r#"y="0.2""#),
-            "expected clipPath rect coords unchanged, got: {out}"
+            out.contains(r#"<svg viewBox="0 0 240 240" width="24" height="24">"#),
+            "expected display size kept, got: {out}"
         );
+        assert!(out.contains(r#"<rect width="120"/>"#), "got: {out}");
+
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24px" height="12"><rect width="12"/></svg>"#;
+        let out = render_with_options(input, 10.0, &opts)?;
         assert!(
-            out.contains(r#"width="0.5""#) && out.contains(r#"height="0.6""#),
-            "expected clipPath rect size unchanged, got: {out}"
+            out.contains(r#"<svg viewBox="0 0 240 120" width="24px" height="12">"#),
+            "expected viewBox synthesized for the denser coordinates, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn gradient_percent_values_are_preserved() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <linearGradient id="g1" x1="0%" y1="0%" x2="100%" y2="100%"/>
-                <radialGradient id="g2" cx="50%" cy="60%" r="40%"/>
-            </defs>
-            <rect width="100" height="100" fill="url(#g1)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn shared_stylesheet_matches_per_walk_collection() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><style>.a { stroke-width: 4 }</style><rect class="a" width="40"/></svg>"#;
+        let doc = roxmltree::Document::parse(input)?;
+        let styles = StyleSheet::collect(doc.root_element());
+        for scale in [0.25, 0.5, 2.0] {
+            let ctx = ScaleCtx {
+                scale,
+                ..ScaleCtx::default()
+            };
+            let mut shared = XmlWriter::new(xmlwriter::Options::default());
+            walk_with_styles(
+                doc.root_element(),
+                &mut shared,
+                &ctx,
+                &WalkOptions::default(),
+                &styles,
+            )?;
+            assert_eq!(shared.end_document(), render_scaled_svg(input, scale)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn symbol_viewport_and_view_box_scale_together() -> Result<()> {
+        let input = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 48 48"><defs><symbol id="s" viewBox="0,0,10,10" refX="5" refY="center" x="1" y="2" width="20" height="20"><circle cx="5" cy="5" r="4"/></symbol></defs><use href="#s" x="10" y="10" width="24" height="24"/></svg>"##;
+        let out = render_scaled_svg(input, 2.0)?;
         assert!(
-            out.contains(r#"x1="0%""#) && out.contains(r#"y1="0%""#),
-            "expected linear gradient percents preserved, got: {out}"
+            out.contains(r#"<symbol id="s" viewBox="0 0 20 20" refX="10" refY="center" x="2" y="4" width="40" height="40">"#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"x2="100%""#) && out.contains(r#"y2="100%""#),
-            "expected linear gradient percents preserved, got: {out}"
+            out.contains(r#"<circle cx="10" cy="10" r="8"/>"#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"cx="50%""#)
-                && out.contains(r#"cy="60%""#)
-                && out.contains(r#"r="40%""#),
-            "expected radial gradient percents preserved, got: {out}"
+            out.contains(r##"<use href="#s" x="20" y="20" width="48" height="48"/>"##),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn gradient_object_bounding_box_is_not_scaled() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <linearGradient id="g1" gradientUnits="objectBoundingBox" x1="0.1" y1="0.2" x2="0.9" y2="1"/>
-                <radialGradient id="g2" gradientUnits="objectBoundingBox" cx="0.5" cy="0.6" r="0.4" fx="0.2" fy="0.3"/>
-            </defs>
-            <rect width="100" height="100" fill="url(#g1)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
-        assert!(
-            out.contains(r#"x1="0.1""#) && out.contains(r#"y1="0.2""#),
-            "expected linear gradient values unchanged, got: {out}"
-        );
-        assert!(
-            out.contains(r#"x2="0.9""#) && out.contains(r#"y2="1""#),
-            "expected linear gradient values unchanged, got: {out}"
-        );
+    fn view_box_stays_with_unscaled_content() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 48 48"><defs><marker id="m" viewBox="0 0 10 10" markerWidth="4" markerHeight="4"><path d="M0 0L10 5L0 10Z"/></marker></defs><g transform="rotate(45)"><svg viewBox="0 0 5 5" width="5"><rect width="5" height="5"/></svg></g></svg>"#;
+        let out = render_scaled_svg(input, 2.0)?;
         assert!(
-            out.contains(r#"cx="0.5""#)
-                && out.contains(r#"cy="0.6""#)
-                && out.contains(r#"r="0.4""#),
-            "expected radial gradient values unchanged, got: {out}"
+            out.contains(r#"<marker id="m" viewBox="0 0 10 10" markerWidth="4" markerHeight="4">"#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"fx="0.2""#) && out.contains(r#"fy="0.3""#),
-            "expected focal point unchanged, got: {out}"
+            out.contains(r#"<svg viewBox="0 0 5 5" width="5">"#),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn pattern_content_units_object_bounding_box_skips_child_scaling() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <pattern id="p1" patternUnits="userSpaceOnUse" patternContentUnits="objectBoundingBox" x="10" y="20" width="40" height="50">
-                    <rect x="0.1" y="0.2" width="0.5" height="0.6"/>
-                </pattern>
-            </defs>
-            <rect width="100" height="100" fill="url(#p1)"/>
-        </svg>"#;
+    fn enable_background_region_scales() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100" enable-background="new 0 0 100 100"><g style="enable-background:new 10 20 30 40"/><g enable-background="accumulate"/></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"x="5""#) && out.contains(r#"y="10""#),
-            "expected pattern x/y scaled, got: {out}"
-        );
-        assert!(
-            out.contains(r#"width="20""#) && out.contains(r#"height="25""#),
-            "expected pattern size scaled, got: {out}"
+            out.contains(r#"enable-background="new 0 0 50 50""#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"x="0.1""#) && out.contains(r#"y="0.2""#),
-            "expected child rect coords unchanged, got: {out}"
+            out.contains(r#"style="enable-background:new 5 10 15 20""#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"width="0.5""#) && out.contains(r#"height="0.6""#),
-            "expected child rect size unchanged, got: {out}"
+            out.contains(r#"enable-background="accumulate""#),
+            "got: {out}"
         );
         Ok(())
     }
 
+    fn render_fixing_stroke(input: &str, scale: f64) -> Result<String> {
+        let doc = roxmltree::Document::parse(input)?;
+        let ctx = ScaleCtx {
+            scale,
+            fix_stroke: true,
+            ..ScaleCtx::default()
+        };
+        let mut writer = XmlWriter::new(xmlwriter::Options::default());
+        walk(doc.root_element(), &mut writer, &ctx)?;
+        Ok(writer.end_document())
+    }
+
     #[test]
-    fn length_units_are_scaled_and_preserved() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="10mm" height="8pt" x="1cm" y="2in"/></svg>"#;
+    fn vector_effect_lists_are_recognized() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" vector-effect="non-rotation non-scaling-stroke" stroke-width="2"/><rect x="4" width="8" height="8" stroke-width="2" style="vector-effect:non-scaling-size" transform="translate(10 10)"/><circle r="4" vector-effect="fixed-position"/></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"width="5mm""#) && out.contains(r#"height="4pt""#),
-            "expected mm/pt scaled, got: {out}"
+            out.contains(r#"<path d="M5 0 L10 0" vector-effect="non-rotation non-scaling-stroke" stroke-width="2"/>"#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"x="0.5cm""#) && out.contains(r#"y="1in""#),
-            "expected cm/in scaled, got: {out}"
+            out.contains(
+                r#"<rect x="4" width="8" height="8" stroke-width="2" transform="translate(5,5)""#
+            ),
+            "expected screen-sized geometry unscaled, got: {out}"
         );
+        assert!(out.contains(r#"<circle r="2""#), "got: {out}");
         Ok(())
     }
 
     #[test]
-    fn dasharray_units_are_scaled_and_preserved() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M0 0 L10 0" stroke-dasharray="2pt 4pt,1mm"/></svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn fix_stroke_drops_only_non_scaling_keywords() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" vector-effect="non-scaling-stroke non-rotation" stroke-width="2"/><rect width="8" style="vector-effect:non-scaling-size"/><line x2="4" vector-effect="non-scaling-stroke"/></svg>"#;
+        let out = render_fixing_stroke(input, 0.5)?;
         assert!(
-            out.contains(r#"stroke-dasharray="1pt 2pt,0.5mm""#),
-            "expected dasharray units scaled, got: {out}"
+            out.contains(r#"<path d="M5 0 L10 0" vector-effect="non-rotation" stroke-width="1"/>"#),
+            "got: {out}"
         );
+        assert!(out.contains(r#"<rect width="4"/>"#), "got: {out}");
+        assert!(out.contains(r#"<line x2="2"/>"#), "got: {out}");
         Ok(())
     }
 
     #[test]
-    fn filter_object_bounding_box_is_not_scaled() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <filter id="f1" filterUnits="objectBoundingBox" x="0.1" y="0.2" width="0.5" height="0.6">
-                    <feOffset dx="10" dy="20"/>
-                </filter>
-            </defs>
-            <rect width="100" height="100" filter="url(#f1)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
-        assert!(
-            out.contains(r#"x="0.1""#) && out.contains(r#"y="0.2""#),
-            "expected filter region unchanged, got: {out}"
-        );
+    fn fill_box_transform_scales_content_around_its_origin() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><rect x="2" y="2" width="8" height="4" style="transform-box:fill-box; transform-origin:50% 50%" transform="rotate(90)"/></svg>"#;
+        let out = render_scaled_svg(input, 2.0)?;
         assert!(
-            out.contains(r#"width="0.5""#) && out.contains(r#"height="0.6""#),
-            "expected filter region unchanged, got: {out}"
+            out.contains(
+                r#"<rect x="4" y="4" width="16" height="8" transform="matrix(0,1,-1,0,0,0)""#
+            ),
+            "expected content scaled and the rotation kept, got: {out}"
         );
         assert!(
-            out.contains(r#"dx="5""#) && out.contains(r#"dy="10""#),
-            "expected feOffset scaled in user space, got: {out}"
+            out.contains("transform-box:fill-box; transform-origin:50% 50%"),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn filter_primitives_scale_in_user_space() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <filter id="f2" x="10" y="20" width="100" height="120">
-                    <feGaussianBlur stdDeviation="4 2"/>
-                    <feOffset dx="10" dy="20"/>
-                    <feMorphology radius="6"/>
-                </filter>
-            </defs>
-            <rect width="100" height="100" filter="url(#f2)"/>
-        </svg>"#;
+    fn absolute_transform_origin_scales_with_content() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M0 0L4 0" transform="translate(1 1) scale(2)" transform-origin="6 center"/></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"x="5""#) && out.contains(r#"y="10""#),
-            "expected filter region scaled, got: {out}"
-        );
-        assert!(
-            out.contains(r#"width="50""#) && out.contains(r#"height="60""#),
-            "expected filter size scaled, got: {out}"
+            out.contains(r#"<path d="M0 0L2 0" transform="matrix(2,0,0,2,0.5,0.5)" transform-origin="3 center"/>"#),
+            "got: {out}"
         );
+        Ok(())
+    }
+
+    #[test]
+    fn transforms_inside_folded_content_are_kept() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><g transform="rotate(90)"><g transform="rotate(90)"><rect width="4"/></g><g transform="translate(3 4)"/></g></svg>"#;
+        let out = render_scaled_svg(input, 2.0)?;
         assert!(
-            out.contains(r#"stdDeviation="2 1""#),
-            "expected stdDeviation scaled, got: {out}"
+            out.contains(r#"<g transform="matrix(0,2,-2,0,0,0)">"#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"dx="5""#) && out.contains(r#"dy="10""#),
-            "expected feOffset scaled, got: {out}"
+            out.contains(r#"<g transform="rotate(90)">"#),
+            "expected the inner transform to stay in unscaled space, got: {out}"
         );
         assert!(
-            out.contains(r#"radius="3""#),
-            "expected feMorphology radius scaled, got: {out}"
+            out.contains(r#"<g transform="translate(3 4)"/>"#),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn marker_units_stroke_width_skips_scaling() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <marker id="m1" markerUnits="strokeWidth" markerWidth="10" markerHeight="8" refX="2" refY="3">
-                    <rect x="1" y="2" width="3" height="4"/>
-                </marker>
-            </defs>
-            <path d="M0 0 L10 0" marker-end="url(#m1)"/>
-        </svg>"#;
+    fn marker_ref_keywords_are_kept_in_user_space() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><marker id="m" markerUnits="userSpaceOnUse" markerWidth="10" markerHeight="8" refX="center" refY="4"><path d="M0 0L10 4"/></marker></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"markerWidth="10""#)
-                && out.contains(r#"markerHeight="8""#)
-                && out.contains(r#"refX="2""#)
-                && out.contains(r#"refY="3""#),
-            "expected marker attributes unchanged, got: {out}"
+            out.contains(r#"markerWidth="5" markerHeight="4" refX="center" refY="2""#),
+            "got: {out}"
         );
+        Ok(())
+    }
+
+    #[test]
+    fn baked_marker_refs_use_the_view_box_or_marker_size() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><marker id="a" markerUnits="userSpaceOnUse" viewBox="10 0 20 10" markerWidth="6" markerHeight="6" refX="right" refY="center"/><marker id="b" markerWidth="6" markerHeight="4" refX="left" refY="bottom"/><marker id="c" refX="top"/></svg>"#;
+        let opts = WalkOptions {
+            bake_marker_refs: true,
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.5, &opts)?;
+        assert!(out.contains(r#"refX="15" refY="2.5""#), "got: {out}");
         assert!(
-            out.contains(r#"x="1""#) && out.contains(r#"y="2""#),
-            "expected marker child coords unchanged, got: {out}"
+            out.contains(r#"refX="0" refY="4""#),
+            "expected strokeWidth markers baked but unscaled, got: {out}"
         );
         assert!(
-            out.contains(r#"width="3""#) && out.contains(r#"height="4""#),
-            "expected marker child size unchanged, got: {out}"
+            out.contains(r#"refX="top""#),
+            "expected keywords for the other axis kept, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn marker_user_space_scales() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <marker id="m2" markerUnits="userSpaceOnUse" markerWidth="10" markerHeight="8" refX="2" refY="3">
-                    <rect x="4" y="6" width="10" height="12"/>
-                </marker>
-            </defs>
-            <path d="M0 0 L10 0" marker-end="url(#m2)"/>
-        </svg>"#;
+    fn dash_percentages_are_kept() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 30 40"><path d="M0 0L1 1" stroke-dasharray="10% 4" style="stroke-dasharray:10%"/></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"markerWidth="5""#)
-                && out.contains(r#"markerHeight="4""#)
-                && out.contains(r#"refX="1""#)
-                && out.contains(r#"refY="1.5""#),
-            "expected marker attributes scaled, got: {out}"
+            out.contains(r#"stroke-dasharray="10% 2" style="stroke-dasharray:10%""#),
+            "got: {out}"
         );
+        Ok(())
+    }
+
+    #[test]
+    fn dash_percentages_resolve_against_the_viewport_diagonal() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 30 40"><path d="M0 0L1 1" stroke-dasharray="10%, 4" style="stroke-dasharray:50%"/><svg width="6" height="8"><line stroke-dasharray="20%"/></svg></svg>"#;
+        let opts = WalkOptions {
+            resolve_dash_percentages: true,
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.5, &opts)?;
+        // sqrt((30² + 40²) / 2) = 35.3553
         assert!(
-            out.contains(r#"x="2""#) && out.contains(r#"y="3""#),
-            "expected marker child coords scaled, got: {out}"
+            out.contains(r#"stroke-dasharray="1.7678, 2" style="stroke-dasharray:8.8388""#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"width="5""#) && out.contains(r#"height="6""#),
-            "expected marker child size scaled, got: {out}"
+            out.contains(r#"<line stroke-dasharray="0.7071"/>"#),
+            "expected the nested viewport to be used, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn filter_drop_shadow_and_displacement_scale() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <filter id="f3" x="10" y="20" width="100" height="120">
-                    <feDropShadow dx="4" dy="6" stdDeviation="5"/>
-                    <feDisplacementMap scale="8"/>
-                </filter>
-            </defs>
-            <rect width="100" height="100" filter="url(#f3)"/>
-        </svg>"#;
+    fn text_spacing_lengths_scale_and_keywords_pass() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><text font-size="20" word-spacing="4" baseline-shift="-6px">a<tspan baseline-shift="super" style="word-spacing:normal; baseline-shift:2">b</tspan><tspan baseline-shift="30%" style="word-spacing:8">c</tspan></text></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"dx="2""#) && out.contains(r#"dy="3""#),
-            "expected drop shadow offset scaled, got: {out}"
+            out.contains(r#"font-size="10" word-spacing="2" baseline-shift="-3px""#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"stdDeviation="2.5""#),
-            "expected drop shadow blur scaled, got: {out}"
+            out.contains(r#"baseline-shift="super" style="word-spacing:normal; baseline-shift:1""#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"scale="4""#),
-            "expected displacement scale scaled, got: {out}"
+            out.contains(r#"baseline-shift="30%" style="word-spacing:4""#),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn filter_kernel_unit_length_and_surface_scale() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <filter id="f4" x="10" y="20" width="100" height="120">
-                    <feDiffuseLighting surfaceScale="5" kernelUnitLength="2 4"/>
-                </filter>
-            </defs>
-            <rect width="100" height="100" filter="url(#f4)"/>
-        </svg>"#;
+    fn text_position_lists_scale_but_rotate_is_kept() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><text x="10 20 30" y="5" dx="1,2" dy="2em 4" rotate="10 20 30">ab<tspan x="8 16" rotate="90">c</tspan></text></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"surfaceScale="2.5""#),
-            "expected surfaceScale scaled, got: {out}"
-        );
-        assert!(
-            out.contains(r#"kernelUnitLength="1 2""#),
-            "expected kernelUnitLength scaled, got: {out}"
+            out.contains(r#"<text x="5 10 15" y="2.5" dx="0.5,1" dy="2em 2" rotate="10 20 30">"#),
+            "got: {out}"
         );
+        assert!(out.contains(r#"<tspan x="4 8" rotate="90">"#), "got: {out}");
         Ok(())
     }
 
     #[test]
-    fn stylesheet_specificity_overrides() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <style>
-                rect { width: 20; }
-                .big { width: 30; }
-                #solo { width: 40; }
-                rect.big { height: 20; }
-                rect#solo { height: 40; }
-            </style>
-            <rect id="solo" class="big"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn css_root_size_scales_and_follows_root_modes() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><style>svg { width: 24px; height: 12px }</style><rect width="4"/></svg>"#;
+        let out = render_scaled_svg(input, 2.0)?;
         assert!(
-            out.contains(r#"width:20"#),
-            "expected id width scaled to 20, got: {out}"
+            out.contains(r#"<svg style="width:48px; height:24px">"#),
+            "got: {out}"
         );
+
+        let opts = WalkOptions {
+            root_size: RootSize::Responsive,
+            ..Default::default()
+        };
+        let out = render_with_options(input, 2.0, &opts)?;
+        assert!(out.contains(r#"<svg viewBox="0 0 48 24">"#), "got: {out}");
+
+        let opts = WalkOptions {
+            root_size: RootSize::Preserve,
+            ..Default::default()
+        };
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" style="width:24px;height:12px"><rect width="4"/></svg>"#;
+        let out = render_with_options(input, 2.0, &opts)?;
         assert!(
-            out.contains(r#"height:20"#),
-            "expected id+element height scaled to 20, got: {out}"
+            out.contains(r#"viewBox="0 0 48 24" style="width:24px; height:12px""#),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn stylesheet_descendant_selector_applies() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <style>
-                g .inner { width: 30; }
-            </style>
-            <g>
-                <rect class="inner"/>
-            </g>
-        </svg>"#;
+    fn css_items_keep_nested_blocks_whole() {
+        let css = "@import url(\"a{b}.css\"); @media (min-width: 10px) { .a { x: 1 } } /* } */ rect { y: 2 }";
+        let items = css_items(css);
+        let texts: Vec<(&str, Option<&str>)> = items
+            .iter()
+            .map(|i| (&css[i.prelude.clone()], i.block.clone().map(|b| &css[b])))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                ("@import url(\"a{b}.css\")", None),
+                ("@media (min-width: 10px) ", Some(" .a { x: 1 } ")),
+                ("rect ", Some(" y: 2 ")),
+            ]
+        );
+    }
+
+    #[test]
+    fn at_rules_do_not_swallow_later_rules() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><style>@font-face { font-family: X; src: url(x.woff) } @supports (display: grid) { .a { stroke-width: 2 } } rect { stroke-width: 4 }</style><rect class="a"/></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"width:15"#),
-            "expected descendant rule to apply, got: {out}"
+            out.contains(r#"<rect class="a" style="stroke-width:2"/>"#),
+            "expected the rule after the at-rules to apply, got: {out}"
+        );
+        assert!(
+            out.contains("@font-face { font-family: X; src: url(x.woff) }"),
+            "got: {out}"
+        );
+        assert!(
+            out.contains("@supports (display: grid) { .a { stroke-width: 1 } }"),
+            "expected rules inside @supports scaled in place, got: {out}"
         );
+        assert!(out.contains("rect { stroke-width: 4 }"), "got: {out}");
         Ok(())
     }
 
     #[test]
-    fn stylesheet_child_selector_applies() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <style>
-                g > .inner { width: 30; }
-            </style>
-            <g>
-                <rect class="inner"/>
-            </g>
-        </svg>"#;
+    fn nested_media_rules_scale_and_keep_important() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><style>@media screen { @supports (x: y) { path { stroke-width : 4px !important; fill: red } } }</style></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"width:15"#),
-            "expected child rule to apply, got: {out}"
+            out.contains("path { stroke-width : 2px !important; fill: red }"),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn filter_light_positions_scale() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <filter id="f5" x="10" y="20" width="100" height="120">
-                    <fePointLight x="10" y="20" z="30"/>
-                    <feSpotLight x="5" y="6" z="7" pointsAtX="8" pointsAtY="9" pointsAtZ="10"/>
-                </filter>
-            </defs>
-            <rect width="100" height="100" filter="url(#f5)"/>
-        </svg>"#;
+    fn sibling_combinators_match_following_elements() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><style>.a + rect { stroke-width: 4 } .a ~ circle { stroke-width: 8 }</style><rect class="a"/><!-- c --><rect id="next"/><rect id="far"/><circle r="1"/><g><circle id="nested" r="1"/></g></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"x="5""#) && out.contains(r#"y="10""#) && out.contains(r#"z="15""#),
-            "expected point light scaled, got: {out}"
+            out.contains(r#"<rect id="next" style="stroke-width:2"/>"#),
+            "expected adjacent sibling match, got: {out}"
         );
+        assert!(out.contains(r#"<rect id="far"/>"#), "got: {out}");
         assert!(
-            out.contains(r#"pointsAtX="4""#)
-                && out.contains(r#"pointsAtY="4.5""#)
-                && out.contains(r#"pointsAtZ="5""#),
-            "expected spot light points scaled, got: {out}"
+            out.contains(r#"<circle r="0.5" style="stroke-width:4"/>"#),
+            "expected general sibling match, got: {out}"
+        );
+        assert!(
+            out.contains(r#"<circle id="nested" r="0.5"/>"#),
+            "expected no match outside the sibling list, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn marker_orient_is_preserved() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <marker id="m4" orient="auto" markerWidth="10" markerHeight="8" refX="2" refY="3">
-                    <rect x="1" y="2" width="3" height="4"/>
-                </marker>
-            </defs>
-            <path d="M0 0 L10 0" marker-end="url(#m4)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
-        assert!(
-            out.contains(r#"orient="auto""#),
-            "expected orient preserved, got: {out}"
-        );
+    fn switch_keeps_only_the_matching_branch() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+<switch>
+  <text systemLanguage="de, fr" x="1">Hallo</text>
+  <text systemLanguage="en" x="2">Hello</text>
+  <text x="3">Fallback</text>
+</switch>
+</svg>"#;
+        let opts = |lang: &str| WalkOptions {
+            switch_language: Some(lang.to_string()),
+            ..WalkOptions::default()
+        };
+        let out = render_with_options(input, 2.0, &opts("en-GB"))?;
+        assert!(out.contains("Hello") && !out.contains("Hallo") && !out.contains("Fallback"));
+        assert!(out.contains(r#"x="4""#));
+        let out = render_with_options(input, 2.0, &opts("FR"))?;
+        assert!(out.contains("Hallo") && !out.contains("Hello"));
+        let out = render_with_options(input, 2.0, &opts("ja"))?;
+        assert!(out.contains("Fallback") && !out.contains("Hello"));
+        let all = render_with_options(input, 2.0, &WalkOptions::default())?;
+        assert!(all.contains("Hallo") && all.contains("Hello") && all.contains("Fallback"));
         Ok(())
     }
 
     #[test]
-    fn marker_orient_angle_is_preserved() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <marker id="m5" orient="45" markerWidth="10" markerHeight="8" refX="2" refY="3">
-                    <rect x="1" y="2" width="3" height="4"/>
-                </marker>
-            </defs>
-            <path d="M0 0 L10 0" marker-end="url(#m5)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn polygon_and_polyline_points_are_scaled() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20">
+<polygon points="0,0 10,0 5-8.5"/>
+<polyline id="p" points="1 2, 3e1 4"/>
+<g transform="rotate(45)"><polygon points="1,1 2,2 3,1"/></g>
+</svg>"#;
+        let out = render_scaled_svg(input, 2.0)?;
         assert!(
-            out.contains(r#"orient="45""#),
-            "expected orient angle preserved, got: {out}"
+            out.contains(r#"<polygon points="0,0 20,0 10-17"/>"#),
+            "{}",
+            out
         );
+        assert!(out.contains(r#"points="2 4, 60 8""#), "{}", out);
+        assert!(out.contains(r#"points="1,1 2,2 3,1""#), "{}", out);
         Ok(())
     }
 
     #[test]
-    fn turbulence_base_frequency_scales_inverse() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <filter id="f6">
-                    <feTurbulence baseFrequency="0.05 0.1"/>
-                </filter>
-            </defs>
-            <rect width="100" height="100" filter="url(#f6)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn clip_path_basic_shapes_scale_their_lengths() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="40">
+<style>.c { clip-path: inset(2px 4px round 1px) }</style>
+<rect id="a" style="clip-path: circle(10px at 20px 20px)" width="40" height="40"/>
+<rect id="b" clip-path="polygon(0 0, 10px 0, 50% 100%)" width="40" height="40"/>
+<rect id="c" style="clip-path: ellipse(closest-side 5px at 50% 50%)"/>
+<rect id="d" clip-path="url(#12)"/>
+<rect id="e" class="c"/>
+</svg>"#;
+        let out = render_scaled_svg(input, 2.0)?;
         assert!(
-            out.contains(r#"baseFrequency="0.1 0.2""#),
-            "expected baseFrequency inverse scaled, got: {out}"
+            out.contains("clip-path:circle(20px at 40px 40px)"),
+            "{}",
+            out
+        );
+        assert!(out.contains(r#"clip-path="polygon(0 0, 20px 0, 50% 100%)""#));
+        assert!(out.contains("clip-path:ellipse(closest-side 10px at 50% 50%)"));
+        assert!(out.contains(r#"clip-path="url(#12)""#));
+        assert!(
+            out.contains("clip-path:inset(4px 8px round 2px)"),
+            "{}",
+            out
         );
         Ok(())
     }
 
     #[test]
-    fn marker_default_units_stroke_width_skips_scaling() -> Result<()> {
-        let input = r#"
-        <svg xmlns="http://www.w3.org/2000/svg">
-            <defs>
-                <marker id="m3" markerWidth="10" markerHeight="8" refX="2" refY="3">
-                    <rect x="1" y="2" width="3" height="4"/>
-                </marker>
-            </defs>
-            <path d="M0 0 L10 0" marker-end="url(#m3)"/>
-        </svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn view_elements_track_the_root_coordinates() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24" width="24" height="24">
+<view id="zoomed" viewBox="6 6 12 12" preserveAspectRatio="xMinYMin slice"/>
+<g transform="rotate(45)"><view id="nested" viewBox="0 0 12 12"/></g>
+</svg>"#;
+        let out = render_scaled_svg(input, 2.0)?;
+        assert!(out.contains(
+            r#"<view id="zoomed" viewBox="12 12 24 24" preserveAspectRatio="xMinYMin slice"/>"#
+        ));
         assert!(
-            out.contains(r#"markerWidth="10""#)
-                && out.contains(r#"markerHeight="8""#)
-                && out.contains(r#"refX="2""#)
-                && out.contains(r#"refY="3""#),
-            "expected default marker units to skip scaling, got: {out}"
+            out.contains(r#"<view id="nested" viewBox="0 0 24 24"/>"#),
+            "{}",
+            out
         );
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_cursor_and_font_elements() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+<cursor id="c" x="2" y="3" href="c.png"/>
+<font horiz-adv-x="500"><font-face units-per-em="1000"/><glyph unicode="a" d="M0 0L10 10" horiz-adv-x="400"/></font>
+<text x="1" font-size="4"><altGlyph x="1">a</altGlyph></text>
+</svg>"#;
+        let out = render_scaled_svg(input, 2.0)?;
+        assert!(out.contains(r#"<cursor id="c" x="4" y="6" href="c.png"/>"#));
         assert!(
-            out.contains(r#"x="1""#) && out.contains(r#"y="2""#),
-            "expected marker child coords unchanged, got: {out}"
+            out.contains(r#"d="M0 0L10 10" horiz-adv-x="400""#),
+            "{}",
+            out
         );
+        assert!(out.contains(r#"<font horiz-adv-x="500">"#));
+
+        let opts = WalkOptions {
+            drop_deprecated: true,
+            ..WalkOptions::default()
+        };
+        let out = render_with_options(input, 2.0, &opts)?;
+        assert!(!out.contains("cursor") && !out.contains("glyph") && !out.contains("font-face"));
+        assert!(!out.contains("altGlyph"));
+        assert!(out.contains(r#"font-size="8""#) && out.contains('a'));
         Ok(())
     }
 
     #[test]
-    fn transform_scientific_e_notation_is_supported() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M10 0 L20 0" transform="scale(1E2) translate(1e1,2E1) rotate(3e1 4E0 5e0) matrix(1E0,0,0,1e0,1E1,2e1)"/></svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn physical_root_size_keeps_user_units_in_step() -> Result<()> {
+        let opts = WalkOptions {
+            root_size: RootSize::Physical {
+                width: 25.0,
+                height: 12.5,
+                unit: "mm",
+            },
+            ..WalkOptions::default()
+        };
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="12" style="width:24px"><rect width="24" height="12"/></svg>"#;
+        let out = render_with_options(input, 4.0, &opts)?;
         assert!(
-            out.contains(r#"transform="matrix("#)
-                || out.contains(
-                    r#"transform="scale(50) translate(5,10) rotate(30,2,2.5) matrix(0.5,0,0,0.5,5,10)""#
-                ),
-            "expected scientific notation to parse, got: {out}"
+            out.contains(r#"<svg viewBox="0 0 96 48" width="25mm" height="12.5mm">"#),
+            "{}",
+            out
+        );
+        assert!(out.contains(r#"<rect width="96" height="48"/>"#));
+        let with_vb = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 12" width="24"><rect width="24"/></svg>"#;
+        let out = render_with_options(with_vb, 4.0, &opts)?;
+        assert!(
+            out.contains(r#"viewBox="0 0 96 48" width="25mm" height="12.5mm""#),
+            "{}",
+            out
         );
         Ok(())
     }
 
     #[test]
-    fn viewbox_scientific_e_notation_is_supported() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 1E2 2e2"><path d="M10 0 L20 0"/></svg>"#;
-        let out = render_scaled_svg(input, 0.5)?;
+    fn stroke_compensation_scales_stroke_width_by_a_power() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="128" height="128"><path d="M0 0L128 128" stroke-width="8" style="stroke-width:8"/></svg>"#;
+        let doc = roxmltree::Document::parse(input)?;
+        let ctx = ScaleCtx {
+            scale: 0.125,
+            stroke_exponent: Some(0.5),
+            ..ScaleCtx::default()
+        };
+        let out = crate::write_svg(&doc, &ctx)?;
+        // 8 * 0.125^0.5, while the geometry still scales linearly
         assert!(
-            out.contains(r#"viewBox="0 0 50 100""#),
-            "expected viewBox scaled with scientific notation, got: {out}"
+            out.contains(r#"d="M0 0L16 16" stroke-width="2.8284" style="stroke-width:2.8284""#),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn fixture_basic_svg_scales() -> Result<()> {
-        let input = include_str!("../tests/fixtures/basic.svg");
+    fn gradients_scale_by_the_units_they_inherit() -> Result<()> {
+        let input = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><radialGradient id="base" gradientUnits="objectBoundingBox" cx="0.5" cy="0.5" r="0.5" fr="0.1"/><radialGradient id="derived" xlink:href="#base" fx="0.2"/><radialGradient id="user" href="#base" gradientUnits="userSpaceOnUse" r="8"/><radialGradient id="plain" cx="4" r="6" fr="2"/></svg>"##;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"viewBox="0 0 50 50""#),
-            "expected viewBox scaled, got: {out}"
+            out.contains(r##"<radialGradient id="derived" xlink:href="#base" fx="0.2"/>"##),
+            "expected inherited objectBoundingBox units to be kept, got: {out}"
         );
-        assert!(out.contains(r#"x="5""#) && out.contains(r#"y="10""#));
-        assert!(out.contains(r#"width="15""#) && out.contains(r#"height="20""#));
-        assert!(out.contains(r#"stroke-width="1""#));
         assert!(
-            out.contains(r#"cx="25""#) && out.contains(r#"cy="30""#) && out.contains(r#"r="5""#)
+            out.contains(r##"<radialGradient id="user" href="#base" gradientUnits="userSpaceOnUse" r="4" cx="0.25" cy="0.25" fr="0.05"/>"##),
+            "expected inherited geometry copied and scaled in user space, got: {out}"
+        );
+        assert!(
+            out.contains(r#"cx="2" r="3" fr="1""#),
+            "expected fr to scale with r, got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn fixture_complex_svg_scales() -> Result<()> {
-        let input = include_str!("../tests/fixtures/complex.svg");
+    fn clip_rect_and_mask_position_scale_in_style() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><svg width="40" height="40" clip="rect(2px, 30px, 30px, auto)"/><g style="clip: rect(4px 8px 12px 16px); mask-position: 10px center; -webkit-mask-size: 20px 50%; mask-size: cover"/><rect clip="auto"/></svg>"#;
         let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"viewBox="0 0 100 50""#),
-            "expected viewBox scaled, got: {out}"
+            out.contains(r#"clip="rect(1px, 15px, 15px, auto)""#),
+            "expected clip attribute scaled, got: {out}"
         );
         assert!(
-            out.contains(r#"transform="translate(5,10)""#),
-            "expected translate scaled, got: {out}"
+            out.contains("clip:rect(2px 4px 6px 8px)") && out.contains("mask-position:5px center"),
+            "expected clip and mask-position scaled in style, got: {out}"
         );
         assert!(
-            out.contains(r#"stroke-width="2""#),
-            "expected non-scaling stroke to remain, got: {out}"
+            out.contains("-webkit-mask-size:10px 50%") && out.contains("mask-size:cover"),
+            "expected mask-size lengths scaled and keywords kept, got: {out}"
         );
-        assert!(
-            out.contains(r#"transform="scale(1)""#) || out.contains(r#"transform="scale(1,1)""#),
-            "expected scale transformed, got: {out}"
+        assert!(out.contains(r#"clip="auto""#), "got: {out}");
+        Ok(())
+    }
+
+    #[test]
+    fn embedded_svg_images_scale_with_the_document() -> Result<()> {
+        let inner = "data:image/svg+xml,%3Csvg%20xmlns=%22http://www.w3.org/2000/svg%22%20width=%2210%22%3E%3Crect%20width=%2210%22/%3E%3C/svg%3E";
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><image width="10" xlink:href="{inner}"/><image href="data:image/png;base64,AAAA"/></svg>"#
         );
+        let doc = roxmltree::Document::parse(&svg)?;
+        let ctx = ScaleCtx {
+            scale: 2.0,
+            ..ScaleCtx::default()
+        };
+        let opts = WalkOptions {
+            recurse_embedded: true,
+            ..WalkOptions::default()
+        };
+        let out = crate::write_svg_with_options(&doc, &ctx, &opts)?;
+        let out = roxmltree::Document::parse(&out)?;
+        let images: Vec<_> = out
+            .descendants()
+            .filter(|n| n.has_tag_name("image"))
+            .collect();
+        let href = images[0]
+            .attribute(("http://www.w3.org/1999/xlink", "href"))
+            .unwrap_or_default();
+        let embedded = data_uri::decode(href)?.unwrap_or_default();
+        assert!(embedded.contains(r#"<svg width="20""#), "got: {embedded}");
         assert!(
-            out.contains(r#"transform="matrix("#)
-                || out.contains(r#"transform="rotate(45,20,25)""#),
-            "expected rotate center scaled or matrix, got: {out}"
+            embedded.contains(r#"<rect width="20"/>"#),
+            "got: {embedded}"
         );
-        assert!(
-            out.contains(r#"A 2.5 2.5 0 1 0 5 5""#),
-            "expected arc scaled correctly, got: {out}"
+        assert_eq!(
+            images[1].attribute("href"),
+            Some("data:image/png;base64,AAAA")
         );
+        // Off by default
+        let plain = crate::write_svg(&doc, &ctx)?;
+        assert!(plain.contains(inner), "got: {plain}");
         Ok(())
     }
 
     #[test]
-    fn fixture_skew_and_matrix_svg_scales() -> Result<()> {
-        let input = include_str!("../tests/fixtures/skew-matrix.svg");
-        let out = render_scaled_svg(input, 0.5)?;
+    fn stitched_turbulence_keeps_whole_tiles_and_its_seed() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><filter id="f" filterUnits="userSpaceOnUse" width="100" height="100"><feTurbulence baseFrequency="0.023" stitchTiles="stitch" seed="3" numOctaves="2"/></filter></svg>"#;
+        let opts = WalkOptions {
+            hooks: vec![scale_attr_hook(vec![(
+                "seed".to_string(),
+                ValueKind::Length,
+            )])],
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.5, &opts)?;
+        // Two cycles over the tile, before and after
         assert!(
-            out.contains(r#"viewBox="0 0 60 30""#),
-            "expected viewBox scaled, got: {out}"
+            out.contains(r#"<feTurbulence baseFrequency="0.04" stitchTiles="stitch" seed="3" numOctaves="2"/>"#),
+            "got: {out}"
         );
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_view_box_keeps_content_and_scales_the_tile() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><pattern id="p" patternUnits="userSpaceOnUse" width="10" height="8" viewBox="0 0 4 4" preserveAspectRatio="xMidYMid slice"><circle cx="2" cy="2" r="1"/></pattern><pattern id="q" width="0.1" height="0.1" patternUnits="objectBoundingBox" patternContentUnits="objectBoundingBox" viewBox="0 0 4 4"><rect width="2" height="2"/></pattern><rect width="100" height="100" fill="url(#p)"/></svg>"#;
+        let out = render_scaled_svg(input, 0.5)?;
         assert!(
-            out.contains(r#"transform="matrix("#)
-                || out.contains(r#"transform="skewX(30) skewY(10)""#),
-            "expected skew transform preserved or matrix, got: {out}"
+            out.contains(
+                r#"width="5" height="4" viewBox="0 0 4 4" preserveAspectRatio="xMidYMid slice""#
+            ) && out.contains(r#"<circle cx="2" cy="2" r="1"/>"#),
+            "got: {out}"
         );
         assert!(
-            out.contains(r#"transform="matrix(0.5,1,1.5,2,5,10)""#),
-            "expected matrix scaled, got: {out}"
+            out.contains(r#"width="0.1" height="0.1""#)
+                && out.contains(r#"<rect width="2" height="2"/>"#),
+            "got: {out}"
         );
         Ok(())
     }
 
     #[test]
-    fn matrix_with_mirror_is_treated_as_non_translate() -> Result<()> {
-        let input = r#"<svg xmlns="http://www.w3.org/2000/svg"><g transform="matrix(1,0,0,-1,0,216)"><path d="M10 0 L20 0"/></g></svg>"#;
-        let out = render_scaled_svg(input, 0.25)?;
+    fn only_matches_follow_their_ancestors_transforms() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100"><g transform="translate(40 0)"><g transform="rotate(90)"><rect class="glyph" x="10" width="20" height="4"/></g><rect x="10" width="20"/></g></svg>"#;
+        let opts = WalkOptions {
+            only: Some(".glyph".parse()?),
+            ..Default::default()
+        };
+        let out = render_with_options(input, 0.5, &opts)?;
+        // A whole-document scale draws the glyph from (20, 5) to (18, 15);
+        // in the rotated frame that is x 5..15 after translate(0 20)
         assert!(
-            out.contains(r#"transform="matrix(0.25,0,0,-0.25,0,54)""#),
-            "expected matrix scaled, got: {out}"
+            out.contains(
+                r#"<rect class="glyph" x="5" width="10" height="2" transform="translate(0 20)"/>"#
+            ),
+            "expected the glyph shifted into place, got: {out}"
         );
         assert!(
-            out.contains(r#"d="M10 0 L20 0""#),
-            "expected path not double-scaled, got: {out}"
+            out.contains(r#"<g transform="translate(40 0)">"#)
+                && out.contains(r#"<rect x="10" width="20"/>"#),
+            "expected unmatched elements unchanged, got: {out}"
         );
         Ok(())
     }
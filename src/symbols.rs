@@ -0,0 +1,281 @@
+//! `<symbol>`s reused at several sizes.
+//!
+//! Each `<use>` of a symbol with a `viewBox` draws it in its own viewport,
+//! so one symbol can appear at several sizes, each with its own factor from
+//! symbol units to the referencing user space. The scaler keeps those
+//! factors, but tools that later inline symbols or rewrite ids see one
+//! definition for differently sized instances. [`mixed_size_symbols`]
+//! reports such reuse and [`bake_instance_scales`] moves each instance's
+//! factor into its own `transform`, so every `<use>` draws the symbol at its
+//! natural size.
+
+use anyhow::Result;
+use roxmltree::Node;
+use xmlwriter::XmlWriter;
+
+use crate::svg;
+use crate::viewbox::ViewBox;
+use crate::DEFAULT_DPI;
+
+/// One `<use>` of a symbol and the viewport it draws it in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    /// `use`, with `#id` when the element has one
+    pub element: String,
+    /// 1-based line of the `<use>` in the source
+    pub line: u32,
+    pub width: f64,
+    pub height: f64,
+    /// Factors from symbol units to the `<use>`'s user space
+    pub factor: (f64, f64),
+}
+
+/// A symbol its instances draw at more than one size
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedSymbol {
+    pub id: String,
+    pub view_box: ViewBox,
+    pub instances: Vec<Instance>,
+}
+
+/// Symbols with a `viewBox` that two or more `<use>` elements draw at
+/// different sizes, in document order. Instances whose size is not known
+/// in user units (percentages, or no size anywhere) are left out.
+pub fn mixed_size_symbols(doc: &roxmltree::Document) -> Vec<MixedSymbol> {
+    let mut found: Vec<MixedSymbol> = Vec::new();
+    for node in doc.descendants().filter(|n| n.has_tag_name("use")) {
+        let Some((symbol, view_box)) = referenced_symbol(node) else {
+            continue;
+        };
+        let Some(viewport) = instance_viewport(node, symbol) else {
+            continue;
+        };
+        let fit = Fit::of(symbol, &view_box, &viewport);
+        let id = node.attribute("id").unwrap_or("");
+        let instance = Instance {
+            element: if id.is_empty() {
+                "use".to_string()
+            } else {
+                format!("use#{}", id)
+            },
+            line: doc.text_pos_at(node.range().start).row,
+            width: viewport.width,
+            height: viewport.height,
+            factor: (fit.kx, fit.ky),
+        };
+        let symbol_id = symbol.attribute("id").unwrap_or("");
+        match found.iter_mut().find(|s| s.id == symbol_id) {
+            Some(group) => group.instances.push(instance),
+            None => found.push(MixedSymbol {
+                id: symbol_id.to_string(),
+                view_box,
+                instances: vec![instance],
+            }),
+        }
+    }
+    found.retain(|s| {
+        s.instances
+            .iter()
+            .any(|i| (i.width, i.height) != (s.instances[0].width, s.instances[0].height))
+    });
+    found
+}
+
+/// The document, unindented, with every `<use>` of a [`mixed_size_symbols`]
+/// entry drawing the symbol at its viewBox size, under a `transform` that
+/// places and scales it as its viewport did
+pub fn bake_instance_scales(doc: &roxmltree::Document) -> Result<String> {
+    let mixed = mixed_size_symbols(doc);
+    let mut w = XmlWriter::new(xmlwriter::Options::default());
+    write_baked(doc.root_element(), &mixed, &mut w);
+    crate::strip_indentation(&crate::finish_document(doc, w))
+}
+
+fn write_baked(node: Node, mixed: &[MixedSymbol], w: &mut XmlWriter) {
+    match node.node_type() {
+        roxmltree::NodeType::Element => {
+            w.start_element(node.tag_name().name());
+            let baked = node
+                .has_tag_name("use")
+                .then(|| referenced_symbol(node))
+                .flatten()
+                .filter(|(symbol, _)| {
+                    mixed
+                        .iter()
+                        .any(|m| symbol.attribute("id") == Some(m.id.as_str()))
+                })
+                .and_then(|(symbol, view_box)| {
+                    let viewport = instance_viewport(node, symbol)?;
+                    Some((Fit::of(symbol, &view_box, &viewport), view_box))
+                });
+            match baked {
+                Some((fit, view_box)) => {
+                    let placement = format!(
+                        "translate({} {}) scale({} {})",
+                        fmt(fit.tx + view_box.x * fit.kx),
+                        fmt(fit.ty + view_box.y * fit.ky),
+                        fmt(fit.kx),
+                        fmt(fit.ky)
+                    );
+                    for attr in node.attributes() {
+                        let name = svg::qualified_attr_name(node, &attr);
+                        if !matches!(&*name, "x" | "y" | "width" | "height" | "transform") {
+                            w.write_attribute(&name, attr.value());
+                        }
+                    }
+                    let transform = match node.attribute("transform") {
+                        Some(t) => format!("{} {}", t.trim(), placement),
+                        None => placement,
+                    };
+                    w.write_attribute("transform", &transform);
+                    w.write_attribute("width", &fmt(view_box.width));
+                    w.write_attribute("height", &fmt(view_box.height));
+                }
+                None => {
+                    for attr in node.attributes() {
+                        let name = svg::qualified_attr_name(node, &attr);
+                        w.write_attribute(&name, attr.value());
+                    }
+                }
+            }
+            for c in node.children() {
+                write_baked(c, mixed, w);
+            }
+            w.end_element();
+        }
+        roxmltree::NodeType::Text => {
+            w.write_text(node.text().unwrap_or(""));
+        }
+        _ => {}
+    }
+}
+
+fn fmt(n: f64) -> String {
+    let n = (n * 1e9).round() / 1e9;
+    format!("{}", if n == 0.0 { 0.0 } else { n })
+}
+
+/// The `<symbol>` a `<use>` points at, with its viewBox
+fn referenced_symbol<'a, 'i>(node: Node<'a, 'i>) -> Option<(Node<'a, 'i>, ViewBox)> {
+    let href = node
+        .attributes()
+        .find(|a| a.name() == "href")?
+        .value()
+        .trim();
+    let id = href.strip_prefix('#')?;
+    let symbol = node
+        .document()
+        .descendants()
+        .find(|n| n.has_tag_name("symbol") && n.attribute("id") == Some(id))?;
+    let view_box = symbol.attribute("viewBox").and_then(ViewBox::parse)?;
+    Some((symbol, view_box))
+}
+
+/// Where a `<use>` draws its symbol: its own `x/y/width/height`, the size
+/// falling back to the symbol's
+fn instance_viewport(node: Node, symbol: Node) -> Option<ViewBox> {
+    let number = |n: Node, name| svg::length_to_px(n.attribute(name)?, DEFAULT_DPI);
+    let size = |name| number(node, name).or_else(|| number(symbol, name));
+    Some(ViewBox {
+        x: number(node, "x").unwrap_or(0.0),
+        y: number(node, "y").unwrap_or(0.0),
+        width: size("width").filter(|&w| w > 0.0)?,
+        height: size("height").filter(|&h| h > 0.0)?,
+    })
+}
+
+/// How a viewBox maps onto a viewport under `preserveAspectRatio`: a point
+/// `p` in viewBox units lands at `(tx + kx·px, ty + ky·py)`
+struct Fit {
+    kx: f64,
+    ky: f64,
+    tx: f64,
+    ty: f64,
+}
+
+impl Fit {
+    fn of(symbol: Node, view_box: &ViewBox, viewport: &ViewBox) -> Fit {
+        let par = symbol.attribute("preserveAspectRatio").unwrap_or("");
+        let mut words = par.split_whitespace();
+        let align = words.next().unwrap_or("xMidYMid");
+        let slice = words.next() == Some("slice");
+        let (kx, ky) = (
+            viewport.width / view_box.width,
+            viewport.height / view_box.height,
+        );
+        if align == "none" {
+            return Fit {
+                kx,
+                ky,
+                tx: viewport.x - view_box.x * kx,
+                ty: viewport.y - view_box.y * ky,
+            };
+        }
+        let k = if slice { kx.max(ky) } else { kx.min(ky) };
+        let offset = |free: f64, min: &str, mid: &str| {
+            if align.contains(min) {
+                0.0
+            } else if align.contains(mid) {
+                free / 2.0
+            } else {
+                free
+            }
+        };
+        let dx = offset(viewport.width - view_box.width * k, "xMin", "xMid");
+        let dy = offset(viewport.height - view_box.height * k, "YMin", "YMid");
+        Fit {
+            kx: k,
+            ky: k,
+            tx: viewport.x + dx - view_box.x * k,
+            ty: viewport.y + dy - view_box.y * k,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPRITE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><symbol id="dot" viewBox="0 0 10 10"><circle cx="5" cy="5" r="5"/></symbol><symbol id="same" viewBox="0 0 4 4"/><use id="a" href="#dot" width="20" height="20"/><use id="b" xlink:href="#dot" x="30" width="40" height="20"/><use href="#same" width="8" height="8"/><use href="#same" width="8" height="8"/></svg>"##;
+
+    #[test]
+    fn reports_symbols_used_at_several_sizes() -> Result<()> {
+        let doc = roxmltree::Document::parse(SPRITE)?;
+        let mixed = mixed_size_symbols(&doc);
+        assert_eq!(mixed.len(), 1);
+        assert_eq!(mixed[0].id, "dot");
+        let sizes: Vec<_> = mixed[0]
+            .instances
+            .iter()
+            .map(|i| (i.element.as_str(), i.width, i.height, i.factor))
+            .collect();
+        assert_eq!(
+            sizes,
+            [
+                ("use#a", 20.0, 20.0, (2.0, 2.0)),
+                ("use#b", 40.0, 20.0, (2.0, 2.0))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn baked_instances_draw_the_symbol_at_its_own_size() -> Result<()> {
+        let doc = roxmltree::Document::parse(SPRITE)?;
+        let out = bake_instance_scales(&doc)?;
+        assert!(
+            out.contains(r##"<use id="a" href="#dot" transform="translate(0 0) scale(2 2)" width="10" height="10"/>"##),
+            "got: {out}"
+        );
+        // Centered in the 40x20 viewport, as xMidYMid meet does
+        assert!(
+            out.contains(r##"xlink:href="#dot" transform="translate(40 0) scale(2 2)" width="10" height="10"/>"##),
+            "got: {out}"
+        );
+        assert!(
+            out.contains(r##"<use href="#same" width="8" height="8"/>"##),
+            "got: {out}"
+        );
+        Ok(())
+    }
+}
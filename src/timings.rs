@@ -0,0 +1,62 @@
+use anyhow::Result;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Durations of the pipeline stages for one input or output file, printed by `--timings`
+#[derive(Debug, Default)]
+pub struct Timings {
+    stages: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    /// Run `f` and record its duration under `stage`, adding to earlier runs of the same stage
+    pub fn time<T>(&mut self, stage: &'static str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let start = Instant::now();
+        let v = f()?;
+        let elapsed = start.elapsed();
+        match self.stages.iter_mut().find(|(name, _)| *name == stage) {
+            Some((_, d)) => *d += elapsed,
+            None => self.stages.push((stage, elapsed)),
+        }
+        Ok(v)
+    }
+
+    /// Recorded stages with their durations, in the order they first ran
+    pub fn stages(&self) -> impl Iterator<Item = (&'static str, Duration)> + '_ {
+        self.stages.iter().copied()
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|(_, d)| *d).sum()
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, d) in &self.stages {
+            write!(f, "{} {:.3?}  ", name, d)?;
+        }
+        write!(f, "合计 {:.3?}", self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_stages_accumulate() -> Result<()> {
+        let mut t = Timings::default();
+        t.time("scale", || Ok(()))?;
+        t.time("serialize", || Ok(()))?;
+        t.time("scale", || Ok(()))?;
+        assert_eq!(t.stages.len(), 2);
+        t.stages[0].1 = Duration::from_millis(2);
+        t.stages[1].1 = Duration::from_millis(1);
+        assert_eq!(
+            t.to_string(),
+            "scale 2.000ms  serialize 1.000ms  合计 3.000ms"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,375 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use svg_scale::audit::audit_document;
+use svg_scale::optimize::{optimize, OptimizeOptions};
+use svg_scale::path::scale_path;
+use svg_scale::scale::{self, ScaleCtx};
+use svg_scale::transform::{
+    decompose_matrix, format_matrix, invert_matrix, parse_transform_list, transform_to_matrix,
+};
+use svg_scale::{scale_svg, ScaleOptions, DEFAULT_DPI};
+
+#[derive(Args)]
+pub struct PathArgs {
+    /// 缩放比例，支持 0.5、2/3、150% 等表达式
+    #[arg(long, value_parser = scale::parse_scale_factor)]
+    pub scale: f64,
+
+    /// 路径数据；省略时从 stdin 逐行读取
+    #[arg(long)]
+    pub d: Option<String>,
+
+    #[arg(long, default_value = "4", value_parser = scale::parse_precision)]
+    pub precision: usize,
+}
+
+pub fn run_path(args: &PathArgs) -> Result<()> {
+    let ctx = ScaleCtx {
+        scale: args.scale,
+        precision: args.precision,
+        ..ScaleCtx::default()
+    };
+    let input = match &args.d {
+        Some(d) => d.clone(),
+        None => {
+            let mut buf = String::new();
+            std::io::stdin()
+                .read_to_string(&mut buf)
+                .context("read stdin")?;
+            buf
+        }
+    };
+    println!("{}", scale_path_lines(&input, &ctx)?);
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct OptimizeArgs {
+    /// 输入 SVG 文件
+    pub input: String,
+
+    /// 输出文件（默认输出到 stdout）
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// 优化前先按比例缩放，支持 0.5、2/3、150% 等表达式
+    #[arg(long, value_parser = scale::parse_scale_factor)]
+    pub scale: Option<f64>,
+
+    /// 优化前先缩放到目标尺寸
+    #[arg(long)]
+    pub to: Option<f64>,
+
+    /// 原始尺寸（可选）
+    #[arg(long)]
+    pub from: Option<f64>,
+
+    #[arg(long, default_value = "4", value_parser = scale::parse_precision)]
+    pub precision: usize,
+
+    /// 移除 non-scaling-stroke
+    #[arg(long)]
+    pub fix_stroke: bool,
+
+    /// 保留未被引用的 defs、渐变、裁剪路径等
+    #[arg(long)]
+    pub keep_defs: bool,
+
+    /// 保留 display="none" 的元素和尺寸为 0 的图形
+    #[arg(long)]
+    pub keep_hidden: bool,
+
+    /// 保留编辑器数据（Inkscape、Sketch、Illustrator 命名空间及 <metadata>）
+    #[arg(long)]
+    pub keep_editor_data: bool,
+
+    /// 保留没有属性的 <g> 和空的 <g>
+    #[arg(long)]
+    pub keep_groups: bool,
+
+    /// 不压缩数字和路径数据
+    #[arg(long)]
+    pub no_compact: bool,
+}
+
+pub fn run_optimize(args: &OptimizeArgs) -> Result<()> {
+    let input_svg =
+        fs::read_to_string(&args.input).with_context(|| format!("read {}", args.input))?;
+    let scaled = if args.scale.is_some() || args.to.is_some() {
+        scale_svg(
+            &input_svg,
+            &ScaleOptions {
+                scale: args.scale,
+                to: args.to,
+                from: args.from,
+                precision: args.precision,
+                fix_stroke: args.fix_stroke,
+                dpi: DEFAULT_DPI,
+                exact: false,
+            },
+        )?
+    } else {
+        input_svg.clone()
+    };
+    let doc = roxmltree::Document::parse(&scaled)?;
+    let out = optimize(
+        &doc,
+        &OptimizeOptions {
+            prune_defs: !args.keep_defs,
+            remove_hidden: !args.keep_hidden,
+            strip_editor_data: !args.keep_editor_data,
+            collapse_groups: !args.keep_groups,
+            compact_precision: (!args.no_compact).then_some(args.precision),
+        },
+    )?;
+
+    if let Some(output) = &args.output {
+        fs::write(output, &out)?;
+        println!(
+            "输出: {} ({} -> {} 字节)",
+            output,
+            input_svg.len(),
+            out.len()
+        );
+    } else {
+        println!("{}", out);
+    }
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct GetArgs {
+    /// 输入 SVG 文件
+    pub input: String,
+
+    /// 要输出的根元素属性，如 viewBox、width、height
+    #[arg(long)]
+    pub attr: String,
+
+    /// 先按比例缩放再取值，支持 0.5、2/3、150% 等表达式
+    #[arg(long, value_parser = scale::parse_scale_factor)]
+    pub scale: Option<f64>,
+
+    /// 先缩放到目标尺寸再取值
+    #[arg(long)]
+    pub to: Option<f64>,
+
+    /// 原始尺寸（可选）
+    #[arg(long)]
+    pub from: Option<f64>,
+
+    #[arg(long, default_value = "4", value_parser = scale::parse_precision)]
+    pub precision: usize,
+}
+
+pub fn run_get(args: &GetArgs) -> Result<()> {
+    let input_svg =
+        fs::read_to_string(&args.input).with_context(|| format!("read {}", args.input))?;
+    println!("{}", query_attr(&input_svg, args)?);
+    Ok(())
+}
+
+/// Value of the root attribute `args.attr`, after scaling when requested
+fn query_attr(svg_text: &str, args: &GetArgs) -> Result<String> {
+    let scaled;
+    let svg_text = if args.scale.is_some() || args.to.is_some() {
+        scaled = scale_svg(
+            svg_text,
+            &ScaleOptions {
+                scale: args.scale,
+                to: args.to,
+                from: args.from,
+                precision: args.precision,
+                fix_stroke: false,
+                dpi: DEFAULT_DPI,
+                exact: false,
+            },
+        )?;
+        &scaled
+    } else {
+        svg_text
+    };
+    let doc = roxmltree::Document::parse(svg_text)?;
+    let root = doc.root_element();
+    root.attributes()
+        .find(|a| a.name() == args.attr)
+        .map(|a| a.value().trim().to_string())
+        .with_context(|| format!("根元素没有 {} 属性", args.attr))
+}
+
+#[derive(Args)]
+pub struct AuditArgs {
+    /// 图标目录（递归查找 .svg）或单个 SVG 文件
+    pub path: String,
+
+    /// 期望的网格尺寸（px），如 24
+    #[arg(long)]
+    pub expect: f64,
+
+    /// 物理单位（mm、pt 等）换算为像素时使用的 DPI
+    #[arg(long, default_value_t = DEFAULT_DPI)]
+    pub dpi: f64,
+}
+
+pub fn run_audit(args: &AuditArgs) -> Result<()> {
+    if !(args.expect > 0.0 && args.expect.is_finite()) {
+        bail!("--expect 必须是正数: {}", args.expect);
+    }
+    let files = if Path::new(&args.path).is_dir() {
+        let pattern = Path::new(&args.path).join("**").join("*.svg");
+        crate::glob::expand(&pattern.to_string_lossy())?
+    } else {
+        vec![args.path.clone()]
+    };
+    let mut failing = 0;
+    for file in &files {
+        let findings = fs::read_to_string(file)
+            .with_context(|| format!("read {}", file))
+            .and_then(|text| {
+                let doc = roxmltree::Document::parse(&text)?;
+                audit_document(&doc, args.expect, args.dpi)
+            });
+        match findings {
+            Ok(findings) if findings.is_empty() => {}
+            Ok(findings) => {
+                failing += 1;
+                println!("{}:", file);
+                for finding in &findings {
+                    println!("  {}", finding);
+                }
+            }
+            Err(e) => {
+                failing += 1;
+                println!("{}:", file);
+                println!("  错误: {:#}", e);
+            }
+        }
+    }
+    if failing > 0 {
+        bail!(
+            "{} / {} 个文件不符合 {} 像素网格",
+            failing,
+            files.len(),
+            args.expect
+        );
+    }
+    println!("{} 个文件全部符合 {} 像素网格", files.len(), args.expect);
+    Ok(())
+}
+
+#[derive(Args)]
+pub struct TransformArgs {
+    /// 变换列表，如 "translate(10,20) rotate(30)"
+    #[arg(long)]
+    pub combine: String,
+
+    /// 同时输出分解结果（translate / rotate / skewX / scale）
+    #[arg(long)]
+    pub decompose: bool,
+
+    /// 同时输出逆矩阵
+    #[arg(long)]
+    pub inverse: bool,
+
+    #[arg(long, default_value = "6", value_parser = scale::parse_precision)]
+    pub precision: usize,
+}
+
+pub fn run_transform(args: &TransformArgs) -> Result<()> {
+    for line in transform_report(args)? {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn transform_report(args: &TransformArgs) -> Result<Vec<String>> {
+    let m = transform_to_matrix(&parse_transform_list(&args.combine)?)?;
+    let mut lines = vec![format_matrix(m, args.precision)];
+    if args.decompose {
+        let d = decompose_matrix(m).context("变换矩阵退化，无法分解")?;
+        lines.push(format!(
+            "decompose: {}",
+            d.to_transform_string(args.precision)
+        ));
+    }
+    if args.inverse {
+        let inv = invert_matrix(m).context("变换矩阵不可逆")?;
+        lines.push(format!("inverse: {}", format_matrix(inv, args.precision)));
+    }
+    Ok(lines)
+}
+
+/// Scale every non-empty line as a separate path
+fn scale_path_lines(input: &str, ctx: &ScaleCtx) -> Result<String> {
+    let mut out = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        out.push(scale_path(line, ctx).with_context(|| format!("line {}", i + 1))?);
+    }
+    Ok(out.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_lines_are_scaled_independently() -> Result<()> {
+        let ctx = ScaleCtx {
+            scale: 0.5,
+            ..ScaleCtx::default()
+        };
+        let out = scale_path_lines("M10 10 L20 20\n\n  M0 0 A10 10 0 0 1 20 0  \n", &ctx)?;
+        assert_eq!(out, "M5 5 L10 10\nM0 0 A5 5 0 0 1 10 0");
+
+        let err = scale_path_lines("M0 0\nM1 1 Lx", &ctx).unwrap_err();
+        assert!(format!("{:#}", err).starts_with("line 2"), "got: {err:#}");
+        Ok(())
+    }
+
+    #[test]
+    fn query_attr_prints_the_root_value() -> Result<()> {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="12" viewBox="0 0 24 12"/>"#;
+        let mut args = GetArgs {
+            input: String::new(),
+            attr: "viewBox".to_string(),
+            scale: None,
+            to: None,
+            from: None,
+            precision: 4,
+        };
+        assert_eq!(query_attr(svg, &args)?, "0 0 24 12");
+        args.attr = "height".to_string();
+        args.to = Some(48.0);
+        assert_eq!(query_attr(svg, &args)?, "24");
+        args.attr = "preserveAspectRatio".to_string();
+        assert!(query_attr(svg, &args).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn transform_report_lists_requested_views() -> Result<()> {
+        let args = TransformArgs {
+            combine: "translate(10,20) scale(2)".to_string(),
+            decompose: true,
+            inverse: true,
+            precision: 6,
+        };
+        assert_eq!(
+            transform_report(&args)?,
+            [
+                "matrix(2,0,0,2,10,20)",
+                "decompose: translate(10,20) scale(2,2)",
+                "inverse: matrix(0.5,0,0,0.5,-5,-10)",
+            ]
+        );
+        Ok(())
+    }
+}
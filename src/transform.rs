@@ -51,7 +51,7 @@ pub fn parse_transform_list(input: &str) -> Result<Vec<Transform>> {
     }
 }
 
-fn mat_mul(a: [f64; 6], b: [f64; 6]) -> [f64; 6] {
+pub(crate) fn mat_mul(a: [f64; 6], b: [f64; 6]) -> [f64; 6] {
     let (a1, b1, c1, d1, e1, f1) = (a[0], a[1], a[2], a[3], a[4], a[5]);
     let (a2, b2, c2, d2, e2, f2) = (b[0], b[1], b[2], b[3], b[4], b[5]);
     [
@@ -125,6 +125,90 @@ pub fn transform_to_matrix(list: &[Transform]) -> Result<[f64; 6]> {
     Ok(m)
 }
 
+/// Inverse of an affine matrix, or `None` if it is singular
+pub fn invert_matrix(m: [f64; 6]) -> Option<[f64; 6]> {
+    let [a, b, c, d, e, f] = m;
+    let det = a * d - b * c;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    Some([
+        d / det,
+        -b / det,
+        -c / det,
+        a / det,
+        (c * f - d * e) / det,
+        (b * e - a * f) / det,
+    ])
+}
+
+/// A matrix split into `translate(tx,ty) rotate(rotate) skewX(skew_x) scale(sx,sy)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decomposition {
+    pub tx: f64,
+    pub ty: f64,
+    /// Degrees
+    pub rotate: f64,
+    /// Degrees
+    pub skew_x: f64,
+    pub sx: f64,
+    pub sy: f64,
+}
+
+/// Decompose an affine matrix; `None` if it collapses the x axis
+pub fn decompose_matrix(m: [f64; 6]) -> Option<Decomposition> {
+    let [a, b, c, d, e, f] = m;
+    let sx = a.hypot(b);
+    if sx < 1e-12 {
+        return None;
+    }
+    let det = a * d - b * c;
+    let sy = det / sx;
+    let skew_x = if det.abs() < 1e-12 {
+        0.0
+    } else {
+        ((a * c + b * d) / det).atan().to_degrees()
+    };
+    Some(Decomposition {
+        tx: e,
+        ty: f,
+        rotate: b.atan2(a).to_degrees(),
+        skew_x,
+        sx,
+        sy,
+    })
+}
+
+impl Decomposition {
+    /// Transform list equivalent to the decomposed matrix, omitting identity parts
+    pub fn to_transform_string(&self, precision: usize) -> String {
+        let n = |v: f64| fmt_num(clean_matrix_value(v), precision);
+        let mut parts = Vec::new();
+        if n(self.tx) != "0" || n(self.ty) != "0" {
+            parts.push(format!("translate({},{})", n(self.tx), n(self.ty)));
+        }
+        if n(self.rotate) != "0" {
+            parts.push(format!("rotate({})", n(self.rotate)));
+        }
+        if n(self.skew_x) != "0" {
+            parts.push(format!("skewX({})", n(self.skew_x)));
+        }
+        if n(self.sx) != "1" || n(self.sy) != "1" {
+            parts.push(format!("scale({},{})", n(self.sx), n(self.sy)));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Format a matrix as `matrix(a,b,c,d,e,f)`
+pub fn format_matrix(m: [f64; 6], precision: usize) -> String {
+    let parts: Vec<String> = m
+        .iter()
+        .map(|v| fmt_num(clean_matrix_value(*v), precision))
+        .collect();
+    format!("matrix({})", parts.join(","))
+}
+
 fn clean_matrix_value(v: f64) -> f64 {
     if v.abs() < 1e-12 {
         0.0
@@ -134,8 +218,9 @@ fn clean_matrix_value(v: f64) -> f64 {
 }
 
 fn fmt_num(v: f64, precision: usize) -> String {
-    let s = format!("{:.*}", precision, v);
-    s.trim_end_matches('0').trim_end_matches('.').to_string()
+    let mut s = String::with_capacity(24);
+    crate::scale::push_fixed(&mut s, v, precision);
+    s
 }
 
 pub fn scale_transform_value(input: &str, scale: f64, precision: usize) -> Result<String> {
@@ -191,6 +276,52 @@ pub fn scale_transform_value(input: &str, scale: f64, precision: usize) -> Resul
     Ok(parts.join(" "))
 }
 
+/// Scale a transform for content that is itself scaled: the linear part is
+/// kept and only the translation is multiplied, so `S * T * p` becomes
+/// `T' * (S * p)`. Used when the transform has a reference box or origin
+/// (`transform-box`, `transform-origin`) that moves with the content.
+pub fn conjugate_transform_value(input: &str, scale: f64, precision: usize) -> Result<String> {
+    let list = parse_transform_list(input).context("parse transform")?;
+    if list.iter().all(|t| t.name == "translate") {
+        return scale_transform_value(input, scale, precision);
+    }
+    let m = transform_to_matrix(&list)?;
+    Ok(format!(
+        "matrix({},{},{},{},{},{})",
+        fmt_num(clean_matrix_value(m[0]), precision),
+        fmt_num(clean_matrix_value(m[1]), precision),
+        fmt_num(clean_matrix_value(m[2]), precision),
+        fmt_num(clean_matrix_value(m[3]), precision),
+        fmt_num(clean_matrix_value(m[4] * scale), precision),
+        fmt_num(clean_matrix_value(m[5] * scale), precision)
+    ))
+}
+
+/// Rewrite a transform for content stretched by `kx`, `ky` around the
+/// origin: `K * T * p` becomes `T' * (K * p)` with `T' = K * T * K^-1`, so a
+/// rotation turns into the matching shear. Translations stay translations.
+pub fn stretch_transform_value(input: &str, kx: f64, ky: f64, precision: usize) -> Result<String> {
+    let list = parse_transform_list(input).context("parse transform")?;
+    let m = transform_to_matrix(&list)?;
+    let m = [
+        m[0],
+        m[1] * ky / kx,
+        m[2] * kx / ky,
+        m[3],
+        m[4] * kx,
+        m[5] * ky,
+    ]
+    .map(clean_matrix_value);
+    if m[..4] == [1.0, 0.0, 0.0, 1.0] {
+        return Ok(format!(
+            "translate({},{})",
+            fmt_num(m[4], precision),
+            fmt_num(m[5], precision)
+        ));
+    }
+    Ok(format_matrix(m, precision))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,4 +332,61 @@ mod tests {
         let list = parse_transform_list(s).unwrap();
         assert_eq!(list.len(), 3);
     }
+
+    fn combine(s: &str) -> [f64; 6] {
+        transform_to_matrix(&parse_transform_list(s).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn matrix_inverse_round_trips() {
+        let m = combine("translate(10,20) rotate(30) scale(2,3)");
+        let inv = invert_matrix(m).unwrap();
+        let id = mat_mul(m, inv);
+        assert_eq!(format_matrix(id, 6), "matrix(1,0,0,1,0,0)");
+        assert!(invert_matrix(combine("scale(0)")).is_none());
+    }
+
+    #[test]
+    fn matrix_decomposition_recomposes() {
+        let m = combine("translate(10,20) rotate(30) skewX(15) scale(2,3)");
+        let d = decompose_matrix(m).unwrap();
+        let s = d.to_transform_string(6);
+        assert_eq!(s, "translate(10,20) rotate(30) skewX(15) scale(2,3)");
+        assert_eq!(format_matrix(combine(&s), 6), format_matrix(m, 6));
+        assert_eq!(
+            decompose_matrix(combine("translate(5)"))
+                .unwrap()
+                .to_transform_string(4),
+            "translate(5,0)"
+        );
+    }
+
+    #[test]
+    fn conjugate_keeps_linear_part() {
+        assert_eq!(
+            conjugate_transform_value("rotate(90 2 0)", 3.0, 4).unwrap(),
+            "matrix(0,1,-1,0,6,-6)"
+        );
+        assert_eq!(
+            conjugate_transform_value("translate(1 2)", 3.0, 4).unwrap(),
+            "translate(3,6)"
+        );
+    }
+
+    #[test]
+    fn stretch_transform_conjugates_by_the_axis_factors() -> Result<()> {
+        assert_eq!(
+            stretch_transform_value("translate(10,20)", 2.0, 0.5, 4)?,
+            "translate(20,10)"
+        );
+        assert_eq!(
+            stretch_transform_value("rotate(90)", 1.0, 2.0, 4)?,
+            "matrix(0,2,-0.5,0,0,0)"
+        );
+        assert_eq!(
+            stretch_transform_value("scale(3)", 1.0, 2.0, 4)?,
+            "matrix(3,0,0,3,0,0)"
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,380 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+
+use resvg::tiny_skia::Pixmap;
+use svg_scale::ops::{self, Op};
+use svg_scale::render::render_svg_to_pixmap;
+use svg_scale::scale::{self, ScaleCtx};
+use svg_scale::{get_svg_dimensions, get_svg_size, write_svg, DEFAULT_DPI};
+
+#[derive(Args)]
+pub struct TuiArgs {
+    /// 输入 SVG 文件
+    pub input: String,
+
+    /// 初始比例，支持 0.5、2/3、150% 等表达式
+    #[arg(long, value_parser = scale::parse_scale_factor, default_value = "1")]
+    pub scale: f64,
+
+    #[arg(long, default_value = "4", value_parser = scale::parse_precision)]
+    pub precision: usize,
+
+    /// 预览最多占用的终端列数；更大的结果按比例缩小显示
+    #[arg(long, default_value = "64")]
+    pub columns: u32,
+}
+
+const HELP: &str = "\
+命令:
+  scale <EXPR>     设置比例，如 0.5、2/3、150%
+  to <PX>          按目标宽度设置比例
+  precision <N>    设置小数位数
+  fix-stroke       切换是否移除 non-scaling-stroke
+  snap [STEP|off]  把坐标对齐到 STEP 的整数倍（默认 1），off 关闭
+  write <FILE>     按当前设置写出 .svg 或 .png
+  settings         输出等价的命令行
+  help             显示本帮助
+  quit             退出";
+
+/// Parameters being tuned
+#[derive(Debug, Clone, PartialEq)]
+struct Settings {
+    scale: f64,
+    precision: usize,
+    fix_stroke: bool,
+    snap: Option<f64>,
+}
+
+/// One line typed at the prompt
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Scale(f64),
+    To(f64),
+    Precision(usize),
+    ToggleFixStroke,
+    Snap(Option<f64>),
+    Write(String),
+    Settings,
+    Help,
+    Quit,
+}
+
+impl std::str::FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, arg) = match line.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, Some(arg.trim())),
+            None => (line, None),
+        };
+        let required = |what: &str| arg.with_context(|| format!("{} 需要参数", what));
+        Ok(match name {
+            "scale" => Command::Scale(scale::parse_scale_factor(required("scale")?)?),
+            "to" => {
+                let px = required("to")?;
+                match px.parse::<f64>() {
+                    Ok(px) if px > 0.0 && px.is_finite() => Command::To(px),
+                    _ => bail!("无效的目标宽度: {}", px),
+                }
+            }
+            "precision" => {
+                let precision = required("precision")?;
+                Command::Precision(
+                    scale::parse_precision(precision)
+                        .with_context(|| format!("无效的精度: {}", precision))?,
+                )
+            }
+            "fix-stroke" => Command::ToggleFixStroke,
+            "snap" => match arg {
+                None => Command::Snap(Some(1.0)),
+                Some("off") => Command::Snap(None),
+                Some(step) => match step.parse::<f64>() {
+                    Ok(step) if step > 0.0 && step.is_finite() => Command::Snap(Some(step)),
+                    _ => bail!("无效的对齐步长: {}", step),
+                },
+            },
+            "write" => Command::Write(required("write")?.to_string()),
+            "settings" => Command::Settings,
+            "help" | "?" => Command::Help,
+            "quit" | "q" | "exit" => Command::Quit,
+            _ => bail!("未知命令: {}（输入 help 查看可用命令）", name),
+        })
+    }
+}
+
+impl Settings {
+    /// The input scaled with these settings
+    fn apply(&self, input_svg: &str) -> Result<String> {
+        let ctx = ScaleCtx {
+            scale: self.scale,
+            precision: self.precision,
+            fix_stroke: self.fix_stroke,
+            ..ScaleCtx::default()
+        };
+        match self.snap {
+            // Snapping runs on the scaled document at full precision; the
+            // last pass only rounds to --precision
+            Some(step) => {
+                let snapped = ops::run_ops(
+                    input_svg,
+                    &[Op::Scale(self.scale), Op::Snap(step)],
+                    DEFAULT_DPI,
+                )?;
+                let doc = roxmltree::Document::parse(&snapped)?;
+                write_svg(&doc, &ScaleCtx { scale: 1.0, ..ctx })
+            }
+            None => write_svg(&roxmltree::Document::parse(input_svg)?, &ctx),
+        }
+    }
+
+    /// The `svg-scale` invocation producing the same output
+    fn command_line(&self, input: &str) -> String {
+        let mut line = format!("svg-scale -i {}", input);
+        match self.snap {
+            Some(step) => line.push_str(&format!(" --ops \"scale={},snap={}\"", self.scale, step)),
+            None => line.push_str(&format!(" --scale {}", self.scale)),
+        }
+        line.push_str(&format!(" --precision {}", self.precision));
+        if self.fix_stroke {
+            line.push_str(" --fix-stroke");
+        }
+        line
+    }
+
+    fn status(&self, width: u32, height: u32, bytes: usize) -> String {
+        let on_off = |on: bool| if on { "开" } else { "关" };
+        format!(
+            "比例 {} ({}x{} px) · 精度 {} · fix-stroke {} · 对齐 {} · {} 字节",
+            self.scale,
+            width,
+            height,
+            self.precision,
+            on_off(self.fix_stroke),
+            self.snap.map_or("关".to_string(), |step| step.to_string()),
+            bytes
+        )
+    }
+}
+
+pub fn run(args: &TuiArgs) -> Result<()> {
+    let input_svg =
+        fs::read_to_string(&args.input).with_context(|| format!("read {}", args.input))?;
+    let source_width = {
+        let doc = roxmltree::Document::parse(&input_svg)?;
+        get_svg_size(&doc, DEFAULT_DPI).context("未能从SVG检测到尺寸")?
+    };
+    let mut settings = Settings {
+        scale: args.scale,
+        precision: args.precision,
+        fix_stroke: false,
+        snap: None,
+    };
+    let interactive = std::io::stdout().is_terminal();
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut note = HELP.to_string();
+    loop {
+        let scaled = settings.apply(&input_svg);
+        let mut out = std::io::stdout().lock();
+        if interactive {
+            // Clear the screen so each preview replaces the last one
+            write!(out, "\x1b[2J\x1b[H")?;
+        }
+        match scaled {
+            Ok(scaled) => {
+                let (width, height) = output_size(&scaled)?;
+                writeln!(out, "{}", settings.status(width, height, scaled.len()))?;
+                let (cols, rows) = preview_size(width, height, args.columns);
+                let pixmap = render_svg_to_pixmap(&scaled, cols, rows)?;
+                for line in half_block_lines(&pixmap) {
+                    writeln!(out, "{}", line)?;
+                }
+            }
+            Err(e) => writeln!(out, "错误: {:#}", e)?,
+        }
+        if !note.is_empty() {
+            writeln!(out, "{}", note)?;
+        }
+        write!(out, "> ")?;
+        out.flush()?;
+        drop(out);
+
+        let Some(line) = lines.next().transpose()? else {
+            println!();
+            return Ok(());
+        };
+        if line.trim().is_empty() {
+            note.clear();
+            continue;
+        }
+        note = match line.parse::<Command>() {
+            Ok(Command::Scale(scale)) => {
+                settings.scale = scale;
+                String::new()
+            }
+            Ok(Command::To(px)) => {
+                settings.scale = px / source_width;
+                String::new()
+            }
+            Ok(Command::Precision(precision)) => {
+                settings.precision = precision;
+                String::new()
+            }
+            Ok(Command::ToggleFixStroke) => {
+                settings.fix_stroke = !settings.fix_stroke;
+                String::new()
+            }
+            Ok(Command::Snap(snap)) => {
+                settings.snap = snap;
+                String::new()
+            }
+            Ok(Command::Write(path)) => match write_output(&settings, &input_svg, &path) {
+                Ok(()) => format!("输出: {}", path),
+                Err(e) => format!("错误: {:#}", e),
+            },
+            Ok(Command::Settings) => settings.command_line(&args.input),
+            Ok(Command::Help) => HELP.to_string(),
+            Ok(Command::Quit) => return Ok(()),
+            Err(e) => format!("错误: {:#}", e),
+        };
+    }
+}
+
+/// Pixel size of a scaled document, as written to a PNG
+fn output_size(svg: &str) -> Result<(u32, u32)> {
+    let doc = roxmltree::Document::parse(svg)?;
+    let (w, h) = get_svg_dimensions(&doc, DEFAULT_DPI).context("未能从SVG检测到尺寸")?;
+    Ok((w.round().max(1.0) as u32, h.round().max(1.0) as u32))
+}
+
+/// Preview size in pixels (one column and half a row each): the output
+/// size itself, so every pixel can be inspected, unless it is wider than
+/// `columns`
+fn preview_size(width: u32, height: u32, columns: u32) -> (u32, u32) {
+    if width <= columns {
+        return (width, height);
+    }
+    let factor = columns as f64 / width as f64;
+    (columns, ((height as f64 * factor).round() as u32).max(1))
+}
+
+fn write_output(settings: &Settings, input_svg: &str, path: &str) -> Result<()> {
+    let scaled = settings.apply(input_svg)?;
+    if path.to_ascii_lowercase().ends_with(".png") {
+        let (width, height) = output_size(&scaled)?;
+        render_svg_to_pixmap(&scaled, width, height)?
+            .save_png(path)
+            .context("write png output")?;
+    } else {
+        fs::write(path, scaled)?;
+    }
+    Ok(())
+}
+
+/// The pixmap as rows of `▀` cells, the upper pixel in the foreground
+/// colour and the lower one in the background, over a checkerboard so
+/// transparency shows
+fn half_block_lines(pixmap: &Pixmap) -> Vec<String> {
+    let (width, height) = (pixmap.width(), pixmap.height());
+    let color = |x: u32, y: u32| -> [u8; 3] {
+        let checker = if (x / 2 + y / 2).is_multiple_of(2) {
+            204
+        } else {
+            153
+        };
+        if y >= height {
+            return [checker; 3];
+        }
+        let p = pixmap.pixels()[(y * width + x) as usize];
+        let over = |c: u8| c as u32 + checker as u32 * (255 - p.alpha() as u32) / 255;
+        [
+            over(p.red()) as u8,
+            over(p.green()) as u8,
+            over(p.blue()) as u8,
+        ]
+    };
+    (0..height.div_ceil(2))
+        .map(|row| {
+            let mut line = String::new();
+            for x in 0..width {
+                let [r, g, b] = color(x, row * 2);
+                let [br, bg, bb] = color(x, row * 2 + 1);
+                line.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀",
+                    r, g, b, br, bg, bb
+                ));
+            }
+            line.push_str("\x1b[0m");
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_prompt_commands() -> Result<()> {
+        assert_eq!("scale 1/2".parse::<Command>()?, Command::Scale(0.5));
+        assert_eq!(" to 16 ".parse::<Command>()?, Command::To(16.0));
+        assert_eq!("precision 2".parse::<Command>()?, Command::Precision(2));
+        assert_eq!("snap".parse::<Command>()?, Command::Snap(Some(1.0)));
+        assert_eq!("snap off".parse::<Command>()?, Command::Snap(None));
+        assert_eq!(
+            "write out.png".parse::<Command>()?,
+            Command::Write("out.png".to_string())
+        );
+        assert!("precision".parse::<Command>().is_err());
+        assert!("spin".parse::<Command>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn settings_apply_and_print_the_same_command() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24"><rect x="2.3" width="10"/></svg>"#;
+        let mut settings = Settings {
+            scale: 0.5,
+            precision: 4,
+            fix_stroke: false,
+            snap: None,
+        };
+        assert!(settings
+            .apply(input)?
+            .contains(r#"<rect x="1.15" width="5"/>"#));
+        assert_eq!(
+            settings.command_line("a.svg"),
+            "svg-scale -i a.svg --scale 0.5 --precision 4"
+        );
+        settings.snap = Some(1.0);
+        settings.fix_stroke = true;
+        assert!(settings
+            .apply(input)?
+            .contains(r#"<rect x="1" width="5"/>"#));
+        assert_eq!(
+            settings.command_line("a.svg"),
+            "svg-scale -i a.svg --ops \"scale=0.5,snap=1\" --precision 4 --fix-stroke"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn preview_uses_half_blocks_over_a_checkerboard() {
+        let mut pixmap = Pixmap::new(1, 3).unwrap();
+        pixmap.pixels_mut()[0] =
+            resvg::tiny_skia::PremultipliedColorU8::from_rgba(255, 0, 0, 255).unwrap();
+        let lines = half_block_lines(&pixmap);
+        assert_eq!(
+            lines,
+            [
+                "\x1b[38;2;255;0;0m\x1b[48;2;204;204;204m▀\x1b[0m",
+                "\x1b[38;2;153;153;153m\x1b[48;2;153;153;153m▀\x1b[0m",
+            ]
+        );
+        assert_eq!(preview_size(16, 8, 64), (16, 8));
+        assert_eq!(preview_size(128, 64, 64), (64, 32));
+    }
+}
@@ -0,0 +1,243 @@
+//! Structural check that a scaled document draws the same geometry as its source.
+//!
+//! Both documents are parsed with usvg, which resolves styles, `use`
+//! references and nested viewports. Every drawable node is then compared in
+//! canvas space: the source geometry multiplied by the expected scale must
+//! land on the output geometry within a tolerance.
+
+use anyhow::{Context, Result};
+use resvg::usvg::{self, Node, Transform};
+use std::fmt;
+
+/// One element whose scaled geometry does not match the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// The element's `id`, or its kind and position among drawable nodes
+    pub element: String,
+    pub detail: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.element, self.detail)
+    }
+}
+
+/// Compare `output` against `input` scaled by `scale` (in canvas pixels).
+///
+/// Path points are compared after applying each node's absolute transform,
+/// stroke widths and dash patterns after applying the transform's scale
+/// factor, and images and text by their bounding boxes. Differences larger
+/// than `tolerance` output pixels are reported per element.
+pub fn compare_geometry(
+    input: &str,
+    output: &str,
+    scale: f64,
+    tolerance: f64,
+) -> Result<Vec<Divergence>> {
+    let opt = usvg::Options::default();
+    let before = usvg::Tree::from_str(input, &opt).context("parse input svg for verification")?;
+    let after = usvg::Tree::from_str(output, &opt).context("parse output svg for verification")?;
+    let scale = scale as f32;
+    let tolerance = tolerance as f32;
+    let mut found = Vec::new();
+
+    let (bs, os) = (before.size(), after.size());
+    let size_error = (bs.width() * scale - os.width())
+        .abs()
+        .max((bs.height() * scale - os.height()).abs());
+    if size_error > tolerance {
+        found.push(Divergence {
+            element: "svg".to_string(),
+            detail: format!(
+                "canvas {}x{}, expected {}x{}",
+                os.width(),
+                os.height(),
+                bs.width() * scale,
+                bs.height() * scale
+            ),
+        });
+    }
+
+    let mut before_nodes = Vec::new();
+    collect_drawables(before.root(), &mut before_nodes);
+    let mut after_nodes = Vec::new();
+    collect_drawables(after.root(), &mut after_nodes);
+    if before_nodes.len() != after_nodes.len() {
+        found.push(Divergence {
+            element: "svg".to_string(),
+            detail: format!(
+                "{} drawable elements, expected {}",
+                after_nodes.len(),
+                before_nodes.len()
+            ),
+        });
+    }
+
+    for (index, (b, a)) in before_nodes.iter().zip(&after_nodes).enumerate() {
+        let element = label(b, index);
+        let mut report = |detail: String| {
+            found.push(Divergence {
+                element: element.clone(),
+                detail,
+            })
+        };
+        match (b, a) {
+            (Node::Path(b), Node::Path(a)) => {
+                for detail in compare_paths(b, a, scale, tolerance) {
+                    report(detail);
+                }
+            }
+            (Node::Image(_), Node::Image(_)) | (Node::Text(_), Node::Text(_)) => {
+                let expected = b.abs_bounding_box();
+                let actual = a.abs_bounding_box();
+                let error = (expected.left() * scale - actual.left())
+                    .abs()
+                    .max((expected.top() * scale - actual.top()).abs())
+                    .max((expected.right() * scale - actual.right()).abs())
+                    .max((expected.bottom() * scale - actual.bottom()).abs());
+                if error > tolerance {
+                    report(format!("bounding box off by {}", error));
+                }
+            }
+            _ => report(format!("became {}", kind(a))),
+        }
+    }
+    Ok(found)
+}
+
+/// Leaf nodes in document order; groups only contribute through their transforms
+fn collect_drawables<'a>(group: &'a usvg::Group, out: &mut Vec<&'a Node>) {
+    for node in group.children() {
+        match node {
+            Node::Group(g) => collect_drawables(g, out),
+            _ => out.push(node),
+        }
+    }
+}
+
+fn kind(node: &Node) -> &'static str {
+    match node {
+        Node::Group(_) => "group",
+        Node::Path(_) => "path",
+        Node::Image(_) => "image",
+        Node::Text(_) => "text",
+    }
+}
+
+fn label(node: &Node, index: usize) -> String {
+    match node.id() {
+        "" => format!("{} #{}", kind(node), index + 1),
+        id => format!("#{}", id),
+    }
+}
+
+/// Uniform scale factor of a transform, as used for stroke widths
+fn transform_scale(ts: Transform) -> f32 {
+    (ts.sx * ts.sy - ts.kx * ts.ky).abs().sqrt()
+}
+
+fn compare_paths(b: &usvg::Path, a: &usvg::Path, scale: f32, tolerance: f32) -> Vec<String> {
+    let mut details = Vec::new();
+    let (bt, at) = (b.abs_transform(), a.abs_transform());
+
+    let (bp, ap) = (b.data().points(), a.data().points());
+    if bp.len() != ap.len() {
+        details.push(format!("{} path points, expected {}", ap.len(), bp.len()));
+    } else {
+        let error = bp
+            .iter()
+            .zip(ap)
+            .map(|(p, q)| {
+                let (mut p, mut q) = (*p, *q);
+                bt.map_point(&mut p);
+                at.map_point(&mut q);
+                (p.x * scale - q.x).abs().max((p.y * scale - q.y).abs())
+            })
+            .fold(0.0, f32::max);
+        if error > tolerance {
+            details.push(format!("path points off by {}", error));
+        }
+    }
+
+    match (b.stroke(), a.stroke()) {
+        (Some(bs), Some(as_)) => {
+            let (bf, af) = (transform_scale(bt) * scale, transform_scale(at));
+            let expected = bs.width().get() * bf;
+            let actual = as_.width().get() * af;
+            if (expected - actual).abs() > tolerance {
+                details.push(format!("stroke width {}, expected {}", actual, expected));
+            }
+            let expected: Vec<f32> = bs
+                .dasharray()
+                .unwrap_or(&[])
+                .iter()
+                .map(|d| d * bf)
+                .collect();
+            let actual: Vec<f32> = as_
+                .dasharray()
+                .unwrap_or(&[])
+                .iter()
+                .map(|d| d * af)
+                .collect();
+            let dash_differs = expected.len() != actual.len()
+                || expected
+                    .iter()
+                    .zip(&actual)
+                    .any(|(e, a)| (e - a).abs() > tolerance)
+                || (bs.dashoffset() * bf - as_.dashoffset() * af).abs() > tolerance;
+            if dash_differs {
+                details.push(format!(
+                    "dash pattern {:?}, expected {:?}",
+                    actual, expected
+                ));
+            }
+        }
+        (None, None) => {}
+        (Some(_), None) => details.push("stroke was removed".to_string()),
+        (None, Some(_)) => details.push("stroke was added".to_string()),
+    }
+    details
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scale::ScaleCtx;
+
+    fn scaled(input: &str, scale: f64) -> Result<String> {
+        let doc = roxmltree::Document::parse(input)?;
+        let ctx = ScaleCtx {
+            scale,
+            ..ScaleCtx::default()
+        };
+        crate::write_svg(&doc, &ctx)
+    }
+
+    #[test]
+    fn scaled_output_matches_source_geometry() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24" viewBox="0 0 24 24">
+  <g transform="translate(2 3) rotate(30)">
+    <path d="M1 1 L10 4 Q12 12 4 10 Z" stroke="black" stroke-width="1.5" stroke-dasharray="2 1"/>
+  </g>
+  <circle cx="12" cy="12" r="5" fill="none" stroke="red" stroke-width="1"/>
+</svg>"#;
+        let output = scaled(input, 2.5)?;
+        let found = compare_geometry(input, &output, 2.5, 0.01)?;
+        assert!(found.is_empty(), "got: {found:?}\noutput: {output}");
+        Ok(())
+    }
+
+    #[test]
+    fn diverging_elements_are_reported_by_id() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24"><rect id="a" width="10" height="10" stroke="black"/><rect width="4" height="4"/></svg>"#;
+        let output = r#"<svg xmlns="http://www.w3.org/2000/svg" width="48" height="48"><rect id="a" width="20" height="20" stroke="black" stroke-width="1"/><rect width="8" height="9"/></svg>"#;
+        let found = compare_geometry(input, output, 2.0, 0.01)?;
+        assert_eq!(found.len(), 2, "got: {found:?}");
+        assert_eq!(found[0].element, "#a");
+        assert!(found[0].detail.contains("stroke width"), "got: {found:?}");
+        assert_eq!(found[1].element, "path #2");
+        assert!(found[1].detail.contains("path points"), "got: {found:?}");
+        Ok(())
+    }
+}
@@ -0,0 +1,466 @@
+//! Root `viewBox` helpers.
+
+use anyhow::{bail, Result};
+
+use crate::scale::ScaleCtx;
+use crate::svg;
+
+/// Elements that stay direct children of the root when content is wrapped in a group
+const ROOT_ONLY_TAGS: &[&str] = &["defs", "style", "title", "desc", "metadata"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl ViewBox {
+    /// Parse `min-x min-y width height`, separated by whitespace and/or commas
+    pub fn parse(s: &str) -> Option<Self> {
+        let nums: Vec<f64> = s
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.parse().ok())
+            .collect::<Option<_>>()?;
+        match nums[..] {
+            [x, y, width, height] => Some(ViewBox {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The root element's viewBox, if present and valid
+    pub fn of_root(doc: &roxmltree::Document) -> Option<Self> {
+        doc.root_element()
+            .attribute("viewBox")
+            .and_then(Self::parse)
+    }
+
+    /// Parse a viewBox given on the command line; width and height must be positive
+    pub fn parse_arg(s: &str) -> Result<Self> {
+        match Self::parse(s) {
+            Some(vb) if vb.width > 0.0 && vb.height > 0.0 => Ok(vb),
+            _ => bail!("invalid viewBox: {}", s),
+        }
+    }
+
+    pub fn format(&self, ctx: &ScaleCtx) -> String {
+        format!(
+            "{} {} {} {}",
+            ctx.fmt(self.x),
+            ctx.fmt(self.y),
+            ctx.fmt(self.width),
+            ctx.fmt(self.height)
+        )
+    }
+}
+
+/// Rewrite a root viewBox with a non-zero origin to `0 0 W H`, moving the
+/// content by the old origin so the rendering is unchanged.
+///
+/// Documents without a viewBox, or whose viewBox already starts at the
+/// origin, are returned unchanged.
+pub fn normalize_viewbox(doc: &roxmltree::Document, precision: usize) -> Result<String> {
+    match ViewBox::of_root(doc) {
+        Some(vb) if vb.x != 0.0 || vb.y != 0.0 => {
+            let normalized = ViewBox {
+                x: 0.0,
+                y: 0.0,
+                ..vb
+            };
+            let transform = translation(-vb.x, -vb.y, precision);
+            Ok(reframe(doc, normalized, None, transform, precision))
+        }
+        _ => {
+            let mut w = xmlwriter::XmlWriter::new(xmlwriter::Options::default());
+            svg::write_verbatim(doc.root_element(), &mut w);
+            Ok(crate::finish_document(doc, w))
+        }
+    }
+}
+
+/// Prepare a document for `--target-viewbox`.
+///
+/// Returns the document rewritten in its own units together with the scale
+/// factor that maps it onto `target`: scaling the returned text by that
+/// factor yields exactly `target` as the root viewBox, with the content
+/// fitted and centered the way `preserveAspectRatio="xMidYMid meet"` would.
+pub fn fit_to_view_box(
+    doc: &roxmltree::Document,
+    target: ViewBox,
+    dpi: f64,
+) -> Result<(String, f64)> {
+    let current = current_view_box(doc, dpi)?;
+    let scale = (target.width / current.width).min(target.height / current.height);
+    let frame = ViewBox {
+        x: target.x / scale,
+        y: target.y / scale,
+        width: target.width / scale,
+        height: target.height / scale,
+    };
+    // Center along the axis that has slack after the uniform fit
+    let dx = frame.x + (frame.width - current.width) / 2.0 - current.x;
+    let dy = frame.y + (frame.height - current.height) / 2.0 - current.y;
+    let size = Some((frame.width, frame.height));
+    let transform = translation(dx, dy, EXACT_PRECISION);
+    Ok((reframe(doc, frame, size, transform, EXACT_PRECISION), scale))
+}
+
+/// The root viewBox, or `0 0 width height` when the document has none
+pub fn current_view_box(doc: &roxmltree::Document, dpi: f64) -> Result<ViewBox> {
+    let current = match ViewBox::of_root(doc).or_else(|| {
+        crate::get_svg_dimensions(doc, dpi).map(|(width, height)| ViewBox {
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+        })
+    }) {
+        Some(vb) => vb,
+        None => bail!("未能从SVG检测到尺寸，无法映射到目标 viewBox"),
+    };
+    if current.width <= 0.0 || current.height <= 0.0 {
+        bail!(
+            "invalid source viewBox: {}x{}",
+            current.width,
+            current.height
+        );
+    }
+    Ok(current)
+}
+
+/// Displayed pixels per user unit: root width over viewBox width, 1 when
+/// either is missing
+pub fn display_factor(doc: &roxmltree::Document, dpi: f64) -> f64 {
+    match (ViewBox::of_root(doc), crate::get_svg_dimensions(doc, dpi)) {
+        (Some(vb), Some((width, _))) if vb.width > 0.0 => width / vb.width,
+        _ => 1.0,
+    }
+}
+
+/// Replace the root viewBox with `view_box`, leaving the content where it
+/// is; existing root `width`/`height` are resized to keep the
+/// [`display_factor`]
+pub fn crop_view_box(doc: &roxmltree::Document, view_box: ViewBox, dpi: f64) -> String {
+    let factor = display_factor(doc, dpi);
+    let size = Some((view_box.width * factor, view_box.height * factor));
+    reframe(doc, view_box, size, None, EXACT_PRECISION)
+}
+
+/// Mirror the drawing horizontally and/or vertically across the middle of
+/// the root viewBox, which stays where it is
+pub fn mirror_view_box(
+    doc: &roxmltree::Document,
+    mirror_x: bool,
+    mirror_y: bool,
+    dpi: f64,
+) -> Result<String> {
+    let current = current_view_box(doc, dpi)?;
+    let axis = |mirror: bool, start: f64, length: f64| match mirror {
+        true => (-1.0, 2.0 * start + length),
+        false => (1.0, 0.0),
+    };
+    let (a, e) = axis(mirror_x, current.x, current.width);
+    let (d, f) = axis(mirror_y, current.y, current.height);
+    let ctx = format_ctx(EXACT_PRECISION);
+    let transform = format!(
+        "matrix({} 0 0 {} {} {})",
+        ctx.fmt(a),
+        ctx.fmt(d),
+        ctx.fmt(e),
+        ctx.fmt(f)
+    );
+    crate::strip_indentation(&reframe(
+        doc,
+        current,
+        None,
+        Some(transform),
+        EXACT_PRECISION,
+    ))
+}
+
+/// Grow the root viewBox by `amount` user units on every side (shrink when
+/// negative), resizing existing root `width`/`height` to match
+pub fn pad_view_box(doc: &roxmltree::Document, amount: f64, dpi: f64) -> Result<String> {
+    let current = current_view_box(doc, dpi)?;
+    let padded = ViewBox {
+        x: current.x - amount,
+        y: current.y - amount,
+        width: current.width + 2.0 * amount,
+        height: current.height + 2.0 * amount,
+    };
+    if padded.width <= 0.0 || padded.height <= 0.0 {
+        bail!("padding {} leaves an empty viewBox", amount);
+    }
+    Ok(crop_view_box(doc, padded, dpi))
+}
+
+/// Grow or shrink the root viewBox about its center by `kx` horizontally
+/// and `ky` vertically, leaving the content where it is and resizing
+/// existing root `width`/`height` to match
+pub fn resize_view_box(doc: &roxmltree::Document, kx: f64, ky: f64, dpi: f64) -> Result<String> {
+    let current = current_view_box(doc, dpi)?;
+    let (width, height) = (current.width * kx, current.height * ky);
+    let resized = ViewBox {
+        x: current.x - (width - current.width) / 2.0,
+        y: current.y - (height - current.height) / 2.0,
+        width,
+        height,
+    };
+    crate::strip_indentation(&crop_view_box(doc, resized, dpi))
+}
+
+/// Round the root viewBox outward to whole user units, leaving the content
+/// where it is, with root `width`/`height` in px resized to match (to whole
+/// pixels). Sizes in other units and percentages are kept.
+pub fn round_view_box(doc: &roxmltree::Document, dpi: f64) -> Result<String> {
+    let current = current_view_box(doc, dpi)?;
+    // Float noise such as 47.9999999 should not grow the box by a unit
+    let snap = |v: f64| (v * 1e6).round() / 1e6;
+    let (x, y) = (snap(current.x).floor(), snap(current.y).floor());
+    let rounded = ViewBox {
+        x,
+        y,
+        width: (snap(current.x + current.width).ceil() - x).max(1.0),
+        height: (snap(current.y + current.height).ceil() - y).max(1.0),
+    };
+    let root = doc.root_element();
+    let is_px = |name| {
+        root.attribute(name)
+            .is_none_or(|v| v.trim().trim_end_matches("px").parse::<f64>().is_ok())
+    };
+    let factor = display_factor(doc, dpi);
+    let size = (is_px("width") && is_px("height")).then(|| {
+        (
+            (rounded.width * factor).round(),
+            (rounded.height * factor).round(),
+        )
+    });
+    Ok(reframe(doc, rounded, size, None, EXACT_PRECISION))
+}
+
+/// Precision for intermediate documents that are scaled again afterwards
+pub(crate) const EXACT_PRECISION: usize = 12;
+
+fn format_ctx(precision: usize) -> ScaleCtx {
+    ScaleCtx {
+        precision,
+        ..ScaleCtx::default()
+    }
+}
+
+/// `translate(dx dy)`, or `None` for no move
+fn translation(dx: f64, dy: f64, precision: usize) -> Option<String> {
+    let ctx = format_ctx(precision);
+    (dx != 0.0 || dy != 0.0).then(|| format!("translate({} {})", ctx.fmt(dx), ctx.fmt(dy)))
+}
+
+/// Write the document with `view_box` on the root and the drawable content
+/// wrapped in a group with `transform`; `size` replaces existing root
+/// `width`/`height`
+fn reframe(
+    doc: &roxmltree::Document,
+    view_box: ViewBox,
+    size: Option<(f64, f64)>,
+    transform: Option<String>,
+    precision: usize,
+) -> String {
+    let root = doc.root_element();
+    let fmt_ctx = format_ctx(precision);
+    let mut w = xmlwriter::XmlWriter::new(xmlwriter::Options::default());
+
+    w.start_element(root.tag_name().name());
+    for attr in root.attributes() {
+        let name = svg::qualified_attr_name(root, &attr);
+        match &*name {
+            "viewBox" => {}
+            "width" if size.is_some() => w.write_attribute(&name, &fmt_ctx.fmt(size.unwrap().0)),
+            "height" if size.is_some() => w.write_attribute(&name, &fmt_ctx.fmt(size.unwrap().1)),
+            _ => w.write_attribute(&name, attr.value()),
+        }
+    }
+    w.write_attribute("viewBox", &view_box.format(&fmt_ctx));
+
+    let is_root_only =
+        |n: &roxmltree::Node| n.is_element() && ROOT_ONLY_TAGS.contains(&n.tag_name().name());
+    for child in root.children().filter(is_root_only) {
+        svg::write_verbatim(child, &mut w);
+    }
+    let content = root
+        .children()
+        .filter(|n| n.is_element() && !is_root_only(n));
+    if let Some(transform) = transform {
+        w.start_element("g");
+        w.write_attribute("transform", &transform);
+        for child in content {
+            svg::write_verbatim(child, &mut w);
+        }
+        w.end_element();
+    } else {
+        for child in content {
+            svg::write_verbatim(child, &mut w);
+        }
+    }
+    w.end_element();
+
+    crate::finish_document(doc, w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_box_parses_commas_and_whitespace() {
+        assert_eq!(
+            ViewBox::parse("10,-5 120  60"),
+            Some(ViewBox {
+                x: 10.0,
+                y: -5.0,
+                width: 120.0,
+                height: 60.0
+            })
+        );
+        assert_eq!(ViewBox::parse("0 0 24"), None);
+        assert_eq!(ViewBox::parse("0 0 24 a"), None);
+    }
+
+    #[test]
+    fn offset_view_box_is_moved_to_origin() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="10 10 120 120"><title>t</title><defs><linearGradient id="g"/></defs><rect x="10" y="10" width="5"/></svg>"#;
+        let doc = roxmltree::Document::parse(input)?;
+        let out = normalize_viewbox(&doc, 4)?;
+        assert!(out.contains(r#"viewBox="0 0 120 120""#), "got: {out}");
+        assert!(
+            out.contains(r#"<g transform="translate(-10 -10)">"#),
+            "expected content wrapped in a translate, got: {out}"
+        );
+        let defs = out.find("<defs>").unwrap_or(usize::MAX);
+        let group = out.find("<g ").unwrap_or(0);
+        assert!(
+            defs < group,
+            "expected defs to stay outside the group, got: {out}"
+        );
+        assert!(
+            out.contains(r#"<rect x="10" y="10" width="5"/>"#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn zero_origin_view_box_is_unchanged() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24"><rect width="5"/></svg>"#;
+        let doc = roxmltree::Document::parse(input)?;
+        let out = normalize_viewbox(&doc, 4)?;
+        assert!(out.contains(r#"viewBox="0 0 24 24""#), "got: {out}");
+        assert!(!out.contains("<g"), "got: {out}");
+        Ok(())
+    }
+
+    fn fit_and_scale(input: &str, target: &str) -> Result<String> {
+        let doc = roxmltree::Document::parse(input)?;
+        let (text, scale) = fit_to_view_box(&doc, ViewBox::parse_arg(target)?, 96.0)?;
+        let doc = roxmltree::Document::parse(&text)?;
+        let ctx = ScaleCtx {
+            scale,
+            ..ScaleCtx::default()
+        };
+        crate::write_svg(&doc, &ctx)
+    }
+
+    #[test]
+    fn target_view_box_maps_offset_content() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="100 100 48 48"><rect x="100" y="100" width="48" height="48"/></svg>"#;
+        let out = fit_and_scale(input, "0 0 24 24")?;
+        assert!(out.contains(r#"viewBox="0 0 24 24""#), "got: {out}");
+        assert!(out.contains(r#"translate(-50,-50)"#), "got: {out}");
+        assert!(
+            out.contains(r#"<rect x="50" y="50" width="24" height="24"/>"#),
+            "expected rect scaled into the target box, got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn target_view_box_centers_other_aspect_ratios() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100"><rect width="200" height="100"/></svg>"#;
+        let out = fit_and_scale(input, "0 0 24 24")?;
+        assert!(
+            out.contains(r#"width="24" height="24" viewBox="0 0 24 24""#),
+            "got: {out}"
+        );
+        assert!(out.contains(r#"translate(0,6)"#), "got: {out}");
+        assert!(
+            out.contains(r#"<rect width="24" height="12"/>"#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn target_view_box_rejects_empty_boxes() {
+        assert!(ViewBox::parse_arg("0 0 0 24").is_err());
+        assert!(ViewBox::parse_arg("0 0 24").is_err());
+    }
+
+    #[test]
+    fn padding_grows_the_view_box_around_the_content() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="12"><rect width="5"/></svg>"#;
+        let doc = roxmltree::Document::parse(input)?;
+        let out = pad_view_box(&doc, 2.0, 96.0)?;
+        assert!(
+            out.contains(r#"width="28" height="16" viewBox="-2 -2 28 16""#),
+            "got: {out}"
+        );
+        assert!(out.contains(r#"<rect width="5"/>"#), "got: {out}");
+        assert!(pad_view_box(&doc, -6.0, 96.0).is_err());
+
+        let out = resize_view_box(&doc, 2.0, 0.5, 96.0)?;
+        assert!(
+            out.contains(r#"width="48" height="6" viewBox="-12 3 48 6""#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rounded_view_box_grows_outward() -> Result<()> {
+        let input = r#"<svg xmlns="http://www.w3.org/2000/svg" width="12" height="7.5" viewBox="0.25 0 12 7.5"><rect width="5"/></svg>"#;
+        let out = round_view_box(&roxmltree::Document::parse(input)?, 96.0)?;
+        assert!(
+            out.contains(r#"width="13" height="8" viewBox="0 0 13 8""#),
+            "got: {out}"
+        );
+        assert!(out.contains(r#"<rect width="5"/>"#), "got: {out}");
+
+        let physical = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10mm" height="5mm" viewBox="0 0 47.9999999 23.5"/>"#;
+        let out = round_view_box(&roxmltree::Document::parse(physical)?, 96.0)?;
+        assert!(
+            out.contains(r#"width="10mm" height="5mm" viewBox="0 0 48 24""#),
+            "got: {out}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn mirroring_flips_across_the_view_box_center() -> Result<()> {
+        let doc = roxmltree::Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="10 0 20 10"><rect width="4" height="2"/></svg>"#,
+        )?;
+        let out = mirror_view_box(&doc, true, false, 96.0)?;
+        assert!(
+            out.contains(
+                r#"xmlns="http://www.w3.org/2000/svg"><g transform="matrix(-1 0 0 1 40 0)"><rect"#
+            ),
+            "got: {out}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! Raw WebAssembly ABI behind `scaleSvg` in `js/svg-scale.js`.
+//!
+//! Strings cross the boundary as UTF-8 buffers allocated with
+//! [`svg_scale_alloc`]; the result of the last call is kept until the next one.
+//! The ABI is hand-written rather than generated by wasm-bindgen so the module
+//! builds with plain `cargo build --target wasm32-unknown-unknown`, without a
+//! bindgen CLI pinned to the crate's version; the JS side is one small file.
+
+use std::cell::RefCell;
+
+use crate::{json, scale_svg, ScaleOptions};
+
+thread_local! {
+    static LAST_RESULT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Allocate `len` bytes for the host to write an input string into
+#[no_mangle]
+pub extern "C" fn svg_scale_alloc(len: usize) -> *mut u8 {
+    let mut buf = Vec::<u8>::with_capacity(len);
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// Release a buffer returned by [`svg_scale_alloc`]
+///
+/// # Safety
+/// `ptr` and `len` must come from a single `svg_scale_alloc` call.
+#[no_mangle]
+pub unsafe extern "C" fn svg_scale_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// Scale `svg` with JSON-encoded `opts`; returns 0 on success, 1 on error.
+/// The scaled document (or the error message) is then available through
+/// [`svg_scale_result_ptr`] / [`svg_scale_result_len`].
+///
+/// # Safety
+/// Both pointer/length pairs must describe valid UTF-8 buffers.
+#[no_mangle]
+pub unsafe extern "C" fn svg_scale_run(
+    svg_ptr: *const u8,
+    svg_len: usize,
+    opts_ptr: *const u8,
+    opts_len: usize,
+) -> i32 {
+    let svg = std::slice::from_raw_parts(svg_ptr, svg_len);
+    let opts = std::slice::from_raw_parts(opts_ptr, opts_len);
+    let (status, out) = match run(svg, opts) {
+        Ok(s) => (0, s),
+        Err(e) => (1, format!("{:#}", e)),
+    };
+    LAST_RESULT.with(|r| *r.borrow_mut() = out.into_bytes());
+    status
+}
+
+#[no_mangle]
+pub extern "C" fn svg_scale_result_ptr() -> *const u8 {
+    LAST_RESULT.with(|r| r.borrow().as_ptr())
+}
+
+#[no_mangle]
+pub extern "C" fn svg_scale_result_len() -> usize {
+    LAST_RESULT.with(|r| r.borrow().len())
+}
+
+fn run(svg: &[u8], opts: &[u8]) -> anyhow::Result<String> {
+    let svg = std::str::from_utf8(svg)?;
+    let opts = std::str::from_utf8(opts)?;
+    let opts = if opts.trim().is_empty() {
+        ScaleOptions::default()
+    } else {
+        ScaleOptions::from_json(&json::parse(opts)?)?
+    };
+    scale_svg(svg, &opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(svg: &str, opts: &str) -> (i32, String) {
+        let status = unsafe { svg_scale_run(svg.as_ptr(), svg.len(), opts.as_ptr(), opts.len()) };
+        let out = unsafe {
+            std::slice::from_raw_parts(svg_scale_result_ptr(), svg_scale_result_len()).to_vec()
+        };
+        (status, String::from_utf8(out).unwrap())
+    }
+
+    /// Copy `s` into a buffer from `svg_scale_alloc`, as `pass` in the JS wrapper does
+    fn pass(s: &str) -> (*mut u8, usize) {
+        let ptr = svg_scale_alloc(s.len());
+        unsafe { std::ptr::copy_nonoverlapping(s.as_ptr(), ptr, s.len()) };
+        (ptr, s.len())
+    }
+
+    #[test]
+    fn run_scales_with_js_style_options() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100"><rect width="40"/></svg>"#;
+        let (status, out) = call(svg, r#"{"scale":0.5,"fixStroke":true}"#);
+        assert_eq!(status, 0, "got: {out}");
+        assert!(out.contains(r#"<rect width="20"/>"#), "got: {out}");
+    }
+
+    #[test]
+    fn run_reports_errors_as_text() {
+        let (status, out) = call("<svg", "{}");
+        assert_eq!(status, 1);
+        assert!(!out.is_empty());
+    }
+
+    #[test]
+    fn allocated_buffers_round_trip_a_document() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="24"><path d="M2 2h20"/></svg>"#;
+        // The empty options buffer is a zero-length allocation, and an error
+        for (opts, status, expected) in [(r#"{"to":48}"#, 0, "M4 4h40"), ("", 1, "scale")] {
+            let (svg_ptr, svg_len) = pass(svg);
+            let (opts_ptr, opts_len) = pass(opts);
+            let got = unsafe { svg_scale_run(svg_ptr, svg_len, opts_ptr, opts_len) };
+            let out = unsafe {
+                std::slice::from_raw_parts(svg_scale_result_ptr(), svg_scale_result_len()).to_vec()
+            };
+            unsafe {
+                svg_scale_free(svg_ptr, svg_len);
+                svg_scale_free(opts_ptr, opts_len);
+            }
+            let out = String::from_utf8(out).unwrap();
+            assert_eq!(got, status, "got: {out}");
+            assert!(out.contains(expected), "got: {out}");
+        }
+    }
+}
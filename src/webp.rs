@@ -0,0 +1,203 @@
+//! Minimal lossless WebP (VP8L) encoder.
+//!
+//! Pixels are stored as literals without transforms or backward references,
+//! so files are not much smaller than raw RGBA, but every decoder accepts
+//! them and the output is bit-exact. Channels holding a single value across
+//! the whole image cost nothing per pixel, which covers the common opaque
+//! icon.
+
+use anyhow::{bail, Result};
+
+/// Largest width or height the 14-bit VP8L size fields hold
+pub const MAX_DIMENSION: u32 = 16384;
+
+/// Order in which the code length code lengths are stored (VP8L spec, 3.7.2.1.2)
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Green alphabet: 256 literals plus 24 length prefix codes (no color cache)
+const GREEN_ALPHABET: usize = 256 + 24;
+
+/// LSB-first bit writer, as VP8L reads its stream
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    used: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.acc |= (value as u64) << self.used;
+        self.used += bits;
+        while self.used >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.used -= 8;
+        }
+    }
+
+    /// Huffman codes are read starting from their most significant bit
+    fn write_code(&mut self, code: u32, len: u32) {
+        for i in (0..len).rev() {
+            self.write((code >> i) & 1, 1);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.used > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Prefix code for one channel: a single value costs zero bits per pixel,
+/// anything else is a flat 8-bit code over all literals
+#[derive(Clone, Copy)]
+enum ChannelCode {
+    Single(u8),
+    Flat,
+}
+
+impl ChannelCode {
+    fn for_values(mut values: impl Iterator<Item = u8>) -> Self {
+        let Some(first) = values.next() else {
+            return ChannelCode::Single(0);
+        };
+        if values.all(|v| v == first) {
+            ChannelCode::Single(first)
+        } else {
+            ChannelCode::Flat
+        }
+    }
+
+    fn write_header(self, w: &mut BitWriter, alphabet: usize) {
+        match self {
+            ChannelCode::Single(v) => {
+                // simple code, one symbol, stored in 8 bits
+                w.write(1, 1);
+                w.write(0, 1);
+                w.write(1, 1);
+                w.write(v as u32, 8);
+            }
+            ChannelCode::Flat => {
+                // normal code; the code length code has two 1-bit entries,
+                // for length 0 (code 0) and length 8 (code 1)
+                w.write(0, 1);
+                let count = CODE_LENGTH_ORDER.iter().position(|&s| s == 8).unwrap() + 1;
+                w.write(count as u32 - 4, 4);
+                for &symbol in &CODE_LENGTH_ORDER[..count] {
+                    w.write(u32::from(symbol == 0 || symbol == 8), 3);
+                }
+                // lengths for the whole alphabet follow
+                w.write(0, 1);
+                for symbol in 0..alphabet {
+                    w.write_code(u32::from(symbol < 256), 1);
+                }
+            }
+        }
+    }
+
+    fn write_value(self, w: &mut BitWriter, v: u8) {
+        if let ChannelCode::Flat = self {
+            w.write_code(v as u32, 8);
+        }
+    }
+}
+
+/// Fail unless `width` and `height` are between 1 and [`MAX_DIMENSION`]
+pub fn check_dimensions(width: u32, height: u32) -> Result<()> {
+    if !(1..=MAX_DIMENSION).contains(&width) || !(1..=MAX_DIMENSION).contains(&height) {
+        bail!(
+            "{}x{} does not fit lossless WebP, which holds at most {} px per side",
+            width,
+            height,
+            MAX_DIMENSION
+        );
+    }
+    Ok(())
+}
+
+/// Encode straight (non-premultiplied) RGBA pixels as a lossless WebP file
+/// of a size [`check_dimensions`] accepts
+pub fn encode_lossless(rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    check_dimensions(width, height)?;
+    assert_eq!(rgba.len(), width as usize * height as usize * 4);
+
+    let channel = |i: usize| ChannelCode::for_values(rgba.iter().skip(i).step_by(4).copied());
+    let (red, green, blue, alpha) = (channel(0), channel(1), channel(2), channel(3));
+    let has_alpha = !matches!(alpha, ChannelCode::Single(255));
+
+    let mut w = BitWriter::default();
+    w.write(0x2f, 8);
+    w.write(width - 1, 14);
+    w.write(height - 1, 14);
+    w.write(u32::from(has_alpha), 1);
+    w.write(0, 3);
+    // no transforms, no color cache, no meta prefix codes
+    w.write(0, 1);
+    w.write(0, 1);
+    w.write(0, 1);
+    green.write_header(&mut w, GREEN_ALPHABET);
+    red.write_header(&mut w, 256);
+    blue.write_header(&mut w, 256);
+    alpha.write_header(&mut w, 256);
+    // distance code, never used
+    ChannelCode::Single(0).write_header(&mut w, 40);
+    for px in rgba.chunks_exact(4) {
+        green.write_value(&mut w, px[1]);
+        red.write_value(&mut w, px[0]);
+        blue.write_value(&mut w, px[2]);
+        alpha.write_value(&mut w, px[3]);
+    }
+    let mut data = w.finish();
+    let chunk_len = data.len() as u32;
+    if data.len() % 2 == 1 {
+        data.push(0);
+    }
+
+    let mut out = Vec::with_capacity(data.len() + 20);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(data.len() as u32 + 12).to_le_bytes());
+    out.extend_from_slice(b"WEBPVP8L");
+    out.extend_from_slice(&chunk_len.to_le_bytes());
+    out.extend_from_slice(&data);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_records_size_and_alpha() -> Result<()> {
+        let out = encode_lossless(&[255, 0, 0, 128, 0, 255, 0, 255], 2, 1)?;
+        assert_eq!(&out[0..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(out[4..8].try_into().unwrap()) as usize,
+            out.len() - 8
+        );
+        assert_eq!(&out[8..16], b"WEBPVP8L");
+        assert_eq!(out[20], 0x2f);
+        let bits = u32::from_le_bytes(out[21..25].try_into().unwrap());
+        assert_eq!(bits & 0x3fff, 1, "width - 1");
+        assert_eq!((bits >> 14) & 0x3fff, 0, "height - 1");
+        assert_eq!((bits >> 28) & 1, 1, "alpha in use");
+
+        let wide = [0; 4].repeat(20000);
+        let err = encode_lossless(&wide, 20000, 1).unwrap_err();
+        assert!(err.to_string().contains("16384"), "got: {err}");
+        Ok(())
+    }
+
+    #[test]
+    fn constant_channels_cost_no_pixel_bits() -> Result<()> {
+        let opaque_red = [255, 0, 0, 255].repeat(64 * 64);
+        let out = encode_lossless(&opaque_red, 64, 64)?;
+        // header and five one-symbol codes only
+        assert!(out.len() < 40, "got {} bytes", out.len());
+        Ok(())
+    }
+}